@@ -25,10 +25,19 @@ pub struct ImportDecl<'a> {
     pub module: &'a str,
     pub name: &'a str,
     pub calling_convention: Option<&'a str>, // e.g., "c" for C calling convention
+    /// Name TILT code calls the import by (`... as alias`), if not `name`
+    pub alias: Option<&'a str>,
     pub params: Vec<TypedIdentifier<'a>>,
     pub return_type: Type,
 }
 
+impl<'a> ImportDecl<'a> {
+    /// The name calls use: the alias if there is one, else the symbol name
+    pub fn call_name(&self) -> &'a str {
+        self.alias.unwrap_or(self.name)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunctionDef<'a> {
     pub name: Identifier<'a>,
@@ -70,8 +79,14 @@ pub enum Expression<'a> {
         name: Identifier<'a>,
         args: Vec<Value<'a>>,
     },
-    // Direct constant value (e.g., `42`)
-    Constant(i32),
+    // e.g., `try_call file_open(path)`: a host error yields -1 instead of
+    // aborting the program
+    TryCall {
+        name: Identifier<'a>,
+        args: Vec<Value<'a>>,
+    },
+    // Direct constant value (e.g., `42`), typed by the destination
+    Constant(i64),
     // e.g., `phi [entry: v_init], [loop: v_next]`
     Phi {
         nodes: Vec<(Identifier<'a>, Value<'a>)>,
@@ -97,26 +112,75 @@ pub enum Terminator<'a> {
     },
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct TypedIdentifier<'a> {
     pub name: Identifier<'a>,
     pub ty: Type,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Type {
     I32,
     I64,
     F32,
     F64,
-    Usize, // Platform-native unsigned integer type for sizes, indices, and pointers
+    Usize, // Platform-native unsigned integer type for sizes, indices, and raw pointers
     Void,
+    // Typed pointer, e.g. `ptr<i32>`; build with `Type::ptr`
+    Ptr(Box<Type>),
+}
+
+impl Type {
+    /// A pointer to `pointee`
+    pub fn ptr(pointee: Type) -> Type {
+        Type::Ptr(Box::new(pointee))
+    }
+
+    /// The type a typed pointer points to
+    pub fn pointee(&self) -> Option<&Type> {
+        match self {
+            Type::Ptr(pointee) => Some(pointee),
+            _ => None,
+        }
+    }
+
+    /// Whether values of this type are addresses: `usize` or a typed pointer
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, Type::Usize | Type::Ptr(_))
+    }
+
+    /// The type backends represent this type with. Typed pointers are
+    /// pointer-width `usize` values; every other type is itself.
+    pub fn repr(&self) -> Type {
+        match self {
+            Type::Ptr(_) => Type::Usize,
+            other => other.clone(),
+        }
+    }
+}
+
+/// Formats a type the way it is written in TILT source (e.g. `i32`)
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Type::I32 => "i32",
+            Type::I64 => "i64",
+            Type::F32 => "f32",
+            Type::F64 => "f64",
+            Type::Usize => "usize",
+            Type::Void => "void",
+            Type::Ptr(pointee) => return write!(f, "ptr<{}>", pointee),
+        };
+        f.write_str(name)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Value<'a> {
     // A reference to another SSA value, e.g., `my_var`
     Variable(Identifier<'a>),
-    // A literal constant, e.g., `123`
-    Constant(i32),
+    // A literal constant, e.g., `123`; its type is inferred from context
+    Constant(i64),
+    // A float literal, e.g., `3.14`; only valid where an f32 or f64 is expected
+    Float(f64),
 }
@@ -1,16 +1,28 @@
-use cranelift::codegen::ir::BlockArg;
+use cranelift::codegen::ir::{BlockArg, BlockCall, JumpTableData};
 use cranelift::prelude::*;
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
 use cranelift_jit::{JITBuilder, JITModule};
-use cranelift_module::{FuncId, Linkage, Module};
+use cranelift_module::{DataDescription, DataId as ModuleDataId, FuncId, Linkage, Module};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use tilt_ast::Type as IRType;
-use tilt_host_abi::{HostABI, JITMemoryHostABI};
+use tilt_host_abi::{
+    begin_native_call, native_last_error, set_native_error, HostABI, JITMemoryHostABI,
+    HOST_ERROR_RESULT, HOST_ERR_INVALID_INPUT,
+};
+use tilt_ir::opt::fusable_compare_branches;
 use tilt_ir::{
-    BinaryOperator, BlockId, Function as IRFunction, Instruction, Program, Terminator,
-    UnaryOperator, ValueId,
+    format_signature, BinaryOperator, BlockId, CalleeIndex, DataId, Function as IRFunction,
+    ImportDecl, Instruction, Program, SwitchCase, Terminator, UnaryOperator, ValueId,
 };
 
+/// Fewest cases worth a jump table; smaller switches compare one by one
+const MIN_JUMP_TABLE_CASES: usize = 4;
+
+/// Trap raised by a load or store through a null pointer. Like any trap it
+/// aborts the process, but deterministically rather than as a stray fault.
+pub const NULL_POINTER_TRAP: TrapCode = TrapCode::unwrap_user(1);
+
 #[cfg(test)]
 mod tests;
 
@@ -22,8 +34,17 @@ pub struct JIT {
     module: JITModule,
     /// Function IDs for imports and declared functions
     function_ids: HashMap<String, FuncId>,
+    /// Read-only data objects, keyed by owning function and data ID
+    data_ids: HashMap<(String, DataId), ModuleDataId>,
+    /// TILT IR of every compiled function, kept for on-demand IR dumps
+    compiled_functions: HashMap<String, IRFunction>,
+    /// Size in bytes of every compiled function's machine code
+    code_sizes: HashMap<String, usize>,
     /// Whether to show Cranelift IR during compilation
     show_cranelift_ir: bool,
+    /// Symbols added with `define_symbol`, consulted when linking imports
+    /// the builder didn't register
+    defined_symbols: Arc<Mutex<HashMap<String, usize>>>,
     /// Host ABI for handling host function calls
     host_abi: Box<dyn HostABI + Send + Sync>,
 }
@@ -38,15 +59,23 @@ impl JIT {
         let mut builder = JITBuilder::new(cranelift_module::default_libcall_names())
             .map_err(|e| format!("Failed to create JIT builder: {}", e))?;
 
-        // Register host functions that will dynamically dispatch to the Host ABI
-        builder.symbol("print_hello", host_print_hello as *const u8);
-        builder.symbol("print_char", host_print_char as *const u8);
-        builder.symbol("print_i32", host_print_i32 as *const u8);
-        builder.symbol("print_i64", host_print_i64 as *const u8);
-        builder.symbol("println", host_println as *const u8);
-        builder.symbol("read_i32", host_read_i32 as *const u8);
-        builder.symbol("alloc", host_alloc as *const u8);
-        builder.symbol("free", host_free as *const u8);
+        // Register the built-in host functions the host ABI provides, using
+        // the ABI's own native version where it has one. The rest stay
+        // unlinked, so importing one fails as it does on the VM.
+        for (name, ptr) in builtin_symbols() {
+            if host_abi.has_function(name) {
+                builder.symbol(name, host_abi.native_symbol(name).unwrap_or(ptr));
+            }
+        }
+
+        // Imports are only resolved when definitions are finalized, so symbols
+        // defined after construction are still found through this table
+        let defined_symbols: Arc<Mutex<HashMap<String, usize>>> = Arc::default();
+        let lookup_table = Arc::clone(&defined_symbols);
+        builder.symbol_lookup_fn(Box::new(move |name| {
+            let table = lookup_table.lock().unwrap();
+            table.get(name).map(|&addr| addr as *const u8)
+        }));
 
         // Create the JIT module.
         let module = JITModule::new(builder);
@@ -54,48 +83,195 @@ impl JIT {
         Ok(Self {
             module,
             function_ids: HashMap::new(),
+            data_ids: HashMap::new(),
+            compiled_functions: HashMap::new(),
+            code_sizes: HashMap::new(),
             show_cranelift_ir: false,
+            defined_symbols,
             host_abi,
         })
     }
 
+    /// Register an extra symbol for imports to link against, such as a
+    /// user callback that the built-in host functions don't cover. Built-in
+    /// symbols the host ABI provides take precedence over a definition with
+    /// the same name.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a function that stays valid for as long as code
+    /// compiled by this JIT can run, and whose C ABI signature matches every
+    /// import declared with `name`. The JIT cannot check either, so a
+    /// mismatch is undefined behavior when the compiled code calls it.
+    pub unsafe fn define_symbol(&mut self, name: &str, ptr: *const u8) {
+        self.defined_symbols
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), ptr as usize);
+    }
+
+    /// Check an import against the signature the host ABI declares for it,
+    /// as `VM::validate_imports` does, so a mismatch fails to compile rather
+    /// than calling the host function with the wrong arguments
+    fn check_import_signature(&self, import: &ImportDecl) -> Result<(), String> {
+        match self.host_abi.function_signature(&import.name) {
+            Some((params, return_type)) if !import.matches_signature(&params, &return_type) => {
+                Err(format!(
+                    "Import '{}' is declared as {} but the host provides {}",
+                    import.name,
+                    format_signature(&import.params, &import.return_type),
+                    format_signature(&params, &return_type)
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reject a `try_call` to a host function. Native host functions can't
+    /// abort the program, so there is no error for the call to turn into
+    /// `HOST_ERROR_RESULT` the way the VM does; compiling it as a plain call
+    /// would quietly change what the program computes. A `try_call` to a
+    /// program function behaves like a plain call on both backends.
+    fn check_try_calls(&self, program: &Program, function: &IRFunction) -> Result<(), String> {
+        let calls = function
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|instruction| match instruction {
+                Instruction::TryCall { function, .. } => Some(function),
+                _ => None,
+            });
+        for callee in calls {
+            let local = match program.callee_index(callee) {
+                Some(CalleeIndex::Local(_)) => true,
+                Some(CalleeIndex::Import(_)) => false,
+                // Compiled by an earlier `compile` call
+                None => self.compiled_functions.contains_key(callee),
+            };
+            if !local {
+                return Err(format!(
+                    "try_call to host function '{}' in '{}' is not supported by the JIT: \
+                     native host functions can't report an error for it to catch",
+                    callee, function.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure an import the host ABI provides has a symbol to link
+    /// against: a built-in one, one from `define_symbol`, or the native
+    /// function the host ABI offers for it. Importing a built-in host
+    /// function the host ABI leaves out is an error.
+    fn link_host_import(&mut self, name: &str) -> Result<(), String> {
+        let provided = self.host_abi.has_function(name);
+        let builtin = builtin_symbols()
+            .iter()
+            .any(|(builtin, _)| *builtin == name);
+        if (provided && builtin) || self.defined_symbols.lock().unwrap().contains_key(name) {
+            return Ok(());
+        }
+        if !provided {
+            if builtin {
+                return Err(format!(
+                    "Host ABI does not provide built-in function '{}'",
+                    name
+                ));
+            }
+            return Ok(());
+        }
+
+        let ptr = self.host_abi.native_symbol(name).ok_or_else(|| {
+            format!(
+                "Host ABI function '{}' has no native symbol for the JIT to link",
+                name
+            )
+        })?;
+        self.defined_symbols
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), ptr as usize);
+        Ok(())
+    }
+
     /// Compile a TILT IR program into executable code in memory.
+    ///
+    /// Compiling is additive: functions compiled by an earlier call are kept,
+    /// and a program that contains one again with the same body reuses it,
+    /// so a REPL can compile `{a}` and later `{a, b}`. Changing the body of
+    /// a compiled function is an error, since machine code can't be replaced.
     pub fn compile(&mut self, program: &Program) -> Result<(), String> {
-        // First pass: Declare all functions (both imports and local functions)
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("jit_compile", functions = program.functions.len()).entered();
+
         for import in &program.imports {
+            self.check_import_signature(import)?;
+            self.link_host_import(&import.name)?;
+        }
+
+        // First pass: Declare all functions (both imports and local functions).
+        // Calls resolve as `Program::callee` does, so an import shadowed by a
+        // program function, or by an earlier import, is never declared.
+        for (index, import) in program.imports.iter().enumerate() {
+            if program.callee_index(import.call_name()) != Some(CalleeIndex::Import(index)) {
+                continue;
+            }
             let mut sig = self.module.make_signature();
 
             // Add parameters
             for param_type in &import.params {
-                sig.params.push(AbiParam::new(translate_type(param_type)));
+                let cl_type = translate_type(param_type)
+                    .map_err(|e| format!("Invalid parameter of import '{}': {}", import.name, e))?;
+                sig.params.push(AbiParam::new(cl_type));
             }
 
             // Add return type
             if import.return_type != IRType::Void {
                 sig.returns
-                    .push(AbiParam::new(translate_type(&import.return_type)));
+                    .push(AbiParam::new(translate_type(&import.return_type)?));
             }
 
+            // Link against the symbol, but let calls find it by its alias
             let func_id = self
                 .module
                 .declare_function(&import.name, Linkage::Import, &sig)
                 .map_err(|e| format!("Failed to declare import '{}': {}", import.name, e))?;
 
-            self.function_ids.insert(import.name.clone(), func_id);
+            self.function_ids
+                .insert(import.call_name().to_string(), func_id);
         }
 
+        let mut new_functions = Vec::new();
+        // Objects for `Program::rodata` entries, defined once for all the
+        // functions sharing them
+        let mut shared_objects = HashMap::new();
         for function in &program.functions {
+            match self.compiled_functions.get(&function.name) {
+                Some(compiled) if compiled == function => continue,
+                Some(_) => {
+                    return Err(format!(
+                        "Function '{}' is already compiled with a different body",
+                        function.name
+                    ))
+                }
+                None => new_functions.push(function),
+            }
+
             let mut sig = self.module.make_signature();
 
             // Add parameters
             for param_type in &function.params {
-                sig.params.push(AbiParam::new(translate_type(param_type)));
+                let cl_type = translate_type(param_type).map_err(|e| {
+                    format!("Invalid parameter of function '{}': {}", function.name, e)
+                })?;
+                sig.params.push(AbiParam::new(cl_type));
             }
 
             // Add return type
             if function.return_type != IRType::Void {
                 sig.returns
-                    .push(AbiParam::new(translate_type(&function.return_type)));
+                    .push(AbiParam::new(translate_type(&function.return_type)?));
             }
 
             let func_id = self
@@ -104,11 +280,39 @@ impl JIT {
                 .map_err(|e| format!("Failed to declare function '{}': {}", function.name, e))?;
 
             self.function_ids.insert(function.name.clone(), func_id);
+
+            for (data, bytes) in &function.rodata {
+                let name = format!("{}.rodata{}", function.name, data.0);
+                let data_id = self.define_rodata(Some(&name), bytes)?;
+                self.data_ids
+                    .insert((function.name.clone(), *data), data_id);
+            }
+            for (data, index) in &function.shared_rodata {
+                let data_id = match shared_objects.get(index) {
+                    Some(&data_id) => data_id,
+                    None => {
+                        let bytes = program.rodata.get(*index).ok_or_else(|| {
+                            format!(
+                                "Shared read-only data {} of function '{}' not found",
+                                index, function.name
+                            )
+                        })?;
+                        let data_id = self.define_rodata(None, bytes)?;
+                        shared_objects.insert(*index, data_id);
+                        data_id
+                    }
+                };
+                self.data_ids
+                    .insert((function.name.clone(), *data), data_id);
+            }
         }
 
         // Second pass: Compile function bodies
-        for function in &program.functions {
+        for function in new_functions {
+            self.check_try_calls(program, function)?;
             self.translate_function(function)?;
+            self.compiled_functions
+                .insert(function.name.clone(), function.clone());
         }
 
         // Finalize all functions, which resolves any forward-declared calls.
@@ -119,23 +323,150 @@ impl JIT {
         Ok(())
     }
 
+    /// Define read-only data as a module-local, non-writable data object,
+    /// anonymous if it has no `name`
+    fn define_rodata(&mut self, name: Option<&str>, bytes: &[u8]) -> Result<ModuleDataId, String> {
+        let label = name.unwrap_or("shared rodata");
+        let data_id = match name {
+            Some(name) => self.module.declare_data(name, Linkage::Local, false, false),
+            None => self.module.declare_anonymous_data(false, false),
+        }
+        .map_err(|e| format!("Failed to declare data '{}': {}", label, e))?;
+
+        let mut description = DataDescription::new();
+        description.define(bytes.to_vec().into_boxed_slice());
+        // Tables may hold 8-byte values; keep their loads aligned
+        description.set_align(8);
+        self.module
+            .define_data(data_id, &description)
+            .map_err(|e| format!("Failed to define data '{}': {}", label, e))?;
+        Ok(data_id)
+    }
+
     /// Get a raw pointer to a compiled function.
     pub fn get_func_ptr(&mut self, func_name: &str) -> Option<*const u8> {
         let func_id = *self.function_ids.get(func_name)?;
         Some(self.module.get_finalized_function(func_id))
     }
 
+    /// Get pointers to every compiled (exported) function, keyed by name.
+    /// Imports are not included.
+    pub fn finalized_functions(&mut self) -> HashMap<String, *const u8> {
+        self.compiled_functions
+            .keys()
+            .map(|name| {
+                let func_id = self.function_ids[name];
+                (name.clone(), self.module.get_finalized_function(func_id))
+            })
+            .collect()
+    }
+
+    /// Release the executable memory of every compiled function. A JIT
+    /// that is simply dropped keeps its code mapped for the life of the
+    /// process, so callers compiling many programs should free them.
+    ///
+    /// # Safety
+    ///
+    /// No function pointer obtained from this JIT may be called afterwards.
+    pub unsafe fn free_memory(self) {
+        self.module.free_memory();
+    }
+
     /// Enable or disable Cranelift IR output during compilation
     pub fn set_show_cranelift_ir(&mut self, show: bool) {
         self.show_cranelift_ir = show;
     }
 
+    /// Get the Cranelift IR text for a single compiled function.
+    /// The function is translated again into a throwaway context.
+    pub fn cranelift_ir_for(&mut self, func_name: &str) -> Option<String> {
+        let func = self.compiled_functions.get(func_name)?.clone();
+        let func_id = *self.function_ids.get(func_name)?;
+        let ctx = self.build_function_context(&func, func_id).ok()?;
+        Some(ctx.func.display().to_string())
+    }
+
+    /// Get the size in bytes of a compiled function's machine code
+    pub fn code_size(&self, func_name: &str) -> Option<usize> {
+        self.code_sizes.get(func_name).copied()
+    }
+
+    /// Disassemble the machine code compiled for a function, one
+    /// instruction per line with its address. Returns `None` for unknown
+    /// functions and for hosts the disassembler doesn't support.
+    #[cfg(feature = "disas")]
+    pub fn disassemble(&mut self, func_name: &str) -> Option<String> {
+        use capstone::prelude::*;
+
+        let size = self.code_size(func_name)?;
+        let ptr = self.get_func_ptr(func_name)?;
+        // SAFETY: finalized code stays mapped and readable for the JIT's
+        // lifetime, and the function occupies `size` bytes from `ptr`
+        let code = unsafe { std::slice::from_raw_parts(ptr, size) };
+
+        let disassembler = if cfg!(target_arch = "x86_64") {
+            Capstone::new()
+                .x86()
+                .mode(arch::x86::ArchMode::Mode64)
+                .build()
+        } else if cfg!(target_arch = "aarch64") {
+            Capstone::new()
+                .arm64()
+                .mode(arch::arm64::ArchMode::Arm)
+                .build()
+        } else {
+            return None;
+        }
+        .ok()?;
+
+        let instructions = disassembler.disasm_all(code, ptr as u64).ok()?;
+        Some(
+            instructions
+                .iter()
+                .map(|instruction| format!("{}\n", instruction))
+                .collect(),
+        )
+    }
+
     fn translate_function(&mut self, func: &IRFunction) -> Result<(), String> {
-        let func_id = self
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("translate_function", name = %func.name).entered();
+
+        let func_id = *self
             .function_ids
             .get(&func.name)
             .ok_or_else(|| format!("Function '{}' not declared", func.name))?;
 
+        let mut ctx = self.build_function_context(func, func_id)?;
+
+        // Show Cranelift IR if requested
+        if self.show_cranelift_ir {
+            println!("🔧 Cranelift IR for function '{}':", func.name);
+            println!("{}", ctx.func.display());
+            println!();
+        }
+
+        // Define the function body.
+        self.module
+            .define_function(func_id, &mut ctx)
+            .map_err(|e| format!("Failed to define function '{}': {}", func.name, e))?;
+        if let Some(code) = ctx.compiled_code() {
+            self.code_sizes
+                .insert(func.name.clone(), code.code_buffer().len());
+        }
+
+        // Clear the context for the next function.
+        self.module.clear_context(&mut ctx);
+
+        Ok(())
+    }
+
+    /// Translate a TILT function into a fresh Cranelift context
+    fn build_function_context(
+        &mut self,
+        func: &IRFunction,
+        func_id: FuncId,
+    ) -> Result<cranelift::codegen::Context, String> {
         // The context holds information about the current function being compiled.
         let mut ctx = self.module.make_context();
 
@@ -143,7 +474,7 @@ impl JIT {
         ctx.func.signature = self
             .module
             .declarations()
-            .get_function_decl(*func_id)
+            .get_function_decl(func_id)
             .signature
             .clone();
 
@@ -157,27 +488,13 @@ impl JIT {
             tilt_func: func,
             module: &mut self.module,
             function_ids: &self.function_ids,
+            data_ids: &self.data_ids,
             block_map: HashMap::new(),
             value_map: HashMap::new(),
         };
         translator.translate()?;
 
-        // Show Cranelift IR if requested
-        if self.show_cranelift_ir {
-            println!("🔧 Cranelift IR for function '{}':", func.name);
-            println!("{}", ctx.func.display());
-            println!();
-        }
-
-        // Define the function body.
-        self.module
-            .define_function(*func_id, &mut ctx)
-            .map_err(|e| format!("Failed to define function '{}': {}", func.name, e))?;
-
-        // Clear the context for the next function.
-        self.module.clear_context(&mut ctx);
-
-        Ok(())
+        Ok(ctx)
     }
 }
 
@@ -190,6 +507,8 @@ struct Translator<'a> {
     module: &'a mut JITModule,
     // Function IDs for calling other functions
     function_ids: &'a HashMap<String, FuncId>,
+    // Read-only data objects of every compiled function
+    data_ids: &'a HashMap<(String, DataId), ModuleDataId>,
 
     // MAPPINGS: The key to the whole process!
     // Maps our block IDs to Cranelift's block objects.
@@ -208,7 +527,7 @@ impl<'a> Translator<'a> {
 
             // Handle block parameters (phi nodes)
             for (value_id, param_type) in &block.params {
-                let cl_type = translate_type(param_type);
+                let cl_type = translate_type(param_type)?;
                 let cl_value = self.builder.append_block_param(cl_block, cl_type);
                 self.value_map.insert(*value_id, cl_value);
             }
@@ -232,7 +551,7 @@ impl<'a> Translator<'a> {
         // add them as block parameters (for manually constructed IR)
         if entry_block_data.params.len() != self.tilt_func.params.len() {
             for (i, param_type) in self.tilt_func.params.iter().enumerate() {
-                let cl_type = translate_type(param_type);
+                let cl_type = translate_type(param_type)?;
                 let cl_value = self.builder.append_block_param(*entry_block, cl_type);
                 self.value_map.insert(ValueId::new(i), cl_value);
             }
@@ -240,14 +559,24 @@ impl<'a> Translator<'a> {
 
         self.builder.switch_to_block(*entry_block);
 
-        // 3. Now, iterate and translate the contents of every block.
+        // 3. Now, iterate and translate the contents of every block. Only
+        // recorded fused branches that still qualify are fused.
+        let fusable = fusable_compare_branches(self.tilt_func);
         for block in &self.tilt_func.blocks {
             let cl_block = self.block_map[&block.id];
             self.builder.switch_to_block(cl_block);
 
-            // Translate each instruction.
-            for instr in &block.instructions {
-                self.translate_instruction(instr)?;
+            // Translate each instruction. A comparison that only feeds this
+            // block's branch (see `tilt_ir::opt::fold_compare_branch`) is
+            // left as a bare flag for `brif` to test.
+            let fused = self.tilt_func.fused_compare_branches.contains(&block.id)
+                && fusable.contains(&block.id);
+            for (i, instr) in block.instructions.iter().enumerate() {
+                if fused && i + 1 == block.instructions.len() {
+                    self.translate_fused_compare(instr)?;
+                } else {
+                    self.translate_instruction(instr)?;
+                }
             }
 
             // Translate the terminator.
@@ -261,11 +590,19 @@ impl<'a> Translator<'a> {
 
     fn translate_instruction(&mut self, instr: &Instruction) -> Result<(), String> {
         match instr {
+            // compile() only lets a try_call to a program function through,
+            // and that is a plain call
             Instruction::Call {
                 dest,
                 function,
                 args,
                 return_type: _,
+            }
+            | Instruction::TryCall {
+                dest,
+                function,
+                args,
+                return_type: _,
             } => {
                 let func_id = self
                     .function_ids
@@ -315,10 +652,19 @@ impl<'a> Translator<'a> {
                 lhs,
                 rhs,
             } => {
+                if *ty == IRType::Void {
+                    return Err(format!("Binary operator {:?} cannot operate on void", op));
+                }
+
                 let lhs_val = self.get_value_or_constant(*lhs)?;
                 let rhs_val = self.get_value_or_constant(*rhs)?;
 
+                let is_float = matches!(ty, IRType::F32 | IRType::F64);
+
                 let result = match op {
+                    BinaryOperator::Add if is_float => self.builder.ins().fadd(lhs_val, rhs_val),
+                    BinaryOperator::Sub if is_float => self.builder.ins().fsub(lhs_val, rhs_val),
+                    BinaryOperator::Mul if is_float => self.builder.ins().fmul(lhs_val, rhs_val),
                     BinaryOperator::Add => self.builder.ins().iadd(lhs_val, rhs_val),
                     BinaryOperator::Sub => self.builder.ins().isub(lhs_val, rhs_val),
                     BinaryOperator::Mul => self.builder.ins().imul(lhs_val, rhs_val),
@@ -327,44 +673,27 @@ impl<'a> Translator<'a> {
                         IRType::F32 | IRType::F64 => self.builder.ins().fdiv(lhs_val, rhs_val),
                         _ => return Err(format!("Division not supported for type {:?}", ty)),
                     },
-                    BinaryOperator::Eq => {
-                        let cmp_result = self.builder.ins().icmp(IntCC::Equal, lhs_val, rhs_val);
-                        self.builder.ins().uextend(types::I32, cmp_result)
-                    }
-                    BinaryOperator::Ne => {
-                        let cmp_result = self.builder.ins().icmp(IntCC::NotEqual, lhs_val, rhs_val);
-                        self.builder.ins().uextend(types::I32, cmp_result)
-                    }
-                    BinaryOperator::Lt => {
-                        let cmp_result =
-                            self.builder
-                                .ins()
-                                .icmp(IntCC::SignedLessThan, lhs_val, rhs_val);
-                        self.builder.ins().uextend(types::I32, cmp_result)
-                    }
-                    BinaryOperator::Le => {
-                        let cmp_result =
-                            self.builder
-                                .ins()
-                                .icmp(IntCC::SignedLessThanOrEqual, lhs_val, rhs_val);
-                        self.builder.ins().uextend(types::I32, cmp_result)
-                    }
-                    BinaryOperator::Gt => {
-                        let cmp_result =
-                            self.builder
-                                .ins()
-                                .icmp(IntCC::SignedGreaterThan, lhs_val, rhs_val);
-                        self.builder.ins().uextend(types::I32, cmp_result)
-                    }
-                    BinaryOperator::Ge => {
-                        let cmp_result = self.builder.ins().icmp(
-                            IntCC::SignedGreaterThanOrEqual,
-                            lhs_val,
-                            rhs_val,
-                        );
-                        self.builder.ins().uextend(types::I32, cmp_result)
-                    }
-                    _ => return Err(format!("Binary operator {:?} not implemented", op)),
+                    BinaryOperator::Rem => match ty {
+                        IRType::I32 | IRType::I64 => self.builder.ins().srem(lhs_val, rhs_val),
+                        IRType::Usize => self.builder.ins().urem(lhs_val, rhs_val),
+                        _ => return Err(format!("Remainder not supported for type {:?}", ty)),
+                    },
+                    BinaryOperator::And if !is_float => self.builder.ins().band(lhs_val, rhs_val),
+                    BinaryOperator::Or if !is_float => self.builder.ins().bor(lhs_val, rhs_val),
+                    BinaryOperator::Xor if !is_float => self.builder.ins().bxor(lhs_val, rhs_val),
+                    // Cranelift wraps the shift amount to the operand width, as
+                    // the VM does. `shr` is arithmetic (sshr) for i32 and i64,
+                    // and logical (ushr) for usize.
+                    BinaryOperator::Shl if !is_float => self.builder.ins().ishl(lhs_val, rhs_val),
+                    BinaryOperator::Shr => match ty {
+                        IRType::I32 | IRType::I64 => self.builder.ins().sshr(lhs_val, rhs_val),
+                        IRType::Usize => self.builder.ins().ushr(lhs_val, rhs_val),
+                        _ => return Err(format!("Right shift not supported for type {:?}", ty)),
+                    },
+                    _ => match self.emit_compare(*op, ty, lhs_val, rhs_val) {
+                        Some(cmp_result) => self.builder.ins().uextend(types::I32, cmp_result),
+                        None => return Err(format!("Binary operator {:?} not implemented", op)),
+                    },
                 };
 
                 self.value_map.insert(*dest, result);
@@ -373,57 +702,68 @@ impl<'a> Translator<'a> {
             Instruction::UnaryOp {
                 dest,
                 op,
-                ty: _,
+                ty,
                 operand,
             } => {
                 let operand_val = self.get_value_or_constant(*operand)?;
+                let is_float = matches!(ty, IRType::F32 | IRType::F64);
 
                 let result = match op {
+                    UnaryOperator::Neg if is_float => self.builder.ins().fneg(operand_val),
                     UnaryOperator::Neg => self.builder.ins().ineg(operand_val),
-                    UnaryOperator::Not => self.builder.ins().bnot(operand_val),
+                    UnaryOperator::Not if !is_float => self.builder.ins().bnot(operand_val),
+                    UnaryOperator::Sqrt if is_float => self.builder.ins().sqrt(operand_val),
+                    UnaryOperator::Floor if is_float => self.builder.ins().floor(operand_val),
+                    UnaryOperator::Ceil if is_float => self.builder.ins().ceil(operand_val),
+                    UnaryOperator::Abs if is_float => self.builder.ins().fabs(operand_val),
+                    UnaryOperator::Eqz if !is_float => {
+                        let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, operand_val, 0);
+                        self.builder.ins().uextend(types::I32, is_zero)
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Unary operator {:?} not supported for type {:?}",
+                            op, ty
+                        ))
+                    }
                 };
 
                 self.value_map.insert(*dest, result);
                 Ok(())
             }
             Instruction::Const { dest, value, ty } => {
-                let cl_value = match ty {
-                    IRType::I32 => self.builder.ins().iconst(types::I32, *value),
-                    IRType::I64 => self.builder.ins().iconst(types::I64, *value),
-                    IRType::Usize => {
-                        if cfg!(target_pointer_width = "64") {
-                            self.builder.ins().iconst(types::I64, *value)
-                        } else {
-                            self.builder.ins().iconst(types::I32, *value)
-                        }
-                    }
-                    IRType::F32 => self.builder.ins().f32const(*value as f32),
-                    IRType::F64 => self.builder.ins().f64const(*value as f64),
-                    _ => return Err(format!("Constant type {:?} not supported", ty)),
-                };
-
+                let cl_value = self.get_constant(*value, ty)?;
                 self.value_map.insert(*dest, cl_value);
                 Ok(())
             }
-            Instruction::Store {
-                address,
-                value,
-                ty: _,
-            } => {
+            Instruction::Store { address, value, ty } => {
                 let addr_val = self.get_value_or_constant(*address)?;
                 let val = self.get_value_or_constant(*value)?;
 
-                // Debug: Print the address being stored to
-                // Note: This would require runtime printing which we can't do here
-                // Let's add a comment for now and fix the actual issue
+                // The declared type sets the stored width: a wider integer is
+                // narrowed to it, anything else must already match
+                let store_type = translate_type(ty)?;
+                let value_type = self.builder.func.dfg.value_type(val);
+                let val = if value_type == store_type {
+                    val
+                } else if value_type.is_int()
+                    && store_type.is_int()
+                    && value_type.bits() > store_type.bits()
+                {
+                    self.builder.ins().ireduce(store_type, val)
+                } else {
+                    return Err(format!("Cannot store a {} value as {}", value_type, ty));
+                };
 
+                self.builder.ins().trapz(addr_val, NULL_POINTER_TRAP);
                 self.builder.ins().store(MemFlags::new(), val, addr_val, 0);
                 Ok(())
             }
             Instruction::Load { dest, ty, address } => {
                 let addr_val = self.get_value_or_constant(*address)?;
 
-                let cl_type = translate_type(ty);
+                let cl_type = translate_type(ty)?;
+                self.builder.ins().trapz(addr_val, NULL_POINTER_TRAP);
                 let result = self
                     .builder
                     .ins()
@@ -447,20 +787,40 @@ impl<'a> Translator<'a> {
                     IRType::I64 => 8,
                     IRType::F32 => 4,
                     IRType::F64 => 8,
-                    IRType::Usize => std::mem::size_of::<usize>() as i64, // Platform-dependent
+                    IRType::Usize | IRType::Ptr(_) => std::mem::size_of::<usize>() as i64, // Platform-dependent
                     IRType::Void => 0,
                 };
 
-                // SizeOf returns usize, so we use the appropriate type based on platform
-                let size_val = if cfg!(target_pointer_width = "64") {
-                    self.builder.ins().iconst(types::I64, size)
-                } else {
-                    self.builder.ins().iconst(types::I32, size)
-                };
+                // SizeOf returns usize, so emit it with the same type as every other usize value
+                let size_val = self
+                    .builder
+                    .ins()
+                    .iconst(translate_type(&IRType::Usize)?, size);
                 self.value_map.insert(*dest, size_val);
                 Ok(())
             }
 
+            Instruction::DataAddr { dest, data } => {
+                let data_id = self
+                    .data_ids
+                    .get(&(self.tilt_func.name.clone(), *data))
+                    .copied()
+                    .ok_or_else(|| {
+                        format!(
+                            "Read-only data d{} not found in function '{}'",
+                            data.0, self.tilt_func.name
+                        )
+                    })?;
+
+                let global = self.module.declare_data_in_func(data_id, self.builder.func);
+                let addr = self
+                    .builder
+                    .ins()
+                    .global_value(translate_type(&IRType::Usize)?, global);
+                self.value_map.insert(*dest, addr);
+                Ok(())
+            }
+
             Instruction::Alloc { dest, size } => {
                 // Call the host ABI alloc function
                 let size_val = self.get_value_or_constant(*size)?;
@@ -474,7 +834,7 @@ impl<'a> Translator<'a> {
 
                 let alloc_func_ref = self
                     .module
-                    .declare_func_in_func(alloc_func_id, &mut self.builder.func);
+                    .declare_func_in_func(alloc_func_id, self.builder.func);
                 let call_result = self.builder.ins().call(alloc_func_ref, &[size_val]);
                 let result_val = self.builder.inst_results(call_result)[0];
 
@@ -494,7 +854,7 @@ impl<'a> Translator<'a> {
 
                 let free_func_ref = self
                     .module
-                    .declare_func_in_func(free_func_id, &mut self.builder.func);
+                    .declare_func_in_func(free_func_id, self.builder.func);
                 self.builder.ins().call(free_func_ref, &[ptr_val]);
                 Ok(())
             }
@@ -507,8 +867,30 @@ impl<'a> Translator<'a> {
             } => {
                 let src_val = self.get_value_or_constant(*src)?;
 
-                // Perform type conversion using Cranelift instructions
+                // Perform type conversion using Cranelift instructions. The
+                // supported set must match the VM: every pair of integer
+                // types, and every pair of i32, i64, f32 and f64.
                 let result = match (from_ty, to_ty) {
+                    (IRType::I32, IRType::I32)
+                    | (IRType::I64, IRType::I64)
+                    | (IRType::Usize, IRType::Usize)
+                    | (IRType::F32, IRType::F32)
+                    | (IRType::F64, IRType::F64) => src_val,
+                    (IRType::I32 | IRType::I64, IRType::F32) => {
+                        self.builder.ins().fcvt_from_sint(types::F32, src_val)
+                    }
+                    (IRType::I32 | IRType::I64, IRType::F64) => {
+                        self.builder.ins().fcvt_from_sint(types::F64, src_val)
+                    }
+                    (IRType::F32, IRType::F64) => self.builder.ins().fpromote(types::F64, src_val),
+                    (IRType::F64, IRType::F32) => self.builder.ins().fdemote(types::F32, src_val),
+                    // Saturate like the VM's `as` casts, with NaN becoming 0
+                    (IRType::F32 | IRType::F64, IRType::I32) => {
+                        self.builder.ins().fcvt_to_sint_sat(types::I32, src_val)
+                    }
+                    (IRType::F32 | IRType::F64, IRType::I64) => {
+                        self.builder.ins().fcvt_to_sint_sat(types::I64, src_val)
+                    }
                     (IRType::I32, IRType::I64) => {
                         // Sign-extend i32 to i64
                         self.builder.ins().sextend(types::I64, src_val)
@@ -532,8 +914,8 @@ impl<'a> Translator<'a> {
                             // On 64-bit platforms, usize is already i64, so just return as-is
                             src_val
                         } else {
-                            // On 32-bit platforms, sign-extend usize (i32) to i64
-                            self.builder.ins().sextend(types::I64, src_val)
+                            // On 32-bit platforms, zero-extend usize (i32) to i64
+                            self.builder.ins().uextend(types::I64, src_val)
                         }
                     }
                     (IRType::Usize, IRType::I32) => {
@@ -567,6 +949,54 @@ impl<'a> Translator<'a> {
                 self.value_map.insert(*dest, result);
                 Ok(())
             }
+            Instruction::ConvertChecked {
+                dest,
+                src,
+                from_ty,
+                to_ty,
+            } => {
+                let src_val = self.get_value_or_constant(*src)?;
+
+                // Trap when the value does not survive the round trip through
+                // the narrower type, matching the VM's range check
+                let (narrowed, fits) = match (from_ty, to_ty) {
+                    (IRType::I64, IRType::I32) => {
+                        let narrowed = self.builder.ins().ireduce(types::I32, src_val);
+                        let widened = self.builder.ins().sextend(types::I64, narrowed);
+                        let fits = self.builder.ins().icmp(IntCC::Equal, widened, src_val);
+                        (narrowed, fits)
+                    }
+                    (IRType::Usize, IRType::I32) => {
+                        let fits = self.builder.ins().icmp_imm(
+                            IntCC::UnsignedLessThanOrEqual,
+                            src_val,
+                            i32::MAX as i64,
+                        );
+                        let narrowed = if cfg!(target_pointer_width = "64") {
+                            self.builder.ins().ireduce(types::I32, src_val)
+                        } else {
+                            src_val
+                        };
+                        (narrowed, fits)
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Unsupported checked conversion from {:?} to {:?}",
+                            from_ty, to_ty
+                        ));
+                    }
+                };
+                self.builder.ins().trapz(fits, TrapCode::INTEGER_OVERFLOW);
+
+                self.value_map.insert(*dest, narrowed);
+                Ok(())
+            }
+            // Only the verifier looks at type assertions
+            Instruction::AssertType { .. } => Ok(()),
+
+            Instruction::Phi { .. } => Err(
+                "Phi instructions must be eliminated before codegen (see tilt_ir::phi)".to_string(),
+            ),
         }
     }
 
@@ -613,6 +1043,9 @@ impl<'a> Translator<'a> {
                 false_target,
                 false_args,
             } => {
+                // `brif` takes the true edge for any nonzero value, which is
+                // exactly the IR's "nonzero is true" contract, so the raw
+                // condition is passed through without normalizing it to 0/1
                 let cond_val = self.get_value_or_constant(*cond)?;
                 let true_block = self
                     .block_map
@@ -660,26 +1093,190 @@ impl<'a> Translator<'a> {
                 }
                 Ok(())
             }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+                default_args,
+            } => {
+                let switch_val = self.get_value_or_constant(*value)?;
+                let default_block = self.target_block(*default)?;
+
+                // `br_table` can't pass block arguments, so only argument-free
+                // switches over a dense range of values get a jump table
+                let no_args = default_args.is_empty() && cases.iter().all(|c| c.args.is_empty());
+                match dense_case_range(cases) {
+                    Some((min, span)) if no_args => {
+                        self.translate_dense_switch(switch_val, cases, min, span, default_block)
+                    }
+                    _ => {
+                        for case in cases {
+                            let target = self.target_block(case.target)?;
+                            let args = self.branch_args(&case.args)?;
+                            let matches =
+                                self.builder
+                                    .ins()
+                                    .icmp_imm(IntCC::Equal, switch_val, case.value);
+                            let next = self.builder.create_block();
+                            self.builder
+                                .ins()
+                                .brif(matches, target, args.iter(), next, &[]);
+                            self.builder.switch_to_block(next);
+                        }
+                        let args = self.branch_args(default_args)?;
+                        self.builder.ins().jump(default_block, args.iter());
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    /// Translate the comparison feeding a fused branch, keeping the `icmp`
+    /// flag as the condition value instead of widening it to i32
+    fn translate_fused_compare(&mut self, instr: &Instruction) -> Result<(), String> {
+        let Instruction::BinaryOp {
+            dest,
+            op,
+            ty,
+            lhs,
+            rhs,
+        } = instr
+        else {
+            return self.translate_instruction(instr);
+        };
+        if !op.is_comparison() {
+            return self.translate_instruction(instr);
+        }
+
+        let lhs_val = self.get_value_or_constant(*lhs)?;
+        let rhs_val = self.get_value_or_constant(*rhs)?;
+        let Some(flag) = self.emit_compare(*op, ty, lhs_val, rhs_val) else {
+            return Err(format!("Binary operator {:?} not implemented", op));
+        };
+        self.value_map.insert(*dest, flag);
+        Ok(())
+    }
+
+    /// Emit a comparison of two `ty` values as an `icmp` or, for floats, an
+    /// ordered `fcmp`, returning the flag. `None` if `op` is not a comparison.
+    fn emit_compare(
+        &mut self,
+        op: BinaryOperator,
+        ty: &IRType,
+        lhs: Value,
+        rhs: Value,
+    ) -> Option<Value> {
+        if matches!(ty, IRType::F32 | IRType::F64) {
+            let cond = float_compare_cond(op)?;
+            Some(self.builder.ins().fcmp(cond, lhs, rhs))
+        } else {
+            let cond = int_compare_cond(op, ty)?;
+            Some(self.builder.ins().icmp(cond, lhs, rhs))
+        }
+    }
+
+    /// Emit a `br_table` for cases covering `span` values starting at `min`.
+    /// Gaps in the range go to the default block.
+    fn translate_dense_switch(
+        &mut self,
+        switch_val: Value,
+        cases: &[SwitchCase],
+        min: i64,
+        span: u32,
+        default_block: Block,
+    ) -> Result<(), String> {
+        let mut index = if min == 0 {
+            switch_val
+        } else {
+            self.builder.ins().iadd_imm(switch_val, min.wrapping_neg())
+        };
+
+        // The table index is an i32, so wider values are range-checked first
+        // rather than truncated into the table
+        if self.builder.func.dfg.value_type(index) != types::I32 {
+            let in_range = self
+                .builder
+                .ins()
+                .icmp_imm(IntCC::UnsignedLessThan, index, span as i64);
+            let table_block = self.builder.create_block();
+            self.builder
+                .ins()
+                .brif(in_range, table_block, &[], default_block, &[]);
+            self.builder.switch_to_block(table_block);
+            index = self.builder.ins().ireduce(types::I32, index);
+        }
+
+        let mut targets = vec![None; span as usize];
+        for case in cases {
+            let slot = &mut targets[(case.value - min) as usize];
+            // Like the VM, the first case with a given value wins
+            if slot.is_none() {
+                *slot = Some(self.target_block(case.target)?);
+            }
         }
+
+        let pool = &mut self.builder.func.dfg.value_lists;
+        let default_call = BlockCall::new(default_block, std::iter::empty(), pool);
+        let table: Vec<BlockCall> = targets
+            .into_iter()
+            .map(|target| match target {
+                Some(block) => BlockCall::new(block, std::iter::empty(), pool),
+                None => default_call,
+            })
+            .collect();
+
+        let jump_table = self
+            .builder
+            .create_jump_table(JumpTableData::new(default_call, &table));
+        self.builder.ins().br_table(index, jump_table);
+        Ok(())
+    }
+
+    /// Look up the Cranelift block for a branch target
+    fn target_block(&self, target: BlockId) -> Result<Block, String> {
+        self.block_map
+            .get(&target)
+            .copied()
+            .ok_or_else(|| format!("Branch target {:?} not found", target))
+    }
+
+    /// Resolve the arguments passed along a branch edge
+    fn branch_args(&mut self, args: &[ValueId]) -> Result<Vec<BlockArg>, String> {
+        args.iter()
+            .map(|arg| self.get_value_or_constant(*arg).map(BlockArg::Value))
+            .collect()
     }
 
     /// Get or create a constant value (always create fresh to respect SSA)
-    fn get_constant(&mut self, value: i64, ty: &IRType) -> Value {
-        // Always create a fresh constant in the current block to respect SSA form
-        match ty {
-            IRType::I32 => self.builder.ins().iconst(types::I32, value),
+    fn get_constant(&mut self, value: i64, ty: &IRType) -> Result<Value, String> {
+        // Always create a fresh constant in the current block to respect SSA form.
+        // Cranelift wants narrow immediates zero-extended, so the stored bit
+        // pattern is cut down to the type's width first, once it is known not
+        // to lose any set bits.
+        if !tilt_ir::constant_fits(value, ty) {
+            return Err(format!(
+                "Constant {} does not fit in {} in function '{}'",
+                value, ty, self.tilt_func.name
+            ));
+        }
+        let cl_value = match ty {
+            IRType::I32 => self.builder.ins().iconst(types::I32, value as u32 as i64),
             IRType::I64 => self.builder.ins().iconst(types::I64, value),
-            IRType::Usize => {
+            IRType::Usize | IRType::Ptr(_) => {
                 if cfg!(target_pointer_width = "64") {
                     self.builder.ins().iconst(types::I64, value)
                 } else {
-                    self.builder.ins().iconst(types::I32, value)
+                    self.builder.ins().iconst(types::I32, value as u32 as i64)
                 }
             }
-            IRType::F32 => self.builder.ins().f32const(value as f32),
-            IRType::F64 => self.builder.ins().f64const(value as f64),
-            IRType::Void => self.builder.ins().iconst(types::I8, 0), // Placeholder
-        }
+            IRType::F32 => self.builder.ins().f32const(Ieee32::with_bits(value as u32)),
+            IRType::F64 => self.builder.ins().f64const(Ieee64::with_bits(value as u64)),
+            IRType::Void => {
+                return Err("Cannot create a constant of type void".to_string());
+            }
+        };
+        Ok(cl_value)
     }
 
     /// Get a value, either from the value map or create a constant
@@ -691,28 +1288,80 @@ impl<'a> Translator<'a> {
 
         // Check if it's a constant
         if let Some(&(const_val, ref const_type)) = self.tilt_func.constants.get(&value_id) {
-            return Ok(self.get_constant(const_val, const_type));
+            return self.get_constant(const_val, const_type);
         }
 
         Err(format!("Value {:?} not found", value_id))
     }
 }
 
-fn translate_type(ir_type: &IRType) -> types::Type {
+/// Get the integer condition code for a comparison operator on `ty`, which
+/// compares usize values and pointers as unsigned
+fn int_compare_cond(op: BinaryOperator, ty: &IRType) -> Option<IntCC> {
+    let unsigned = ty.repr() == IRType::Usize;
+    let cond = match op {
+        BinaryOperator::Eq => IntCC::Equal,
+        BinaryOperator::Ne => IntCC::NotEqual,
+        BinaryOperator::Lt if unsigned => IntCC::UnsignedLessThan,
+        BinaryOperator::Le if unsigned => IntCC::UnsignedLessThanOrEqual,
+        BinaryOperator::Gt if unsigned => IntCC::UnsignedGreaterThan,
+        BinaryOperator::Ge if unsigned => IntCC::UnsignedGreaterThanOrEqual,
+        BinaryOperator::Lt => IntCC::SignedLessThan,
+        BinaryOperator::Le => IntCC::SignedLessThanOrEqual,
+        BinaryOperator::Gt => IntCC::SignedGreaterThan,
+        BinaryOperator::Ge => IntCC::SignedGreaterThanOrEqual,
+        _ => return None,
+    };
+    Some(cond)
+}
+
+/// Get the float condition code for a comparison operator. Comparisons are
+/// ordered, so any comparison with a NaN is false, except `ne`, which is true.
+fn float_compare_cond(op: BinaryOperator) -> Option<FloatCC> {
+    let cond = match op {
+        BinaryOperator::Eq => FloatCC::Equal,
+        BinaryOperator::Ne => FloatCC::NotEqual,
+        BinaryOperator::Lt => FloatCC::LessThan,
+        BinaryOperator::Le => FloatCC::LessThanOrEqual,
+        BinaryOperator::Gt => FloatCC::GreaterThan,
+        BinaryOperator::Ge => FloatCC::GreaterThanOrEqual,
+        _ => return None,
+    };
+    Some(cond)
+}
+
+/// If the switch cases fill at least half of the range between their smallest
+/// and largest value, return that range as `(min, span)`
+fn dense_case_range(cases: &[SwitchCase]) -> Option<(i64, u32)> {
+    if cases.len() < MIN_JUMP_TABLE_CASES {
+        return None;
+    }
+    let min = cases.iter().map(|c| c.value).min()?;
+    let max = cases.iter().map(|c| c.value).max()?;
+    let span = max as i128 - min as i128 + 1;
+    if span > 2 * cases.len() as i128 {
+        return None;
+    }
+    Some((min, span as u32))
+}
+
+/// Map a TILT value type to its Cranelift type.
+/// Void has no values, so it is rejected rather than given a placeholder type.
+fn translate_type(ir_type: &IRType) -> Result<types::Type, String> {
     match ir_type {
-        IRType::I32 => types::I32,
-        IRType::I64 => types::I64,
-        IRType::F32 => types::F32,
-        IRType::F64 => types::F64,
-        IRType::Usize => {
+        IRType::I32 => Ok(types::I32),
+        IRType::I64 => Ok(types::I64),
+        IRType::F32 => Ok(types::F32),
+        IRType::F64 => Ok(types::F64),
+        IRType::Usize | IRType::Ptr(_) => {
             // Use the native pointer size for the target platform
             if cfg!(target_pointer_width = "64") {
-                types::I64
+                Ok(types::I64)
             } else {
-                types::I32
+                Ok(types::I32)
             }
         }
-        IRType::Void => types::I8, // Placeholder, void functions return nothing
+        IRType::Void => Err("void is not a value type".to_string()),
     }
 }
 
@@ -720,46 +1369,85 @@ fn translate_type(ir_type: &IRType) -> types::Type {
 // For now, these are simple implementations that don't use the dynamic ABI
 // In the future, we could implement proper per-instance ABI support
 
+/// Native host functions every JIT links imports against
+fn builtin_symbols() -> [(&'static str, *const u8); 11] {
+    [
+        ("print_hello", host_print_hello as *const u8),
+        ("print_char", host_print_char as *const u8),
+        ("print_i32", host_print_i32 as *const u8),
+        ("print_i64", host_print_i64 as *const u8),
+        ("print_ptr", host_print_ptr as *const u8),
+        ("println", host_println as *const u8),
+        ("read_i32", host_read_i32 as *const u8),
+        ("last_error", host_last_error as *const u8),
+        ("alloc", host_alloc as *const u8),
+        ("alloc_aligned", host_alloc_aligned as *const u8),
+        ("free", host_free as *const u8),
+    ]
+}
+
 fn host_print_hello() {
+    begin_native_call();
     print!("Hello from JIT!");
 }
 
 fn host_print_char(c: i32) {
+    begin_native_call();
     if let Some(ch) = char::from_u32(c as u32) {
         print!("{}", ch);
     }
 }
 
 fn host_print_i32(value: i32) {
+    begin_native_call();
     print!("{}", value);
 }
 
 fn host_print_i64(value: i64) {
+    begin_native_call();
     print!("{}", value);
 }
 
+fn host_print_ptr(ptr: usize) {
+    begin_native_call();
+    print!("0x{:x}", ptr);
+}
+
 fn host_println() {
+    begin_native_call();
     println!();
 }
 
 fn host_read_i32() -> i32 {
     use std::io::{self, Write};
+    begin_native_call();
     print!("Enter i32: ");
     io::stdout().flush().unwrap();
 
     let mut input = String::new();
     io::stdin().read_line(&mut input).unwrap();
-    input.trim().parse().unwrap_or(0)
+    input.trim().parse().unwrap_or_else(|_| {
+        set_native_error(HOST_ERR_INVALID_INPUT);
+        HOST_ERROR_RESULT
+    })
+}
+
+fn host_last_error() -> i32 {
+    native_last_error()
 }
 
 #[cfg(target_pointer_width = "64")]
 fn host_alloc(size: u64) -> u64 {
     use std::alloc::{alloc, Layout};
+    begin_native_call();
     if size == 0 {
         return 0;
     }
 
-    let layout = Layout::from_size_align(size as usize, 8).unwrap();
+    // Sizes too large for a layout fail like any other allocation
+    let Ok(layout) = Layout::from_size_align(size as usize, 8) else {
+        return 0;
+    };
     let ptr = unsafe { alloc(layout) };
     if ptr.is_null() {
         0
@@ -771,11 +1459,15 @@ fn host_alloc(size: u64) -> u64 {
 #[cfg(target_pointer_width = "32")]
 fn host_alloc(size: u32) -> u32 {
     use std::alloc::{alloc, Layout};
+    begin_native_call();
     if size == 0 {
         return 0;
     }
 
-    let layout = Layout::from_size_align(size as usize, 4).unwrap();
+    // Sizes too large for a layout fail like any other allocation
+    let Ok(layout) = Layout::from_size_align(size as usize, 4) else {
+        return 0;
+    };
     let ptr = unsafe { alloc(layout) };
     if ptr.is_null() {
         0
@@ -784,8 +1476,34 @@ fn host_alloc(size: u32) -> u32 {
     }
 }
 
+/// Allocate `size` bytes aligned to `align`, returning 0 and setting
+/// `last_error()` when the alignment is not a power of two
+fn host_alloc_aligned(size: usize, align: usize) -> usize {
+    use std::alloc::{alloc, Layout};
+    begin_native_call();
+    if !align.is_power_of_two() {
+        set_native_error(HOST_ERR_INVALID_INPUT);
+        return 0;
+    }
+    if size == 0 {
+        return 0;
+    }
+
+    // Sizes too large for a layout fail like any other allocation
+    let Ok(layout) = Layout::from_size_align(size, align) else {
+        return 0;
+    };
+    let ptr = unsafe { alloc(layout) };
+    if ptr.is_null() {
+        0
+    } else {
+        ptr as usize
+    }
+}
+
 #[cfg(target_pointer_width = "64")]
 fn host_free(ptr: u64) {
+    begin_native_call();
     if ptr != 0 {
         // Note: This is unsafe because we don't know the original size
         // In a real implementation, we'd need to track allocations
@@ -796,6 +1514,7 @@ fn host_free(ptr: u64) {
 
 #[cfg(target_pointer_width = "32")]
 fn host_free(ptr: u32) {
+    begin_native_call();
     if ptr != 0 {
         // Note: This is unsafe because we don't know the original size
         // In a real implementation, we'd need to track allocations
@@ -28,7 +28,7 @@ fn parse_and_lower(source: &str) -> Result<tilt_ir::Program, String> {
         println!("Tokens for alloc test:");
         for (i, (start, token, end)) in tokens.iter().enumerate() {
             println!("{}: {}..{} {:?}", i, start, end, token);
-            if i >= 35 && i <= 42 {
+            if (35..=42).contains(&i) {
                 println!("  -> Text: '{}'", &source[*start..*end]);
             }
         }
@@ -177,6 +177,77 @@ mod tests {
         assert_eq!(result, RuntimeValue::I32(1));
     }
 
+    #[test]
+    fn test_jit_alloc_aligned() {
+        let source = r#"
+            import "host" "alloc_aligned" (size:usize, align:usize) -> usize
+
+            fn main() -> usize {
+            entry:
+                size:usize = usize.const(24)
+                align:usize = usize.const(16)
+                ptr:usize = alloc_aligned(size, align)
+                ret (ptr)
+            }
+        "#;
+
+        let result = execute_jit_program(source).expect("JIT execution should succeed");
+        let addr = result.as_ptr();
+        assert_ne!(addr, 0);
+        assert_eq!(addr % 16, 0);
+    }
+
+    #[test]
+    fn test_jit_last_error_reports_most_recent_call() {
+        let source = r#"
+            import "host" "alloc_aligned" (size:usize, align:usize) -> usize
+            import "host" "last_error" -> i32
+
+            fn main() -> i32 {
+            entry:
+                size:usize = usize.const(16)
+                align:usize = usize.const(12)
+                bad:usize = alloc_aligned(size, align)
+                failed:i32 = last_error()
+                good:usize = alloc_aligned(size, size)
+                cleared:i32 = last_error()
+                ten:i32 = i32.const(10)
+                scaled:i32 = i32.mul(failed, ten)
+                r:i32 = i32.add(scaled, cleared)
+                ret (r)
+            }
+        "#;
+
+        // The failed call reports 1, and the next call clears it to 0
+        let result = execute_jit_program(source).expect("JIT execution should succeed");
+        assert_eq!(result, RuntimeValue::I32(10));
+
+        // Errors on another thread stay there
+        std::thread::spawn(|| crate::host_alloc_aligned(16, 12))
+            .join()
+            .unwrap();
+        assert_eq!(crate::host_last_error(), tilt_host_abi::HOST_ERR_NONE);
+    }
+
+    #[test]
+    fn test_jit_alloc_huge_size_returns_null() {
+        let source = r#"
+            import "host" "alloc" (size:usize) -> usize
+
+            fn main() -> usize {
+            entry:
+                zero:usize = usize.const(0)
+                one:usize = usize.const(1)
+                size:usize = usize.sub(zero, one)
+                ptr:usize = alloc(size)
+                ret (ptr)
+            }
+        "#;
+
+        let result = execute_jit_program(source).expect("JIT execution should succeed");
+        assert_eq!(result, RuntimeValue::Usize(0));
+    }
+
     #[test]
     fn test_jit_alloc_free_basic() {
         let source = r#"
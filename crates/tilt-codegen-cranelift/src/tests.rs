@@ -14,7 +14,7 @@ use tilt_parser::{ProgramParser, Token};
 
 // Global storage for capturing output from host functions
 thread_local! {
-    static OUTPUT: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+    static OUTPUT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
 }
 
 // Helper to capture output from our test host functions
@@ -164,8 +164,8 @@ extern "C" fn test_identity(a: i32) -> i32 {
     a
 }
 
-// Helper function to compile and run a TILT program
-fn compile_and_run(source: &str) -> Result<String, String> {
+// Helper function to lex, parse and lower a TILT program to IR
+fn lower_source(source: &str) -> Result<tilt_ir::Program, String> {
     // 1. Lexing
     let lexer = Token::lexer(source);
     let tokens: Result<Vec<Token>, _> = lexer.collect();
@@ -182,13 +182,18 @@ fn compile_and_run(source: &str) -> Result<String, String> {
         .map_err(|e| format!("Parsing failed: {:?}", e))?;
 
     // 3. IR Generation
-    let program_ir = lower_program(&program_ast).map_err(|errors| {
+    lower_program(&program_ast).map_err(|errors| {
         let mut error_msg = String::from("Semantic errors:\n");
         for error in errors {
             error_msg.push_str(&format!("  {}\n", error));
         }
         error_msg
-    })?;
+    })
+}
+
+// Helper function to compile and run a TILT program
+fn compile_and_run(source: &str) -> Result<String, String> {
+    let program_ir = lower_source(source)?;
 
     // 4. JIT Compilation
     let mut jit = create_test_jit()?;
@@ -248,12 +253,17 @@ fn create_test_jit() -> Result<JIT, String> {
     Ok(JIT {
         module,
         function_ids: HashMap::new(),
+        data_ids: HashMap::new(),
+        compiled_functions: HashMap::new(),
+        code_sizes: HashMap::new(),
         show_cranelift_ir: false,
+        defined_symbols: Arc::default(),
         host_abi: Box::new(tilt_host_abi::JITMemoryHostABI::new()),
     })
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use super::*;
 
@@ -941,4 +951,545 @@ entry:
 
         println!("✓ JIT successfully created with custom Host ABI integration");
     }
+
+    #[test]
+    fn test_cranelift_ir_for_single_function() {
+        let source = r#"
+fn add(a:i32, b:i32) -> i32 {
+entry:
+    sum:i32 = i32.add(a, b)
+    ret (sum)
+}
+
+fn main() -> i32 {
+entry:
+    result:i32 = i32.mul(2, 3)
+    ret (result)
+}
+"#;
+        let program = lower_source(source).unwrap();
+        let mut jit = JIT::new().unwrap();
+        jit.compile(&program).unwrap();
+
+        let ir = jit.cranelift_ir_for("add").expect("IR for 'add'");
+        assert!(ir.contains("iadd"), "unexpected IR: {}", ir);
+        assert!(!ir.contains("imul"), "IR should only cover 'add': {}", ir);
+
+        assert!(jit.cranelift_ir_for("missing").is_none());
+    }
+
+    #[cfg(feature = "disas")]
+    #[test]
+    fn test_disassemble_single_function() {
+        let source = r#"
+fn add(a:i32, b:i32) -> i32 {
+entry:
+    sum:i32 = i32.add(a, b)
+    ret (sum)
+}
+"#;
+        let program = lower_source(source).unwrap();
+        let mut jit = JIT::new().unwrap();
+        jit.compile(&program).unwrap();
+
+        let asm = jit.disassemble("add").expect("disassembly for 'add'");
+        assert!(!asm.is_empty());
+        // x86-64 often adds with `lea`; other targets use `add`
+        assert!(
+            asm.lines()
+                .any(|line| line.contains(" add") || line.contains(" lea")),
+            "unexpected disassembly: {}",
+            asm
+        );
+
+        assert!(jit.disassemble("missing").is_none());
+    }
+
+    #[test]
+    fn test_finalized_functions_returns_every_function() {
+        let source = r#"
+import "env" "print_i32" (n:i32) -> void
+
+fn double(x:i32) -> i32 {
+entry:
+    result:i32 = i32.add(x, x)
+    ret (result)
+}
+
+fn answer() -> i32 {
+entry:
+    result:i32 = double(21)
+    ret (result)
+}
+"#;
+        let program = lower_source(source).unwrap();
+        let mut jit = JIT::new().unwrap();
+        jit.compile(&program).unwrap();
+
+        let functions = jit.finalized_functions();
+        assert_eq!(functions.len(), 2);
+        assert!(!functions.contains_key("print_i32"));
+
+        let double: fn(i32) -> i32 = unsafe { mem::transmute(functions["double"]) };
+        let answer: fn() -> i32 = unsafe { mem::transmute(functions["answer"]) };
+        assert_eq!(double(5), 10);
+        assert_eq!(answer(), 42);
+    }
+
+    #[test]
+    fn test_void_constant_is_rejected() {
+        let mut func = tilt_ir::Function::new("main".to_string(), vec![], IRType::I32);
+        let void_value = func.next_value();
+        let result = func.next_value();
+
+        let mut entry = tilt_ir::BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Const {
+            dest: void_value,
+            value: 0,
+            ty: IRType::Void,
+        });
+        entry.instructions.push(Instruction::Const {
+            dest: result,
+            value: 1,
+            ty: IRType::I32,
+        });
+        entry.terminator = tilt_ir::Terminator::Ret {
+            value: Some(result),
+        };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut jit = JIT::new().unwrap();
+        let err = jit.compile(&program).unwrap_err();
+        assert!(
+            err.contains("constant of type void"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_store_narrows_to_declared_type() {
+        // fn narrow(x:i64, p:usize) -> i32 { i32.store(p, x); r = i32.load(p); ret r }
+        let mut func = tilt_ir::Function::new(
+            "narrow".to_string(),
+            vec![IRType::I64, IRType::Usize],
+            IRType::I32,
+        );
+        let x = func.next_value();
+        let p = func.next_value();
+        let result = func.next_value();
+
+        let mut entry = tilt_ir::BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Store {
+            address: p,
+            value: x,
+            ty: IRType::I32,
+        });
+        entry.instructions.push(Instruction::Load {
+            dest: result,
+            ty: IRType::I32,
+            address: p,
+        });
+        entry.terminator = tilt_ir::Terminator::Ret {
+            value: Some(result),
+        };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut jit = JIT::new().unwrap();
+        jit.compile(&program).unwrap();
+        let narrow_ptr = jit.get_func_ptr("narrow").unwrap();
+        let narrow = unsafe { mem::transmute::<*const u8, fn(i64, *mut i64) -> i32>(narrow_ptr) };
+
+        // Only the low four bytes are written
+        let mut slot: i64 = -1;
+        assert_eq!(narrow(0x1_0000_002A, &mut slot), 42);
+        assert_eq!(slot.to_le_bytes(), [42, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_dense_case_range_picks_jump_table_switches() {
+        let cases = |values: &[i64]| -> Vec<SwitchCase> {
+            values
+                .iter()
+                .map(|value| SwitchCase {
+                    value: *value,
+                    target: BlockId::new(1),
+                    args: vec![],
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            dense_case_range(&cases(&[0, 1, 2, 3, 4, 5, 6, 7])),
+            Some((0, 8))
+        );
+        assert_eq!(dense_case_range(&cases(&[10, 12, 14, 16])), Some((10, 7)));
+        assert_eq!(dense_case_range(&cases(&[1, 100, 1000])), None);
+        assert_eq!(dense_case_range(&cases(&[0, 1, 2])), None);
+        assert_eq!(dense_case_range(&cases(&[0, 1, 2, 1000])), None);
+    }
+
+    #[test]
+    fn test_fused_compare_branch_skips_widening() {
+        let source = r#"
+fn max(a:i32, b:i32) -> i32 {
+entry:
+    b_wins:i32 = i32.lt(a, b)
+    br_if b_wins, take_b, take_a
+
+take_b:
+    ret (b)
+
+take_a:
+    ret (a)
+}
+"#;
+        let mut program = lower_source(source).unwrap();
+        tilt_ir::opt::fold_compare_branch(&mut program.functions[0]);
+
+        let mut jit = JIT::new().unwrap();
+        jit.compile(&program).unwrap();
+
+        let ir = jit.cranelift_ir_for("max").expect("IR for 'max'");
+        assert!(ir.contains("icmp slt"), "unexpected IR: {}", ir);
+        assert!(
+            !ir.contains("uextend"),
+            "comparison should not be widened: {}",
+            ir
+        );
+
+        let max_ptr = jit.get_func_ptr("max").unwrap();
+        let max = unsafe { mem::transmute::<*const u8, fn(i32, i32) -> i32>(max_ptr) };
+        assert_eq!(max(3, 7), 7);
+        assert_eq!(max(7, 3), 7);
+        assert_eq!(max(-2, -2), -2);
+    }
+
+    #[test]
+    fn test_stale_fused_compare_branch_is_not_fused() {
+        let source = r#"
+fn less(a:i32, b:i32) -> i32 {
+entry:
+    lt:i32 = i32.lt(a, b)
+    br_if lt, yes, no
+
+yes:
+    ret (a)
+
+no:
+    ret (b)
+}
+"#;
+        let mut program = lower_source(source).unwrap();
+        let func = &mut program.functions[0];
+        tilt_ir::opt::fold_compare_branch(func);
+        assert!(!func.fused_compare_branches.is_empty());
+
+        // Return the comparison too, which the recorded fusion doesn't know
+        let tilt_ir::Terminator::BrIf { cond, .. } = func.blocks[0].terminator else {
+            unreachable!();
+        };
+        func.blocks[1].terminator = tilt_ir::Terminator::Ret { value: Some(cond) };
+
+        let mut jit = JIT::new().unwrap();
+        jit.compile(&program).unwrap();
+
+        let less_ptr = jit.get_func_ptr("less").unwrap();
+        let less = unsafe { mem::transmute::<*const u8, fn(i32, i32) -> i32>(less_ptr) };
+        assert_eq!(less(3, 7), 1);
+        assert_eq!(less(7, 3), 3);
+    }
+
+    extern "C" fn triple_callback(x: i32) -> i32 {
+        x * 3
+    }
+
+    #[test]
+    fn test_define_symbol_links_custom_import() {
+        let source = r#"
+import "env" "my_callback" (x:i32) -> i32
+
+fn run(x:i32) -> i32 {
+entry:
+    r:i32 = my_callback(x)
+    ret (r)
+}
+"#;
+        let program = lower_source(source).unwrap();
+
+        let mut jit = JIT::new().unwrap();
+        unsafe { jit.define_symbol("my_callback", triple_callback as *const u8) };
+        jit.compile(&program).unwrap();
+
+        let run_ptr = jit.get_func_ptr("run").unwrap();
+        let run = unsafe { mem::transmute::<*const u8, fn(i32) -> i32>(run_ptr) };
+        assert_eq!(run(14), 42);
+    }
+
+    use tilt_host_abi::{HostResult, RuntimeValue};
+
+    /// Host ABI providing `triple`, natively when `native` is set
+    struct TripleHostABI {
+        native: bool,
+    }
+
+    impl HostABI for TripleHostABI {
+        fn call_host_function(&mut self, name: &str, args: &[RuntimeValue]) -> HostResult {
+            match (name, args) {
+                ("triple", [RuntimeValue::I32(x)]) => Ok(RuntimeValue::I32(x * 3)),
+                _ => Err(format!("Unknown host function: {}", name)),
+            }
+        }
+
+        fn available_functions(&self) -> Vec<&str> {
+            vec!["triple"]
+        }
+
+        fn native_symbol(&self, name: &str) -> Option<*const u8> {
+            (self.native && name == "triple").then_some(triple_callback as *const u8)
+        }
+    }
+
+    const TRIPLE_SOURCE: &str = r#"
+import "env" "triple" (x:i32) -> i32
+
+fn run(x:i32) -> i32 {
+entry:
+    r:i32 = triple(x)
+    ret (r)
+}
+"#;
+
+    #[test]
+    fn test_host_abi_function_links_as_import() {
+        let program = lower_source(TRIPLE_SOURCE).unwrap();
+
+        let mut jit = JIT::new_with_abi(Box::new(TripleHostABI { native: true })).unwrap();
+        jit.compile(&program).unwrap();
+
+        let run_ptr = jit.get_func_ptr("run").unwrap();
+        let run = unsafe { mem::transmute::<*const u8, fn(i32) -> i32>(run_ptr) };
+        assert_eq!(run(5), 15);
+    }
+
+    #[test]
+    fn test_host_abi_function_without_native_symbol_is_rejected() {
+        let program = lower_source(TRIPLE_SOURCE).unwrap();
+
+        let mut jit = JIT::new_with_abi(Box::new(TripleHostABI { native: false })).unwrap();
+        let err = jit.compile(&program).unwrap_err();
+        assert_eq!(
+            err,
+            "Host ABI function 'triple' has no native symbol for the JIT to link"
+        );
+    }
+
+    #[test]
+    fn test_builtin_missing_from_host_abi_is_rejected() {
+        let source = r#"
+import "host" "alloc" (size:usize) -> usize
+
+fn run() -> usize {
+entry:
+    p:usize = alloc(8)
+    ret (p)
+}
+"#;
+        let program = lower_source(source).unwrap();
+
+        let mut jit = JIT::new_with_abi(Box::new(tilt_host_abi::NullHostABI::new())).unwrap();
+        let err = jit.compile(&program).unwrap_err();
+        assert_eq!(err, "Host ABI does not provide built-in function 'alloc'");
+    }
+
+    #[test]
+    fn test_jit_alloc_uses_deterministic_arena() {
+        let source = r#"
+import "host" "alloc" (size:usize) -> usize
+
+fn run() -> usize {
+entry:
+    p:usize = alloc(12)
+    ret (p)
+}
+"#;
+        let program = lower_source(source).unwrap();
+
+        let abi = tilt_host_abi::DeterministicJITMemoryHostABI::new();
+        let base = abi.arena_base();
+        let mut jit = JIT::new_with_abi(Box::new(abi)).unwrap();
+        jit.compile(&program).unwrap();
+
+        let run_ptr = jit.get_func_ptr("run").unwrap();
+        let run = unsafe { mem::transmute::<*const u8, fn() -> u64>(run_ptr) };
+        assert_eq!(run(), base);
+    }
+
+    #[test]
+    fn test_import_signature_mismatch_is_rejected() {
+        let source = r#"
+import "host" "print_i32" (a:i32, b:i32) -> void
+
+fn run() -> void {
+entry:
+    one:i32 = i32.const(1)
+    print_i32(one, one)
+    ret
+}
+"#;
+        let program = lower_source(source).unwrap();
+
+        let mut jit = JIT::new().unwrap();
+        let err = jit.compile(&program).unwrap_err();
+        assert_eq!(
+            err,
+            "Import 'print_i32' is declared as (i32, i32) -> void but the host provides (i32) -> void"
+        );
+    }
+
+    #[test]
+    fn test_try_call_to_host_function_is_rejected() {
+        let source = r#"
+import "host" "read_i32" () -> i32
+
+fn run() -> i32 {
+entry:
+    n:i32 = try_call read_i32()
+    one:i32 = i32.const(1)
+    r:i32 = i32.add(n, one)
+    ret (r)
+}
+"#;
+        let program = lower_source(source).unwrap();
+
+        let mut jit = JIT::new().unwrap();
+        let err = jit.compile(&program).unwrap_err();
+        assert!(
+            err.contains("try_call to host function 'read_i32' in 'run' is not supported"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_try_call_to_program_function_runs_like_a_call() {
+        let source = r#"
+fn seven() -> i32 {
+entry:
+    r:i32 = i32.const(7)
+    ret (r)
+}
+
+fn run() -> i32 {
+entry:
+    n:i32 = try_call seven()
+    one:i32 = i32.const(1)
+    r:i32 = i32.add(n, one)
+    ret (r)
+}
+"#;
+        let program = lower_source(source).unwrap();
+
+        let mut jit = JIT::new().unwrap();
+        jit.compile(&program).unwrap();
+        let run = jit.get_func_ptr("run").unwrap();
+        let run = unsafe { std::mem::transmute::<*const u8, fn() -> i32>(run) };
+        assert_eq!(run(), 8);
+    }
+
+    /// Add fn `name`(i: i64) -> i32 returning element `i` of an i32 table
+    fn add_table_lookup(builder: &mut tilt_ir_builder::ProgramBuilder, name: &str, table: &[i32]) {
+        let func_idx = builder.create_function(name, vec![IRType::I64], IRType::I32);
+        let mut fb = builder.function_builder(func_idx);
+        let bytes = table.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let table = fb.add_rodata(bytes);
+
+        let entry = fb.create_block("entry");
+        fb.switch_to_block(entry);
+        let i = fb.declare_entry_params()[0];
+        let base = fb.ins().data_addr(table);
+        let index = fb.ins().convert(i, IRType::I64, IRType::Usize);
+        let size = fb.ins().size_of(IRType::I32);
+        let offset = fb.ins().mul(IRType::Usize, index, size);
+        let addr = fb.ins().ptr_add(base, offset);
+        let value = fb.ins().load(IRType::I32, addr);
+        fb.ins().ret(Some(value));
+    }
+
+    #[test]
+    fn test_shared_rodata_is_one_data_object() {
+        let squares: Vec<i32> = (0..16).map(|i| i * i).collect();
+        let cubes: Vec<i32> = (0..16).map(|i| i * i * i).collect();
+        let mut builder = tilt_ir_builder::ProgramBuilder::new();
+        add_table_lookup(&mut builder, "square", &squares);
+        add_table_lookup(&mut builder, "square_again", &squares);
+        add_table_lookup(&mut builder, "cube", &cubes);
+        let mut program = builder.build();
+        assert!(tilt_ir::opt::share_rodata(&mut program));
+
+        let mut jit = JIT::new().unwrap();
+        jit.compile(&program).unwrap();
+
+        // The two 64-byte square tables are one object; the cubes another
+        let objects: std::collections::HashSet<_> = jit.data_ids.values().collect();
+        assert_eq!(jit.data_ids.len(), 3);
+        assert_eq!(objects.len(), 2);
+        assert_eq!(
+            jit.data_ids[&("square".to_string(), tilt_ir::DataId::new(0))],
+            jit.data_ids[&("square_again".to_string(), tilt_ir::DataId::new(0))]
+        );
+
+        for (name, expected) in [("square", 49), ("square_again", 49), ("cube", 343)] {
+            let ptr = jit.get_func_ptr(name).unwrap();
+            let lookup = unsafe { mem::transmute::<*const u8, fn(i64) -> i32>(ptr) };
+            assert_eq!(lookup(7), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_compile_adds_functions_incrementally() {
+        let double = r#"
+fn double(x:i32) -> i32 {
+entry:
+    result:i32 = i32.add(x, x)
+    ret (result)
+}
+"#;
+        let answer = r#"
+fn answer() -> i32 {
+entry:
+    result:i32 = double(21)
+    ret (result)
+}
+"#;
+        let mut jit = JIT::new().unwrap();
+        jit.compile(&lower_source(double).unwrap()).unwrap();
+        let first_double = jit.get_func_ptr("double").unwrap();
+
+        // Recompiling `double` alongside a new caller keeps the existing code
+        jit.compile(&lower_source(&format!("{}{}", double, answer)).unwrap())
+            .unwrap();
+        assert_eq!(jit.get_func_ptr("double").unwrap(), first_double);
+        let answer_fn = unsafe {
+            mem::transmute::<*const u8, fn() -> i32>(jit.get_func_ptr("answer").unwrap())
+        };
+        assert_eq!(answer_fn(), 42);
+
+        // A compiled function's body can't change
+        let changed = double.replace("i32.add(x, x)", "i32.mul(x, x)");
+        let error = jit.compile(&lower_source(&changed).unwrap()).unwrap_err();
+        assert!(error.contains("already compiled"), "{}", error);
+    }
 }
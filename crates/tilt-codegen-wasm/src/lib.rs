@@ -0,0 +1,632 @@
+// ===================================================================
+// FILE: lib.rs (tilt-codegen-wasm crate)
+//
+// DESC: WebAssembly text (WAT) backend for TILT IR. Emits a wasm32
+//       module covering the subset the VM fully supports: arithmetic,
+//       comparisons, branches, calls and memory access, with `alloc`
+//       and `free` going through imported host functions.
+// ===================================================================
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use tilt_ast::Type;
+use tilt_ir::visit::{InstructionVisitor, walk_program};
+use tilt_ir::*;
+
+#[cfg(test)]
+mod tests;
+
+/// Translate a program into a WebAssembly text module. Every function is
+/// exported under its own name, and pointers and `usize` are 32 bits wide
+/// as on wasm32. Returns an error for IR the backend cannot express yet,
+/// such as read-only data.
+pub fn emit_wat(program: &Program) -> Result<String, String> {
+    let mut out = String::from("(module\n");
+
+    for import in &program.imports {
+        let mut signature = String::new();
+        for ty in &import.params {
+            write!(signature, " (param {})", wasm_type(ty)?).unwrap();
+        }
+        if import.return_type != Type::Void {
+            write!(signature, " (result {})", wasm_type(&import.return_type)?).unwrap();
+        }
+        writeln!(
+            out,
+            "  (import \"{}\" \"{}\" (func ${}{}))",
+            import.module,
+            import.name,
+            import.call_name(),
+            signature
+        )
+        .unwrap();
+    }
+
+    // `alloc` and `free` are host functions; import them unless the
+    // program already declares functions by those names
+    let mut usage = MemoryUsage::default();
+    walk_program(program, &mut usage);
+    if usage.alloc && program.callee("alloc").is_none() {
+        out.push_str("  (import \"host\" \"alloc\" (func $alloc (param i32) (result i32)))\n");
+    }
+    if usage.free && program.callee("free").is_none() {
+        out.push_str("  (import \"host\" \"free\" (func $free (param i32)))\n");
+    }
+    if usage.memory {
+        out.push_str("  (memory (export \"memory\") 1)\n");
+    }
+
+    for func in &program.functions {
+        FunctionEmitter::new(program, func).emit(&mut out)?;
+    }
+
+    out.push_str(")\n");
+    Ok(out)
+}
+
+/// Which memory features a program uses
+#[derive(Default)]
+struct MemoryUsage {
+    memory: bool,
+    alloc: bool,
+    free: bool,
+}
+
+impl InstructionVisitor for MemoryUsage {
+    fn visit_load(&mut self, _dest: ValueId, _ty: &Type, _address: ValueId) {
+        self.memory = true;
+    }
+
+    fn visit_store(&mut self, _address: ValueId, _value: ValueId, _ty: &Type) {
+        self.memory = true;
+    }
+
+    fn visit_alloc(&mut self, _dest: ValueId, _size: ValueId) {
+        self.memory = true;
+        self.alloc = true;
+    }
+
+    fn visit_free(&mut self, _ptr: ValueId) {
+        self.memory = true;
+        self.free = true;
+    }
+}
+
+/// Emits one function. Blocks become arms of a dispatch loop: `$block`
+/// holds the index of the block to run next, a `br_table` jumps to it, and
+/// branches set `$block` and restart the loop. Every SSA value is a local
+/// named after its ID, so function parameters are `$v0`, `$v1` and so on.
+struct FunctionEmitter<'a> {
+    program: &'a Program,
+    func: &'a Function,
+    /// Position of each block in `func.blocks`
+    block_index: HashMap<BlockId, usize>,
+    /// Type of every value the function defines
+    types: HashMap<ValueId, Type>,
+}
+
+impl<'a> FunctionEmitter<'a> {
+    fn new(program: &'a Program, func: &'a Function) -> Self {
+        let block_index = func
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (block.id, index))
+            .collect();
+
+        let mut types: HashMap<ValueId, Type> = func
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| (ValueId(i), ty.clone()))
+            .collect();
+        for block in &func.blocks {
+            types.extend(block.params.iter().cloned());
+            for instruction in &block.instructions {
+                if let Some(dest) = instruction.dest() {
+                    // `PtrAdd` has no result type of its own; all pointers
+                    // are the same on wasm32
+                    let ty = instruction.result_type().unwrap_or(Type::Usize);
+                    types.insert(dest, ty);
+                }
+            }
+        }
+
+        Self {
+            program,
+            func,
+            block_index,
+            types,
+        }
+    }
+
+    fn emit(&self, out: &mut String) -> Result<(), String> {
+        let func = self.func;
+        write!(out, "  (func ${} (export \"{}\")", func.name, func.name).unwrap();
+        for (i, ty) in func.params.iter().enumerate() {
+            write!(out, " (param $v{} {})", i, wasm_type(ty)?).unwrap();
+        }
+        if func.return_type != Type::Void {
+            write!(out, " (result {})", wasm_type(&func.return_type)?).unwrap();
+        }
+        out.push('\n');
+
+        line(out, 2, "(local $block i32)");
+        let mut locals: Vec<(ValueId, Type)> = self
+            .types
+            .iter()
+            .filter(|(value, _)| value.0 >= func.params.len())
+            .map(|(value, ty)| (*value, ty.clone()))
+            .collect();
+        locals.sort_by_key(|(value, _)| *value);
+        for (value, ty) in locals {
+            line(
+                out,
+                2,
+                &format!("(local $v{} {})", value.0, wasm_type(&ty)?),
+            );
+        }
+
+        let entry_index = self.target_index(func.entry_block)?;
+        let entry = &func.blocks[entry_index];
+        for (i, (param, _)) in entry.params.iter().enumerate() {
+            if param.0 != i {
+                line(
+                    out,
+                    2,
+                    &format!("(local.set $v{} (local.get $v{}))", param.0, i),
+                );
+            }
+        }
+        line(
+            out,
+            2,
+            &format!("(local.set $block (i32.const {}))", entry_index),
+        );
+
+        let count = func.blocks.len();
+        line(out, 2, "(loop $dispatch");
+        for index in (0..count).rev() {
+            line(out, 3 + count - 1 - index, &format!("(block $b{}", index));
+        }
+        let labels: Vec<String> = (0..count).map(|index| format!("$b{}", index)).collect();
+        line(
+            out,
+            3 + count,
+            &format!("(br_table {} (local.get $block))", labels.join(" ")),
+        );
+        for (index, block) in func.blocks.iter().enumerate() {
+            let depth = 3 + count - 1 - index;
+            line(out, depth, ")");
+            line(out, depth, &format!(";; {}", block.label));
+            for instruction in &block.instructions {
+                self.emit_instruction(out, depth, instruction)?;
+            }
+            self.emit_terminator(out, depth, &block.terminator)?;
+        }
+        line(out, 2, ")");
+        // Every block leaves through `return` or `br`, so control never
+        // falls out of the loop
+        line(out, 2, "unreachable");
+        line(out, 1, ")");
+        Ok(())
+    }
+
+    fn emit_instruction(
+        &self,
+        out: &mut String,
+        depth: usize,
+        instruction: &Instruction,
+    ) -> Result<(), String> {
+        let set = |out: &mut String, dest: &ValueId, expr: String| {
+            line(out, depth, &format!("(local.set $v{} {})", dest.0, expr));
+        };
+
+        match instruction {
+            Instruction::BinaryOp {
+                dest,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let name = binary_instruction(*op, ty)?;
+                set(
+                    out,
+                    dest,
+                    format!("({} {} {})", name, self.operand(*lhs)?, self.operand(*rhs)?),
+                );
+            }
+            Instruction::UnaryOp {
+                dest,
+                op,
+                ty,
+                operand,
+            } => set(out, dest, unary_expr(*op, ty, self.operand(*operand)?)?),
+            Instruction::Call {
+                dest,
+                function,
+                args,
+                ..
+            }
+            | Instruction::TryCall {
+                dest,
+                function,
+                args,
+                ..
+            } => set(out, dest, self.call_expr(function, args)?),
+            Instruction::CallVoid { function, args } => {
+                let call = self.call_expr(function, args)?;
+                let returns_value = match self.program.callee(function) {
+                    Some(Callee::Local(callee)) => callee.return_type != Type::Void,
+                    Some(Callee::Import(import)) => import.return_type != Type::Void,
+                    None => false,
+                };
+                if returns_value {
+                    line(out, depth, &format!("(drop {})", call));
+                } else {
+                    line(out, depth, &call);
+                }
+            }
+            Instruction::Load { dest, ty, address } => {
+                let expr = format!("({}.load {})", wasm_type(ty)?, self.operand(*address)?);
+                set(out, dest, expr);
+            }
+            Instruction::Store { address, value, ty } => {
+                let store = format!(
+                    "({}.store {} {})",
+                    wasm_type(ty)?,
+                    self.operand(*address)?,
+                    self.operand(*value)?
+                );
+                line(out, depth, &store);
+            }
+            Instruction::Const { dest, value, ty } => set(out, dest, const_expr(*value, ty)?),
+            Instruction::PtrAdd { dest, ptr, offset } => {
+                let expr = format!(
+                    "(i32.add {} {})",
+                    self.operand(*ptr)?,
+                    self.operand(*offset)?
+                );
+                set(out, dest, expr);
+            }
+            Instruction::SizeOf { dest, ty } => {
+                set(out, dest, format!("(i32.const {})", wasm_size(ty)?));
+            }
+            Instruction::Alloc { dest, size } => {
+                set(out, dest, format!("(call $alloc {})", self.operand(*size)?));
+            }
+            Instruction::Free { ptr } => {
+                line(out, depth, &format!("(call $free {})", self.operand(*ptr)?));
+            }
+            Instruction::Convert {
+                dest,
+                src,
+                from_ty,
+                to_ty,
+            } => set(
+                out,
+                dest,
+                convert_expr(from_ty, to_ty, self.operand(*src)?)?,
+            ),
+            Instruction::ConvertChecked {
+                dest,
+                src,
+                from_ty,
+                to_ty,
+            } => {
+                let src = self.operand(*src)?;
+                let overflow = match (from_ty.repr(), to_ty.repr()) {
+                    (Type::I64, Type::I32) => {
+                        set(out, dest, format!("(i32.wrap_i64 {})", src));
+                        format!(
+                            "(i64.ne (i64.extend_i32_s (local.get $v{})) {})",
+                            dest.0, src
+                        )
+                    }
+                    // A 32-bit usize above i32::MAX reads as negative
+                    (Type::Usize, Type::I32) => {
+                        set(out, dest, src);
+                        format!("(i32.lt_s (local.get $v{}) (i32.const 0))", dest.0)
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Unsupported checked conversion from {} to {}",
+                            from_ty, to_ty
+                        ));
+                    }
+                };
+                line(out, depth, &format!("(if {} (then unreachable))", overflow));
+            }
+            Instruction::DataAddr { .. } => {
+                return Err(format!(
+                    "Read-only data in function '{}' is not supported by the WebAssembly backend",
+                    self.func.name
+                ));
+            }
+            // Only the verifier looks at type assertions
+            Instruction::AssertType { .. } => {}
+            Instruction::Phi { .. } => {
+                return Err(
+                    "Phi instructions must be eliminated before emitting WebAssembly (see tilt_ir::phi)"
+                        .to_string(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_terminator(
+        &self,
+        out: &mut String,
+        depth: usize,
+        terminator: &Terminator,
+    ) -> Result<(), String> {
+        match terminator {
+            Terminator::Ret { value: Some(value) } => {
+                line(out, depth, &format!("(return {})", self.operand(*value)?));
+            }
+            Terminator::Ret { value: None } => line(out, depth, "(return)"),
+            Terminator::Br { target, args } => self.emit_jump(out, depth, *target, args)?,
+            Terminator::BrIf {
+                cond,
+                true_target,
+                true_args,
+                false_target,
+                false_args,
+            } => {
+                line(out, depth, &format!("(if {}", self.condition(*cond)?));
+                line(out, depth + 1, "(then");
+                self.emit_jump(out, depth + 2, *true_target, true_args)?;
+                line(out, depth + 1, ")");
+                line(out, depth + 1, "(else");
+                self.emit_jump(out, depth + 2, *false_target, false_args)?;
+                line(out, depth + 1, ")");
+                line(out, depth, ")");
+            }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+                default_args,
+            } => {
+                let ty = self.value_type(*value).unwrap_or(Type::I32);
+                let scrutinee = self.operand(*value)?;
+                for case in cases {
+                    line(
+                        out,
+                        depth,
+                        &format!(
+                            "(if ({}.eq {} {})",
+                            wasm_type(&ty)?,
+                            scrutinee,
+                            const_expr(case.value, &ty)?
+                        ),
+                    );
+                    line(out, depth + 1, "(then");
+                    self.emit_jump(out, depth + 2, case.target, &case.args)?;
+                    line(out, depth + 1, ")");
+                    line(out, depth, ")");
+                }
+                self.emit_jump(out, depth, *default, default_args)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pass `args` to the target block's parameters and continue there. All
+    /// arguments are pushed before any parameter is written, so branches
+    /// that swap values stay correct.
+    fn emit_jump(
+        &self,
+        out: &mut String,
+        depth: usize,
+        target: BlockId,
+        args: &[ValueId],
+    ) -> Result<(), String> {
+        let index = self.target_index(target)?;
+        for arg in args {
+            line(out, depth, &self.operand(*arg)?);
+        }
+        for (param, _) in self.func.blocks[index].params.iter().rev() {
+            line(out, depth, &format!("(local.set $v{})", param.0));
+        }
+        line(
+            out,
+            depth,
+            &format!("(local.set $block (i32.const {}))", index),
+        );
+        line(out, depth, "(br $dispatch)");
+        Ok(())
+    }
+
+    fn call_expr(&self, function: &str, args: &[ValueId]) -> Result<String, String> {
+        let mut call = format!("(call ${}", function);
+        for arg in args {
+            write!(call, " {}", self.operand(*arg)?).unwrap();
+        }
+        call.push(')');
+        Ok(call)
+    }
+
+    /// A branch condition as a wasm `i32`; any nonzero value is true
+    fn condition(&self, cond: ValueId) -> Result<String, String> {
+        let operand = self.operand(cond)?;
+        Ok(match self.value_type(cond).as_ref().map(Type::repr) {
+            Some(Type::I64) => format!("(i64.ne {} (i64.const 0))", operand),
+            _ => operand,
+        })
+    }
+
+    /// The expression reading `value`: constants are inlined, anything else
+    /// reads its local
+    fn operand(&self, value: ValueId) -> Result<String, String> {
+        match self.func.constants.get(&value) {
+            Some((constant, ty)) => const_expr(*constant, ty),
+            None => Ok(format!("(local.get $v{})", value.0)),
+        }
+    }
+
+    fn value_type(&self, value: ValueId) -> Option<Type> {
+        self.types
+            .get(&value)
+            .or_else(|| self.func.constants.get(&value).map(|(_, ty)| ty))
+            .cloned()
+    }
+
+    fn target_index(&self, target: BlockId) -> Result<usize, String> {
+        self.block_index.get(&target).copied().ok_or_else(|| {
+            format!(
+                "Block {:?} not found in function '{}'",
+                target, self.func.name
+            )
+        })
+    }
+}
+
+/// Append one line of WAT indented by `depth` levels
+fn line(out: &mut String, depth: usize, text: &str) {
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+    out.push_str(text);
+    out.push('\n');
+}
+
+/// Get the wasm value type that holds a TILT type
+fn wasm_type(ty: &Type) -> Result<&'static str, String> {
+    match ty.repr() {
+        Type::I32 | Type::Usize => Ok("i32"),
+        Type::I64 => Ok("i64"),
+        Type::F32 => Ok("f32"),
+        Type::F64 => Ok("f64"),
+        Type::Void | Type::Ptr(_) => Err(format!("Type {} has no WebAssembly value type", ty)),
+    }
+}
+
+/// Get the size of a type on wasm32
+fn wasm_size(ty: &Type) -> Result<u32, String> {
+    match ty.repr() {
+        Type::I32 | Type::F32 | Type::Usize => Ok(4),
+        Type::I64 | Type::F64 => Ok(8),
+        Type::Void | Type::Ptr(_) => Err(format!("Type {} has no size", ty)),
+    }
+}
+
+/// A constant as a wasm expression. Floats are rebuilt from their bits so
+/// no precision is lost in the text form. A `usize` constant wider than
+/// wasm32's 32 bits is an error rather than truncated.
+fn const_expr(value: i64, ty: &Type) -> Result<String, String> {
+    if !constant_fits_pointer_width(value, ty, 4) {
+        return Err(format!(
+            "Constant {} does not fit in {} on wasm32",
+            value, ty
+        ));
+    }
+    Ok(match ty.repr() {
+        Type::I32 | Type::Usize => format!("(i32.const {})", value as i32),
+        Type::I64 => format!("(i64.const {})", value),
+        Type::F32 => format!("(f32.reinterpret_i32 (i32.const {}))", value as i32),
+        Type::F64 => format!("(f64.reinterpret_i64 (i64.const {}))", value),
+        Type::Void | Type::Ptr(_) => {
+            return Err(format!("Constant of type {} is not supported", ty));
+        }
+    })
+}
+
+/// Get the wasm instruction for a binary operator. `usize` operations are
+/// unsigned, like the VM's.
+fn binary_instruction(op: BinaryOperator, ty: &Type) -> Result<String, String> {
+    let float = matches!(ty, Type::F32 | Type::F64);
+    let sign = if ty.repr() == Type::Usize { "_u" } else { "_s" };
+    let name = match op {
+        BinaryOperator::Add => "add".to_string(),
+        BinaryOperator::Sub => "sub".to_string(),
+        BinaryOperator::Mul => "mul".to_string(),
+        BinaryOperator::Div if float => "div".to_string(),
+        BinaryOperator::Div => format!("div{}", sign),
+        BinaryOperator::Rem if !float => format!("rem{}", sign),
+        BinaryOperator::And if !float => "and".to_string(),
+        BinaryOperator::Or if !float => "or".to_string(),
+        BinaryOperator::Xor if !float => "xor".to_string(),
+        BinaryOperator::Shl if !float => "shl".to_string(),
+        BinaryOperator::Shr if !float => format!("shr{}", sign),
+        BinaryOperator::Eq => "eq".to_string(),
+        BinaryOperator::Ne => "ne".to_string(),
+        BinaryOperator::Lt | BinaryOperator::Le | BinaryOperator::Gt | BinaryOperator::Ge => {
+            let base = match op {
+                BinaryOperator::Lt => "lt",
+                BinaryOperator::Le => "le",
+                BinaryOperator::Gt => "gt",
+                _ => "ge",
+            };
+            if float {
+                base.to_string()
+            } else {
+                format!("{}{}", base, sign)
+            }
+        }
+        _ => {
+            return Err(format!("Binary operator {:?} not supported for {}", op, ty));
+        }
+    };
+    Ok(format!("{}.{}", wasm_type(ty)?, name))
+}
+
+/// Build the wasm expression for a unary operator applied to `operand`
+fn unary_expr(op: UnaryOperator, ty: &Type, operand: String) -> Result<String, String> {
+    let float = matches!(ty, Type::F32 | Type::F64);
+    let t = wasm_type(ty)?;
+    Ok(match op {
+        UnaryOperator::Neg if float => format!("({}.neg {})", t, operand),
+        UnaryOperator::Neg => format!("({}.sub ({}.const 0) {})", t, t, operand),
+        UnaryOperator::Not if !float => format!("({}.xor {} ({}.const -1))", t, operand, t),
+        UnaryOperator::Eqz if !float => format!("({}.eqz {})", t, operand),
+        UnaryOperator::Sqrt if float => format!("({}.sqrt {})", t, operand),
+        UnaryOperator::Floor if float => format!("({}.floor {})", t, operand),
+        UnaryOperator::Ceil if float => format!("({}.ceil {})", t, operand),
+        UnaryOperator::Abs if float => format!("({}.abs {})", t, operand),
+        _ => {
+            return Err(format!("Unary operator {:?} not supported for {}", op, ty));
+        }
+    })
+}
+
+/// Build the wasm expression for a conversion the VM supports. Float to
+/// integer conversions saturate, like Rust's `as`.
+fn convert_expr(from_ty: &Type, to_ty: &Type, src: String) -> Result<String, String> {
+    let unsupported = || {
+        Err(format!(
+            "Unsupported type conversion from {} to {}",
+            from_ty, to_ty
+        ))
+    };
+    let (from, to) = (from_ty.repr(), to_ty.repr());
+    if from == to {
+        return Ok(src);
+    }
+    Ok(match (&from, &to) {
+        // Both are i32 on wasm32
+        (Type::I32, Type::Usize) | (Type::Usize, Type::I32) => src,
+        (Type::I32, Type::I64) => format!("(i64.extend_i32_s {})", src),
+        (Type::Usize, Type::I64) => format!("(i64.extend_i32_u {})", src),
+        (Type::I64, Type::I32) | (Type::I64, Type::Usize) => format!("(i32.wrap_i64 {})", src),
+        (Type::I32 | Type::I64, Type::F32 | Type::F64) => format!(
+            "({}.convert_{}_s {})",
+            wasm_type(&to)?,
+            wasm_type(&from)?,
+            src
+        ),
+        (Type::F32 | Type::F64, Type::I32 | Type::I64) => format!(
+            "({}.trunc_sat_{}_s {})",
+            wasm_type(&to)?,
+            wasm_type(&from)?,
+            src
+        ),
+        (Type::F32, Type::F64) => format!("(f64.promote_f32 {})", src),
+        (Type::F64, Type::F32) => format!("(f32.demote_f64 {})", src),
+        _ => return unsupported(),
+    })
+}
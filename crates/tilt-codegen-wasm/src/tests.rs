@@ -0,0 +1,104 @@
+// ===================================================================
+// FILE: tests.rs (tilt-codegen-wasm crate)
+//
+// DESC: Tests for the WebAssembly text backend.
+// ===================================================================
+
+use super::*;
+use tiltc::compile_str;
+
+/// Assemble the text module and check that it is a valid wasm module
+fn validate(wat: &str) {
+    let bytes = wat::parse_str(wat).unwrap_or_else(|e| panic!("{}\n{}", e, wat));
+    wasmparser::validate(&bytes).unwrap_or_else(|e| panic!("{}\n{}", e, wat));
+}
+
+#[test]
+fn test_add_emits_i32_add() {
+    let program = compile_str(
+        r#"
+fn add(a:i32, b:i32) -> i32 {
+entry:
+    sum:i32 = i32.add(a, b)
+    ret (sum)
+}
+"#,
+    )
+    .unwrap();
+
+    let wat = emit_wat(&program).unwrap();
+    assert!(wat.starts_with("(module"));
+    assert!(
+        wat.contains("(func $add (export \"add\") (param $v0 i32) (param $v1 i32) (result i32)")
+    );
+    assert!(wat.contains("(i32.add (local.get $v0) (local.get $v1))"));
+    validate(&wat);
+}
+
+#[test]
+fn test_loops_and_memory_use_dispatch_and_host_alloc() {
+    let program = compile_str(
+        r#"
+fn sum_to(n:i32) -> i32 {
+entry:
+    size:usize = sizeof.i32()
+    p:usize = alloc(size)
+    i32.store(p, 0)
+    br loop(n)
+
+loop(i:i32):
+    more:i32 = i32.gt(i, 0)
+    br_if more, body(i), done(i)
+
+body(k:i32):
+    total:i32 = i32.load(p)
+    next_total:i32 = i32.add(total, k)
+    i32.store(p, next_total)
+    next_k:i32 = i32.sub(k, 1)
+    br loop(next_k)
+
+done(unused:i32):
+    result:i32 = i32.load(p)
+    free(p)
+    ret (result)
+}
+"#,
+    )
+    .unwrap();
+
+    let wat = emit_wat(&program).unwrap();
+    assert!(wat.contains("(import \"host\" \"alloc\" (func $alloc (param i32) (result i32)))"));
+    assert!(wat.contains("(import \"host\" \"free\" (func $free (param i32)))"));
+    assert!(wat.contains("(memory (export \"memory\") 1)"));
+    assert!(wat.contains("(br_table $b0 $b1 $b2 $b3 (local.get $block))"));
+    assert!(wat.contains("(i32.gt_s"));
+    assert!(wat.contains("(i32.const 4)"));
+    validate(&wat);
+}
+
+#[test]
+fn test_usize_constant_wider_than_wasm32_is_rejected() {
+    let source = |value: &str| {
+        format!(
+            r#"
+fn big() -> usize {{
+entry:
+    r:usize = usize.const({})
+    ret (r)
+}}
+"#,
+            value
+        )
+    };
+
+    let program = compile_str(&source("4294967295")).unwrap();
+    let wat = emit_wat(&program).unwrap();
+    assert!(wat.contains("(i32.const -1)"));
+    validate(&wat);
+
+    let program = compile_str(&source("4294967296")).unwrap();
+    assert_eq!(
+        emit_wat(&program).unwrap_err(),
+        "Constant 4294967296 does not fit in usize on wasm32"
+    );
+}
@@ -6,6 +6,8 @@
 //       environment in a portable way, whether running in JIT or interpreter.
 // ===================================================================
 
+use std::cell::{Cell, RefCell};
+use std::sync::{Arc, Mutex, Weak};
 use tilt_ast::Type;
 
 /// Runtime values that can be passed between TILT and the host
@@ -14,6 +16,8 @@ pub enum RuntimeValue {
     I32(i32),
     I64(i64),
     Usize(usize), // Platform-native unsigned integer for sizes, indices, and pointers
+    F32(f32),
+    F64(f64),
     Void,
 }
 
@@ -24,6 +28,8 @@ impl RuntimeValue {
             RuntimeValue::I32(_) => Type::I32,
             RuntimeValue::I64(_) => Type::I64,
             RuntimeValue::Usize(_) => Type::Usize,
+            RuntimeValue::F32(_) => Type::F32,
+            RuntimeValue::F64(_) => Type::F64,
             RuntimeValue::Void => Type::Void,
         }
     }
@@ -52,6 +58,22 @@ impl RuntimeValue {
         }
     }
 
+    /// Extract an f32 value, panicking if the type doesn't match
+    pub fn as_f32(&self) -> f32 {
+        match self {
+            RuntimeValue::F32(val) => *val,
+            _ => panic!("Expected f32, got {:?}", self),
+        }
+    }
+
+    /// Extract an f64 value, panicking if the type doesn't match
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            RuntimeValue::F64(val) => *val,
+            _ => panic!("Expected f64, got {:?}", self),
+        }
+    }
+
     /// Try to extract an i32 value, returning None if the type doesn't match
     pub fn try_as_i32(&self) -> Option<i32> {
         match self {
@@ -75,11 +97,65 @@ impl RuntimeValue {
             _ => None,
         }
     }
+
+    /// Try to extract an f32 value, returning None if the type doesn't match
+    pub fn try_as_f32(&self) -> Option<f32> {
+        match self {
+            RuntimeValue::F32(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    /// Try to extract an f64 value, returning None if the type doesn't match
+    pub fn try_as_f64(&self) -> Option<f64> {
+        match self {
+            RuntimeValue::F64(val) => Some(*val),
+            _ => None,
+        }
+    }
 }
 
-/// Result type for host function calls
+/// Result type for host function calls.
+///
+/// An `Err` aborts the running TILT program. Host functions that can fail in a
+/// way the program may want to handle should instead return `HOST_ERROR_RESULT`
+/// and record an error code that the program can fetch with `last_error()`.
 pub type HostResult = Result<RuntimeValue, String>;
 
+/// Value returned by host functions that fail with a recoverable error
+pub const HOST_ERROR_RESULT: i32 = -1;
+
+/// Error code reported by `last_error()` when no host call has failed
+pub const HOST_ERR_NONE: i32 = 0;
+
+/// Error code reported by `last_error()` when a host function received invalid input
+pub const HOST_ERR_INVALID_INPUT: i32 = 1;
+
+thread_local! {
+    /// Code of the recoverable error reported by the most recent native host
+    /// call on this thread. JIT code runs on the thread that calls it, so
+    /// programs on other threads can't see it.
+    static NATIVE_LAST_ERROR: Cell<i32> = const { Cell::new(HOST_ERR_NONE) };
+}
+
+/// Start a native host call, the kind JIT code calls directly. Every call
+/// but `last_error` itself clears the error code, so `native_last_error`
+/// reports on the most recent call, as `HostABI::last_error` does.
+pub fn begin_native_call() {
+    NATIVE_LAST_ERROR.set(HOST_ERR_NONE);
+}
+
+/// Record a recoverable error from the current native host call
+pub fn set_native_error(code: i32) {
+    NATIVE_LAST_ERROR.set(code);
+}
+
+/// Get the code of the recoverable error reported by the most recent native
+/// host call on this thread
+pub fn native_last_error() -> i32 {
+    NATIVE_LAST_ERROR.get()
+}
+
 /// Trait that defines the interface between TILT programs and the host environment.
 /// This allows the same TILT code to run portably across different execution environments
 /// (JIT, interpreter, etc.) by abstracting the host interaction layer.
@@ -95,6 +171,46 @@ pub trait HostABI {
         self.available_functions().contains(&name)
     }
 
+    /// Get the parameter and return types of a host function, if known.
+    /// Callers use this to validate arguments before `call_host_function`.
+    /// The default knows the built-in functions this ABI provides; an ABI
+    /// adding functions of its own overrides it to describe them too.
+    fn function_signature(&self, name: &str) -> Option<(Vec<Type>, Type)> {
+        if self.has_function(name) {
+            builtin_signature(name)
+        } else {
+            None
+        }
+    }
+
+    /// Get the code of the recoverable error reported by the most recent
+    /// host call, or `HOST_ERR_NONE` if it succeeded
+    fn last_error(&self) -> i32 {
+        HOST_ERR_NONE
+    }
+
+    /// Clear per-run state such as allocations and the last error, so one
+    /// ABI instance can be reused across runs deterministically
+    fn reset(&mut self) {}
+
+    /// Get a native function implementing `name` with the C ABI, for JIT
+    /// compiled code to call directly. The JIT links imports of host
+    /// functions it has no built-in symbol for through this.
+    fn native_symbol(&self, _name: &str) -> Option<*const u8> {
+        None
+    }
+
+    /// Check `ptr + offset` before the VM computes it; an error aborts the
+    /// program. The default accepts all pointer arithmetic.
+    fn check_ptr_add(&self, _ptr: u64, _offset: u64) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Mark `len` bytes starting at `addr` as read-only, after the VM has
+    /// copied read-only data there. ABIs that track memory reject later
+    /// writes to the region; the default ignores it.
+    fn mark_read_only(&mut self, _addr: u64, _len: usize) {}
+
     /// Read a typed value from memory (default implementation returns error)
     fn read_memory_value(&self, _addr: u64, _ty: tilt_ast::Type) -> Result<RuntimeValue, String> {
         Err("Memory operations not supported by this host ABI".to_string())
@@ -104,15 +220,64 @@ pub trait HostABI {
     fn write_memory_value(&mut self, _addr: u64, _value: &RuntimeValue) -> Result<(), String> {
         Err("Memory operations not supported by this host ABI".to_string())
     }
+
+    /// Read `len` raw bytes starting at `addr` (default implementation returns error)
+    fn read_memory_bytes(&self, _addr: u64, _len: usize) -> Result<Vec<u8>, String> {
+        Err("Memory operations not supported by this host ABI".to_string())
+    }
+
+    /// Write raw bytes starting at `addr` (default implementation returns error)
+    fn write_memory_bytes(&mut self, _addr: u64, _data: &[u8]) -> Result<(), String> {
+        Err("Memory operations not supported by this host ABI".to_string())
+    }
+}
+
+/// Get the signature of a host function provided by the built-in ABIs
+pub fn builtin_signature(name: &str) -> Option<(Vec<Type>, Type)> {
+    let signature = match name {
+        "print_hello" | "println" => (vec![], Type::Void),
+        "print_i32" | "print_char" => (vec![Type::I32], Type::Void),
+        "print_i64" => (vec![Type::I64], Type::Void),
+        "print_ptr" | "free" => (vec![Type::Usize], Type::Void),
+        "read_i32" | "last_error" => (vec![], Type::I32),
+        "alloc" => (vec![Type::Usize], Type::Usize),
+        "alloc_aligned" => (vec![Type::Usize, Type::Usize], Type::Usize),
+        _ => return None,
+    };
+    Some(signature)
 }
 
 /// Standard console-based host ABI implementation
 /// This provides basic I/O functions for console interaction
-pub struct ConsoleHostABI;
+pub struct ConsoleHostABI {
+    /// Code of the last recoverable error, returned by `last_error()`
+    last_error: i32,
+    /// Captured output, or `None` to print directly to stdout
+    buffer: Option<String>,
+}
 
 impl ConsoleHostABI {
     pub fn new() -> Self {
-        Self
+        Self {
+            last_error: HOST_ERR_NONE,
+            buffer: None,
+        }
+    }
+
+    /// Write program output to the buffer if there is one, otherwise to stdout
+    fn write_output(&mut self, text: &str) {
+        match &mut self.buffer {
+            Some(buffer) => buffer.push_str(text),
+            None => print!("{}", text),
+        }
+    }
+
+    /// Start a host call. Every call but `last_error` itself clears the
+    /// error code, so `last_error()` reports on the most recent call.
+    fn begin_call(&mut self, name: &str) {
+        if name != "last_error" {
+            self.last_error = HOST_ERR_NONE;
+        }
     }
 }
 
@@ -124,6 +289,7 @@ impl Default for ConsoleHostABI {
 
 impl HostABI for ConsoleHostABI {
     fn call_host_function(&mut self, name: &str, args: &[RuntimeValue]) -> HostResult {
+        self.begin_call(name);
         match name {
             "print_hello" => {
                 if !args.is_empty() {
@@ -132,7 +298,7 @@ impl HostABI for ConsoleHostABI {
                         args.len()
                     ));
                 }
-                println!("Hello from TILT!");
+                self.write_output("Hello from TILT!\n");
                 Ok(RuntimeValue::Void)
             }
 
@@ -141,7 +307,7 @@ impl HostABI for ConsoleHostABI {
                     return Err(format!("print_i32 expects 1 argument, got {}", args.len()));
                 }
                 let value = args[0].as_i32();
-                print!("{}", value);
+                self.write_output(&value.to_string());
                 Ok(RuntimeValue::Void)
             }
 
@@ -150,7 +316,16 @@ impl HostABI for ConsoleHostABI {
                     return Err(format!("print_i64 expects 1 argument, got {}", args.len()));
                 }
                 let value = args[0].as_i64();
-                print!("{}", value);
+                self.write_output(&value.to_string());
+                Ok(RuntimeValue::Void)
+            }
+
+            "print_ptr" => {
+                if args.len() != 1 {
+                    return Err(format!("print_ptr expects 1 argument, got {}", args.len()));
+                }
+                let value = args[0].as_ptr();
+                self.write_output(&format!("0x{:x}", value));
                 Ok(RuntimeValue::Void)
             }
 
@@ -160,7 +335,7 @@ impl HostABI for ConsoleHostABI {
                 }
                 let value = args[0].as_i32();
                 if let Some(ch) = char::from_u32(value as u32) {
-                    print!("{}", ch);
+                    self.write_output(&ch.to_string());
                     Ok(RuntimeValue::Void)
                 } else {
                     Err(format!("Invalid character code: {}", value))
@@ -171,7 +346,7 @@ impl HostABI for ConsoleHostABI {
                 if !args.is_empty() {
                     return Err(format!("println expects 0 arguments, got {}", args.len()));
                 }
-                println!();
+                self.write_output("\n");
                 Ok(RuntimeValue::Void)
             }
 
@@ -182,19 +357,33 @@ impl HostABI for ConsoleHostABI {
                     return Err(format!("read_i32 expects 0 arguments, got {}", args.len()));
                 }
 
-                print!("Enter an integer: ");
+                self.write_output("Enter an integer: ");
                 io::stdout().flush().unwrap();
 
                 let mut input = String::new();
                 match io::stdin().read_line(&mut input) {
                     Ok(_) => match input.trim().parse::<i32>() {
                         Ok(value) => Ok(RuntimeValue::I32(value)),
-                        Err(_) => Err("Failed to parse integer".to_string()),
+                        Err(_) => {
+                            // Bad input is recoverable, the program can check last_error()
+                            self.last_error = HOST_ERR_INVALID_INPUT;
+                            Ok(RuntimeValue::I32(HOST_ERROR_RESULT))
+                        }
                     },
                     Err(e) => Err(format!("Failed to read input: {}", e)),
                 }
             }
 
+            "last_error" => {
+                if !args.is_empty() {
+                    return Err(format!(
+                        "last_error expects 0 arguments, got {}",
+                        args.len()
+                    ));
+                }
+                Ok(RuntimeValue::I32(self.last_error))
+            }
+
             _ => Err(format!("Unknown host function: {}", name)),
         }
     }
@@ -204,34 +393,172 @@ impl HostABI for ConsoleHostABI {
             "print_hello",
             "print_i32",
             "print_i64",
+            "print_ptr",
             "print_char",
             "println",
             "read_i32",
+            "last_error",
         ]
     }
+
+    fn last_error(&self) -> i32 {
+        self.last_error
+    }
+
+    fn reset(&mut self) {
+        self.last_error = HOST_ERR_NONE;
+    }
+}
+
+/// Console host ABI that captures program output in memory instead of
+/// printing it, so tests can inspect what a program printed
+pub struct BufferedConsoleHostABI {
+    console: ConsoleHostABI,
+}
+
+impl BufferedConsoleHostABI {
+    pub fn new() -> Self {
+        Self {
+            console: ConsoleHostABI {
+                last_error: HOST_ERR_NONE,
+                buffer: Some(String::new()),
+            },
+        }
+    }
+
+    /// Get everything printed so far
+    pub fn output(&self) -> &str {
+        self.console.buffer.as_deref().unwrap_or_default()
+    }
+
+    /// Take the printed output, leaving the buffer empty
+    pub fn take_output(&mut self) -> String {
+        self.console
+            .buffer
+            .replace(String::new())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for BufferedConsoleHostABI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostABI for BufferedConsoleHostABI {
+    fn call_host_function(&mut self, name: &str, args: &[RuntimeValue]) -> HostResult {
+        self.console.call_host_function(name, args)
+    }
+
+    fn available_functions(&self) -> Vec<&str> {
+        self.console.available_functions()
+    }
+
+    fn last_error(&self) -> i32 {
+        self.console.last_error()
+    }
+
+    fn reset(&mut self) {
+        // Captured output is kept; use `take_output` to clear it
+        self.console.reset();
+    }
+}
+
+/// Alignment used by plain `alloc`
+pub const DEFAULT_ALLOC_ALIGN: u64 = 8;
+
+/// Read the `(size, align)` arguments of `alloc_aligned`. An alignment that
+/// is not a power of two is recoverable bad input: the call returns null and
+/// sets `last_error()`, as the JIT's native `alloc_aligned` does.
+fn alloc_aligned_args(args: &[RuntimeValue]) -> Result<(u64, u64), String> {
+    if args.len() != 2 {
+        return Err(format!(
+            "alloc_aligned expects 2 arguments, got {}",
+            args.len()
+        ));
+    }
+    Ok((args[0].as_ptr(), args[1].as_ptr()))
 }
 
+/// Address of the first `MemoryHostABI` allocation, kept non-zero so it never
+/// looks like a null pointer
+const INITIAL_ADDR: u64 = 0x1000;
+
+/// Largest single `MemoryHostABI` allocation by default: 1 GiB
+pub const DEFAULT_MAX_ALLOCATION: u64 = 1 << 30;
+
 /// Extended host ABI that includes memory management functions
 pub struct MemoryHostABI {
     /// Simple memory allocator using a HashMap to track allocations
     memory: std::collections::HashMap<u64, Vec<u8>>,
     /// Next allocation address
     next_addr: u64,
+    /// Whether pointer arithmetic must stay inside its allocation
+    strict_pointers: bool,
+    /// Largest allocation `alloc` attempts; larger requests return null
+    max_allocation: u64,
+    /// Start and end of the regions marked read-only
+    read_only: Vec<(u64, u64)>,
+    /// Console ABI handling non-memory functions
+    console: ConsoleHostABI,
+}
+
+impl Default for MemoryHostABI {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MemoryHostABI {
     pub fn new() -> Self {
         Self {
             memory: std::collections::HashMap::new(),
-            next_addr: 0x1000, // Start at a non-zero address
+            next_addr: INITIAL_ADDR,
+            strict_pointers: false,
+            max_allocation: DEFAULT_MAX_ALLOCATION,
+            read_only: Vec::new(),
+            console: ConsoleHostABI::new(),
         }
     }
 
+    /// Set the largest allocation `alloc` and `alloc_aligned` attempt, in
+    /// bytes. Larger requests return null instead of asking the system for
+    /// memory, so one huge `alloc` can't abort the host.
+    pub fn set_max_allocation(&mut self, bytes: u64) {
+        self.max_allocation = bytes;
+    }
+
+    /// Enable or disable strict pointer mode. When strict, `check_ptr_add`
+    /// rejects arithmetic on a pointer outside every allocation, and
+    /// arithmetic that moves a pointer out of the allocation it points
+    /// into. One past the end still counts as inside.
+    pub fn set_strict_pointers(&mut self, strict: bool) {
+        self.strict_pointers = strict;
+    }
+
+    /// Find the allocation `addr` points into or one past the end of, as
+    /// its base address and length
+    fn allocation_of(&self, addr: u64) -> Option<(u64, u64)> {
+        self.memory
+            .iter()
+            .map(|(base, data)| (*base, data.len() as u64))
+            .find(|(base, len)| addr >= *base && addr - base <= *len)
+    }
+
+    /// Whether any byte in `start..end` is read-only
+    fn is_read_only(&self, start: u64, end: u64) -> bool {
+        self.read_only
+            .iter()
+            .any(|&(ro_start, ro_end)| start < ro_end && ro_start < end)
+    }
+
     /// Read bytes from memory at the given address
     pub fn read_memory(&self, addr: u64, size: usize) -> Result<Vec<u8>, String> {
         // Find the allocation that contains this address
+        let end = addr.checked_add(size as u64);
         for (base_addr, data) in &self.memory {
-            if addr >= *base_addr && addr + size as u64 <= *base_addr + data.len() as u64 {
+            if addr >= *base_addr && end.is_some_and(|end| end <= *base_addr + data.len() as u64) {
                 let offset = (addr - base_addr) as usize;
                 return Ok(data[offset..offset + size].to_vec());
             }
@@ -239,12 +566,19 @@ impl MemoryHostABI {
         Err(format!("Invalid memory access at address 0x{:x}", addr))
     }
 
-    /// Write bytes to memory at the given address
+    /// Write bytes to memory at the given address. Writes touching a
+    /// read-only region fail.
     pub fn write_memory(&mut self, addr: u64, data: &[u8]) -> Result<(), String> {
         // Find the allocation that contains this address
+        let end = addr.checked_add(data.len() as u64);
+        if let Some(end) = end
+            && self.is_read_only(addr, end)
+        {
+            return Err(format!("Write to read-only data at address 0x{:x}", addr));
+        }
         for (base_addr, memory_data) in &mut self.memory {
             if addr >= *base_addr
-                && addr + data.len() as u64 <= *base_addr + memory_data.len() as u64
+                && end.is_some_and(|end| end <= *base_addr + memory_data.len() as u64)
             {
                 let offset = (addr - base_addr) as usize;
                 memory_data[offset..offset + data.len()].copy_from_slice(data);
@@ -254,10 +588,44 @@ impl MemoryHostABI {
         Err(format!("Invalid memory write at address 0x{:x}", addr))
     }
 
+    /// Copy out `len` bytes starting at `addr` for inspection, e.g. to assert
+    /// on what a program left in memory after it ran
+    pub fn dump_region(&self, addr: u64, len: usize) -> Result<Vec<u8>, String> {
+        self.read_memory(addr, len).map_err(|_| {
+            format!(
+                "Region 0x{:x}..0x{:x} is not inside a single allocation",
+                addr,
+                addr + len as u64
+            )
+        })
+    }
+
+    /// Read `len` consecutive i32 values starting at `ptr`.
+    ///
+    /// TILT has no array type; arrays are passed to functions as a
+    /// `(ptr: usize, len: usize)` pair pointing at `len` little-endian i32s
+    /// laid out 4 bytes apart. This reads such an array back for inspection.
+    pub fn read_i32_array(&self, ptr: u64, len: usize) -> Result<Vec<i32>, String> {
+        let size = len
+            .checked_mul(4)
+            .ok_or_else(|| format!("i32 array of length {} is too large", len))?;
+        let bytes = self.read_memory(ptr, size)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+
+    /// Write `values` as a `(ptr, len)` i32 array starting at `ptr`
+    pub fn write_i32_array(&mut self, ptr: u64, values: &[i32]) -> Result<(), String> {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.write_memory(ptr, &bytes)
+    }
+
     /// Read a typed value from memory
     pub fn read_value(&self, addr: u64, ty: tilt_ast::Type) -> Result<RuntimeValue, String> {
         use tilt_ast::Type;
-        match ty {
+        match ty.repr() {
             Type::I32 => {
                 let bytes = self.read_memory(addr, 4)?;
                 let value = i32::from_le_bytes(bytes.try_into().unwrap());
@@ -269,23 +637,21 @@ impl MemoryHostABI {
                 Ok(RuntimeValue::I64(value))
             }
             Type::F32 => {
-                // For now, treat as i32 since we don't have F32 variant
                 let bytes = self.read_memory(addr, 4)?;
-                let value = i32::from_le_bytes(bytes.try_into().unwrap());
-                Ok(RuntimeValue::I32(value))
+                let value = f32::from_le_bytes(bytes.try_into().unwrap());
+                Ok(RuntimeValue::F32(value))
             }
             Type::F64 => {
-                // For now, treat as i64 since we don't have F64 variant
                 let bytes = self.read_memory(addr, 8)?;
-                let value = i64::from_le_bytes(bytes.try_into().unwrap());
-                Ok(RuntimeValue::I64(value))
+                let value = f64::from_le_bytes(bytes.try_into().unwrap());
+                Ok(RuntimeValue::F64(value))
             }
             Type::Usize => {
                 let bytes = self.read_memory(addr, 8)?;
                 let value = u64::from_le_bytes(bytes.try_into().unwrap());
                 Ok(RuntimeValue::Usize(value.try_into().unwrap()))
             }
-            Type::Void => Err("Cannot read void type from memory".to_string()),
+            Type::Void | Type::Ptr(_) => Err("Cannot read void type from memory".to_string()),
         }
     }
 
@@ -295,18 +661,41 @@ impl MemoryHostABI {
             RuntimeValue::I32(v) => self.write_memory(addr, &v.to_le_bytes()),
             RuntimeValue::I64(v) => self.write_memory(addr, &v.to_le_bytes()),
             RuntimeValue::Usize(v) => self.write_memory(addr, &v.to_le_bytes()),
+            RuntimeValue::F32(v) => self.write_memory(addr, &v.to_le_bytes()),
+            RuntimeValue::F64(v) => self.write_memory(addr, &v.to_le_bytes()),
             RuntimeValue::Void => Err("Cannot write void type to memory".to_string()),
         }
     }
 
-    fn allocate(&mut self, size: u64) -> u64 {
+    /// Allocate `size` zeroed bytes. Zero-sized requests, requests over the
+    /// allocation cap, and requests too large for the address space or the
+    /// host, return null (0) instead.
+    fn allocate(&mut self, size: u64, align: u64) -> u64 {
         if size == 0 {
             return 0; // Null pointer for zero-sized allocation
         }
+        if size > self.max_allocation {
+            return 0;
+        }
+
+        let Some(addr) = self.next_addr.checked_next_multiple_of(align) else {
+            return 0;
+        };
+        // Add some padding between allocations
+        let Some(next_addr) = addr.checked_add(size).and_then(|end| end.checked_add(8)) else {
+            return 0;
+        };
+        let Ok(len) = usize::try_from(size) else {
+            return 0;
+        };
+        let mut data = Vec::new();
+        if data.try_reserve_exact(len).is_err() {
+            return 0;
+        }
+        data.resize(len, 0);
 
-        let addr = self.next_addr;
-        self.next_addr += size + 8; // Add some padding between allocations
-        self.memory.insert(addr, vec![0; size as usize]);
+        self.next_addr = next_addr;
+        self.memory.insert(addr, data);
         addr
     }
 
@@ -315,6 +704,9 @@ impl MemoryHostABI {
             return Ok(()); // Freeing null pointer is a no-op
         }
 
+        if self.read_only.iter().any(|&(start, _)| start == addr) {
+            return Err(format!("Attempt to free read-only data at 0x{:x}", addr));
+        }
         if self.memory.remove(&addr).is_some() {
             Ok(())
         } else {
@@ -325,13 +717,24 @@ impl MemoryHostABI {
 
 impl HostABI for MemoryHostABI {
     fn call_host_function(&mut self, name: &str, args: &[RuntimeValue]) -> HostResult {
+        self.console.begin_call(name);
         match name {
             "alloc" => {
                 if args.len() != 1 {
                     return Err(format!("alloc expects 1 argument, got {}", args.len()));
                 }
                 let size = args[0].as_ptr();
-                let addr = self.allocate(size);
+                let addr = self.allocate(size, DEFAULT_ALLOC_ALIGN);
+                Ok(RuntimeValue::Usize(addr.try_into().unwrap()))
+            }
+
+            "alloc_aligned" => {
+                let (size, align) = alloc_aligned_args(args)?;
+                if !align.is_power_of_two() {
+                    self.console.last_error = HOST_ERR_INVALID_INPUT;
+                    return Ok(RuntimeValue::Usize(0));
+                }
+                let addr = self.allocate(size, align);
                 Ok(RuntimeValue::Usize(addr.try_into().unwrap()))
             }
 
@@ -344,27 +747,65 @@ impl HostABI for MemoryHostABI {
                 Ok(RuntimeValue::Void)
             }
 
-            // Delegate other functions to a console ABI
-            _ => {
-                let mut console_abi = ConsoleHostABI::new();
-                console_abi.call_host_function(name, args)
-            }
+            // Delegate other functions to the console ABI
+            _ => self.console.call_host_function(name, args),
         }
     }
 
     fn available_functions(&self) -> Vec<&str> {
         vec![
             "alloc",
+            "alloc_aligned",
             "free",
             "print_hello",
             "print_i32",
             "print_i64",
+            "print_ptr",
             "print_char",
             "println",
             "read_i32",
+            "last_error",
         ]
     }
 
+    fn last_error(&self) -> i32 {
+        self.console.last_error()
+    }
+
+    fn check_ptr_add(&self, ptr: u64, offset: u64) -> Result<(), String> {
+        if !self.strict_pointers {
+            return Ok(());
+        }
+        let Some((base, len)) = self.allocation_of(ptr) else {
+            return Err(format!(
+                "Pointer 0x{:x} does not point into any allocation",
+                ptr
+            ));
+        };
+        let result = ptr.wrapping_add(offset);
+        if result < base || result - base > len {
+            return Err(format!(
+                "Pointer 0x{:x} + {} escapes its allocation 0x{:x}..0x{:x}",
+                ptr,
+                offset as i64,
+                base,
+                base + len
+            ));
+        }
+        Ok(())
+    }
+
+    fn mark_read_only(&mut self, addr: u64, len: usize) {
+        self.read_only.push((addr, addr.saturating_add(len as u64)));
+    }
+
+    fn reset(&mut self) {
+        self.memory.clear();
+        self.read_only.clear();
+        self.next_addr = INITIAL_ADDR;
+        self.console.reset();
+    }
+
     fn read_memory_value(&self, addr: u64, ty: tilt_ast::Type) -> Result<RuntimeValue, String> {
         self.read_value(addr, ty)
     }
@@ -372,6 +813,14 @@ impl HostABI for MemoryHostABI {
     fn write_memory_value(&mut self, addr: u64, value: &RuntimeValue) -> Result<(), String> {
         self.write_value(addr, value)
     }
+
+    fn read_memory_bytes(&self, addr: u64, len: usize) -> Result<Vec<u8>, String> {
+        self.read_memory(addr, len)
+    }
+
+    fn write_memory_bytes(&mut self, addr: u64, data: &[u8]) -> Result<(), String> {
+        self.write_memory(addr, data)
+    }
 }
 
 /// A no-op host ABI for testing or isolated execution
@@ -423,28 +872,45 @@ impl IntoRuntimeValue for () {
 }
 
 /// Real memory allocation Host ABI for JIT
-/// This allocates actual system memory that the JIT can directly access
+/// This allocates actual system memory that the JIT can directly access.
+/// Only allocations made through `call_host_function` are tracked: compiled
+/// JIT code calls the JIT's native `alloc` directly, so `reset` does not
+/// release memory that JIT code allocated.
 pub struct JITMemoryHostABI {
     /// Track allocations for proper cleanup using addresses
-    allocations: std::collections::HashMap<u64, usize>, // addr -> size
+    allocations: std::collections::HashMap<u64, std::alloc::Layout>, // addr -> layout
+    /// Console ABI handling non-memory functions
+    console: ConsoleHostABI,
+}
+
+impl Default for JITMemoryHostABI {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl JITMemoryHostABI {
     pub fn new() -> Self {
         Self {
             allocations: std::collections::HashMap::new(),
+            console: ConsoleHostABI::new(),
         }
     }
 
-    /// Allocate real memory and return the raw pointer address
-    fn allocate_real_memory(&mut self, size: u64) -> u64 {
+    /// Allocate real memory aligned to `align` and return the raw pointer address
+    fn allocate_real_memory(&mut self, size: u64, align: u64) -> u64 {
         if size == 0 {
             return 0;
         }
 
         use std::alloc::{Layout, alloc};
 
-        let layout = Layout::from_size_align(size as usize, 8).unwrap();
+        let (Ok(size), Ok(align)) = (usize::try_from(size), usize::try_from(align)) else {
+            return 0; // More than the address space can hold
+        };
+        let Ok(layout) = Layout::from_size_align(size, align) else {
+            return 0; // Size overflows when rounded up to the alignment
+        };
         let ptr = unsafe { alloc(layout) };
 
         if ptr.is_null() {
@@ -452,7 +918,7 @@ impl JITMemoryHostABI {
         }
 
         let addr = ptr as u64;
-        self.allocations.insert(addr, size as usize);
+        self.allocations.insert(addr, layout);
 
         addr
     }
@@ -463,9 +929,8 @@ impl JITMemoryHostABI {
             return Ok(());
         }
 
-        if let Some(size) = self.allocations.remove(&addr) {
-            use std::alloc::{Layout, dealloc};
-            let layout = Layout::from_size_align(size, 8).unwrap();
+        if let Some(layout) = self.allocations.remove(&addr) {
+            use std::alloc::dealloc;
             let ptr = addr as *mut u8;
             unsafe { dealloc(ptr, layout) };
             Ok(())
@@ -477,13 +942,24 @@ impl JITMemoryHostABI {
 
 impl HostABI for JITMemoryHostABI {
     fn call_host_function(&mut self, name: &str, args: &[RuntimeValue]) -> HostResult {
+        self.console.begin_call(name);
         match name {
             "alloc" => {
                 if args.len() != 1 {
                     return Err(format!("alloc expects 1 argument, got {}", args.len()));
                 }
                 let size = args[0].as_ptr();
-                let addr = self.allocate_real_memory(size);
+                let addr = self.allocate_real_memory(size, DEFAULT_ALLOC_ALIGN);
+                Ok(RuntimeValue::Usize(addr.try_into().unwrap()))
+            }
+
+            "alloc_aligned" => {
+                let (size, align) = alloc_aligned_args(args)?;
+                if !align.is_power_of_two() {
+                    self.console.last_error = HOST_ERR_INVALID_INPUT;
+                    return Ok(RuntimeValue::Usize(0));
+                }
+                let addr = self.allocate_real_memory(size, align);
                 Ok(RuntimeValue::Usize(addr.try_into().unwrap()))
             }
 
@@ -496,26 +972,539 @@ impl HostABI for JITMemoryHostABI {
                 Ok(RuntimeValue::Void)
             }
 
-            // Delegate other functions to a console ABI
-            _ => {
-                let mut console_abi = ConsoleHostABI::new();
-                console_abi.call_host_function(name, args)
+            // Delegate other functions to the console ABI
+            _ => self.console.call_host_function(name, args),
+        }
+    }
+
+    fn available_functions(&self) -> Vec<&str> {
+        vec![
+            "alloc",
+            "alloc_aligned",
+            "free",
+            "print_hello",
+            "print_i32",
+            "print_i64",
+            "print_ptr",
+            "print_char",
+            "println",
+            "read_i32",
+            "last_error",
+        ]
+    }
+
+    fn last_error(&self) -> i32 {
+        self.console.last_error()
+    }
+
+    fn reset(&mut self) {
+        for (addr, layout) in self.allocations.drain() {
+            unsafe { std::alloc::dealloc(addr as *mut u8, layout) };
+        }
+        self.console.reset();
+    }
+}
+
+/// Size of the arena used by `DeterministicJITMemoryHostABI::new`
+pub const DEFAULT_ARENA_SIZE: usize = 1024 * 1024;
+
+/// Host ABI with reproducible addresses for golden tests.
+/// Allocations are carved sequentially out of a single mmap'd arena, so the
+/// first allocation is always at the arena base and later ones follow at
+/// predictable offsets. The memory is real, so addresses it hands out can be
+/// passed to JIT code and read there. The ABI also offers native `alloc`,
+/// `alloc_aligned` and `free` for the JIT to link, so JIT code allocates
+/// from the same arena. Those use the arena of the ABI whose `native_symbol`
+/// was last asked for them on the calling thread.
+pub struct DeterministicJITMemoryHostABI {
+    /// The arena backing all allocations, shared with the native functions
+    arena: Arc<Mutex<Arena>>,
+    /// Console ABI handling non-memory functions
+    console: ConsoleHostABI,
+}
+
+/// Bump allocator over a mapped region
+struct Arena {
+    /// The mapped region
+    mapping: region::Allocation,
+    /// Offset of the next allocation from the arena base
+    next_offset: usize,
+    /// Live allocations: addr -> size
+    allocations: std::collections::HashMap<u64, usize>,
+}
+
+// SAFETY: the mapping is owned exclusively by the arena and is only handed
+// out as plain addresses, so moving it between threads is sound.
+unsafe impl Send for Arena {}
+
+impl Arena {
+    fn base(&self) -> u64 {
+        self.mapping.as_ptr::<u8>() as u64
+    }
+
+    /// Bump-allocate from the arena, returning 0 when it is exhausted
+    fn allocate(&mut self, size: u64, align: u64) -> u64 {
+        if size == 0 {
+            return 0;
+        }
+
+        let size = size as usize;
+        let base = self.base() as usize;
+        let start = match (base + self.next_offset).checked_next_multiple_of(align as usize) {
+            Some(aligned) => aligned - base,
+            None => return 0,
+        };
+        let end = match start.checked_add(size) {
+            Some(end) if end <= self.mapping.len() => end,
+            _ => return 0, // Arena exhausted
+        };
+
+        let addr = self.base() + start as u64;
+        // Keep every allocation at least 8-byte aligned, like JITMemoryHostABI
+        self.next_offset = end.next_multiple_of(DEFAULT_ALLOC_ALIGN as usize);
+        self.allocations.insert(addr, size);
+        addr
+    }
+
+    /// Release an allocation. Arena memory is not reused so addresses stay predictable.
+    fn free(&mut self, addr: u64) -> Result<(), String> {
+        if addr == 0 {
+            return Ok(());
+        }
+
+        if self.allocations.remove(&addr).is_some() {
+            Ok(())
+        } else {
+            Err(format!("Attempt to free invalid address: 0x{:x}", addr))
+        }
+    }
+}
+
+thread_local! {
+    /// Arena the native allocation functions use on this thread
+    static NATIVE_ARENA: RefCell<Weak<Mutex<Arena>>> = const { RefCell::new(Weak::new()) };
+}
+
+/// Run `f` on this thread's native arena, or return 0 if its ABI is gone
+fn with_native_arena(f: impl FnOnce(&mut Arena) -> u64) -> u64 {
+    match NATIVE_ARENA.with_borrow(Weak::upgrade) {
+        Some(arena) => f(&mut arena.lock().unwrap()),
+        None => 0,
+    }
+}
+
+fn native_arena_alloc(size: usize) -> usize {
+    begin_native_call();
+    with_native_arena(|arena| arena.allocate(size as u64, DEFAULT_ALLOC_ALIGN)) as usize
+}
+
+fn native_arena_alloc_aligned(size: usize, align: usize) -> usize {
+    begin_native_call();
+    if !align.is_power_of_two() {
+        set_native_error(HOST_ERR_INVALID_INPUT);
+        return 0;
+    }
+    with_native_arena(|arena| arena.allocate(size as u64, align as u64)) as usize
+}
+
+fn native_arena_free(addr: usize) {
+    begin_native_call();
+    // Invalid frees are ignored, as by the JIT's own native `free`
+    with_native_arena(|arena| {
+        let _ = arena.free(addr as u64);
+        0
+    });
+}
+
+impl Default for DeterministicJITMemoryHostABI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeterministicJITMemoryHostABI {
+    /// Create an ABI with a `DEFAULT_ARENA_SIZE` arena. Panics if the arena
+    /// can't be mapped; use `with_arena_size` to handle that.
+    pub fn new() -> Self {
+        Self::with_arena_size(DEFAULT_ARENA_SIZE).unwrap()
+    }
+
+    /// Create an ABI whose arena holds `size` bytes, failing if `size` is
+    /// zero or the arena can't be mapped
+    pub fn with_arena_size(size: usize) -> Result<Self, String> {
+        if size == 0 {
+            return Err("Allocation arena must not be empty".to_string());
+        }
+        let mapping = region::alloc(size, region::Protection::READ_WRITE)
+            .map_err(|e| format!("Failed to map allocation arena: {}", e))?;
+        Ok(Self {
+            arena: Arc::new(Mutex::new(Arena {
+                mapping,
+                next_offset: 0,
+                allocations: std::collections::HashMap::new(),
+            })),
+            console: ConsoleHostABI::new(),
+        })
+    }
+
+    /// Get the address of the start of the arena
+    pub fn arena_base(&self) -> u64 {
+        self.arena.lock().unwrap().base()
+    }
+
+    fn allocate_from_arena(&mut self, size: u64, align: u64) -> u64 {
+        self.arena.lock().unwrap().allocate(size, align)
+    }
+
+    fn free_from_arena(&mut self, addr: u64) -> Result<(), String> {
+        self.arena.lock().unwrap().free(addr)
+    }
+}
+
+impl HostABI for DeterministicJITMemoryHostABI {
+    fn call_host_function(&mut self, name: &str, args: &[RuntimeValue]) -> HostResult {
+        self.console.begin_call(name);
+        match name {
+            "alloc" => {
+                if args.len() != 1 {
+                    return Err(format!("alloc expects 1 argument, got {}", args.len()));
+                }
+                let size = args[0].as_ptr();
+                let addr = self.allocate_from_arena(size, DEFAULT_ALLOC_ALIGN);
+                Ok(RuntimeValue::Usize(addr.try_into().unwrap()))
+            }
+
+            "alloc_aligned" => {
+                let (size, align) = alloc_aligned_args(args)?;
+                if !align.is_power_of_two() {
+                    self.console.last_error = HOST_ERR_INVALID_INPUT;
+                    return Ok(RuntimeValue::Usize(0));
+                }
+                let addr = self.allocate_from_arena(size, align);
+                Ok(RuntimeValue::Usize(addr.try_into().unwrap()))
+            }
+
+            "free" => {
+                if args.len() != 1 {
+                    return Err(format!("free expects 1 argument, got {}", args.len()));
+                }
+                let addr = args[0].as_ptr();
+                self.free_from_arena(addr)?;
+                Ok(RuntimeValue::Void)
             }
+
+            // Delegate other functions to the console ABI
+            _ => self.console.call_host_function(name, args),
         }
     }
 
     fn available_functions(&self) -> Vec<&str> {
         vec![
             "alloc",
+            "alloc_aligned",
             "free",
             "print_hello",
             "print_i32",
             "print_i64",
+            "print_ptr",
             "print_char",
             "println",
             "read_i32",
+            "last_error",
         ]
     }
+
+    fn last_error(&self) -> i32 {
+        self.console.last_error()
+    }
+
+    fn reset(&mut self) {
+        let mut arena = self.arena.lock().unwrap();
+        arena.next_offset = 0;
+        arena.allocations.clear();
+        drop(arena);
+        self.console.reset();
+    }
+
+    fn native_symbol(&self, name: &str) -> Option<*const u8> {
+        let symbol = match name {
+            "alloc" => native_arena_alloc as *const u8,
+            "alloc_aligned" => native_arena_alloc_aligned as *const u8,
+            "free" => native_arena_free as *const u8,
+            _ => return None,
+        };
+        NATIVE_ARENA.with_borrow_mut(|arena| *arena = Arc::downgrade(&self.arena));
+        Some(symbol)
+    }
+}
+
+/// Extra host functions that `LayeredHostABI` stacks on top of a base ABI.
+/// Calls get mutable access to the base, e.g. to allocate memory for results.
+pub trait HostLayer<B: HostABI> {
+    /// Names of the host functions this layer provides
+    fn layer_functions(&self) -> Vec<&str>;
+
+    /// Get the parameter and return types of a layer function
+    fn layer_signature(&self, name: &str) -> Option<(Vec<Type>, Type)>;
+
+    /// Call one of `layer_functions`
+    fn call_layer_function(
+        &mut self,
+        base: &mut B,
+        name: &str,
+        args: &[RuntimeValue],
+    ) -> HostResult;
+
+    /// Forget state that refers to the base, called when the base is reset
+    fn reset_layer(&mut self) {}
+}
+
+/// Host ABI made of a layer of extra functions over a base ABI. Calls to the
+/// layer's functions go to the layer; everything else, including memory
+/// access, is handled by the base.
+pub struct LayeredHostABI<L, B> {
+    layer: L,
+    base: B,
+}
+
+impl<L: HostLayer<B>, B: HostABI> LayeredHostABI<L, B> {
+    pub fn new(layer: L, base: B) -> Self {
+        Self { layer, base }
+    }
+
+    /// Get the layer of extra functions
+    pub fn layer(&self) -> &L {
+        &self.layer
+    }
+
+    /// Get the base ABI, e.g. to inspect its memory
+    pub fn base(&self) -> &B {
+        &self.base
+    }
+}
+
+impl<L: HostLayer<B>, B: HostABI> HostABI for LayeredHostABI<L, B> {
+    fn call_host_function(&mut self, name: &str, args: &[RuntimeValue]) -> HostResult {
+        if self.layer.layer_functions().contains(&name) {
+            self.layer.call_layer_function(&mut self.base, name, args)
+        } else {
+            self.base.call_host_function(name, args)
+        }
+    }
+
+    fn available_functions(&self) -> Vec<&str> {
+        let mut functions = self.layer.layer_functions();
+        functions.extend(self.base.available_functions());
+        functions
+    }
+
+    fn function_signature(&self, name: &str) -> Option<(Vec<Type>, Type)> {
+        self.layer
+            .layer_signature(name)
+            .or_else(|| self.base.function_signature(name))
+    }
+
+    fn last_error(&self) -> i32 {
+        self.base.last_error()
+    }
+
+    fn check_ptr_add(&self, ptr: u64, offset: u64) -> Result<(), String> {
+        self.base.check_ptr_add(ptr, offset)
+    }
+
+    fn mark_read_only(&mut self, addr: u64, len: usize) {
+        self.base.mark_read_only(addr, len)
+    }
+
+    fn reset(&mut self) {
+        self.layer.reset_layer();
+        self.base.reset();
+    }
+
+    fn native_symbol(&self, name: &str) -> Option<*const u8> {
+        // Layer functions only exist behind `call_layer_function`
+        if self.layer.layer_functions().contains(&name) {
+            None
+        } else {
+            self.base.native_symbol(name)
+        }
+    }
+
+    fn read_memory_value(&self, addr: u64, ty: tilt_ast::Type) -> Result<RuntimeValue, String> {
+        self.base.read_memory_value(addr, ty)
+    }
+
+    fn write_memory_value(&mut self, addr: u64, value: &RuntimeValue) -> Result<(), String> {
+        self.base.write_memory_value(addr, value)
+    }
+
+    fn read_memory_bytes(&self, addr: u64, len: usize) -> Result<Vec<u8>, String> {
+        self.base.read_memory_bytes(addr, len)
+    }
+
+    fn write_memory_bytes(&mut self, addr: u64, data: &[u8]) -> Result<(), String> {
+        self.base.write_memory_bytes(addr, data)
+    }
+}
+
+/// Host layer giving TILT programs their command-line arguments and
+/// environment through `argc`, `argv` and `getenv`. Strings are copied into
+/// the base `MemoryHostABI` as NUL-terminated UTF-8 the first time they are
+/// requested; a missing argument or variable yields a null pointer.
+pub struct EnvHostABI {
+    args: Vec<String>,
+    vars: std::collections::HashMap<String, String>,
+    /// Addresses of arguments already copied into memory
+    arg_ptrs: std::collections::HashMap<usize, u64>,
+    /// Addresses of variable values already copied into memory
+    var_ptrs: std::collections::HashMap<String, u64>,
+}
+
+impl EnvHostABI {
+    /// Create a layer with the given arguments and environment variables
+    pub fn new(args: Vec<String>, vars: std::collections::HashMap<String, String>) -> Self {
+        Self {
+            args,
+            vars,
+            arg_ptrs: std::collections::HashMap::new(),
+            var_ptrs: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Create a layer with the arguments and environment of this process
+    pub fn from_process() -> Self {
+        Self::new(std::env::args().collect(), std::env::vars().collect())
+    }
+
+    /// Copy `text` into the base's memory as a NUL-terminated string
+    fn write_string(base: &mut MemoryHostABI, text: &str) -> Result<u64, String> {
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.push(0);
+        let addr = base
+            .call_host_function("alloc", &[RuntimeValue::Usize(bytes.len())])?
+            .as_ptr();
+        base.write_memory(addr, &bytes)?;
+        Ok(addr)
+    }
+}
+
+impl HostLayer<MemoryHostABI> for EnvHostABI {
+    fn layer_functions(&self) -> Vec<&str> {
+        vec!["argc", "argv", "getenv"]
+    }
+
+    fn layer_signature(&self, name: &str) -> Option<(Vec<Type>, Type)> {
+        let signature = match name {
+            "argc" => (vec![], Type::I32),
+            "argv" => (vec![Type::I32], Type::Usize),
+            "getenv" => (vec![Type::Usize, Type::Usize], Type::Usize),
+            _ => return None,
+        };
+        Some(signature)
+    }
+
+    fn call_layer_function(
+        &mut self,
+        base: &mut MemoryHostABI,
+        name: &str,
+        args: &[RuntimeValue],
+    ) -> HostResult {
+        match (name, args) {
+            ("argc", []) => Ok(RuntimeValue::I32(self.args.len() as i32)),
+            ("argv", [RuntimeValue::I32(index)]) => {
+                let Some(arg) = usize::try_from(*index).ok().and_then(|i| self.args.get(i)) else {
+                    return Ok(RuntimeValue::Usize(0));
+                };
+                let addr = match self.arg_ptrs.get(&(*index as usize)) {
+                    Some(addr) => *addr,
+                    None => {
+                        let addr = Self::write_string(base, arg)?;
+                        self.arg_ptrs.insert(*index as usize, addr);
+                        addr
+                    }
+                };
+                Ok(RuntimeValue::Usize(addr as usize))
+            }
+            ("getenv", [RuntimeValue::Usize(name_ptr), RuntimeValue::Usize(name_len)]) => {
+                let name_bytes = base.read_memory(*name_ptr as u64, *name_len)?;
+                let name = String::from_utf8(name_bytes)
+                    .map_err(|_| "getenv: variable name is not valid UTF-8".to_string())?;
+                let Some(value) = self.vars.get(&name) else {
+                    return Ok(RuntimeValue::Usize(0));
+                };
+                let addr = match self.var_ptrs.get(&name) {
+                    Some(addr) => *addr,
+                    None => {
+                        let addr = Self::write_string(base, value)?;
+                        self.var_ptrs.insert(name, addr);
+                        addr
+                    }
+                };
+                Ok(RuntimeValue::Usize(addr as usize))
+            }
+            _ => Err(format!("Invalid arguments to {}: {:?}", name, args)),
+        }
+    }
+
+    fn reset_layer(&mut self) {
+        // The copied strings lived in the base's memory
+        self.arg_ptrs.clear();
+        self.var_ptrs.clear();
+    }
+}
+
+/// Host layer answering `read_i32` from a pre-seeded queue instead of
+/// stdin, so interactive programs can be tested deterministically. Reading
+/// past the end of the queue is an error.
+pub struct ScriptedInputHostABI {
+    inputs: std::collections::VecDeque<i32>,
+}
+
+impl ScriptedInputHostABI {
+    /// Create a layer that returns `inputs` in order
+    pub fn new(inputs: impl IntoIterator<Item = i32>) -> Self {
+        Self {
+            inputs: inputs.into_iter().collect(),
+        }
+    }
+
+    /// Queue another input after the existing ones
+    pub fn push(&mut self, input: i32) {
+        self.inputs.push_back(input);
+    }
+
+    /// Get the number of inputs not read yet
+    pub fn remaining(&self) -> usize {
+        self.inputs.len()
+    }
+}
+
+impl<B: HostABI> HostLayer<B> for ScriptedInputHostABI {
+    fn layer_functions(&self) -> Vec<&str> {
+        vec!["read_i32"]
+    }
+
+    fn layer_signature(&self, name: &str) -> Option<(Vec<Type>, Type)> {
+        match name {
+            "read_i32" => builtin_signature(name),
+            _ => None,
+        }
+    }
+
+    fn call_layer_function(
+        &mut self,
+        _base: &mut B,
+        name: &str,
+        args: &[RuntimeValue],
+    ) -> HostResult {
+        if !args.is_empty() {
+            return Err(format!("{} expects 0 arguments, got {}", name, args.len()));
+        }
+        self.inputs
+            .pop_front()
+            .map(RuntimeValue::I32)
+            .ok_or_else(|| "read_i32: no scripted input left".to_string())
+    }
 }
 
 #[cfg(test)]
@@ -542,6 +1531,12 @@ mod tests {
 
         assert_eq!(val.try_as_i32(), Some(42));
         assert_eq!(val.try_as_i64(), None);
+        assert_eq!(val.try_as_f32(), None);
+
+        let val = RuntimeValue::F64(1.5);
+        assert_eq!(val.try_as_f64(), Some(1.5));
+        assert_eq!(val.try_as_f32(), None);
+        assert_eq!(val.as_f64(), 1.5);
     }
 
     #[test]
@@ -555,6 +1550,16 @@ mod tests {
         assert!(functions.contains(&"print_char"));
         assert!(functions.contains(&"println"));
         assert!(functions.contains(&"read_i32"));
+        assert!(functions.contains(&"last_error"));
+    }
+
+    #[test]
+    fn test_console_host_abi_last_error_defaults_to_none() {
+        let mut abi = ConsoleHostABI::new();
+
+        let result = abi.call_host_function("last_error", &[]).unwrap();
+        assert_eq!(result, RuntimeValue::I32(HOST_ERR_NONE));
+        assert_eq!(abi.last_error(), HOST_ERR_NONE);
     }
 
     #[test]
@@ -577,6 +1582,19 @@ mod tests {
         assert_eq!(result.unwrap(), RuntimeValue::Void);
     }
 
+    #[test]
+    fn test_buffered_console_host_abi_print_ptr() {
+        let mut abi = BufferedConsoleHostABI::new();
+
+        abi.call_host_function("print_ptr", &[RuntimeValue::Usize(0x1000)])
+            .unwrap();
+        abi.call_host_function("println", &[]).unwrap();
+
+        assert_eq!(abi.output(), "0x1000\n");
+        assert_eq!(abi.take_output(), "0x1000\n");
+        assert_eq!(abi.output(), "");
+    }
+
     #[test]
     fn test_console_host_abi_wrong_args() {
         let mut abi = ConsoleHostABI::new();
@@ -612,4 +1630,300 @@ mod tests {
         assert_eq!(123i64.into_runtime_value(), RuntimeValue::I64(123));
         assert_eq!(().into_runtime_value(), RuntimeValue::Void);
     }
+
+    #[test]
+    fn test_memory_alloc_returns_null_for_zero_and_oversized_requests() {
+        let mut abi = MemoryHostABI::new();
+
+        for size in [0, usize::MAX, usize::MAX - 4, 1 << 62] {
+            let result = abi
+                .call_host_function("alloc", &[RuntimeValue::Usize(size)])
+                .unwrap();
+            assert_eq!(result, RuntimeValue::Usize(0), "alloc({})", size);
+        }
+
+        // A failed request leaves the allocator usable
+        let ptr = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(4)])
+            .unwrap();
+        assert_ne!(ptr.as_ptr(), 0);
+        assert!(abi.read_memory(u64::MAX - 1, 4).is_err());
+    }
+
+    #[test]
+    fn test_memory_alloc_returns_null_over_cap() {
+        let mut abi = MemoryHostABI::new();
+        let over_default = RuntimeValue::Usize(DEFAULT_MAX_ALLOCATION as usize + 1);
+        assert_eq!(
+            abi.call_host_function("alloc", std::slice::from_ref(&over_default))
+                .unwrap(),
+            RuntimeValue::Usize(0)
+        );
+
+        abi.set_max_allocation(64);
+        assert_eq!(
+            abi.call_host_function("alloc", &[RuntimeValue::Usize(65)])
+                .unwrap(),
+            RuntimeValue::Usize(0)
+        );
+        assert_eq!(
+            abi.call_host_function(
+                "alloc_aligned",
+                &[RuntimeValue::Usize(65), RuntimeValue::Usize(16)]
+            )
+            .unwrap(),
+            RuntimeValue::Usize(0)
+        );
+        let ptr = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(64)])
+            .unwrap();
+        assert_ne!(ptr.as_ptr(), 0);
+    }
+
+    #[test]
+    fn test_memory_bytes_round_trip() {
+        let mut abi = MemoryHostABI::new();
+        let ptr = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(100)])
+            .unwrap()
+            .as_ptr();
+
+        let data: Vec<u8> = (0..100).collect();
+        abi.write_memory_bytes(ptr, &data).unwrap();
+        assert_eq!(abi.read_memory_bytes(ptr, 100).unwrap(), data);
+        assert_eq!(abi.read_memory_bytes(ptr + 98, 2).unwrap(), vec![98, 99]);
+
+        // Out-of-bounds transfers fail, and other ABIs don't support them
+        assert!(abi.read_memory_bytes(ptr + 50, 100).is_err());
+        assert!(NullHostABI::new().read_memory_bytes(ptr, 1).is_err());
+        assert!(NullHostABI::new().write_memory_bytes(ptr, &data).is_err());
+    }
+
+    #[test]
+    fn test_memory_read_only_regions_reject_writes() {
+        let mut abi = MemoryHostABI::new();
+        let ptr = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(8)])
+            .unwrap()
+            .as_ptr();
+        abi.write_memory_bytes(ptr, &[1, 2, 3, 4]).unwrap();
+        abi.mark_read_only(ptr, 4);
+
+        // Reads still work; writes overlapping the region and frees fail
+        assert_eq!(abi.read_memory_bytes(ptr, 4).unwrap(), vec![1, 2, 3, 4]);
+        assert!(abi.write_memory_bytes(ptr, &[9]).is_err());
+        assert!(
+            abi.write_memory_value(ptr + 2, &RuntimeValue::I32(9))
+                .is_err()
+        );
+        assert!(
+            abi.call_host_function("free", &[RuntimeValue::Usize(ptr as usize)])
+                .is_err()
+        );
+        abi.write_memory_bytes(ptr + 4, &[5, 6, 7, 8]).unwrap();
+
+        // Reset forgets the region along with the allocation
+        abi.reset();
+        let ptr = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(8)])
+            .unwrap()
+            .as_ptr();
+        abi.write_memory_bytes(ptr, &[9]).unwrap();
+    }
+
+    #[test]
+    fn test_deterministic_jit_memory_addresses() {
+        let mut abi = DeterministicJITMemoryHostABI::new();
+        let base = abi.arena_base();
+
+        let first = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(12)])
+            .unwrap();
+        let second = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(4)])
+            .unwrap();
+
+        // The first allocation starts the arena, the next follows 8-byte aligned
+        assert_eq!(first.as_ptr(), base);
+        assert_eq!(second.as_ptr(), base + 16);
+
+        // The memory is real and directly accessible
+        let ptr = first.as_ptr() as *mut i32;
+        unsafe {
+            ptr.write(42);
+            assert_eq!(ptr.read(), 42);
+        }
+
+        assert!(abi.call_host_function("free", &[first]).is_ok());
+    }
+
+    #[test]
+    fn test_deterministic_jit_memory_rejects_unmappable_arena() {
+        assert!(DeterministicJITMemoryHostABI::with_arena_size(0).is_err());
+        assert!(DeterministicJITMemoryHostABI::with_arena_size(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_deterministic_jit_memory_exhausted_arena() {
+        let mut abi = DeterministicJITMemoryHostABI::with_arena_size(4096).unwrap();
+
+        let result = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(1 << 20)])
+            .unwrap();
+        assert_eq!(result, RuntimeValue::Usize(0));
+    }
+
+    #[test]
+    fn test_alloc_aligned_honors_alignment() {
+        let aligned_args = [RuntimeValue::Usize(24), RuntimeValue::Usize(16)];
+        let abis: Vec<Box<dyn HostABI>> = vec![
+            Box::new(MemoryHostABI::new()),
+            Box::new(JITMemoryHostABI::new()),
+            Box::new(DeterministicJITMemoryHostABI::new()),
+        ];
+
+        for mut abi in abis {
+            // Knock the next address off 16-byte alignment first
+            abi.call_host_function("alloc", &[RuntimeValue::Usize(4)])
+                .unwrap();
+            let addr = abi
+                .call_host_function("alloc_aligned", &aligned_args)
+                .unwrap()
+                .as_ptr();
+            assert_ne!(addr, 0);
+            assert_eq!(addr % 16, 0);
+        }
+    }
+
+    #[test]
+    fn test_jit_memory_alloc_returns_null_for_oversized_requests() {
+        let mut abi = JITMemoryHostABI::new();
+
+        for size in [usize::MAX, usize::MAX - 4, isize::MAX as usize] {
+            let result = abi
+                .call_host_function("alloc", &[RuntimeValue::Usize(size)])
+                .unwrap();
+            assert_eq!(result, RuntimeValue::Usize(0), "alloc({})", size);
+        }
+
+        let ptr = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(4)])
+            .unwrap();
+        assert_ne!(ptr.as_ptr(), 0);
+    }
+
+    #[test]
+    fn test_alloc_aligned_rejects_non_power_of_two() {
+        let abis: Vec<Box<dyn HostABI>> = vec![
+            Box::new(MemoryHostABI::new()),
+            Box::new(JITMemoryHostABI::new()),
+            Box::new(DeterministicJITMemoryHostABI::new()),
+        ];
+
+        for mut abi in abis {
+            for align in [0, 12] {
+                let result = abi.call_host_function(
+                    "alloc_aligned",
+                    &[RuntimeValue::Usize(16), RuntimeValue::Usize(align)],
+                );
+                assert_eq!(result, Ok(RuntimeValue::Usize(0)));
+                assert_eq!(abi.last_error(), HOST_ERR_INVALID_INPUT);
+                abi.reset();
+            }
+        }
+    }
+
+    #[test]
+    fn test_i32_array_round_trip() {
+        let mut abi = MemoryHostABI::new();
+        let ptr = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(12)])
+            .unwrap()
+            .as_ptr();
+
+        abi.write_i32_array(ptr, &[7, -1, 42]).unwrap();
+        assert_eq!(abi.read_i32_array(ptr, 3).unwrap(), vec![7, -1, 42]);
+        assert!(abi.read_i32_array(ptr, 4).is_err());
+        assert!(abi.read_i32_array(ptr, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_env_layer_over_memory_abi() {
+        let vars = [("HOME".to_string(), "/home/tilt".to_string())].into();
+        let env = EnvHostABI::new(vec!["prog".to_string()], vars);
+        let mut abi = LayeredHostABI::new(env, MemoryHostABI::new());
+
+        assert!(abi.has_function("getenv"));
+        assert!(abi.has_function("alloc"));
+        assert_eq!(
+            abi.function_signature("argv"),
+            Some((vec![Type::I32], Type::Usize))
+        );
+
+        let name_ptr = abi
+            .call_host_function("alloc", &[RuntimeValue::Usize(4)])
+            .unwrap()
+            .as_ptr();
+        abi.base.write_memory(name_ptr, b"HOME").unwrap();
+
+        let value_ptr = abi
+            .call_host_function(
+                "getenv",
+                &[
+                    RuntimeValue::Usize(name_ptr as usize),
+                    RuntimeValue::Usize(4),
+                ],
+            )
+            .unwrap()
+            .as_ptr();
+        assert_eq!(
+            abi.base().dump_region(value_ptr, 11).unwrap(),
+            b"/home/tilt\0"
+        );
+
+        // A second lookup reuses the copy instead of allocating again
+        let again = abi.call_host_function(
+            "getenv",
+            &[
+                RuntimeValue::Usize(name_ptr as usize),
+                RuntimeValue::Usize(4),
+            ],
+        );
+        assert_eq!(again, Ok(RuntimeValue::Usize(value_ptr as usize)));
+
+        // Unset variables and out-of-range arguments are null pointers
+        abi.base.write_memory(name_ptr, b"NOPE").unwrap();
+        let missing = abi.call_host_function(
+            "getenv",
+            &[
+                RuntimeValue::Usize(name_ptr as usize),
+                RuntimeValue::Usize(4),
+            ],
+        );
+        assert_eq!(missing, Ok(RuntimeValue::Usize(0)));
+        assert_eq!(
+            abi.call_host_function("argv", &[RuntimeValue::I32(1)]),
+            Ok(RuntimeValue::Usize(0))
+        );
+    }
+
+    #[test]
+    fn test_scripted_input_returns_seeded_values_in_order() {
+        let input = ScriptedInputHostABI::new([10, 20]);
+        let mut abi = LayeredHostABI::new(input, BufferedConsoleHostABI::new());
+
+        assert_eq!(
+            abi.call_host_function("read_i32", &[]),
+            Ok(RuntimeValue::I32(10))
+        );
+        assert_eq!(abi.layer().remaining(), 1);
+        assert_eq!(
+            abi.call_host_function("read_i32", &[]),
+            Ok(RuntimeValue::I32(20))
+        );
+        assert!(abi.call_host_function("read_i32", &[]).is_err());
+
+        // Nothing was prompted for or printed
+        assert_eq!(abi.base().output(), "");
+    }
 }
@@ -0,0 +1,37 @@
+// ===================================================================
+// FILE: backends.rs (tilt-integration-tests benchmarks)
+//
+// DESC: Measures VM interpretation, JIT compilation and JIT execution
+//       time on the standard kernels. Run with `cargo bench -p tilt-integration-tests`;
+//       `cargo test --benches` runs each benchmark once as a smoke test.
+// ===================================================================
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use tilt_integration_tests::kernels::{compile_on_jit, run_on_vm, standard_kernels};
+
+fn bench_backends(c: &mut Criterion) {
+    for kernel in standard_kernels() {
+        let mut group = c.benchmark_group(kernel.name);
+
+        group.bench_function("vm", |b| {
+            b.iter(|| run_on_vm(black_box(&kernel)).expect("VM run failed"))
+        });
+        group.bench_function("jit_compile", |b| {
+            b.iter(|| {
+                compile_on_jit(black_box(&kernel))
+                    .expect("JIT compilation failed")
+                    .free()
+            })
+        });
+
+        // Compile once, so only execution is measured
+        let compiled = compile_on_jit(&kernel).expect("JIT compilation failed");
+        group.bench_function("jit", |b| b.iter(|| black_box(compiled.run())));
+        compiled.free();
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_backends);
+criterion_main!(benches);
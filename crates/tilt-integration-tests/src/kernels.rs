@@ -0,0 +1,337 @@
+// ===================================================================
+// FILE: kernels.rs
+//
+// DESC: Standard benchmark kernels built with ProgramBuilder, plus
+//       helpers that run them on the VM and the JIT. Shared by the
+//       backend benchmarks and their smoke test.
+// ===================================================================
+
+use tilt_ast::Type;
+use tilt_codegen_cranelift::JIT;
+use tilt_host_abi::{MemoryHostABI, RuntimeValue};
+use tilt_ir::{BlockId, Program, Terminator, ValueId};
+use tilt_ir_builder::{FunctionBuilder, ProgramBuilder};
+use tilt_vm::VM;
+
+/// A program whose entry function takes one i64 and returns an i64
+pub struct Kernel {
+    pub name: &'static str,
+    pub program: Program,
+    pub entry: &'static str,
+    pub input: i64,
+    /// What the entry function returns for `input`
+    pub expected: i64,
+}
+
+/// The kernels the backend benchmarks measure
+pub fn standard_kernels() -> Vec<Kernel> {
+    vec![
+        Kernel {
+            name: "fibonacci",
+            program: create_fibonacci_program(),
+            entry: "fib",
+            input: 40,
+            expected: 102_334_155,
+        },
+        Kernel {
+            name: "array_sum",
+            program: create_array_sum_program(),
+            entry: "array_sum",
+            input: 1000,
+            expected: 1000 * 999 / 2,
+        },
+        Kernel {
+            name: "string_length",
+            program: create_string_length_program(),
+            entry: "string_length",
+            input: 1000,
+            expected: 1000,
+        },
+    ]
+}
+
+/// Run a kernel's entry function on a fresh VM
+pub fn run_on_vm(kernel: &Kernel) -> Result<i64, String> {
+    let mut vm = VM::new(kernel.program.clone(), MemoryHostABI::new());
+    match vm.call_function(kernel.entry, vec![RuntimeValue::I64(kernel.input)]) {
+        Ok(RuntimeValue::I64(result)) => Ok(result),
+        Ok(other) => Err(format!("{} returned {:?}", kernel.entry, other)),
+        Err(e) => Err(format!("VM execution failed: {}", e)),
+    }
+}
+
+/// A kernel compiled by its own JIT, ready to run any number of times
+pub struct CompiledKernel {
+    jit: JIT,
+    entry: fn(i64) -> i64,
+    input: i64,
+}
+
+impl CompiledKernel {
+    /// Run the compiled entry function on the kernel's input
+    pub fn run(&self) -> i64 {
+        (self.entry)(self.input)
+    }
+
+    /// Release the JIT's executable memory
+    pub fn free(self) {
+        // Safety: `entry` points into this JIT's code and is dropped with it
+        unsafe { self.jit.free_memory() }
+    }
+}
+
+/// Compile a kernel with a fresh JIT
+pub fn compile_on_jit(kernel: &Kernel) -> Result<CompiledKernel, String> {
+    let mut jit = JIT::new()?;
+    jit.compile(&kernel.program)?;
+    let func_ptr = jit
+        .get_func_ptr(kernel.entry)
+        .ok_or_else(|| format!("Function '{}' not found in JIT", kernel.entry))?;
+
+    let entry = unsafe { std::mem::transmute::<*const u8, fn(i64) -> i64>(func_ptr) };
+    Ok(CompiledKernel {
+        jit,
+        entry,
+        input: kernel.input,
+    })
+}
+
+/// Compile a kernel with a fresh JIT, run its entry function once and free
+/// the compiled code
+pub fn run_on_jit(kernel: &Kernel) -> Result<i64, String> {
+    let compiled = compile_on_jit(kernel)?;
+    let result = compiled.run();
+    compiled.free();
+    Ok(result)
+}
+
+/// Branch to `target`, passing `args` as its block parameters
+fn br(builder: &mut FunctionBuilder, target: BlockId, args: Vec<ValueId>) {
+    builder.set_terminator(Terminator::Br { target, args });
+}
+
+/// Add the host allocator imports used by the memory kernels
+fn add_memory_imports(builder: &mut ProgramBuilder) {
+    builder.add_import("host", "alloc", vec![Type::Usize], Type::Usize);
+    builder.add_import("host", "free", vec![Type::Usize], Type::Void);
+}
+
+/// Address of element `index` (an i64) in an array of `elem_ty` at `base`
+fn element_address(
+    builder: &mut FunctionBuilder,
+    base: ValueId,
+    index: ValueId,
+    elem_ty: Type,
+) -> ValueId {
+    let index = builder.ins().convert(index, Type::I64, Type::Usize);
+    let size = builder.ins().size_of(elem_ty);
+    let offset = builder.ins().mul(Type::Usize, index, size);
+    builder.ins().ptr_add(base, offset)
+}
+
+/// fn fib(n: i64) -> i64, iterating from (0, 1) n times
+fn create_fibonacci_program() -> Program {
+    let mut builder = ProgramBuilder::new();
+    let func_idx = builder.create_function("fib", vec![Type::I64], Type::I64);
+
+    {
+        let mut fb = builder.function_builder(func_idx);
+        let entry = fb.create_block("entry");
+        let header = fb.create_block("loop");
+        let body = fb.create_block("body");
+        let exit = fb.create_block("exit");
+
+        fb.switch_to_block(entry);
+        let n = fb.declare_entry_params()[0];
+        let zero = fb.ins().const_i64(0);
+        let one = fb.ins().const_i64(1);
+        br(&mut fb, header, vec![zero, zero, one]);
+
+        fb.switch_to_block(header);
+        let i = fb.add_block_param(header, Type::I64);
+        let a = fb.add_block_param(header, Type::I64);
+        let b = fb.add_block_param(header, Type::I64);
+        let more = fb.ins().cmp_lt(Type::I64, i, n);
+        fb.ins().br_if(more, body, exit);
+
+        fb.switch_to_block(body);
+        let next = fb.ins().add(Type::I64, a, b);
+        let one = fb.ins().const_i64(1);
+        let i_next = fb.ins().add(Type::I64, i, one);
+        br(&mut fb, header, vec![i_next, b, next]);
+
+        fb.switch_to_block(exit);
+        fb.ins().ret(Some(a));
+    }
+
+    builder.build()
+}
+
+/// fn array_sum(n: i64) -> i64: fill an array with 0..n, then sum it
+fn create_array_sum_program() -> Program {
+    let mut builder = ProgramBuilder::new();
+    add_memory_imports(&mut builder);
+    let func_idx = builder.create_function("array_sum", vec![Type::I64], Type::I64);
+
+    {
+        let mut fb = builder.function_builder(func_idx);
+        let entry = fb.create_block("entry");
+        let fill = fb.create_block("fill");
+        let fill_body = fb.create_block("fill_body");
+        let sum = fb.create_block("sum");
+        let sum_body = fb.create_block("sum_body");
+        let done = fb.create_block("done");
+
+        fb.switch_to_block(entry);
+        let n = fb.declare_entry_params()[0];
+        let len = fb.ins().convert(n, Type::I64, Type::Usize);
+        let elem_size = fb.ins().size_of(Type::I64);
+        let bytes = fb.ins().mul(Type::Usize, len, elem_size);
+        let array = fb.ins().alloc(bytes);
+        let zero = fb.ins().const_i64(0);
+        br(&mut fb, fill, vec![zero]);
+
+        fb.switch_to_block(fill);
+        let i = fb.add_block_param(fill, Type::I64);
+        let more = fb.ins().cmp_lt(Type::I64, i, n);
+        let zero = fb.ins().const_i64(0);
+        fb.set_terminator(Terminator::BrIf {
+            cond: more,
+            true_target: fill_body,
+            true_args: vec![],
+            false_target: sum,
+            false_args: vec![zero, zero],
+        });
+
+        fb.switch_to_block(fill_body);
+        let addr = element_address(&mut fb, array, i, Type::I64);
+        fb.ins().store(addr, i, Type::I64);
+        let one = fb.ins().const_i64(1);
+        let i_next = fb.ins().add(Type::I64, i, one);
+        br(&mut fb, fill, vec![i_next]);
+
+        fb.switch_to_block(sum);
+        let j = fb.add_block_param(sum, Type::I64);
+        let total = fb.add_block_param(sum, Type::I64);
+        let more = fb.ins().cmp_lt(Type::I64, j, n);
+        fb.ins().br_if(more, sum_body, done);
+
+        fb.switch_to_block(sum_body);
+        let addr = element_address(&mut fb, array, j, Type::I64);
+        let value = fb.ins().load(Type::I64, addr);
+        let total_next = fb.ins().add(Type::I64, total, value);
+        let one = fb.ins().const_i64(1);
+        let j_next = fb.ins().add(Type::I64, j, one);
+        br(&mut fb, sum, vec![j_next, total_next]);
+
+        fb.switch_to_block(done);
+        fb.ins().free(array);
+        fb.ins().ret(Some(total));
+    }
+
+    builder.build()
+}
+
+/// fn string_length(n: i64) -> i64: build a zero-terminated string of n
+/// i32 characters, then count characters up to the terminator
+fn create_string_length_program() -> Program {
+    let mut builder = ProgramBuilder::new();
+    add_memory_imports(&mut builder);
+    let func_idx = builder.create_function("string_length", vec![Type::I64], Type::I64);
+
+    {
+        let mut fb = builder.function_builder(func_idx);
+        let entry = fb.create_block("entry");
+        let fill = fb.create_block("fill");
+        let fill_body = fb.create_block("fill_body");
+        let terminate = fb.create_block("terminate");
+        let count = fb.create_block("count");
+        let count_body = fb.create_block("count_body");
+        let done = fb.create_block("done");
+
+        fb.switch_to_block(entry);
+        let n = fb.declare_entry_params()[0];
+        let one = fb.ins().const_i64(1);
+        let chars = fb.ins().add(Type::I64, n, one);
+        let chars = fb.ins().convert(chars, Type::I64, Type::Usize);
+        let char_size = fb.ins().size_of(Type::I32);
+        let bytes = fb.ins().mul(Type::Usize, chars, char_size);
+        let string = fb.ins().alloc(bytes);
+        let zero = fb.ins().const_i64(0);
+        br(&mut fb, fill, vec![zero]);
+
+        fb.switch_to_block(fill);
+        let i = fb.add_block_param(fill, Type::I64);
+        let more = fb.ins().cmp_lt(Type::I64, i, n);
+        fb.ins().br_if(more, fill_body, terminate);
+
+        fb.switch_to_block(fill_body);
+        let addr = element_address(&mut fb, string, i, Type::I32);
+        let letter = fb.ins().const_i32('a' as i32);
+        fb.ins().store(addr, letter, Type::I32);
+        let one = fb.ins().const_i64(1);
+        let i_next = fb.ins().add(Type::I64, i, one);
+        br(&mut fb, fill, vec![i_next]);
+
+        fb.switch_to_block(terminate);
+        let addr = element_address(&mut fb, string, n, Type::I32);
+        let nul = fb.ins().const_i32(0);
+        fb.ins().store(addr, nul, Type::I32);
+        let zero = fb.ins().const_i64(0);
+        br(&mut fb, count, vec![zero]);
+
+        fb.switch_to_block(count);
+        let k = fb.add_block_param(count, Type::I64);
+        let addr = element_address(&mut fb, string, k, Type::I32);
+        let ch = fb.ins().load(Type::I32, addr);
+        let nul = fb.ins().const_i32(0);
+        let at_end = fb.ins().cmp_eq(Type::I32, ch, nul);
+        fb.ins().br_if(at_end, done, count_body);
+
+        fb.switch_to_block(count_body);
+        let one = fb.ins().const_i64(1);
+        let k_next = fb.ins().add(Type::I64, k, one);
+        br(&mut fb, count, vec![k_next]);
+
+        fb.switch_to_block(done);
+        fb.ins().free(string);
+        fb.ins().ret(Some(k));
+    }
+
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernels_run_once_on_both_backends() {
+        for kernel in standard_kernels() {
+            assert_eq!(
+                run_on_vm(&kernel),
+                Ok(kernel.expected),
+                "{} on VM",
+                kernel.name
+            );
+            assert_eq!(
+                run_on_jit(&kernel),
+                Ok(kernel.expected),
+                "{} on JIT",
+                kernel.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_kernels_verify() {
+        for kernel in standard_kernels() {
+            assert_eq!(
+                tilt_ir::verify_program(&kernel.program),
+                Ok(()),
+                "{}",
+                kernel.name
+            );
+        }
+    }
+}
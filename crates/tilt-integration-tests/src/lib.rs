@@ -1,5 +1,6 @@
 // Integration tests for TILT compiler components
 
+pub mod kernels;
 pub mod vm_jit_compatibility;
 
 #[cfg(test)]
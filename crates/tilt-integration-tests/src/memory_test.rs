@@ -4,8 +4,10 @@
 // DESC: Test program demonstrating the new memory operations
 // ===================================================================
 
+use std::sync::Arc;
 use tilt_ast::Type;
-use tilt_host_abi::{MemoryHostABI, RuntimeValue};
+use tilt_codegen_cranelift::JIT;
+use tilt_host_abi::{HostABI, JITMemoryHostABI, MemoryHostABI, RuntimeValue};
 use tilt_ir_builder::ProgramBuilder;
 use tilt_vm::VM;
 
@@ -46,6 +48,88 @@ fn test_memory_operations() {
     assert_eq!(result.unwrap(), RuntimeValue::Void);
 }
 
+#[test]
+fn test_sizeof_as_alloc_argument_on_both_backends() {
+    let mut builder = ProgramBuilder::new();
+    builder.add_import_with_cc("host", "alloc", None, vec![Type::Usize], Type::Usize);
+    builder.add_import_with_cc("host", "free", None, vec![Type::Usize], Type::Void);
+
+    // Allocate sizeof(usize) bytes, store the size itself there and load it back
+    let func_idx = builder.create_function("sized_alloc", vec![], Type::Usize);
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+
+        let size = func_builder.ins().size_of(Type::Usize);
+        let ptr = func_builder.ins().alloc(size);
+        func_builder.ins().store(ptr, size, Type::Usize);
+        let loaded = func_builder.ins().load(Type::Usize, ptr);
+        func_builder.ins().free(ptr);
+        func_builder.ins().ret(Some(loaded));
+    }
+
+    let program = Arc::new(builder.build());
+    let expected = RuntimeValue::Usize(std::mem::size_of::<usize>());
+
+    let mut vm = VM::new(Arc::clone(&program), MemoryHostABI::new());
+    let vm_result = vm.call_function("sized_alloc", vec![]).unwrap();
+    assert_eq!(vm_result, expected);
+
+    let mut jit = JIT::new_with_abi(Box::new(JITMemoryHostABI::new())).unwrap();
+    jit.compile(&program).unwrap();
+    let func_ptr = jit.get_func_ptr("sized_alloc").unwrap();
+    let sized_alloc = unsafe { std::mem::transmute::<*const u8, fn() -> usize>(func_ptr) };
+    assert_eq!(RuntimeValue::Usize(sized_alloc()), expected);
+}
+
+#[test]
+fn test_sum_i32_array_passed_as_ptr_and_len() {
+    let source = r#"
+fn sum_array(ptr:usize, len:usize) -> i32 {
+entry:
+    zero:usize = usize.const(0)
+    acc0:i32 = i32.const(0)
+    br loop(zero, acc0)
+loop(i:usize, acc:i32):
+    idx:i32 = usize.to_i32(i)
+    count:i32 = usize.to_i32(len)
+    more:i32 = i32.lt(idx, count)
+    br_if more, body, done
+body:
+    four:usize = usize.const(4)
+    offset:usize = usize.mul(i, four)
+    p:usize = usize.add(ptr, offset)
+    v:i32 = i32.load(p)
+    next_acc:i32 = i32.add(acc, v)
+    one:usize = usize.const(1)
+    next_i:usize = usize.add(i, one)
+    br loop(next_i, next_acc)
+done:
+    ret (acc)
+}
+"#;
+    let program = tiltc::compile_str(source).expect("Compilation should succeed");
+
+    let mut host_abi = MemoryHostABI::new();
+    let ptr = host_abi
+        .call_host_function("alloc", &[RuntimeValue::Usize(12)])
+        .unwrap();
+    host_abi
+        .write_i32_array(ptr.as_ptr(), &[10, 20, 12])
+        .unwrap();
+    assert_eq!(
+        host_abi.read_i32_array(ptr.as_ptr(), 3).unwrap(),
+        vec![10, 20, 12]
+    );
+
+    let mut vm = VM::new(program, host_abi);
+    let result = vm
+        .call_function("sum_array", vec![ptr, RuntimeValue::Usize(3)])
+        .unwrap();
+    assert_eq!(result, RuntimeValue::I32(42));
+}
+
 #[test]
 fn test_comprehensive_vertical_slice() {
     // This test exercises ALL current TILT features in a single program:
@@ -191,11 +275,11 @@ fn test_comprehensive_vertical_slice() {
         func_builder.ins().ret(Some(final_result));
     }
 
-    let program = builder.build();
+    let program = Arc::new(builder.build());
 
     // Test with VM
     let host_abi = MemoryHostABI::new();
-    let mut vm = VM::new(program.clone(), host_abi);
+    let mut vm = VM::new(Arc::clone(&program), host_abi);
 
     let vm_result = vm.call_function("comprehensive_test", vec![]);
     assert!(
@@ -301,7 +385,7 @@ entry:
     use tilt_parser::tilt;
 
     // Helper function to tokenize input and create position triples
-    fn tokenize_with_positions(input: &str) -> Result<Vec<(usize, Token, usize)>, String> {
+    fn tokenize_with_positions(input: &str) -> Result<Vec<(usize, Token<'_>, usize)>, String> {
         let mut lexer = Token::lexer(input);
         let mut tokens = Vec::new();
 
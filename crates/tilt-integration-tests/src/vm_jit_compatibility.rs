@@ -4,12 +4,98 @@
 // DESC: Integration tests for VM <-> JIT compatibility
 // ===================================================================
 
+use std::sync::Arc;
 use tilt_ast::Type;
 use tilt_codegen_cranelift::JIT;
-use tilt_host_abi::{NullHostABI, RuntimeValue};
+use tilt_host_abi::{HostABI, NullHostABI, RuntimeValue};
 use tilt_ir_builder::ProgramBuilder;
 use tilt_vm::VM;
 
+/// Build a native call to a JIT-compiled function, picking the Rust type of
+/// each argument from its `RuntimeValue` variant (`Usize` is pointer-width)
+macro_rules! call_native {
+    // All arguments converted: call with the signature implied by the return type
+    (@call $ptr:expr, $ret:expr, [$($ty:ty),*], [$($val:expr),*]) => {
+        match $ret {
+            Type::Void => {
+                std::mem::transmute::<*const u8, fn($($ty),*)>($ptr)($($val),*);
+                RuntimeValue::Void
+            }
+            Type::I32 => RuntimeValue::I32(
+                std::mem::transmute::<*const u8, fn($($ty),*) -> i32>($ptr)($($val),*),
+            ),
+            Type::I64 => RuntimeValue::I64(
+                std::mem::transmute::<*const u8, fn($($ty),*) -> i64>($ptr)($($val),*),
+            ),
+            Type::Usize | Type::Ptr(_) => RuntimeValue::Usize(
+                std::mem::transmute::<*const u8, fn($($ty),*) -> usize>($ptr)($($val),*),
+            ),
+            Type::F32 => RuntimeValue::F32(
+                std::mem::transmute::<*const u8, fn($($ty),*) -> f32>($ptr)($($val),*),
+            ),
+            Type::F64 => RuntimeValue::F64(
+                std::mem::transmute::<*const u8, fn($($ty),*) -> f64>($ptr)($($val),*),
+            ),
+        }
+    };
+    // Convert the next argument to its native type
+    (@args $ptr:expr, $ret:expr, [$($ty:ty),*], [$($val:expr),*], $next:expr $(, $rest:expr)*) => {
+        match $next {
+            RuntimeValue::I32(v) => {
+                let v = *v;
+                call_native!(@args $ptr, $ret, [$($ty,)* i32], [$($val,)* v] $(, $rest)*)
+            }
+            RuntimeValue::I64(v) => {
+                let v = *v;
+                call_native!(@args $ptr, $ret, [$($ty,)* i64], [$($val,)* v] $(, $rest)*)
+            }
+            RuntimeValue::Usize(v) => {
+                let v = *v;
+                call_native!(@args $ptr, $ret, [$($ty,)* usize], [$($val,)* v] $(, $rest)*)
+            }
+            RuntimeValue::F32(v) => {
+                let v = *v;
+                call_native!(@args $ptr, $ret, [$($ty,)* f32], [$($val,)* v] $(, $rest)*)
+            }
+            RuntimeValue::F64(v) => {
+                let v = *v;
+                call_native!(@args $ptr, $ret, [$($ty,)* f64], [$($val,)* v] $(, $rest)*)
+            }
+            RuntimeValue::Void => return Err("Cannot pass void as an argument".to_string()),
+        }
+    };
+    (@args $ptr:expr, $ret:expr, [$($ty:ty),*], [$($val:expr),*]) => {
+        call_native!(@call $ptr, $ret, [$($ty),*], [$($val),*])
+    };
+}
+
+/// Call a JIT-compiled function with up to three scalar arguments
+#[allow(dead_code)]
+unsafe fn call_jit_function(
+    func_ptr: *const u8,
+    args: &[RuntimeValue],
+    return_type: Type,
+) -> Result<RuntimeValue, String> {
+    let result = match args {
+        [] => call_native!(@args func_ptr, return_type, [], []),
+        [a] => call_native!(@args func_ptr, return_type, [], [], a),
+        [a, b] => call_native!(@args func_ptr, return_type, [], [], a, b),
+        [a, b, c] => call_native!(@args func_ptr, return_type, [], [], a, b, c),
+        _ => return Err("Too many arguments for JIT function call".to_string()),
+    };
+    Ok(result)
+}
+
+/// Whether two results are identical, comparing floats by bit pattern
+#[allow(dead_code)]
+fn same_bits(a: &RuntimeValue, b: &RuntimeValue) -> bool {
+    match (a, b) {
+        (RuntimeValue::F32(x), RuntimeValue::F32(y)) => x.to_bits() == y.to_bits(),
+        (RuntimeValue::F64(x), RuntimeValue::F64(y)) => x.to_bits() == y.to_bits(),
+        _ => a == b,
+    }
+}
+
 /// Run a program on both VM and JIT and compare return values
 #[allow(dead_code)]
 fn test_vm_jit_compatibility(
@@ -17,16 +103,28 @@ fn test_vm_jit_compatibility(
     function_name: &str,
     args: Vec<RuntimeValue>,
 ) -> Result<(), String> {
-    // Test with VM first
-    let vm_host_abi = NullHostABI::new();
-    let mut vm = VM::new(program.clone(), vm_host_abi);
+    test_vm_jit_compatibility_with_abi(program, function_name, args, NullHostABI::new())
+}
+
+/// Like `test_vm_jit_compatibility`, but runs the VM on the given host ABI,
+/// e.g. a `MemoryHostABI` for programs that allocate
+#[allow(dead_code)]
+fn test_vm_jit_compatibility_with_abi<H: HostABI>(
+    program: tilt_ir::Program,
+    function_name: &str,
+    args: Vec<RuntimeValue>,
+    vm_host_abi: H,
+) -> Result<(), String> {
+    // Test with VM first, sharing the program with the JIT below
+    let program = Arc::new(program);
+    let mut vm = VM::new(Arc::clone(&program), vm_host_abi);
     
     let vm_result = vm.call_function(function_name, args.clone())
         .map_err(|e| format!("VM execution failed: {:?}", e))?;
 
-    // Test with JIT
-    let jit_host_abi = Box::new(NullHostABI::new());
-    let mut jit = JIT::new_with_abi(jit_host_abi)
+    // Test with JIT, on its default host ABI so that programs can import
+    // the built-in host functions such as alloc
+    let mut jit = JIT::new()
         .map_err(|e| format!("Failed to create JIT: {:?}", e))?;
     
     jit.compile(&program)
@@ -37,110 +135,13 @@ fn test_vm_jit_compatibility(
         .ok_or_else(|| format!("Function '{}' not found in JIT", function_name))?;
     
     // Execute the function based on its signature and return type
-    let jit_result = unsafe {
-        match args.len() {
-            0 => {
-                // Check if this is a void or returning function by examining the program
-                let func = program.functions.iter()
-                    .find(|f| f.name == function_name)
-                    .ok_or_else(|| format!("Function {} not found in program", function_name))?;
-                
-                if func.return_type == Type::Void {
-                    let func_fn = std::mem::transmute::<*const u8, fn()>(func_ptr);
-                    func_fn();
-                    RuntimeValue::Void
-                } else {
-                    let func_fn = std::mem::transmute::<*const u8, fn() -> i32>(func_ptr);
-                    RuntimeValue::I32(func_fn())
-                }
-            }
-            1 => {
-                let arg0 = match &args[0] {
-                    RuntimeValue::I32(val) => *val,
-                    RuntimeValue::I64(val) => *val as i32,
-                    RuntimeValue::Usize(val) => *val as i32,
-                    RuntimeValue::Void => 0,
-                };
-                
-                let func = program.functions.iter()
-                    .find(|f| f.name == function_name)
-                    .ok_or_else(|| format!("Function {} not found in program", function_name))?;
-                
-                if func.return_type == Type::Void {
-                    let func_fn = std::mem::transmute::<*const u8, fn(i32)>(func_ptr);
-                    func_fn(arg0);
-                    RuntimeValue::Void
-                } else {
-                    let func_fn = std::mem::transmute::<*const u8, fn(i32) -> i32>(func_ptr);
-                    RuntimeValue::I32(func_fn(arg0))
-                }
-            }
-            2 => {
-                let arg0 = match &args[0] {
-                    RuntimeValue::I32(val) => *val,
-                    RuntimeValue::I64(val) => *val as i32,
-                    RuntimeValue::Usize(val) => *val as i32,
-                    RuntimeValue::Void => 0,
-                };
-                let arg1 = match &args[1] {
-                    RuntimeValue::I32(val) => *val,
-                    RuntimeValue::I64(val) => *val as i32,
-                    RuntimeValue::Usize(val) => *val as i32,
-                    RuntimeValue::Void => 0,
-                };
-                
-                let func = program.functions.iter()
-                    .find(|f| f.name == function_name)
-                    .ok_or_else(|| format!("Function {} not found in program", function_name))?;
-                
-                if func.return_type == Type::Void {
-                    let func_fn = std::mem::transmute::<*const u8, fn(i32, i32)>(func_ptr);
-                    func_fn(arg0, arg1);
-                    RuntimeValue::Void
-                } else {
-                    let func_fn = std::mem::transmute::<*const u8, fn(i32, i32) -> i32>(func_ptr);
-                    RuntimeValue::I32(func_fn(arg0, arg1))
-                }
-            }
-            3 => {
-                let arg0 = match &args[0] {
-                    RuntimeValue::I32(val) => *val,
-                    RuntimeValue::I64(val) => *val as i32,
-                    RuntimeValue::Usize(val) => *val as i32,
-                    RuntimeValue::Void => 0,
-                };
-                let arg1 = match &args[1] {
-                    RuntimeValue::I32(val) => *val,
-                    RuntimeValue::I64(val) => *val as i32,
-                    RuntimeValue::Usize(val) => *val as i32,
-                    RuntimeValue::Void => 0,
-                };
-                let arg2 = match &args[2] {
-                    RuntimeValue::I32(val) => *val,
-                    RuntimeValue::I64(val) => *val as i32,
-                    RuntimeValue::Usize(val) => *val as i32,
-                    RuntimeValue::Void => 0,
-                };
-                
-                let func = program.functions.iter()
-                    .find(|f| f.name == function_name)
-                    .ok_or_else(|| format!("Function {} not found in program", function_name))?;
-                
-                if func.return_type == Type::Void {
-                    let func_fn = std::mem::transmute::<*const u8, fn(i32, i32, i32)>(func_ptr);
-                    func_fn(arg0, arg1, arg2);
-                    RuntimeValue::Void
-                } else {
-                    let func_fn = std::mem::transmute::<*const u8, fn(i32, i32, i32) -> i32>(func_ptr);
-                    RuntimeValue::I32(func_fn(arg0, arg1, arg2))
-                }
-            }
-            _ => return Err("Too many arguments for JIT function call".to_string()),
-        }
-    };
+    let func = program.functions.iter()
+        .find(|f| f.name == function_name)
+        .ok_or_else(|| format!("Function {} not found in program", function_name))?;
+    let jit_result = unsafe { call_jit_function(func_ptr, &args, func.return_type.clone())? };
 
-    // Compare results
-    if vm_result != jit_result {
+    // Compare results, floats by bit pattern
+    if !same_bits(&vm_result, &jit_result) {
         return Err(format!(
             "VM and JIT results differ!\nVM: {:?}\nJIT: {:?}", 
             vm_result, jit_result
@@ -287,97 +288,1635 @@ fn create_division_program() -> tilt_ir::Program {
     builder.build()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Create a program that offsets a pointer: fn offset_ptr(p: usize, n: usize) -> usize
+#[allow(dead_code)]
+fn create_pointer_offset_program() -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    let func_idx = builder.create_function("offset_ptr", vec![Type::Usize, Type::Usize], Type::Usize);
 
-    #[test]
-    fn test_arithmetic_compatibility() {
-        let program = create_arithmetic_program();
-        let args = vec![RuntimeValue::I32(5), RuntimeValue::I32(3), RuntimeValue::I32(2)];
-        
-        test_vm_jit_compatibility(program, "add_mul", args)
-            .expect("Arithmetic test should pass");
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+
+        let p = func_builder.add_block_param(entry, Type::Usize);
+        let n = func_builder.add_block_param(entry, Type::Usize);
+        let result = func_builder.ins().ptr_add(p, n);
+        func_builder.ins().ret(Some(result));
     }
 
-    #[test]
-    fn test_conditional_compatibility() {
-        let program = create_conditional_program();
-        
-        // Test case where a > b
-        let args1 = vec![RuntimeValue::I32(10), RuntimeValue::I32(5)];
-        test_vm_jit_compatibility(program.clone(), "max", args1)
-            .expect("Conditional test (a > b) should pass");
+    builder.build()
+}
 
-        // Test case where a < b
-        let args2 = vec![RuntimeValue::I32(3), RuntimeValue::I32(7)];
-        test_vm_jit_compatibility(program.clone(), "max", args2)
-            .expect("Conditional test (a < b) should pass");
+/// Create a pointer-sized subtraction: fn usize_sub(a: usize, b: usize) -> usize
+#[allow(dead_code)]
+fn create_usize_sub_program() -> tilt_ir::Program {
+    let source = "fn usize_sub(a:usize, b:usize) -> usize {\nentry:\n    r:usize = usize.sub(a, b)\n    ret (r)\n}\n";
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
 
-        // Test case where a == b
-        let args3 = vec![RuntimeValue::I32(5), RuntimeValue::I32(5)];
-        test_vm_jit_compatibility(program, "max", args3)
-            .expect("Conditional test (a == b) should pass");
+/// Create a switch over the given case values: fn classify(x: i32) -> i32
+/// returns `10 * value + 1` for a matching case and -1 otherwise
+#[allow(dead_code)]
+fn create_switch_program(case_values: &[i64]) -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    let func_idx = builder.create_function("classify", vec![Type::I32], Type::I32);
+
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        let cases: Vec<(i64, tilt_ir::BlockId)> = case_values
+            .iter()
+            .map(|value| (*value, func_builder.create_block(&format!("case_{}", value))))
+            .collect();
+        let default = func_builder.create_block("default");
+
+        func_builder.switch_to_block(entry);
+        let x = func_builder.add_block_param(entry, Type::I32);
+        func_builder.ins().switch(x, cases.clone(), default);
+
+        for (value, block) in cases {
+            func_builder.switch_to_block(block);
+            let result = func_builder.ins().const_i32(value as i32 * 10 + 1);
+            func_builder.ins().ret(Some(result));
+        }
+
+        func_builder.switch_to_block(default);
+        let result = func_builder.ins().const_i32(-1);
+        func_builder.ins().ret(Some(result));
     }
 
-    #[test]
-    fn test_recursion_compatibility() {
-        let program = create_recursive_program();
-        
-        // Test factorial(0) = 1
-        let args1 = vec![RuntimeValue::I32(0)];
-        test_vm_jit_compatibility(program.clone(), "factorial", args1)
-            .expect("Recursion test (factorial 0) should pass");
+    builder.build()
+}
 
-        // Test factorial(1) = 1
-        let args2 = vec![RuntimeValue::I32(1)];
-        test_vm_jit_compatibility(program.clone(), "factorial", args2)
-            .expect("Recursion test (factorial 1) should pass");
+/// Create fn weighted(a: i64, b: i64, c: i32) -> i64 = a - b * c, taking its
+/// entry parameters from the signature
+#[allow(dead_code)]
+fn create_declared_params_program() -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    let func_idx =
+        builder.create_function("weighted", vec![Type::I64, Type::I64, Type::I32], Type::I64);
 
-        // Test factorial(5) = 120
-        let args3 = vec![RuntimeValue::I32(5)];
-        test_vm_jit_compatibility(program, "factorial", args3)
-            .expect("Recursion test (factorial 5) should pass");
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+
+        let params = func_builder.declare_entry_params();
+        let c = func_builder.ins().convert(params[2], Type::I32, Type::I64);
+        let product =
+            func_builder
+                .ins()
+                .binary_op(tilt_ir::BinaryOperator::Mul, Type::I64, params[1], c);
+        let result =
+            func_builder
+                .ins()
+                .binary_op(tilt_ir::BinaryOperator::Sub, Type::I64, params[0], product);
+        func_builder.ins().ret(Some(result));
     }
 
-    #[test]
-    fn test_division_compatibility() {
-        let program = create_division_program();
-        
-        // Test normal division
-        let args1 = vec![RuntimeValue::I32(15), RuntimeValue::I32(3)];
-        test_vm_jit_compatibility(program.clone(), "safe_divide", args1)
-            .expect("Division test (normal) should pass");
+    builder.build()
+}
 
-        // Test division by zero
-        let args2 = vec![RuntimeValue::I32(10), RuntimeValue::I32(0)];
-        test_vm_jit_compatibility(program, "safe_divide", args2)
-            .expect("Division test (by zero) should pass");
+/// Create a program storing its argument and reusing the stored value:
+/// fn store_twice(x: i64) -> i64 returns the stored value plus what loads back
+#[allow(dead_code)]
+fn create_store_value_program() -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    builder.add_import("host", "alloc", vec![Type::Usize], Type::Usize);
+    builder.add_import("host", "free", vec![Type::Usize], Type::Void);
+    let func_idx = builder.create_function("store_twice", vec![Type::I64], Type::I64);
+
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+        let x = func_builder.declare_entry_params()[0];
+        let size = func_builder.ins().size_of(Type::I64);
+        let p = func_builder.ins().alloc(size);
+        let stored = func_builder.ins().store_value(p, x, Type::I64);
+        let loaded = func_builder.ins().load(Type::I64, p);
+        func_builder.ins().free(p);
+        let result = func_builder.ins().add(Type::I64, stored, loaded);
+        func_builder.ins().ret(Some(result));
     }
 
-    #[test]
-    fn test_edge_cases() {
-        // Test with maximum and minimum i32 values
-        let program = create_arithmetic_program();
-        
-        // Test with large numbers (but avoid overflow)
-        let args1 = vec![RuntimeValue::I32(1000), RuntimeValue::I32(0), RuntimeValue::I32(1)];
-        test_vm_jit_compatibility(program.clone(), "add_mul", args1)
-            .expect("Edge case test (large numbers) should pass");
+    builder.build()
+}
 
-        // Test with negative numbers
-        let args2 = vec![RuntimeValue::I32(-10), RuntimeValue::I32(5), RuntimeValue::I32(-2)];
-        test_vm_jit_compatibility(program, "add_mul", args2)
-            .expect("Edge case test (negative numbers) should pass");
+/// Create a lookup into a read-only table embedded with the function:
+/// fn lookup(i: i64) -> i32 returns [10, 20, 30, 40][i]
+#[allow(dead_code)]
+fn create_rodata_table_program() -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    let func_idx = builder.create_function("lookup", vec![Type::I64], Type::I32);
+
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let table: Vec<u8> = [10i32, 20, 30, 40]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let table = func_builder.add_rodata(table);
+
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+        let i = func_builder.declare_entry_params()[0];
+        let base = func_builder.ins().data_addr(table);
+        let index = func_builder.ins().convert(i, Type::I64, Type::Usize);
+        let size = func_builder.ins().size_of(Type::I32);
+        let offset = func_builder.ins().mul(Type::Usize, index, size);
+        let addr = func_builder.ins().ptr_add(base, offset);
+        let value = func_builder.ins().load(Type::I32, addr);
+        func_builder.ins().ret(Some(value));
     }
 
-    #[test]
-    fn test_zero_operations() {
-        let program = create_arithmetic_program();
-        
-        // Test with all zeros
-        let args = vec![RuntimeValue::I32(0), RuntimeValue::I32(0), RuntimeValue::I32(0)];
-        test_vm_jit_compatibility(program, "add_mul", args)
-            .expect("Zero operations test should pass");
+    builder.build()
+}
+
+/// Create fn main(a: i64, b: i64) -> i64 forwarding both parameters to
+/// fn real_main(a: i64, b: i64) -> i64 = a - b
+#[allow(dead_code)]
+fn create_forwarding_program() -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    let real_idx = builder.create_function("real_main", vec![Type::I64, Type::I64], Type::I64);
+    let main_idx = builder.create_function("main", vec![Type::I64, Type::I64], Type::I64);
+
+    {
+        let mut func_builder = builder.function_builder(real_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+        let params = func_builder.declare_entry_params();
+        let result =
+            func_builder
+                .ins()
+                .binary_op(tilt_ir::BinaryOperator::Sub, Type::I64, params[0], params[1]);
+        func_builder.ins().ret(Some(result));
+    }
+
+    {
+        let mut func_builder = builder.function_builder(main_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+        func_builder.declare_entry_params();
+        let result = func_builder.ins().call_forwarding("real_main", Type::I64);
+        func_builder.ins().ret(Some(result));
+    }
+
+    builder.build()
+}
+
+/// Create a program returning one usize constant: fn constant() -> usize
+#[allow(dead_code)]
+fn create_usize_constant_program(value: usize) -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    let func_idx = builder.create_function("constant", vec![], Type::Usize);
+
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+        let result = func_builder.ins().const_usize(value);
+        func_builder.ins().ret(Some(result));
+    }
+
+    builder.build()
+}
+
+/// Create a program applying one float intrinsic: fn unary(x: f64) -> f64
+#[allow(dead_code)]
+fn create_float_unary_program(op: &str) -> tilt_ir::Program {
+    let source = format!(
+        "fn unary(x:f64) -> f64 {{\nentry:\n    r:f64 = f64.{}(x)\n    ret (r)\n}}\n",
+        op
+    );
+    tiltc::compile_str(&source).expect("Compilation should succeed")
+}
+
+/// Create a program passing a float through memory: fn roundtrip(x: ty) -> ty
+#[allow(dead_code)]
+fn create_float_memory_program(ty: &str) -> tilt_ir::Program {
+    let source = format!(
+        r#"
+import "host" "alloc" (size:usize) -> usize
+import "host" "free" (p:usize) -> void
+
+fn roundtrip(x:{ty}) -> {ty} {{
+entry:
+    size:usize = sizeof.{ty}()
+    p:usize = alloc(size)
+    {ty}.store(p, x)
+    r:{ty} = {ty}.load(p)
+    free(p)
+    ret (r)
+}}
+"#
+    );
+    tiltc::compile_str(&source).expect("Compilation should succeed")
+}
+
+/// Create a program calling the host allocator through import aliases:
+/// fn aliased_roundtrip(x: i64) -> i64
+#[allow(dead_code)]
+fn create_aliased_import_program() -> tilt_ir::Program {
+    let source = r#"
+import "host" "alloc" (size:usize) -> usize as grab
+import "host" "free" (p:usize) -> void as release
+
+fn aliased_roundtrip(x:i64) -> i64 {
+entry:
+    size:usize = sizeof.i64()
+    p:usize = grab(size)
+    i64.store(p, x)
+    r:i64 = i64.load(p)
+    release(p)
+    ret (r)
+}
+"#;
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create a program testing one value against three constants in a chain
+/// of br_ifs: fn classify(x: i32) -> i32, returning 10, 20 or 30 for x = 1,
+/// 2 or 3 and 0 otherwise
+#[allow(dead_code)]
+fn create_eq_chain_program() -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    let func_idx = builder.create_function("classify", vec![Type::I32], Type::I32);
+
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        let tests = [
+            entry,
+            func_builder.create_block("test2"),
+            func_builder.create_block("test3"),
+        ];
+        let other = func_builder.create_block("other");
+
+        func_builder.switch_to_block(entry);
+        let x = func_builder.add_block_param(entry, Type::I32);
+
+        for (i, test) in tests.iter().enumerate() {
+            let case = func_builder.create_block(&format!("case{}", i + 1));
+            let next = tests.get(i + 1).copied().unwrap_or(other);
+
+            func_builder.switch_to_block(*test);
+            let key = func_builder.ins().const_i32(i as i32 + 1);
+            let is_key = func_builder.ins().cmp_eq(Type::I32, x, key);
+            func_builder.ins().br_if(is_key, case, next);
+
+            func_builder.switch_to_block(case);
+            let result = func_builder.ins().const_i32(10 * (i as i32 + 1));
+            func_builder.ins().ret(Some(result));
+        }
+
+        func_builder.switch_to_block(other);
+        let zero = func_builder.ins().const_i32(0);
+        func_builder.ins().ret(Some(zero));
+    }
+
+    builder.build()
+}
+
+/// Create the same chain of equality tests from source, with the keys as
+/// literal operands: fn classify(x: i32) -> i32
+#[allow(dead_code)]
+fn create_eq_chain_source_program() -> tilt_ir::Program {
+    let source = r#"
+fn classify(x:i32) -> i32 {
+entry:
+    c1:i32 = i32.eq(x, 1)
+    br_if c1, case1, test2
+case1:
+    r1:i32 = i32.const(10)
+    ret (r1)
+test2:
+    c2:i32 = i32.eq(x, 2)
+    br_if c2, case2, test3
+case2:
+    r2:i32 = i32.const(20)
+    ret (r2)
+test3:
+    c3:i32 = i32.eq(3, x)
+    br_if c3, case3, other
+case3:
+    r3:i32 = i32.const(30)
+    ret (r3)
+other:
+    zero:i32 = i32.const(0)
+    ret (zero)
+}
+"#;
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create a program computing the same product twice, once with swapped
+/// operands: fn double_product(a: i32, b: i32) -> i32 { a * b + b * a }
+#[allow(dead_code)]
+fn create_duplicate_product_program() -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    let func_idx = builder.create_function("double_product", vec![Type::I32, Type::I32], Type::I32);
+
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+        let a = func_builder.add_block_param(entry, Type::I32);
+        let b = func_builder.add_block_param(entry, Type::I32);
+
+        let first = func_builder.ins().mul(Type::I32, a, b);
+        let second = func_builder.ins().mul(Type::I32, b, a);
+        let sum = func_builder.ins().add(Type::I32, first, second);
+        func_builder.ins().ret(Some(sum));
+    }
+
+    builder.build()
+}
+
+/// Create a program storing two i64s through a typed pointer and summing
+/// them after passing the pointers as block arguments:
+/// fn sum_pair(a: i64, b: i64) -> i64
+#[allow(dead_code)]
+fn create_typed_pointer_program() -> tilt_ir::Program {
+    let source = r#"
+import "host" "alloc" (size:usize) -> usize
+import "host" "free" (p:usize) -> void
+
+fn sum_pair(a:i64, b:i64) -> i64 {
+entry:
+    size:usize = sizeof.i64()
+    two:usize = usize.const(2)
+    bytes:usize = usize.mul(size, two)
+    p:ptr<i64> = alloc(bytes)
+    q:ptr<i64> = usize.add(p, size)
+    i64.store(p, a)
+    i64.store(q, b)
+    br read(p, q)
+
+read(first:ptr<i64>, second:ptr<i64>):
+    x:i64 = i64.load(first)
+    y:i64 = i64.load(second)
+    free(first)
+    r:i64 = i64.add(x, y)
+    ret (r)
+}
+"#;
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create fn double(x: i32) -> i32 { x + x } with an assertion that the
+/// sum has type `expected`, run through the verifier
+#[allow(dead_code)]
+fn create_assert_type_program(expected: Type) -> Result<tilt_ir::Program, Vec<tilt_ir::VerifyError>> {
+    let mut builder = ProgramBuilder::new();
+    let func_idx = builder.create_function("double", vec![Type::I32], Type::I32);
+
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+        let x = func_builder.add_block_param(entry, Type::I32);
+
+        let sum = func_builder.ins().binary_op(tilt_ir::BinaryOperator::Add, Type::I32, x, x);
+        func_builder.ins().assert_type(sum, expected);
+        func_builder.ins().ret(Some(sum));
+    }
+
+    builder.try_build()
+}
+
+/// Create a program calling an import and a program function with the same
+/// signature, where the function is named like a host function it must not
+/// be confused with
+#[allow(dead_code)]
+fn create_import_and_local_program() -> tilt_ir::Program {
+    let source = r#"
+import "env" "last_error" -> i32
+
+fn print_hello() -> i32 {
+entry:
+    r:i32 = i32.const(42)
+    ret (r)
+}
+
+fn main() -> i32 {
+entry:
+    local:i32 = print_hello()
+    imported:i32 = last_error()
+    r:i32 = i32.add(local, imported)
+    ret (r)
+}
+"#;
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create a program requesting an alignment that is not a power of two:
+/// fn bad_align() -> i32 returns last_error(), or -1 if the call didn't
+/// return null
+#[allow(dead_code)]
+fn create_bad_alignment_program() -> tilt_ir::Program {
+    let source = r#"
+import "host" "alloc_aligned" (size:usize, align:usize) -> usize
+import "host" "last_error" -> i32
+
+fn bad_align() -> i32 {
+entry:
+    p:usize = alloc_aligned(16, 12)
+    zero:usize = usize.const(0)
+    is_null:i32 = usize.eq(p, zero)
+    br_if is_null, null, not_null
+
+null:
+    e:i32 = last_error()
+    ret (e)
+
+not_null:
+    ret (-1)
+}
+"#;
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create float arithmetic and conversion functions:
+/// fn blend(a: f64, b: i32) -> f64 { f32(((a + b) - 0.25) * 3 / 2) as f64 }
+/// fn average(a: f32, b: f32) -> f32 and fn truncate(x: f64) -> i64
+#[allow(dead_code)]
+fn create_float_arithmetic_program() -> tilt_ir::Program {
+    let source = r#"
+fn blend(a:f64, b:i32) -> f64 {
+entry:
+    wide:f64 = i32.to_f64(b)
+    sum:f64 = f64.add(a, wide)
+    quarter:f64 = f64.const(0.25)
+    diff:f64 = f64.sub(sum, quarter)
+    three:f64 = f64.const(3)
+    scaled:f64 = f64.mul(diff, three)
+    two:f64 = f64.const(2)
+    half:f64 = f64.div(scaled, two)
+    narrow:f32 = f64.to_f32(half)
+    back:f64 = f32.to_f64(narrow)
+    ret (back)
+}
+
+fn average(a:f32, b:f32) -> f32 {
+entry:
+    sum:f32 = f32.add(a, b)
+    two:f32 = f32.const(2)
+    mean:f32 = f32.div(sum, two)
+    ret (mean)
+}
+
+fn truncate(x:f64) -> i64 {
+entry:
+    r:i64 = f64.to_i64(x)
+    ret (r)
+}
+"#;
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create a program applying one binary operator: fn op(a: ty, b: ty) -> ty
+#[allow(dead_code)]
+fn create_binary_op_program(op: &str, ty: &str) -> tilt_ir::Program {
+    let source = format!(
+        r#"
+fn op(a:{ty}, b:{ty}) -> {ty} {{
+entry:
+    r:{ty} = {ty}.{op}(a, b)
+    ret (r)
+}}
+"#
+    );
+    tiltc::compile_str(&source).expect("Compilation should succeed")
+}
+
+/// Create a program returning one float constant: fn constant() -> ty
+#[allow(dead_code)]
+fn create_float_constant_program(ty: &str, literal: &str) -> tilt_ir::Program {
+    let source = format!(
+        r#"
+fn constant() -> {ty} {{
+entry:
+    r:{ty} = {ty}.const({literal})
+    ret (r)
+}}
+"#
+    );
+    tiltc::compile_str(&source).expect("Compilation should succeed")
+}
+
+/// Create fn constant() -> i32 returning a raw `Const` bit pattern, which
+/// neither the parser nor the builder would produce out of i32 range
+#[allow(dead_code)]
+fn create_raw_i32_constant_program(value: i64) -> tilt_ir::Program {
+    let mut func = tilt_ir::Function::new("constant".to_string(), vec![], Type::I32);
+    let dest = func.next_value();
+
+    let mut entry = tilt_ir::BasicBlock::new(tilt_ir::BlockId::new(0), "entry".to_string());
+    entry.instructions.push(tilt_ir::Instruction::Const {
+        dest,
+        value,
+        ty: Type::I32,
+    });
+    entry.terminator = tilt_ir::Terminator::Ret { value: Some(dest) };
+    func.blocks.push(entry);
+
+    tilt_ir::Program {
+        imports: vec![],
+        functions: vec![func],
+        rodata: vec![],
+    }
+}
+
+/// Create a trapping narrowing conversion: fn narrow(x: i64) -> i32
+#[allow(dead_code)]
+fn create_checked_narrowing_program() -> tilt_ir::Program {
+    let source = "fn narrow(x:i64) -> i32 {\nentry:\n    r:i32 = i64.to_i32_checked(x)\n    ret (r)\n}\n";
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create a load through a null pointer: fn deref_null() -> i32
+#[allow(dead_code)]
+fn create_null_load_program() -> tilt_ir::Program {
+    let source = "fn deref_null() -> i32 {\nentry:\n    p:usize = usize.const(0)\n    v:i32 = i32.load(p)\n    ret (v)\n}\n";
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create a zero test on one integer type: fn is_zero(x: ty) -> i32
+#[allow(dead_code)]
+fn create_eqz_program(ty: &str) -> tilt_ir::Program {
+    let source = format!(
+        "fn is_zero(x:{ty}) -> i32 {{\nentry:\n    r:i32 = {ty}.eqz(x)\n    ret (r)\n}}\n"
+    );
+    tiltc::compile_str(&source).expect("Compilation should succeed")
+}
+
+/// Create a comparison both as a value and as a branch condition:
+/// fn compare(a: ty, b: ty) -> i32 and fn branch(a: ty, b: ty) -> i32
+#[allow(dead_code)]
+fn create_compare_program(ty: &str, op: &str) -> tilt_ir::Program {
+    let source = format!(
+        r#"
+fn compare(a:{ty}, b:{ty}) -> i32 {{
+entry:
+    r:i32 = {ty}.{op}(a, b)
+    ret (r)
+}}
+
+fn branch(a:{ty}, b:{ty}) -> i32 {{
+entry:
+    c:i32 = {ty}.{op}(a, b)
+    br_if c, yes, no
+yes:
+    one:i32 = i32.const(1)
+    ret (one)
+no:
+    zero:i32 = i32.const(0)
+    ret (zero)
+}}
+"#
+    );
+    tiltc::compile_str(&source).expect("Compilation should succeed")
+}
+
+/// Create a program branching directly on its argument: fn pick(x: i32) -> i32
+#[allow(dead_code)]
+fn create_branch_on_value_program() -> tilt_ir::Program {
+    let source = r#"
+fn pick(x:i32) -> i32 {
+entry:
+    br_if x, taken, not_taken
+
+taken:
+    ret (1)
+
+not_taken:
+    ret (0)
+}
+"#;
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create a loop whose counter and total are phi nodes: fn sum_to(n: i32) -> i32
+/// returns 1 + 2 + ... + n
+#[allow(dead_code)]
+fn create_phi_loop_program() -> tilt_ir::Program {
+    let source = r#"
+fn sum_to(n:i32) -> i32 {
+entry:
+    zero:i32 = i32.const(0)
+    br loop
+
+loop:
+    i:i32 = phi [entry: zero], [body: next]
+    total:i32 = phi [entry: zero], [body: total_next]
+    done:i32 = i32.eq(i, n)
+    br_if done, exit, body
+
+body:
+    one:i32 = i32.const(1)
+    next:i32 = i32.add(i, one)
+    total_next:i32 = i32.add(total, next)
+    br loop
+
+exit:
+    ret (total)
+}
+"#;
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create a usize negation: fn negate(x: usize) -> usize
+#[allow(dead_code)]
+fn create_usize_neg_program() -> tilt_ir::Program {
+    let source = r#"
+fn negate(x:usize) -> usize {
+entry:
+    r:usize = usize.neg(x)
+    ret (r)
+}
+"#;
+    tiltc::compile_str(source).expect("Compilation should succeed")
+}
+
+/// Create a program with a single conversion: fn convert(x: from_ty) -> to_ty
+#[allow(dead_code)]
+fn create_convert_program(from_ty: Type, to_ty: Type) -> tilt_ir::Program {
+    let mut builder = ProgramBuilder::new();
+    let func_idx = builder.create_function("convert", vec![from_ty.clone()], to_ty.clone());
+
+    {
+        let mut func_builder = builder.function_builder(func_idx);
+        let entry = func_builder.create_block("entry");
+        func_builder.switch_to_block(entry);
+
+        let x = func_builder.add_block_param(entry, from_ty.clone());
+        let result = func_builder.ins().convert(x, from_ty, to_ty);
+        func_builder.ins().ret(Some(result));
+    }
+
+    builder.build()
+}
+
+/// Run a conversion on both backends. `None` means the backend rejected it.
+#[allow(dead_code)]
+fn run_convert_on_both(
+    from_ty: Type,
+    to_ty: Type,
+    input: RuntimeValue,
+) -> (Option<RuntimeValue>, Option<RuntimeValue>) {
+    let program = Arc::new(create_convert_program(from_ty, to_ty.clone()));
+
+    let mut vm = VM::new(Arc::clone(&program), NullHostABI::new());
+    let vm_result = vm.call_function("convert", vec![input.clone()]).ok();
+
+    let mut jit = JIT::new_with_abi(Box::new(NullHostABI::new())).expect("Failed to create JIT");
+    let jit_result = match jit.compile(&program) {
+        Ok(()) => {
+            let func_ptr = jit
+                .get_func_ptr("convert")
+                .expect("Function 'convert' not found in JIT");
+            Some(unsafe { call_jit_function(func_ptr, &[input], to_ty).unwrap() })
+        }
+        Err(_) => None,
+    };
+
+    (vm_result, jit_result)
+}
+
+/// Sample inputs for conversions, including the extremes of each type
+#[allow(dead_code)]
+fn convert_sample_values(ty: Type) -> Vec<RuntimeValue> {
+    match ty {
+        Type::I32 => vec![0, 42, -1, i32::MIN, i32::MAX]
+            .into_iter()
+            .map(RuntimeValue::I32)
+            .collect(),
+        Type::I64 => vec![0, 42, -1, 1 << 40, i64::MIN, i64::MAX]
+            .into_iter()
+            .map(RuntimeValue::I64)
+            .collect(),
+        Type::Usize => vec![0, 42, usize::MAX]
+            .into_iter()
+            .map(RuntimeValue::Usize)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tilt_host_abi::{MemoryHostABI, HOST_ERR_INVALID_INPUT};
+
+    #[test]
+    fn test_arithmetic_compatibility() {
+        let program = create_arithmetic_program();
+        let args = vec![RuntimeValue::I32(5), RuntimeValue::I32(3), RuntimeValue::I32(2)];
+        
+        test_vm_jit_compatibility(program, "add_mul", args)
+            .expect("Arithmetic test should pass");
+    }
+
+    #[test]
+    fn test_conditional_compatibility() {
+        let program = create_conditional_program();
+        
+        // Test case where a > b
+        let args1 = vec![RuntimeValue::I32(10), RuntimeValue::I32(5)];
+        test_vm_jit_compatibility(program.clone(), "max", args1)
+            .expect("Conditional test (a > b) should pass");
+
+        // Test case where a < b
+        let args2 = vec![RuntimeValue::I32(3), RuntimeValue::I32(7)];
+        test_vm_jit_compatibility(program.clone(), "max", args2)
+            .expect("Conditional test (a < b) should pass");
+
+        // Test case where a == b
+        let args3 = vec![RuntimeValue::I32(5), RuntimeValue::I32(5)];
+        test_vm_jit_compatibility(program, "max", args3)
+            .expect("Conditional test (a == b) should pass");
+    }
+
+    #[test]
+    fn test_recursion_compatibility() {
+        let program = create_recursive_program();
+        
+        // Test factorial(0) = 1
+        let args1 = vec![RuntimeValue::I32(0)];
+        test_vm_jit_compatibility(program.clone(), "factorial", args1)
+            .expect("Recursion test (factorial 0) should pass");
+
+        // Test factorial(1) = 1
+        let args2 = vec![RuntimeValue::I32(1)];
+        test_vm_jit_compatibility(program.clone(), "factorial", args2)
+            .expect("Recursion test (factorial 1) should pass");
+
+        // Test factorial(5) = 120
+        let args3 = vec![RuntimeValue::I32(5)];
+        test_vm_jit_compatibility(program, "factorial", args3)
+            .expect("Recursion test (factorial 5) should pass");
+    }
+
+    #[test]
+    fn test_division_compatibility() {
+        let program = create_division_program();
+        
+        // Test normal division
+        let args1 = vec![RuntimeValue::I32(15), RuntimeValue::I32(3)];
+        test_vm_jit_compatibility(program.clone(), "safe_divide", args1)
+            .expect("Division test (normal) should pass");
+
+        // Test division by zero
+        let args2 = vec![RuntimeValue::I32(10), RuntimeValue::I32(0)];
+        test_vm_jit_compatibility(program, "safe_divide", args2)
+            .expect("Division test (by zero) should pass");
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        // Test with maximum and minimum i32 values
+        let program = create_arithmetic_program();
+        
+        // Test with large numbers (but avoid overflow)
+        let args1 = vec![RuntimeValue::I32(1000), RuntimeValue::I32(0), RuntimeValue::I32(1)];
+        test_vm_jit_compatibility(program.clone(), "add_mul", args1)
+            .expect("Edge case test (large numbers) should pass");
+
+        // Test with negative numbers
+        let args2 = vec![RuntimeValue::I32(-10), RuntimeValue::I32(5), RuntimeValue::I32(-2)];
+        test_vm_jit_compatibility(program, "add_mul", args2)
+            .expect("Edge case test (negative numbers) should pass");
+    }
+
+    #[test]
+    fn test_zero_operations() {
+        let program = create_arithmetic_program();
+        
+        // Test with all zeros
+        let args = vec![RuntimeValue::I32(0), RuntimeValue::I32(0), RuntimeValue::I32(0)];
+        test_vm_jit_compatibility(program, "add_mul", args)
+            .expect("Zero operations test should pass");
+    }
+
+    #[test]
+    fn test_convert_matrix_compatibility() {
+        let int_types = [Type::I32, Type::I64, Type::Usize];
+
+        for from_ty in &int_types {
+            for to_ty in &int_types {
+                for input in convert_sample_values(from_ty.clone()) {
+                    let (vm_result, jit_result) =
+                        run_convert_on_both(from_ty.clone(), to_ty.clone(), input.clone());
+
+                    assert_eq!(
+                        vm_result, jit_result,
+                        "VM and JIT disagree converting {:?} from {:?} to {:?}",
+                        input, from_ty, to_ty
+                    );
+                    assert!(
+                        vm_result.is_some(),
+                        "Conversion from {:?} to {:?} should be supported",
+                        from_ty, to_ty
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_usize_argument_and_return_compatibility() {
+        let program = create_pointer_offset_program();
+
+        // Addresses above 4 GiB would be truncated if treated as i32
+        let base = if cfg!(target_pointer_width = "64") { 0x1_0000_1000 } else { 0x1000 };
+        let args = vec![RuntimeValue::Usize(base), RuntimeValue::Usize(24)];
+        test_vm_jit_compatibility(program, "offset_ptr", args)
+            .expect("usize test should pass");
+    }
+
+    #[test]
+    fn test_float_sqrt_compatibility() {
+        let program = create_float_unary_program("sqrt");
+        test_vm_jit_compatibility(program.clone(), "unary", vec![RuntimeValue::F64(16.0)])
+            .expect("sqrt test should pass");
+
+        let mut vm = VM::new(program, NullHostABI::new());
+        let result = vm.call_function("unary", vec![RuntimeValue::F64(16.0)]).unwrap();
+        assert_eq!(result, RuntimeValue::F64(4.0));
+    }
+
+    #[test]
+    fn test_float_rounding_and_abs_compatibility() {
+        for op in ["floor", "ceil", "abs", "neg"] {
+            for input in [2.5, -2.5, 0.0] {
+                test_vm_jit_compatibility(
+                    create_float_unary_program(op),
+                    "unary",
+                    vec![RuntimeValue::F64(input)],
+                )
+                .unwrap_or_else(|e| panic!("f64.{}({}) failed: {}", op, input, e));
+            }
+        }
+    }
+
+    #[test]
+    fn test_usize_neg_wraps() {
+        for (x, expected) in [(1, usize::MAX), (0, 0), (usize::MAX, 1)] {
+            let args = vec![RuntimeValue::Usize(x)];
+            test_vm_jit_compatibility(create_usize_neg_program(), "negate", args.clone())
+                .expect("usize.neg test should pass");
+
+            let mut vm = VM::new(create_usize_neg_program(), NullHostABI::new());
+            assert_eq!(vm.call_function("negate", args).unwrap(), RuntimeValue::Usize(expected));
+        }
+    }
+
+    #[test]
+    fn test_eqz_compatibility() {
+        let cases = [
+            ("i32", RuntimeValue::I32(0), 1),
+            ("i32", RuntimeValue::I32(5), 0),
+            ("i64", RuntimeValue::I64(0), 1),
+            ("i64", RuntimeValue::I64(5), 0),
+        ];
+
+        for (ty, input, expected) in cases {
+            let program = create_eqz_program(ty);
+            test_vm_jit_compatibility(program.clone(), "is_zero", vec![input.clone()])
+                .expect("eqz test should pass");
+
+            let mut vm = VM::new(program, NullHostABI::new());
+            let result = vm.call_function("is_zero", vec![input]).unwrap();
+            assert_eq!(result, RuntimeValue::I32(expected), "{}.eqz", ty);
+        }
+    }
+
+    #[test]
+    fn test_nonzero_condition_takes_true_branch() {
+        for (input, expected) in [(2, 1), (-1, 1), (1, 1), (0, 0)] {
+            let program = create_branch_on_value_program();
+            test_vm_jit_compatibility(program.clone(), "pick", vec![RuntimeValue::I32(input)])
+                .expect("branch test should pass");
+
+            let mut vm = VM::new(program, NullHostABI::new());
+            let result = vm.call_function("pick", vec![RuntimeValue::I32(input)]).unwrap();
+            assert_eq!(result, RuntimeValue::I32(expected), "br_if on {}", input);
+        }
+    }
+
+    #[test]
+    fn test_import_alias_compatibility() {
+        let program = create_aliased_import_program();
+        test_vm_jit_compatibility_with_abi(
+            program.clone(),
+            "aliased_roundtrip",
+            vec![RuntimeValue::I64(-42)],
+            MemoryHostABI::new(),
+        )
+        .expect("aliased import test should pass");
+
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        let result = vm.call_function("aliased_roundtrip", vec![RuntimeValue::I64(-42)]).unwrap();
+        assert_eq!(result, RuntimeValue::I64(-42));
+    }
+
+    #[test]
+    fn test_f64_memory_roundtrip_compatibility() {
+        for input in [3.25, -0.1, f64::MAX, f64::MIN_POSITIVE] {
+            let program = create_float_memory_program("f64");
+            test_vm_jit_compatibility_with_abi(
+                program.clone(),
+                "roundtrip",
+                vec![RuntimeValue::F64(input)],
+                MemoryHostABI::new(),
+            )
+            .expect("f64 memory test should pass");
+
+            let mut vm = VM::new(program, MemoryHostABI::new());
+            let result = vm.call_function("roundtrip", vec![RuntimeValue::F64(input)]).unwrap();
+            assert_eq!(result, RuntimeValue::F64(input));
+        }
+    }
+
+    #[test]
+    fn test_f32_memory_roundtrip_compatibility() {
+        let program = create_float_memory_program("f32");
+        test_vm_jit_compatibility_with_abi(
+            program,
+            "roundtrip",
+            vec![RuntimeValue::F32(-1.5)],
+            MemoryHostABI::new(),
+        )
+        .expect("f32 memory test should pass");
+    }
+
+    #[test]
+    fn test_checked_narrowing_in_range_compatibility() {
+        for input in [0, -5, i32::MAX as i64, i32::MIN as i64] {
+            let program = create_checked_narrowing_program();
+            test_vm_jit_compatibility(program.clone(), "narrow", vec![RuntimeValue::I64(input)])
+                .expect("checked narrowing test should pass");
+
+            let mut vm = VM::new(program, NullHostABI::new());
+            let result = vm.call_function("narrow", vec![RuntimeValue::I64(input)]).unwrap();
+            assert_eq!(result, RuntimeValue::I32(input as i32));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_checked_narrowing_out_of_range_traps() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let out_of_range = i32::MAX as i64 + 1;
+        let program = create_checked_narrowing_program();
+
+        // A JIT trap takes down the whole process, so that half of the test
+        // runs in a child copy of this test binary
+        if std::env::var_os("TILT_CHECKED_NARROWING_CHILD").is_some() {
+            let mut jit = JIT::new().unwrap();
+            jit.compile(&program).unwrap();
+            let func_ptr = jit.get_func_ptr("narrow").unwrap();
+            let narrow = unsafe { std::mem::transmute::<*const u8, fn(i64) -> i32>(func_ptr) };
+            narrow(out_of_range);
+            return;
+        }
+
+        let mut vm = VM::new(program, NullHostABI::new());
+        let result = vm.call_function("narrow", vec![RuntimeValue::I64(out_of_range)]);
+        assert_eq!(
+            result,
+            Err(tilt_vm::VMError::ConversionOverflow {
+                value: RuntimeValue::I64(out_of_range),
+                to: Type::I32,
+            })
+        );
+
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "vm_jit_compatibility::tests::test_checked_narrowing_out_of_range_traps",
+                "--nocapture",
+            ])
+            .env("TILT_CHECKED_NARROWING_CHILD", "1")
+            .status()
+            .unwrap();
+        // The conversion check raises a Cranelift trap, which is SIGILL
+        assert_eq!(status.signal(), Some(4), "JIT should trap on an out-of-range value");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_null_load_traps() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let program = create_null_load_program();
+
+        // As above, the JIT half runs in a child process
+        if std::env::var_os("TILT_NULL_LOAD_CHILD").is_some() {
+            let mut jit = JIT::new().unwrap();
+            jit.compile(&program).unwrap();
+            let func_ptr = jit.get_func_ptr("deref_null").unwrap();
+            let deref_null = unsafe { std::mem::transmute::<*const u8, fn() -> i32>(func_ptr) };
+            deref_null();
+            return;
+        }
+
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        let result = vm.call_function("deref_null", vec![]);
+        assert!(matches!(result, Err(tilt_vm::VMError::NullPointerDereference(_))));
+
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "vm_jit_compatibility::tests::test_null_load_traps", "--nocapture"])
+            .env("TILT_NULL_LOAD_CHILD", "1")
+            .status()
+            .unwrap();
+        // Cranelift traps raise SIGILL; a plain null dereference would be SIGSEGV
+        assert_eq!(status.signal(), Some(4), "JIT should trap on a null load");
+    }
+
+    #[test]
+    fn test_pointer_add_wraps_at_usize_max() {
+        let args = vec![RuntimeValue::Usize(usize::MAX), RuntimeValue::Usize(1)];
+        test_vm_jit_compatibility(create_pointer_offset_program(), "offset_ptr", args.clone())
+            .expect("pointer wraparound test should pass");
+
+        let mut vm = VM::new(create_pointer_offset_program(), NullHostABI::new());
+        let result = vm.call_function("offset_ptr", args).unwrap();
+        assert_eq!(result, RuntimeValue::Usize(0));
+    }
+
+    #[test]
+    fn test_usize_sub_wraps_below_zero() {
+        let args = vec![RuntimeValue::Usize(0), RuntimeValue::Usize(1)];
+        test_vm_jit_compatibility(create_usize_sub_program(), "usize_sub", args.clone())
+            .expect("usize underflow test should pass");
+
+        let mut vm = VM::new(create_usize_sub_program(), NullHostABI::new());
+        let result = vm.call_function("usize_sub", args).unwrap();
+        assert_eq!(result, RuntimeValue::Usize(usize::MAX));
+    }
+
+    #[test]
+    fn test_dense_switch_compatibility() {
+        let program = create_switch_program(&[0, 1, 2, 3, 4, 5, 6, 7]);
+        for input in -1..=8 {
+            test_vm_jit_compatibility(program.clone(), "classify", vec![RuntimeValue::I32(input)])
+                .unwrap_or_else(|e| panic!("dense switch on {} failed: {}", input, e));
+        }
+
+        let mut vm = VM::new(program, NullHostABI::new());
+        assert_eq!(vm.call_function("classify", vec![RuntimeValue::I32(5)]).unwrap(), RuntimeValue::I32(51));
+        assert_eq!(vm.call_function("classify", vec![RuntimeValue::I32(8)]).unwrap(), RuntimeValue::I32(-1));
+    }
+
+    #[test]
+    fn test_sparse_switch_compatibility() {
+        let program = create_switch_program(&[1, 100, 1000]);
+        for input in [-1, 0, 1, 2, 99, 100, 101, 1000, 1001] {
+            test_vm_jit_compatibility(program.clone(), "classify", vec![RuntimeValue::I32(input)])
+                .unwrap_or_else(|e| panic!("sparse switch on {} failed: {}", input, e));
+        }
+
+        let mut vm = VM::new(program, NullHostABI::new());
+        assert_eq!(vm.call_function("classify", vec![RuntimeValue::I32(1000)]).unwrap(), RuntimeValue::I32(10001));
+        assert_eq!(vm.call_function("classify", vec![RuntimeValue::I32(2)]).unwrap(), RuntimeValue::I32(-1));
+    }
+
+    #[test]
+    fn test_large_usize_constant_round_trip() {
+        for value in [usize::MAX, usize::MAX - 1, 1 << (usize::BITS - 1)] {
+            let program = create_usize_constant_program(value);
+            test_vm_jit_compatibility(program.clone(), "constant", vec![])
+                .expect("usize constant test should pass");
+
+            let mut vm = VM::new(program, NullHostABI::new());
+            let result = vm.call_function("constant", vec![]).unwrap();
+            assert_eq!(result, RuntimeValue::Usize(value));
+        }
+    }
+
+    #[test]
+    fn test_declared_entry_params_compatibility() {
+        let args = vec![
+            RuntimeValue::I64(100),
+            RuntimeValue::I64(7),
+            RuntimeValue::I32(-3),
+        ];
+        test_vm_jit_compatibility(create_declared_params_program(), "weighted", args.clone())
+            .expect("declared params test should pass");
+
+        let mut vm = VM::new(create_declared_params_program(), NullHostABI::new());
+        assert_eq!(
+            vm.call_function("weighted", args).unwrap(),
+            RuntimeValue::I64(121)
+        );
+    }
+
+    #[test]
+    fn test_phi_loop_compatibility() {
+        let mut vm = VM::new(create_phi_loop_program(), NullHostABI::new());
+        assert_eq!(
+            vm.call_function("sum_to", vec![RuntimeValue::I32(10)]).unwrap(),
+            RuntimeValue::I32(55)
+        );
+
+        for n in [0, 1, 100] {
+            test_vm_jit_compatibility(create_phi_loop_program(), "sum_to", vec![RuntimeValue::I32(n)])
+                .expect("phi loop test should pass");
+        }
+    }
+
+    #[test]
+    fn test_store_value_compatibility() {
+        let program = create_store_value_program();
+        let args = vec![RuntimeValue::I64(21)];
+        test_vm_jit_compatibility_with_abi(
+            program.clone(),
+            "store_twice",
+            args.clone(),
+            MemoryHostABI::new(),
+        )
+        .expect("store value test should pass");
+
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        assert_eq!(
+            vm.call_function("store_twice", args).unwrap(),
+            RuntimeValue::I64(42)
+        );
+    }
+
+    #[test]
+    fn test_rodata_table_lookup() {
+        let program = create_rodata_table_program();
+        let mut vm = VM::new(program.clone(), MemoryHostABI::new());
+        for (i, expected) in [(0, 10), (2, 30), (3, 40)] {
+            assert_eq!(
+                vm.call_function("lookup", vec![RuntimeValue::I64(i)]).unwrap(),
+                RuntimeValue::I32(expected)
+            );
+
+            test_vm_jit_compatibility_with_abi(
+                program.clone(),
+                "lookup",
+                vec![RuntimeValue::I64(i)],
+                MemoryHostABI::new(),
+            )
+            .expect("rodata test should pass");
+        }
+    }
+
+    #[test]
+    fn test_shared_rodata_compatibility() {
+        let mut program = create_rodata_table_program();
+        let mut again = create_rodata_table_program();
+        again.functions[0].name = "lookup_again".to_string();
+        program.merge(again).unwrap();
+        assert!(tilt_ir::opt::share_rodata(&mut program));
+        assert_eq!(program.rodata.len(), 1);
+
+        let mut vm = VM::new(program.clone(), MemoryHostABI::new());
+        for name in ["lookup", "lookup_again"] {
+            assert_eq!(
+                vm.call_function(name, vec![RuntimeValue::I64(2)]).unwrap(),
+                RuntimeValue::I32(30)
+            );
+
+            test_vm_jit_compatibility_with_abi(
+                program.clone(),
+                name,
+                vec![RuntimeValue::I64(2)],
+                MemoryHostABI::new(),
+            )
+            .expect("shared rodata test should pass");
+        }
+    }
+
+    #[test]
+    fn test_call_forwarding_compatibility() {
+        let args = vec![RuntimeValue::I64(50), RuntimeValue::I64(8)];
+        test_vm_jit_compatibility(create_forwarding_program(), "main", args.clone())
+            .expect("forwarding test should pass");
+
+        let mut vm = VM::new(create_forwarding_program(), NullHostABI::new());
+        assert_eq!(
+            vm.call_function("main", args).unwrap(),
+            RuntimeValue::I64(42)
+        );
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn test_f64_constant_keeps_exact_bits() {
+        for (literal, expected) in [("3.14", 3.14), ("-0.1", -0.1), ("2", 2.0)] {
+            let program = create_float_constant_program("f64", literal);
+            test_vm_jit_compatibility(program.clone(), "constant", vec![])
+                .expect("f64 constant test should pass");
+
+            let mut vm = VM::new(program, NullHostABI::new());
+            let RuntimeValue::F64(result) = vm.call_function("constant", vec![]).unwrap() else {
+                panic!("constant should return an f64");
+            };
+            assert_eq!(result.to_bits(), f64::to_bits(expected), "f64.const({})", literal);
+        }
+    }
+
+    #[test]
+    fn test_assert_type_is_erased() {
+        let program = create_assert_type_program(Type::I32).expect("assertion should hold");
+        test_vm_jit_compatibility(program.clone(), "double", vec![RuntimeValue::I32(21)])
+            .expect("assert_type test should pass");
+
+        let mut vm = VM::new(program, NullHostABI::new());
+        assert_eq!(
+            vm.call_function("double", vec![RuntimeValue::I32(21)]).unwrap(),
+            RuntimeValue::I32(42)
+        );
+    }
+
+    #[test]
+    fn test_import_and_local_function_dispatch() {
+        let program = create_import_and_local_program();
+        assert!(matches!(program.callee("print_hello"), Some(tilt_ir::Callee::Local(_))));
+        assert!(matches!(program.callee("last_error"), Some(tilt_ir::Callee::Import(_))));
+
+        test_vm_jit_compatibility_with_abi(program.clone(), "main", vec![], MemoryHostABI::new())
+            .expect("dispatch test should pass");
+
+        // The host's print_hello returns nothing, so only the program's
+        // function produces 42
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        assert_eq!(
+            vm.call_function("main", vec![]).unwrap(),
+            RuntimeValue::I32(42)
+        );
+    }
+
+    #[test]
+    fn test_program_function_shadows_import_on_both_backends() {
+        // Hand-built IR may import a name the program also defines; calls
+        // go to the program's function on both backends
+        let mut program = create_import_and_local_program();
+        program.imports.push(tilt_ir::ImportDecl {
+            module: "host".to_string(),
+            name: "print_hello".to_string(),
+            calling_convention: None,
+            alias: None,
+            params: vec![],
+            return_type: Type::Void,
+        });
+        assert!(matches!(program.callee("print_hello"), Some(tilt_ir::Callee::Local(_))));
+
+        test_vm_jit_compatibility_with_abi(program.clone(), "main", vec![], MemoryHostABI::new())
+            .expect("shadowing test should pass");
+
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        assert_eq!(
+            vm.call_function("main", vec![]).unwrap(),
+            RuntimeValue::I32(42)
+        );
+    }
+
+    #[test]
+    fn test_bad_alignment_is_recoverable_on_both_backends() {
+        let program = create_bad_alignment_program();
+        test_vm_jit_compatibility_with_abi(program.clone(), "bad_align", vec![], MemoryHostABI::new())
+            .expect("bad alignment test should pass");
+
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        assert_eq!(
+            vm.call_function("bad_align", vec![]).unwrap(),
+            RuntimeValue::I32(HOST_ERR_INVALID_INPUT)
+        );
+    }
+
+    #[test]
+    fn test_float_arithmetic_and_conversions() {
+        let program = create_float_arithmetic_program();
+        let cases = [
+            ("blend", vec![RuntimeValue::F64(0.1), RuntimeValue::I32(7)]),
+            ("blend", vec![RuntimeValue::F64(-1e10), RuntimeValue::I32(-3)]),
+            ("average", vec![RuntimeValue::F32(0.1), RuntimeValue::F32(0.2)]),
+            ("truncate", vec![RuntimeValue::F64(-2.9)]),
+            ("truncate", vec![RuntimeValue::F64(1e300)]),
+        ];
+        for (function, args) in cases {
+            test_vm_jit_compatibility(program.clone(), function, args.clone())
+                .unwrap_or_else(|e| panic!("{}({:?}): {}", function, args, e));
+        }
+
+        let mut vm = VM::new(program, NullHostABI::new());
+        let blended = vm
+            .call_function("blend", vec![RuntimeValue::F64(0.1), RuntimeValue::I32(7)])
+            .unwrap();
+        assert_eq!(blended.as_f64(), ((7.1f64 - 0.25) * 3.0 / 2.0) as f32 as f64);
+        // Float-to-integer conversions truncate toward zero and saturate
+        assert_eq!(
+            vm.call_function("truncate", vec![RuntimeValue::F64(-2.9)]).unwrap(),
+            RuntimeValue::I64(-2)
+        );
+        assert_eq!(
+            vm.call_function("truncate", vec![RuntimeValue::F64(1e300)]).unwrap(),
+            RuntimeValue::I64(i64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_remainder_matches_jit() {
+        let program = create_binary_op_program("rem", "i32");
+        let mut vm = VM::new(program.clone(), NullHostABI::new());
+        for (a, b, expected) in [(7, 3, 1), (-7, 3, -1), (7, -3, 1), (i32::MIN, -1, 0)] {
+            let args = vec![RuntimeValue::I32(a), RuntimeValue::I32(b)];
+            test_vm_jit_compatibility(program.clone(), "op", args.clone())
+                .expect("i32 remainder test should pass");
+            assert_eq!(
+                vm.call_function("op", args).unwrap(),
+                RuntimeValue::I32(expected),
+                "{} % {}",
+                a,
+                b
+            );
+        }
+        assert_eq!(
+            vm.call_function("op", vec![RuntimeValue::I32(7), RuntimeValue::I32(0)]),
+            Err(tilt_vm::VMError::DivisionByZero)
+        );
+
+        let args = vec![RuntimeValue::I64(-7_000_000_000), RuntimeValue::I64(3)];
+        test_vm_jit_compatibility(create_binary_op_program("rem", "i64"), "op", args)
+            .expect("i64 remainder test should pass");
+        let args = vec![RuntimeValue::Usize(usize::MAX), RuntimeValue::Usize(10)];
+        test_vm_jit_compatibility(create_binary_op_program("rem", "usize"), "op", args)
+            .expect("usize remainder test should pass");
+    }
+
+    #[test]
+    fn test_shifts_match_jit() {
+        for op in ["shl", "shr"] {
+            for amount in [0, 31, 32] {
+                let args = vec![RuntimeValue::I32(-3), RuntimeValue::I32(amount)];
+                test_vm_jit_compatibility(create_binary_op_program(op, "i32"), "op", args)
+                    .expect("i32 shift test should pass");
+            }
+            for amount in [0, 63, 64] {
+                let args = vec![RuntimeValue::I64(-3), RuntimeValue::I64(amount)];
+                test_vm_jit_compatibility(create_binary_op_program(op, "i64"), "op", args)
+                    .expect("i64 shift test should pass");
+                let args = vec![
+                    RuntimeValue::Usize(usize::MAX),
+                    RuntimeValue::Usize(amount as usize),
+                ];
+                test_vm_jit_compatibility(create_binary_op_program(op, "usize"), "op", args)
+                    .expect("usize shift test should pass");
+            }
+        }
+    }
+
+    #[test]
+    fn test_bitwise_ops_match_jit() {
+        for op in ["and", "or", "xor"] {
+            let args = vec![RuntimeValue::I32(-6), RuntimeValue::I32(0x0ff0)];
+            test_vm_jit_compatibility(create_binary_op_program(op, "i32"), "op", args)
+                .expect("i32 bitwise test should pass");
+            let args = vec![RuntimeValue::I64(i64::MIN | 5), RuntimeValue::I64(-2)];
+            test_vm_jit_compatibility(create_binary_op_program(op, "i64"), "op", args)
+                .expect("i64 bitwise test should pass");
+            let args = vec![RuntimeValue::Usize(usize::MAX), RuntimeValue::Usize(0x1234)];
+            test_vm_jit_compatibility(create_binary_op_program(op, "usize"), "op", args)
+                .expect("usize bitwise test should pass");
+        }
+    }
+
+    #[test]
+    fn test_f32_constant_keeps_exact_bits() {
+        for (literal, expected) in [("0.1", 0.1f32), ("-2.5", -2.5), ("3", 3.0)] {
+            let program = create_float_constant_program("f32", literal);
+            test_vm_jit_compatibility(program.clone(), "constant", vec![])
+                .expect("f32 constant test should pass");
+
+            let mut vm = VM::new(program, NullHostABI::new());
+            let RuntimeValue::F32(result) = vm.call_function("constant", vec![]).unwrap() else {
+                panic!("constant should return an f32");
+            };
+            assert_eq!(result.to_bits(), f32::to_bits(expected), "f32.const({})", literal);
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_i32_constant_handling() {
+        // The unsigned spelling of -1 fits in 32 bits and is accepted by both
+        let program = create_raw_i32_constant_program(0xFFFF_FFFF);
+        test_vm_jit_compatibility(program.clone(), "constant", vec![])
+            .expect("unsigned i32 constant should pass");
+        let mut vm = VM::new(program, NullHostABI::new());
+        assert_eq!(
+            vm.call_function("constant", vec![]).unwrap(),
+            RuntimeValue::I32(-1)
+        );
+
+        // A value needing more than 32 bits is rejected by both
+        let program = create_raw_i32_constant_program(1 << 40);
+        let mut vm = VM::new(program.clone(), NullHostABI::new());
+        assert!(matches!(
+            vm.call_function("constant", vec![]),
+            Err(tilt_vm::VMError::InvalidInstruction(msg)) if msg.contains("does not fit in i32")
+        ));
+
+        let mut jit = JIT::new().unwrap();
+        let err = jit.compile(&program).unwrap_err();
+        assert!(err.contains("Constant 1099511627776 does not fit in i32"), "{}", err);
+    }
+
+    #[test]
+    fn test_single_exit_normalization_keeps_results() {
+        let mut program = create_conditional_program();
+        assert!(tilt_ir::opt::normalize_single_exit(&mut program.functions[0]));
+        let rets = program.functions[0]
+            .blocks
+            .iter()
+            .filter(|b| matches!(b.terminator, tilt_ir::Terminator::Ret { .. }))
+            .count();
+        assert_eq!(rets, 1);
+
+        for (a, b, expected) in [(7, 3, 7), (3, 7, 7), (-2, -2, -2)] {
+            let args = vec![RuntimeValue::I32(a), RuntimeValue::I32(b)];
+            test_vm_jit_compatibility(program.clone(), "max", args.clone())
+                .expect("normalized max should pass");
+
+            let mut vm = VM::new(program.clone(), NullHostABI::new());
+            assert_eq!(
+                vm.call_function("max", args).unwrap(),
+                RuntimeValue::I32(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_eq_chain_becomes_switch_with_same_results() {
+        let original = create_eq_chain_program();
+        let mut program = original.clone();
+        assert!(tilt_ir::opt::reconstruct_switch(&mut program.functions[0]));
+
+        let func = &program.functions[0];
+        let entry = func.blocks.iter().find(|b| b.id == func.entry_block).unwrap();
+        let tilt_ir::Terminator::Switch { cases, .. } = &entry.terminator else {
+            panic!("expected a switch, got {:?}", entry.terminator);
+        };
+        let keys: Vec<i64> = cases.iter().map(|case| case.value).collect();
+        assert_eq!(keys, vec![1, 2, 3]);
+        assert!(!func.blocks.iter().any(|b| b.label.starts_with("test")));
+
+        for x in [0, 1, 2, 3, 4, -1] {
+            let args = vec![RuntimeValue::I32(x)];
+            let mut before = VM::new(original.clone(), NullHostABI::new());
+            let mut after = VM::new(program.clone(), NullHostABI::new());
+            assert_eq!(
+                before.call_function("classify", args.clone()).unwrap(),
+                after.call_function("classify", args.clone()).unwrap()
+            );
+            test_vm_jit_compatibility(program.clone(), "classify", args)
+                .expect("reconstructed switch should pass");
+        }
+    }
+
+    #[test]
+    fn test_eq_chain_from_source_becomes_switch() {
+        let original = create_eq_chain_source_program();
+        let mut program = original.clone();
+        assert!(tilt_ir::opt::reconstruct_switch(&mut program.functions[0]));
+
+        let func = &program.functions[0];
+        let entry = func.blocks.iter().find(|b| b.id == func.entry_block).unwrap();
+        let tilt_ir::Terminator::Switch { cases, .. } = &entry.terminator else {
+            panic!("expected a switch, got {:?}", entry.terminator);
+        };
+        let keys: Vec<i64> = cases.iter().map(|case| case.value).collect();
+        assert_eq!(keys, vec![1, 2, 3]);
+
+        for x in [0, 1, 2, 3, 4, -1] {
+            let args = vec![RuntimeValue::I32(x)];
+            let mut before = VM::new(original.clone(), NullHostABI::new());
+            let mut after = VM::new(program.clone(), NullHostABI::new());
+            assert_eq!(
+                before.call_function("classify", args.clone()).unwrap(),
+                after.call_function("classify", args.clone()).unwrap()
+            );
+            test_vm_jit_compatibility(program.clone(), "classify", args)
+                .expect("reconstructed switch should pass");
+        }
+    }
+
+    #[test]
+    fn test_common_subexpression_elimination_keeps_results() {
+        let original = create_duplicate_product_program();
+        let mut program = original.clone();
+        assert!(tilt_ir::opt::eliminate_common_subexpressions(&mut program.functions[0]));
+
+        let muls = program.functions[0].blocks[0]
+            .instructions
+            .iter()
+            .filter(|i| {
+                matches!(i, tilt_ir::Instruction::BinaryOp { op: tilt_ir::BinaryOperator::Mul, .. })
+            })
+            .count();
+        assert_eq!(muls, 1);
+
+        for (a, b) in [(6, 7), (-3, 5), (0, 9)] {
+            let args = vec![RuntimeValue::I32(a), RuntimeValue::I32(b)];
+            let mut before = VM::new(original.clone(), NullHostABI::new());
+            let mut after = VM::new(program.clone(), NullHostABI::new());
+            let expected = before.call_function("double_product", args.clone()).unwrap();
+            assert_eq!(expected, RuntimeValue::I32(2 * a * b));
+            assert_eq!(
+                after.call_function("double_product", args.clone()).unwrap(),
+                expected
+            );
+            test_vm_jit_compatibility(program.clone(), "double_product", args)
+                .expect("program without duplicates should pass");
+        }
+    }
+
+    #[test]
+    fn test_typed_pointer_compatibility() {
+        let program = create_typed_pointer_program();
+        let args = vec![RuntimeValue::I64(40), RuntimeValue::I64(2)];
+        test_vm_jit_compatibility_with_abi(
+            program.clone(),
+            "sum_pair",
+            args.clone(),
+            MemoryHostABI::new(),
+        )
+        .expect("typed pointer test should pass");
+
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        let result = vm.call_function("sum_pair", args).unwrap();
+        assert_eq!(result, RuntimeValue::I64(42));
+    }
+
+    #[test]
+    fn test_float_comparisons_with_nan() {
+        let nan = f64::NAN;
+        let cases = [
+            ("lt", nan, 1.0, 0),
+            ("lt", 1.0, 2.0, 1),
+            ("lt", 2.0, 1.0, 0),
+            ("ne", nan, nan, 1),
+            ("eq", nan, nan, 0),
+            ("ge", nan, 1.0, 0),
+            ("le", 1.0, 1.0, 1),
+            ("gt", 2.0, 1.0, 1),
+        ];
+
+        for (op, a, b, expected) in cases {
+            for ty in ["f32", "f64"] {
+                let program = create_compare_program(ty, op);
+                let args = if ty == "f32" {
+                    vec![RuntimeValue::F32(a as f32), RuntimeValue::F32(b as f32)]
+                } else {
+                    vec![RuntimeValue::F64(a), RuntimeValue::F64(b)]
+                };
+
+                for function in ["compare", "branch"] {
+                    test_vm_jit_compatibility(program.clone(), function, args.clone())
+                        .unwrap_or_else(|e| panic!("{ty}.{op}({a}, {b}) in {function}: {e}"));
+
+                    let mut vm = VM::new(program.clone(), NullHostABI::new());
+                    let result = vm.call_function(function, args.clone()).unwrap();
+                    assert_eq!(
+                        result,
+                        RuntimeValue::I32(expected),
+                        "{ty}.{op}({a}, {b}) in {function}"
+                    );
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_usize_comparisons_are_unsigned() {
+        let cases = [("lt", 0), ("le", 0), ("gt", 1), ("ge", 1), ("ne", 1), ("eq", 0)];
+
+        for (op, expected) in cases {
+            let mut program = create_compare_program("usize", op);
+            let args = vec![RuntimeValue::Usize(usize::MAX), RuntimeValue::Usize(1)];
+
+            // Check the branch both on the i32 comparison result and fused
+            // into a compare-and-branch
+            for fuse in [false, true] {
+                if fuse {
+                    tilt_ir::opt::fold_compare_branch(&mut program.functions[1]);
+                }
+                for function in ["compare", "branch"] {
+                    test_vm_jit_compatibility(program.clone(), function, args.clone())
+                        .unwrap_or_else(|e| panic!("usize.{op} in {function}: {e}"));
+
+                    let mut vm = VM::new(program.clone(), NullHostABI::new());
+                    let result = vm.call_function(function, args.clone()).unwrap();
+                    assert_eq!(result, RuntimeValue::I32(expected), "usize.{op} in {function}");
+                }
+            }
+            assert!(!program.functions[1].fused_compare_branches.is_empty());
+        }
     }
 }
@@ -15,8 +15,9 @@ pub struct FunctionBuilder<'a> {
     /// The function IR we are building
     func: &'a mut Function,
 
-    /// The current block we are inserting instructions into
-    current_block: Option<usize>,
+    /// The current block we are inserting instructions into. Always a block
+    /// of `func`: it starts unset and `switch_to_block` checks it.
+    current_block: Option<BlockId>,
 }
 
 impl<'a> FunctionBuilder<'a> {
@@ -28,21 +29,39 @@ impl<'a> FunctionBuilder<'a> {
         }
     }
 
-    /// Switch to inserting instructions into the given block
+    /// Switch to inserting instructions into the given block, which must
+    /// belong to this builder's function
     pub fn switch_to_block(&mut self, block: BlockId) {
-        let block_index = block.index();
-        assert!(block_index < self.func.blocks.len(), "Block does not exist");
-        self.current_block = Some(block_index);
+        assert!(
+            self.func.blocks.iter().any(|b| b.id == block),
+            "Block {:?} does not exist in function '{}'",
+            block,
+            self.func.name
+        );
+        self.current_block = Some(block);
     }
 
     /// Get the current block being built
     pub fn current_block(&self) -> Option<BlockId> {
-        self.current_block.map(BlockId::new)
+        self.current_block
+    }
+
+    /// Get the block instructions are currently inserted into
+    fn current_block_mut(&mut self) -> &mut BasicBlock {
+        let current_block = self
+            .current_block
+            .expect("No current block - call switch_to_block first");
+        self.func
+            .blocks
+            .iter_mut()
+            .find(|b| b.id == current_block)
+            .expect("Current block is not part of this function")
     }
 
-    /// Create a new basic block and add it to the function
+    /// Create a new basic block and add it to the function. Its ID is fresh
+    /// but need not equal its position in `blocks`, e.g. in lowered IR.
     pub fn create_block(&mut self, label: &str) -> BlockId {
-        let block_id = BlockId::new(self.func.blocks.len());
+        let block_id = self.func.next_block_id();
         let block = BasicBlock::new(block_id, label.to_string());
         self.func.blocks.push(block);
         block_id
@@ -53,14 +72,43 @@ impl<'a> FunctionBuilder<'a> {
         let value_id = self.func.next_value();
 
         // Add the parameter to the block
-        let block_index = block.index();
-        if let Some(block_data) = self.func.blocks.get_mut(block_index) {
+        if let Some(block_data) = self.func.blocks.iter_mut().find(|b| b.id == block) {
             block_data.params.push((value_id, ty));
         }
 
         value_id
     }
 
+    /// Add one entry-block parameter per function parameter, in order, so the
+    /// signature and the entry block always agree. Call it once, after
+    /// creating the entry block.
+    pub fn declare_entry_params(&mut self) -> Vec<ValueId> {
+        let entry = self.func.entry_block;
+        let block = self
+            .func
+            .blocks
+            .iter()
+            .find(|b| b.id == entry)
+            .expect("Entry block does not exist - call create_block first");
+        assert!(
+            block.params.is_empty(),
+            "Entry block of '{}' already has parameters",
+            self.func.name
+        );
+
+        let params = self.func.params.clone();
+        params
+            .into_iter()
+            .map(|ty| self.add_block_param(entry, ty))
+            .collect()
+    }
+
+    /// Embed read-only bytes, such as a lookup table, with the function.
+    /// `ins().data_addr` gets their address.
+    pub fn add_rodata(&mut self, bytes: Vec<u8>) -> DataId {
+        self.func.add_rodata(bytes)
+    }
+
     /// Get an instruction builder for fluent API
     pub fn ins(&mut self) -> InstructionBuilder<'_, 'a> {
         InstructionBuilder { builder: self }
@@ -68,43 +116,38 @@ impl<'a> FunctionBuilder<'a> {
 
     /// Add an instruction to the current block
     fn add_instruction(&mut self, instr: Instruction) -> ValueId {
-        let current_block = self
-            .current_block
-            .expect("No current block - call switch_to_block first");
-
         // Extract the destination ValueId from the instruction
         let dest = match &instr {
             Instruction::Call { dest, .. } => *dest,
+            Instruction::TryCall { dest, .. } => *dest,
             Instruction::Const { dest, .. } => *dest,
             Instruction::BinaryOp { dest, .. } => *dest,
             Instruction::UnaryOp { dest, .. } => *dest,
             Instruction::Load { dest, .. } => *dest,
             Instruction::PtrAdd { dest, .. } => *dest,
             Instruction::SizeOf { dest, .. } => *dest,
+            Instruction::DataAddr { dest, .. } => *dest,
             Instruction::Alloc { dest, .. } => *dest,
             Instruction::Convert { dest, .. } => *dest,
-            Instruction::CallVoid { .. } | Instruction::Store { .. } | Instruction::Free { .. } => {
+            Instruction::ConvertChecked { dest, .. } => *dest,
+            Instruction::Phi { dest, .. } => *dest,
+            Instruction::CallVoid { .. }
+            | Instruction::Store { .. }
+            | Instruction::Free { .. }
+            | Instruction::AssertType { .. } => {
                 // These instructions don't produce values
                 ValueId::new(0) // This shouldn't be used
             }
         };
 
-        if let Some(block) = self.func.blocks.get_mut(current_block) {
-            block.instructions.push(instr);
-        }
+        self.current_block_mut().instructions.push(instr);
 
         dest
     }
 
     /// Set the terminator for the current block
     pub fn set_terminator(&mut self, terminator: Terminator) {
-        let current_block = self
-            .current_block
-            .expect("No current block - call switch_to_block first");
-
-        if let Some(block) = self.func.blocks.get_mut(current_block) {
-            block.terminator = terminator;
-        }
+        self.current_block_mut().terminator = terminator;
     }
 }
 
@@ -127,6 +170,44 @@ impl<'a, 'b> InstructionBuilder<'a, 'b> {
         dest
     }
 
+    /// Build a call whose host errors yield -1 (`HOST_ERROR_RESULT`) in the
+    /// result instead of aborting. `return_type` must be an integer type.
+    pub fn try_call(&mut self, func_name: &str, args: Vec<ValueId>, return_type: Type) -> ValueId {
+        let dest = self.builder.func.next_value();
+        let instr = Instruction::TryCall {
+            dest,
+            function: func_name.to_string(),
+            args,
+            return_type,
+        };
+        self.builder.add_instruction(instr);
+        dest
+    }
+
+    /// Build a call passing this function's entry-block parameters, in
+    /// order, to `func_name`, e.g. for a wrapper or trampoline. The entry
+    /// block must have exactly one parameter per function parameter.
+    pub fn call_forwarding(&mut self, func_name: &str, return_type: Type) -> ValueId {
+        let func = &self.builder.func;
+        let entry = func
+            .blocks
+            .iter()
+            .find(|b| b.id == func.entry_block)
+            .expect("Entry block does not exist - call create_block first");
+        assert_eq!(
+            entry.params.len(),
+            func.params.len(),
+            "Function '{}' has {} parameters but its entry block has {}; \
+             call declare_entry_params first",
+            func.name,
+            func.params.len(),
+            entry.params.len()
+        );
+
+        let args = entry.params.iter().map(|(value, _)| *value).collect();
+        self.call(func_name, args, return_type)
+    }
+
     /// Build a void function call instruction
     pub fn call_void(&mut self, func_name: &str, args: Vec<ValueId>) {
         let instr = Instruction::CallVoid {
@@ -173,10 +254,12 @@ impl<'a, 'b> InstructionBuilder<'a, 'b> {
 
     /// Build a constant instruction for usize
     pub fn const_usize(&mut self, value: usize) -> ValueId {
+        // Keep the exact bit pattern; values above i64::MAX come out negative
+        let value = value as u64 as i64;
         let dest = self.builder.func.next_value();
         let instr = Instruction::Const {
             dest,
-            value: value as i64,
+            value,
             ty: Type::Usize,
         };
 
@@ -184,7 +267,33 @@ impl<'a, 'b> InstructionBuilder<'a, 'b> {
         self.builder
             .func
             .constants
-            .insert(dest, (value as i64, Type::Usize));
+            .insert(dest, (value, Type::Usize));
+
+        self.builder.add_instruction(instr);
+        dest
+    }
+
+    /// Build a constant instruction for f32
+    pub fn const_f32(&mut self, value: f32) -> ValueId {
+        self.float_const(value as f64, Type::F32)
+    }
+
+    /// Build a constant instruction for f64
+    pub fn const_f64(&mut self, value: f64) -> ValueId {
+        self.float_const(value, Type::F64)
+    }
+
+    fn float_const(&mut self, value: f64, ty: Type) -> ValueId {
+        let value = float_constant_bits(value, &ty);
+        let dest = self.builder.func.next_value();
+        let instr = Instruction::Const {
+            dest,
+            value,
+            ty: ty.clone(),
+        };
+
+        // Also add to constants map
+        self.builder.func.constants.insert(dest, (value, ty));
 
         self.builder.add_instruction(instr);
         dest
@@ -253,6 +362,24 @@ impl<'a, 'b> InstructionBuilder<'a, 'b> {
         self.builder.set_terminator(terminator);
     }
 
+    /// Build a multi-way branch on an integer value
+    pub fn switch(&mut self, value: ValueId, cases: Vec<(i64, BlockId)>, default: BlockId) {
+        let terminator = Terminator::Switch {
+            value,
+            cases: cases
+                .into_iter()
+                .map(|(value, target)| SwitchCase {
+                    value,
+                    target,
+                    args: vec![],
+                })
+                .collect(),
+            default,
+            default_args: vec![],
+        };
+        self.builder.set_terminator(terminator);
+    }
+
     /// Build an unconditional jump instruction
     pub fn jump(&mut self, target: BlockId) {
         let terminator = Terminator::Br {
@@ -278,6 +405,14 @@ impl<'a, 'b> InstructionBuilder<'a, 'b> {
         dest
     }
 
+    /// Build an instruction taking the address of read-only data
+    pub fn data_addr(&mut self, data: DataId) -> ValueId {
+        let dest = self.builder.func.next_value();
+        let instr = Instruction::DataAddr { dest, data };
+        self.builder.add_instruction(instr);
+        dest
+    }
+
     /// Build an allocation instruction
     pub fn alloc(&mut self, size: ValueId) -> ValueId {
         let dest = self.builder.func.next_value();
@@ -292,6 +427,13 @@ impl<'a, 'b> InstructionBuilder<'a, 'b> {
         self.builder.add_instruction(instr);
     }
 
+    /// Build a type assertion, checked by the verifier and erased by the
+    /// backends
+    pub fn assert_type(&mut self, value: ValueId, expected: Type) {
+        let instr = Instruction::AssertType { value, expected };
+        self.builder.add_instruction(instr);
+    }
+
     /// Build a memory load instruction
     pub fn load(&mut self, ty: Type, address: ValueId) -> ValueId {
         let dest = self.builder.func.next_value();
@@ -305,6 +447,27 @@ impl<'a, 'b> InstructionBuilder<'a, 'b> {
         let instr = Instruction::Store { address, value, ty };
         self.builder.add_instruction(instr);
     }
+
+    /// Build a memory store and return the stored value, like an assignment
+    /// expression in C. SSA values never change, so the result is `value`
+    /// itself and can be used again without a copy.
+    pub fn store_value(&mut self, address: ValueId, value: ValueId, ty: Type) -> ValueId {
+        self.store(address, value, ty);
+        value
+    }
+
+    /// Build a type conversion instruction
+    pub fn convert(&mut self, src: ValueId, from_ty: Type, to_ty: Type) -> ValueId {
+        let dest = self.builder.func.next_value();
+        let instr = Instruction::Convert {
+            dest,
+            src,
+            from_ty,
+            to_ty,
+        };
+        self.builder.add_instruction(instr);
+        dest
+    }
 }
 
 /// Builder for constructing entire programs
@@ -320,6 +483,7 @@ impl ProgramBuilder {
             program: Program {
                 imports: Vec::new(),
                 functions: Vec::new(),
+                rodata: vec![],
             },
         }
     }
@@ -330,6 +494,7 @@ impl ProgramBuilder {
             module: module.to_string(),
             name: name.to_string(),
             calling_convention: None,
+            alias: None,
             params,
             return_type,
         });
@@ -348,6 +513,7 @@ impl ProgramBuilder {
             module: module.to_string(),
             name: name.to_string(),
             calling_convention,
+            alias: None,
             params,
             return_type,
         });
@@ -360,8 +526,22 @@ impl ProgramBuilder {
         self.program.functions.len() - 1 // Return index for later use
     }
 
-    /// Get a builder for an existing function
-    pub fn function_builder(&mut self, function_index: usize) -> FunctionBuilder {
+    /// Check whether a function with this name has been created
+    pub fn has_function(&self, name: &str) -> bool {
+        self.program.functions.iter().any(|f| f.name == name)
+    }
+
+    /// Check whether `name` has already been imported from `module`
+    pub fn has_import(&self, module: &str, name: &str) -> bool {
+        self.program
+            .imports
+            .iter()
+            .any(|i| i.module == module && i.name == name)
+    }
+
+    /// Get a builder for an existing function. The builder is always fresh,
+    /// with no current block, so call `switch_to_block` before inserting.
+    pub fn function_builder(&mut self, function_index: usize) -> FunctionBuilder<'_> {
         let func = &mut self.program.functions[function_index];
         FunctionBuilder::new(func)
     }
@@ -369,6 +549,20 @@ impl ProgramBuilder {
     /// Finalize and return the built program
     pub fn build(self) -> Program {
         self.program
+            .functions
+            .iter()
+            .for_each(debug_assert_unique_values);
+        self.program
+    }
+
+    /// Finalize the built program, running the IR verifier first
+    pub fn try_build(self) -> Result<Program, Vec<VerifyError>> {
+        self.program
+            .functions
+            .iter()
+            .for_each(debug_assert_unique_values);
+        verify_program(&self.program)?;
+        Ok(self.program)
     }
 }
 
@@ -485,4 +679,289 @@ mod tests {
         // Validate the structure
         assert_eq!(builder.func.blocks.len(), 4);
     }
+
+    #[test]
+    fn test_try_build_reports_missing_terminator() {
+        let mut builder = ProgramBuilder::new();
+        let func_idx = builder.create_function("answer", vec![], Type::I32);
+
+        {
+            let mut func_builder = builder.function_builder(func_idx);
+            let entry = func_builder.create_block("entry");
+            func_builder.switch_to_block(entry);
+            func_builder.ins().const_i32(42);
+            // No ret: the block keeps its placeholder terminator
+        }
+
+        let errors = builder.try_build().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![VerifyError::MissingTerminator {
+                function: "answer".to_string(),
+                block: "entry".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_try_build_reports_undefined_rodata() {
+        let mut builder = ProgramBuilder::new();
+        let func_idx = builder.create_function("table", vec![], Type::Usize);
+
+        {
+            let mut func_builder = builder.function_builder(func_idx);
+            let entry = func_builder.create_block("entry");
+            func_builder.switch_to_block(entry);
+            let table = func_builder.add_rodata(vec![1, 2, 3, 4]);
+            assert_eq!(table, DataId(0));
+            let addr = func_builder.ins().data_addr(DataId(1));
+            func_builder.ins().ret(Some(addr));
+        }
+
+        let errors = builder.try_build().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![VerifyError::UndefinedData {
+                function: "table".to_string(),
+                block: "entry".to_string(),
+                data: DataId(1),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_try_build_accepts_valid_program() {
+        let mut builder = ProgramBuilder::new();
+        let func_idx = builder.create_function("answer", vec![], Type::I32);
+
+        {
+            let mut func_builder = builder.function_builder(func_idx);
+            let entry = func_builder.create_block("entry");
+            func_builder.switch_to_block(entry);
+            let value = func_builder.ins().const_i32(42);
+            func_builder.ins().ret(Some(value));
+        }
+
+        let program = builder.try_build().unwrap();
+        assert_eq!(program.functions[0].name, "answer");
+    }
+
+    #[test]
+    fn test_interleaved_function_builders_stay_separate() {
+        let mut builder = ProgramBuilder::new();
+        let first = builder.create_function("first", vec![], Type::I32);
+        let second = builder.create_function("second", vec![], Type::I32);
+
+        let first_entry = {
+            let mut func_builder = builder.function_builder(first);
+            let entry = func_builder.create_block("entry");
+            func_builder.switch_to_block(entry);
+            func_builder.ins().const_i32(1);
+            entry
+        };
+        {
+            let mut func_builder = builder.function_builder(second);
+            assert_eq!(func_builder.current_block(), None);
+            let entry = func_builder.create_block("entry");
+            func_builder.switch_to_block(entry);
+            let value = func_builder.ins().const_i32(2);
+            func_builder.ins().ret(Some(value));
+        }
+        {
+            let mut func_builder = builder.function_builder(first);
+            assert_eq!(func_builder.current_block(), None);
+            func_builder.switch_to_block(first_entry);
+            let value = func_builder.ins().const_i32(3);
+            func_builder.ins().ret(Some(value));
+        }
+
+        let program = builder.build();
+        let constants = |index: usize| -> Vec<i64> {
+            program.functions[index].blocks[0]
+                .instructions
+                .iter()
+                .filter_map(|instr| match instr {
+                    Instruction::Const { value, .. } => Some(*value),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(constants(first), vec![1, 3]);
+        assert_eq!(constants(second), vec![2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not exist in function 'first'")]
+    fn test_switch_to_block_of_another_function_panics() {
+        let mut builder = ProgramBuilder::new();
+        let first = builder.create_function("first", vec![], Type::Void);
+        let second = builder.create_function("second", vec![], Type::Void);
+
+        let foreign = {
+            let mut func_builder = builder.function_builder(second);
+            func_builder.create_block("entry");
+            func_builder.create_block("exit")
+        };
+
+        let mut func_builder = builder.function_builder(first);
+        func_builder.create_block("entry");
+        func_builder.switch_to_block(foreign);
+    }
+
+    #[test]
+    fn test_create_block_on_lowered_function_gets_fresh_id() {
+        use tilt_ast::{Block, FunctionDef, TopLevelItem};
+
+        // Lowering numbers blocks across functions, so `second`'s blocks are
+        // 1 and 2 rather than 0 and 1
+        let function = |name, blocks| {
+            TopLevelItem::Function(FunctionDef {
+                name,
+                params: vec![],
+                return_type: Type::Void,
+                blocks,
+            })
+        };
+        let block = |label, terminator| Block {
+            label,
+            params: vec![],
+            instructions: vec![],
+            terminator,
+        };
+        let ast = tilt_ast::Program {
+            items: vec![
+                function(
+                    "first",
+                    vec![block("entry", tilt_ast::Terminator::Ret(None))],
+                ),
+                function(
+                    "second",
+                    vec![
+                        block(
+                            "entry",
+                            tilt_ast::Terminator::Br {
+                                label: "next",
+                                args: vec![],
+                            },
+                        ),
+                        block("next", tilt_ast::Terminator::Ret(None)),
+                    ],
+                ),
+            ],
+        };
+        let mut program = tilt_ir::lower_program(&ast).unwrap();
+        let func = &mut program.functions[1];
+        let ids: Vec<_> = func.blocks.iter().map(|b| b.id).collect();
+        assert_eq!(ids, vec![BlockId::new(1), BlockId::new(2)]);
+
+        let mut builder = FunctionBuilder::new(func);
+        let extra = builder.create_block("extra");
+        assert!(!ids.contains(&extra));
+
+        builder.switch_to_block(extra);
+        builder.ins().ret(None);
+        builder.switch_to_block(ids[1]);
+        builder.set_terminator(Terminator::Br {
+            target: extra,
+            args: vec![],
+        });
+
+        assert_eq!(
+            func.blocks[1].terminator,
+            Terminator::Br {
+                target: extra,
+                args: vec![]
+            }
+        );
+        assert_eq!(func.blocks[2].label, "extra");
+        assert_eq!(func.blocks[2].terminator, Terminator::Ret { value: None });
+        assert_eq!(tilt_ir::verify_function(func), vec![]);
+    }
+
+    #[test]
+    fn test_store_value_yields_stored_value() {
+        let mut func = Function::new("keep".to_string(), vec![Type::Usize], Type::I32);
+        let mut builder = FunctionBuilder::new(&mut func);
+        let entry = builder.create_block("entry");
+        builder.switch_to_block(entry);
+        let address = builder.declare_entry_params()[0];
+        let value = builder.ins().const_i32(7);
+
+        let stored = builder.ins().store_value(address, value, Type::I32);
+        builder.ins().ret(Some(stored));
+
+        assert_eq!(stored, value);
+        assert_eq!(
+            func.blocks[0].instructions.last(),
+            Some(&Instruction::Store {
+                address,
+                value,
+                ty: Type::I32,
+            })
+        );
+    }
+
+    #[test]
+    fn test_call_forwarding_passes_entry_params() {
+        let mut func = Function::new("main".to_string(), vec![Type::I32, Type::I64], Type::I32);
+        let mut builder = FunctionBuilder::new(&mut func);
+        let entry = builder.create_block("entry");
+        builder.switch_to_block(entry);
+        let params = builder.declare_entry_params();
+
+        let result = builder.ins().call_forwarding("real_main", Type::I32);
+
+        assert_eq!(
+            func.blocks[0].instructions,
+            vec![Instruction::Call {
+                dest: result,
+                function: "real_main".to_string(),
+                args: params,
+                return_type: Type::I32,
+            }]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Function 'main' has 1 parameters but its entry block has 0")]
+    fn test_call_forwarding_requires_declared_params() {
+        let mut func = Function::new("main".to_string(), vec![Type::I32], Type::I32);
+        let mut builder = FunctionBuilder::new(&mut func);
+        let entry = builder.create_block("entry");
+        builder.switch_to_block(entry);
+
+        builder.ins().call_forwarding("real_main", Type::I32);
+    }
+
+    #[test]
+    fn test_declare_entry_params_matches_signature() {
+        let mut func = Function::new("f".to_string(), vec![Type::I64, Type::Usize], Type::Void);
+        let mut builder = FunctionBuilder::new(&mut func);
+        let entry = builder.create_block("entry");
+
+        let params = builder.declare_entry_params();
+
+        assert_eq!(params.len(), 2);
+        let entry_params: Vec<_> = builder.func.blocks[entry.index()].params.clone();
+        assert_eq!(
+            entry_params,
+            vec![(params[0], Type::I64), (params[1], Type::Usize)]
+        );
+    }
+
+    #[test]
+    fn test_has_function_and_has_import() {
+        let mut builder = ProgramBuilder::new();
+        assert!(!builder.has_function("main"));
+        assert!(!builder.has_import("env", "print_char"));
+
+        builder.add_import("env", "print_char", vec![Type::I32], Type::Void);
+        builder.create_function("main", vec![], Type::Void);
+
+        assert!(builder.has_function("main"));
+        assert!(!builder.has_function("print_char"));
+        assert!(builder.has_import("env", "print_char"));
+        assert!(!builder.has_import("host", "print_char"));
+        assert!(!builder.has_import("env", "main"));
+    }
 }
@@ -0,0 +1,174 @@
+// ===================================================================
+// FILE: cfg.rs (tilt-ir crate)
+//
+// DESC: Control-flow graph queries over IR functions, and call graph
+//       queries over whole programs.
+// ===================================================================
+
+use crate::visit::{InstructionVisitor, walk_function};
+use crate::*;
+use std::collections::{HashMap, HashSet};
+
+/// Get the blocks reachable from a function's entry block
+pub fn reachable_blocks(func: &Function) -> HashSet<BlockId> {
+    let blocks: HashMap<BlockId, &BasicBlock> = func.blocks.iter().map(|b| (b.id, b)).collect();
+    let mut reachable = HashSet::new();
+    let mut worklist = vec![func.entry_block];
+
+    while let Some(id) = worklist.pop() {
+        let Some(block) = blocks.get(&id) else {
+            continue;
+        };
+        if !reachable.insert(id) {
+            continue;
+        }
+        for (target, _) in block.terminator.successors() {
+            if !reachable.contains(&target) {
+                worklist.push(target);
+            }
+        }
+    }
+
+    reachable
+}
+
+/// Find the reachable blocks from which no path leads to a `ret`: once
+/// control gets there the function can only loop forever
+pub fn blocks_without_return_path(func: &Function) -> Vec<BlockId> {
+    let mut predecessors: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+    for block in &func.blocks {
+        for (target, _) in block.terminator.successors() {
+            predecessors.entry(target).or_default().push(block.id);
+        }
+    }
+
+    let mut returning = HashSet::new();
+    let mut worklist: Vec<BlockId> = func
+        .blocks
+        .iter()
+        .filter(|block| matches!(block.terminator, Terminator::Ret { .. }))
+        .map(|block| block.id)
+        .collect();
+    while let Some(id) = worklist.pop() {
+        if returning.insert(id) {
+            worklist.extend(predecessors.get(&id).into_iter().flatten());
+        }
+    }
+
+    let mut stuck: Vec<BlockId> = reachable_blocks(func)
+        .into_iter()
+        .filter(|id| !returning.contains(id))
+        .collect();
+    stuck.sort();
+    stuck
+}
+
+/// Find blocks that hang forever without doing anything observable: blocks on
+/// a cycle of unconditional branches (such as `loop: br loop`) where no block
+/// in the cycle has a side effect
+pub fn detect_trivial_infinite_loops(func: &Function) -> Vec<BlockId> {
+    let blocks: HashMap<BlockId, &BasicBlock> = func.blocks.iter().map(|b| (b.id, b)).collect();
+    let is_pure =
+        |block: &BasicBlock| !block.instructions.iter().any(Instruction::has_side_effects);
+
+    let mut looping: Vec<BlockId> = func
+        .blocks
+        .iter()
+        .filter(|start| {
+            let mut visited = HashSet::new();
+            let mut current = *start;
+            loop {
+                if !is_pure(current) || !visited.insert(current.id) {
+                    return false;
+                }
+                let Terminator::Br { target, .. } = &current.terminator else {
+                    return false;
+                };
+                if *target == start.id {
+                    return true;
+                }
+                match blocks.get(target) {
+                    Some(next) => current = next,
+                    None => return false,
+                }
+            }
+        })
+        .map(|block| block.id)
+        .collect();
+
+    looping.sort();
+    looping
+}
+
+/// Get the names of the program functions that `func` calls, in first-call
+/// order. Calls to imports and unknown names are left out.
+pub fn callees(program: &Program, func: &Function) -> Vec<String> {
+    struct CalleeCollector<'a> {
+        program: &'a Program,
+        callees: Vec<String>,
+    }
+
+    impl InstructionVisitor for CalleeCollector<'_> {
+        fn visit_call(&mut self, _dest: Option<ValueId>, function: &str, _args: &[ValueId]) {
+            let is_local = self.program.functions.iter().any(|f| f.name == function);
+            if is_local && !self.callees.iter().any(|c| c == function) {
+                self.callees.push(function.to_string());
+            }
+        }
+    }
+
+    let mut collector = CalleeCollector {
+        program,
+        callees: Vec::new(),
+    };
+    walk_function(func, &mut collector);
+    collector.callees
+}
+
+/// Estimate the deepest VM call stack the program can reach: the number of
+/// frames on the longest chain of calls between program functions, counting
+/// the function called from outside. Host calls don't take a frame and are
+/// not counted. A VM whose `max_stack_depth` is at least this never
+/// overflows. Returns `None` when the call graph has a cycle, since
+/// recursion has no static bound.
+pub fn estimate_max_call_depth(program: &Program) -> Option<usize> {
+    fn depth_of<'a>(
+        name: &'a str,
+        graph: &HashMap<&'a str, Vec<String>>,
+        depths: &mut HashMap<&'a str, usize>,
+        in_progress: &mut HashSet<&'a str>,
+    ) -> Option<usize> {
+        if let Some(&depth) = depths.get(name) {
+            return Some(depth);
+        }
+        if !in_progress.insert(name) {
+            return None; // Recursion
+        }
+
+        let (&key, callees) = graph.get_key_value(name)?;
+        let mut deepest_callee = 0;
+        for callee in callees {
+            let callee = graph.get_key_value(callee.as_str())?.0;
+            deepest_callee = deepest_callee.max(depth_of(callee, graph, depths, in_progress)?);
+        }
+
+        in_progress.remove(key);
+        depths.insert(key, deepest_callee + 1);
+        Some(deepest_callee + 1)
+    }
+
+    let graph: HashMap<&str, Vec<String>> = program
+        .functions
+        .iter()
+        .map(|f| (f.name.as_str(), callees(program, f)))
+        .collect();
+
+    let mut depths = HashMap::new();
+    let mut in_progress = HashSet::new();
+    let mut max_depth = 0;
+    for func in &program.functions {
+        let depth = depth_of(&func.name, &graph, &mut depths, &mut in_progress)?;
+        max_depth = max_depth.max(depth);
+    }
+    Some(max_depth)
+}
@@ -0,0 +1,97 @@
+// ===================================================================
+// FILE: cost.rs (tilt-ir crate)
+//
+// DESC: Static cost model for IR code, giving a rough estimate of how
+//       expensive a function is to run. Used to weigh decisions such
+//       as VM versus JIT execution or whether to inline a call.
+// ===================================================================
+
+use crate::*;
+
+/// Weights assigned to each kind of instruction. The defaults are rough
+/// relative costs: calls and memory operations are heavier than
+/// arithmetic. All fields are public so callers can tune them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostModel {
+    /// Constants, `sizeof` and data addresses, which backends usually
+    /// fold into their users, and type assertions, which they erase
+    pub constant: u64,
+    /// Binary and unary operations, including comparisons
+    pub arithmetic: u64,
+    /// Conversions, pointer arithmetic and phis
+    pub conversion: u64,
+    /// Loads and stores
+    pub memory: u64,
+    /// Calls to program functions or host functions
+    pub call: u64,
+    /// Host allocations and frees
+    pub allocation: u64,
+    /// Every block terminator
+    pub branch: u64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            constant: 0,
+            arithmetic: 1,
+            conversion: 1,
+            memory: 3,
+            call: 10,
+            allocation: 20,
+            branch: 1,
+        }
+    }
+}
+
+impl CostModel {
+    /// Get the weight of a single instruction
+    pub fn instruction_cost(&self, instruction: &Instruction) -> u64 {
+        match instruction {
+            Instruction::Const { .. }
+            | Instruction::SizeOf { .. }
+            | Instruction::DataAddr { .. }
+            | Instruction::AssertType { .. } => self.constant,
+            Instruction::BinaryOp { .. } | Instruction::UnaryOp { .. } => self.arithmetic,
+            Instruction::Convert { .. }
+            | Instruction::ConvertChecked { .. }
+            | Instruction::PtrAdd { .. }
+            | Instruction::Phi { .. } => self.conversion,
+            Instruction::Load { .. } | Instruction::Store { .. } => self.memory,
+            Instruction::Call { .. }
+            | Instruction::TryCall { .. }
+            | Instruction::CallVoid { .. } => self.call,
+            Instruction::Alloc { .. } | Instruction::Free { .. } => self.allocation,
+        }
+    }
+
+    /// Get the summed weight of every instruction and terminator in a
+    /// function. Each one counts once, however often loops run it.
+    pub fn function_cost(&self, func: &Function) -> u64 {
+        func.blocks
+            .iter()
+            .map(|block| {
+                let instructions: u64 = block
+                    .instructions
+                    .iter()
+                    .map(|instruction| self.instruction_cost(instruction))
+                    .sum();
+                instructions + self.branch
+            })
+            .sum()
+    }
+
+    /// Get the summed cost of every function in a program
+    pub fn program_cost(&self, program: &Program) -> u64 {
+        program
+            .functions
+            .iter()
+            .map(|func| self.function_cost(func))
+            .sum()
+    }
+}
+
+/// Estimate the cost of a function with the default `CostModel`
+pub fn estimated_cost(func: &Function) -> u64 {
+    CostModel::default().function_cost(func)
+}
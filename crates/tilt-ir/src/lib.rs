@@ -8,19 +8,35 @@
 
 use tilt_ast::Type;
 
+pub mod cfg;
+pub mod cost;
 pub mod lowering;
+pub mod opt;
+pub mod phi;
+pub mod verify;
+pub mod visit;
 
 #[cfg(test)]
 mod tests;
 
+pub use cost::{CostModel, estimated_cost};
 // Re-export main lowering function
-pub use lowering::lower_program;
+pub use lowering::{
+    LoweringOptions, lower_program, lower_program_with_externs, lower_program_with_options,
+};
+pub use verify::{
+    VerifyError, VerifyWarning, debug_assert_unique_values, find_duplicate_value, program_warnings,
+    verify_function, verify_program,
+};
 
 /// Program-level IR containing all functions and imports
 #[derive(Debug, Clone, PartialEq)]
 pub struct Program {
     pub imports: Vec<ImportDecl>,
     pub functions: Vec<Function>,
+    /// Read-only data shared between functions, as `opt::share_rodata`
+    /// promotes it; functions refer to entries through `Function::shared_rodata`
+    pub rodata: Vec<Vec<u8>>,
 }
 
 /// Import declaration in IR form
@@ -29,10 +45,55 @@ pub struct ImportDecl {
     pub module: String,
     pub name: String,
     pub calling_convention: Option<String>, // e.g., "c" for C calling convention
+    /// Name TILT code calls the import by, if not `name`. The JIT still
+    /// links `name` and the VM dispatches to the host function `name`.
+    pub alias: Option<String>,
     pub params: Vec<Type>,
     pub return_type: Type,
 }
 
+impl ImportDecl {
+    /// The name calls use: the alias if there is one, else the symbol name
+    pub fn call_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Whether the import agrees with a host function's parameter and
+    /// return types. Pointer types match `usize`.
+    pub fn matches_signature(&self, params: &[Type], return_type: &Type) -> bool {
+        self.params.len() == params.len()
+            && self
+                .params
+                .iter()
+                .zip(params)
+                .all(|(declared, host)| declared.repr() == host.repr())
+            && self.return_type.repr() == return_type.repr()
+    }
+}
+
+/// Format a signature the way imports are written, e.g. `(i32, i32) -> i32`
+pub fn format_signature(params: &[Type], return_type: &Type) -> String {
+    let params: Vec<String> = params.iter().map(Type::to_string).collect();
+    format!("({}) -> {}", params.join(", "), return_type)
+}
+
+/// What the name in a call refers to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Callee<'a> {
+    /// A function defined in the program
+    Local(&'a Function),
+    /// An import, provided by the host
+    Import(&'a ImportDecl),
+}
+
+/// What the name in a call refers to, by its position in the program's
+/// `functions` or `imports`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalleeIndex {
+    Local(usize),
+    Import(usize),
+}
+
 /// A function in IR form with resolved references
 #[derive(Debug, Clone, PartialEq)]
 pub struct Function {
@@ -42,8 +103,21 @@ pub struct Function {
     pub blocks: Vec<BasicBlock>,
     pub entry_block: BlockId,
     pub next_value_id: ValueId, // For generating unique value IDs
-    /// Map of constant values (value_id -> (constant_value, type))
+    /// Map of constant values (value_id -> (constant_value, type)).
+    /// Integer constants are stored as the bit pattern of the typed value, so
+    /// `usize::MAX` is stored as -1; backends reinterpret it by type. Float
+    /// constants hold their IEEE bits (see `float_constant_bits`).
     pub constants: std::collections::HashMap<ValueId, (i64, Type)>,
+    /// Blocks whose `BrIf` condition is a comparison used only by that branch,
+    /// as found by `opt::fold_compare_branch`. Backends may branch on the
+    /// comparison directly instead of materializing its i32 result.
+    pub fused_compare_branches: std::collections::HashSet<BlockId>,
+    /// Read-only data embedded with the function, such as lookup or jump
+    /// tables; `DataAddr` yields the address of an entry
+    pub rodata: Vec<(DataId, Vec<u8>)>,
+    /// Read-only data entries that live in `Program::rodata` instead, as
+    /// (ID in this function, index into `Program::rodata`)
+    pub shared_rodata: Vec<(DataId, usize)>,
 }
 
 /// Opaque identifier for a basic block
@@ -54,6 +128,10 @@ pub struct BlockId(pub usize);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ValueId(pub usize);
 
+/// Opaque identifier for a function's read-only data entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DataId(pub usize);
+
 /// A basic block in the IR
 #[derive(Debug, Clone, PartialEq)]
 pub struct BasicBlock {
@@ -65,7 +143,7 @@ pub struct BasicBlock {
 }
 
 /// Instructions in the IR with resolved references
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Instruction {
     /// Binary arithmetic operation
     BinaryOp {
@@ -89,6 +167,15 @@ pub enum Instruction {
         args: Vec<ValueId>,
         return_type: Type,
     },
+    /// Function call whose host errors don't abort the program: a failing
+    /// host function leaves `HOST_ERROR_RESULT` (-1) in `dest` instead.
+    /// Only integer results can carry the error.
+    TryCall {
+        dest: ValueId,
+        function: String,
+        args: Vec<ValueId>,
+        return_type: Type,
+    },
     /// Function call without assignment (void functions)
     CallVoid {
         function: String,
@@ -106,7 +193,7 @@ pub enum Instruction {
         value: ValueId,
         ty: Type,
     },
-    /// Constant assignment
+    /// Constant assignment, with `value` encoded like `Function::constants`
     Const { dest: ValueId, value: i64, ty: Type },
     /// Pointer arithmetic - add offset to pointer
     PtrAdd {
@@ -120,6 +207,8 @@ pub enum Instruction {
     Alloc { dest: ValueId, size: ValueId },
     /// Host ABI deallocation
     Free { ptr: ValueId },
+    /// Address of one of the function's read-only data entries (`data.addr`)
+    DataAddr { dest: ValueId, data: DataId },
     /// Type conversion between numeric types
     Convert {
         dest: ValueId,
@@ -127,6 +216,25 @@ pub enum Instruction {
         from_ty: Type,
         to_ty: Type,
     },
+    /// Narrowing conversion that traps instead of truncating when the
+    /// value does not fit in the target type (e.g. `i64.to_i32_checked`)
+    ConvertChecked {
+        dest: ValueId,
+        src: ValueId,
+        from_ty: Type,
+        to_ty: Type,
+    },
+    /// Debug assertion that `value` has type `expected`, for frontends that
+    /// build IR directly. The verifier checks it; backends erase it.
+    AssertType { value: ValueId, expected: Type },
+    /// Explicit phi node, for IR imported from phi-based formats.
+    /// Backends never see it: `phi::eliminate_phis` rewrites it into a block
+    /// parameter plus one branch argument per incoming edge.
+    Phi {
+        dest: ValueId,
+        ty: Type,
+        incomings: Vec<(BlockId, ValueId)>,
+    },
 }
 
 /// Terminator instructions that end basic blocks
@@ -136,7 +244,8 @@ pub enum Terminator {
     Ret { value: Option<ValueId> },
     /// Unconditional branch
     Br { target: BlockId, args: Vec<ValueId> },
-    /// Conditional branch
+    /// Conditional branch. Any nonzero condition takes the true target, not
+    /// just 1; every backend must follow this "nonzero is true" contract.
     BrIf {
         cond: ValueId,
         true_target: BlockId,
@@ -144,10 +253,26 @@ pub enum Terminator {
         false_target: BlockId,
         false_args: Vec<ValueId>,
     },
+    /// Multi-way branch on an integer value. The first case whose value
+    /// equals it is taken; anything else goes to the default target.
+    Switch {
+        value: ValueId,
+        cases: Vec<SwitchCase>,
+        default: BlockId,
+        default_args: Vec<ValueId>,
+    },
+}
+
+/// One arm of a `Terminator::Switch`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchCase {
+    pub value: i64,
+    pub target: BlockId,
+    pub args: Vec<ValueId>,
 }
 
 /// Binary operators
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BinaryOperator {
     Add,
     Sub,
@@ -168,10 +293,16 @@ pub enum BinaryOperator {
 }
 
 /// Unary operators
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum UnaryOperator {
     Neg,
     Not,
+    Sqrt,
+    Floor,
+    Ceil,
+    Abs,
+    /// Integer zero test; always produces an `i32` 0 or 1
+    Eqz,
 }
 
 /// Errors that can occur during semantic analysis and IR generation
@@ -211,6 +342,18 @@ pub enum SemanticError {
         found: usize,
         location: String,
     },
+    /// Returned value does not match the function's return type
+    ReturnTypeMismatch {
+        function: String,
+        expected: Type,
+        found: Type,
+        /// Conversion op that would fix the mismatch, e.g. `i32.to_i64`
+        suggestion: Option<String>,
+    },
+    /// Bare constant whose type would be inferred from context (strict mode only)
+    ImplicitConstant { value: i64, inferred: Type },
+    /// Integer literal too large for the type it is given
+    ConstantOutOfRange { value: i64, ty: Type },
 }
 
 impl std::fmt::Display for SemanticError {
@@ -275,6 +418,32 @@ impl std::fmt::Display for SemanticError {
                     function, location, expected, found
                 )
             }
+            SemanticError::ReturnTypeMismatch {
+                function,
+                expected,
+                found,
+                suggestion,
+            } => {
+                write!(
+                    f,
+                    "Returning {} from {} function '{}'",
+                    found, expected, function
+                )?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, "; use {}", suggestion)?;
+                }
+                Ok(())
+            }
+            SemanticError::ImplicitConstant { value, inferred } => {
+                write!(
+                    f,
+                    "Bare constant {} would be inferred as {}; write {}.const({}) instead",
+                    value, inferred, inferred, value
+                )
+            }
+            SemanticError::ConstantOutOfRange { value, ty } => {
+                write!(f, "Constant {} does not fit in {}", value, ty)
+            }
         }
     }
 }
@@ -301,6 +470,114 @@ impl ValueId {
     }
 }
 
+impl DataId {
+    pub fn new(id: usize) -> Self {
+        DataId(id)
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl Program {
+    /// Estimate the cost of the whole program with the default `CostModel`
+    pub fn estimated_cost(&self) -> u64 {
+        cost::CostModel::default().program_cost(self)
+    }
+
+    /// Resolve the name a call uses to the function or import it refers to.
+    /// Lowering keeps the two apart, so a program function shadows an
+    /// import only in IR built by hand.
+    pub fn callee(&self, name: &str) -> Option<Callee<'_>> {
+        Some(match self.callee_index(name)? {
+            CalleeIndex::Local(index) => Callee::Local(&self.functions[index]),
+            CalleeIndex::Import(index) => Callee::Import(&self.imports[index]),
+        })
+    }
+
+    /// Like `callee`, but by position, for callers that keep the result
+    /// beyond a borrow of the program
+    pub fn callee_index(&self, name: &str) -> Option<CalleeIndex> {
+        if let Some(index) = self.functions.iter().position(|f| f.name == name) {
+            return Some(CalleeIndex::Local(index));
+        }
+        self.imports
+            .iter()
+            .position(|i| i.call_name() == name)
+            .map(CalleeIndex::Import)
+    }
+
+    /// Get the bytes of read-only data entry `data` of `function`, whether
+    /// embedded with the function or shared
+    pub fn rodata<'a>(&'a self, function: &'a Function, data: DataId) -> Option<&'a [u8]> {
+        match function.shared_rodata(data) {
+            Some(index) => self.rodata.get(index).map(Vec::as_slice),
+            None => function.rodata(data),
+        }
+    }
+
+    /// Get the external symbol a call to `name` refers to: the symbol of
+    /// the import aliased as `name`, or `name` itself
+    pub fn host_symbol<'a>(&'a self, name: &'a str) -> &'a str {
+        self.imports
+            .iter()
+            .find(|i| i.alias.as_deref() == Some(name))
+            .map_or(name, |i| i.name.as_str())
+    }
+
+    /// Link another program into this one, e.g. one lowered from a separate
+    /// source file. Imports declared by both are kept once; a function
+    /// defined in both, or an import whose signature disagrees, is an error.
+    pub fn merge(&mut self, other: Program) -> Result<(), String> {
+        for function in &other.functions {
+            if self.functions.iter().any(|f| f.name == function.name)
+                || self.imports.iter().any(|i| i.call_name() == function.name)
+            {
+                return Err(format!(
+                    "Duplicate definition of function '{}'",
+                    function.name
+                ));
+            }
+        }
+
+        for import in other.imports {
+            if self.functions.iter().any(|f| f.name == import.call_name()) {
+                return Err(format!(
+                    "Duplicate definition of function '{}'",
+                    import.call_name()
+                ));
+            }
+            match self
+                .imports
+                .iter()
+                .find(|i| i.call_name() == import.call_name())
+            {
+                Some(existing) if *existing == import => {}
+                Some(_) => {
+                    return Err(format!(
+                        "Conflicting declarations of import '{}'",
+                        import.call_name()
+                    ));
+                }
+                None => self.imports.push(import),
+            }
+        }
+
+        // The other program's shared data goes after ours
+        let offset = self.rodata.len();
+        self.rodata.extend(other.rodata);
+        self.functions
+            .extend(other.functions.into_iter().map(|mut function| {
+                for (_, index) in &mut function.shared_rodata {
+                    *index += offset;
+                }
+                function
+            }));
+        Ok(())
+    }
+}
+
 impl Function {
     /// Create a new function with the given name and types
     pub fn new(name: String, params: Vec<Type>, return_type: Type) -> Self {
@@ -312,15 +589,68 @@ impl Function {
             entry_block: BlockId(0),
             next_value_id: ValueId(0),
             constants: std::collections::HashMap::new(),
+            fused_compare_branches: std::collections::HashSet::new(),
+            rodata: Vec::new(),
+            shared_rodata: Vec::new(),
         }
     }
 
+    /// Embed read-only bytes with the function, returning their ID
+    pub fn add_rodata(&mut self, bytes: Vec<u8>) -> DataId {
+        let id = DataId(
+            self.rodata
+                .iter()
+                .map(|(id, _)| id)
+                .chain(self.shared_rodata.iter().map(|(id, _)| id))
+                .map(|id| id.0 + 1)
+                .max()
+                .unwrap_or(0),
+        );
+        self.rodata.push((id, bytes));
+        id
+    }
+
+    /// Get the bytes of a read-only data entry embedded with the function.
+    /// Shared entries are looked up with `Program::rodata`.
+    pub fn rodata(&self, id: DataId) -> Option<&[u8]> {
+        self.rodata
+            .iter()
+            .find(|(data, _)| *data == id)
+            .map(|(_, bytes)| bytes.as_slice())
+    }
+
+    /// Get the `Program::rodata` index of a shared read-only data entry
+    pub fn shared_rodata(&self, id: DataId) -> Option<usize> {
+        self.shared_rodata
+            .iter()
+            .find(|(data, _)| *data == id)
+            .map(|(_, index)| *index)
+    }
+
+    /// Whether `id` names a read-only data entry, embedded or shared
+    pub fn has_rodata(&self, id: DataId) -> bool {
+        self.rodata(id).is_some() || self.shared_rodata(id).is_some()
+    }
+
     /// Generate the next unique value ID
     pub fn next_value(&mut self) -> ValueId {
         let id = self.next_value_id;
         self.next_value_id = ValueId(id.0 + 1);
         id
     }
+
+    /// Get an ID no block of this function uses yet: one past the largest.
+    /// Block IDs need not match block positions; lowering numbers blocks
+    /// across the whole program.
+    pub fn next_block_id(&self) -> BlockId {
+        BlockId(self.blocks.iter().map(|b| b.id.0 + 1).max().unwrap_or(0))
+    }
+
+    /// Whether every block reachable from the entry can still reach a `ret`,
+    /// so no path through the function is stuck looping forever
+    pub fn returns_on_all_paths(&self) -> bool {
+        cfg::blocks_without_return_path(self).is_empty()
+    }
 }
 
 impl BasicBlock {
@@ -336,6 +666,154 @@ impl BasicBlock {
     }
 }
 
+impl Instruction {
+    /// Get the value defined by this instruction, if any
+    pub fn dest(&self) -> Option<ValueId> {
+        match self {
+            Instruction::BinaryOp { dest, .. }
+            | Instruction::UnaryOp { dest, .. }
+            | Instruction::Call { dest, .. }
+            | Instruction::TryCall { dest, .. }
+            | Instruction::Load { dest, .. }
+            | Instruction::Const { dest, .. }
+            | Instruction::PtrAdd { dest, .. }
+            | Instruction::SizeOf { dest, .. }
+            | Instruction::DataAddr { dest, .. }
+            | Instruction::Alloc { dest, .. }
+            | Instruction::Convert { dest, .. }
+            | Instruction::ConvertChecked { dest, .. }
+            | Instruction::Phi { dest, .. } => Some(*dest),
+            Instruction::CallVoid { .. }
+            | Instruction::Store { .. }
+            | Instruction::Free { .. }
+            | Instruction::AssertType { .. } => None,
+        }
+    }
+
+    /// Get the type of the value this instruction defines. This is not
+    /// always the instruction's `ty`: comparisons and `eqz` carry their
+    /// operand type but produce an i32 0 or 1. Returns `None` for
+    /// instructions without a result, and for `PtrAdd`, whose result takes
+    /// the type of its pointer operand.
+    pub fn result_type(&self) -> Option<Type> {
+        match self {
+            Instruction::BinaryOp { op, ty, .. } => Some(if op.is_comparison() {
+                Type::I32
+            } else {
+                ty.clone()
+            }),
+            Instruction::UnaryOp { op, ty, .. } => Some(if *op == UnaryOperator::Eqz {
+                Type::I32
+            } else {
+                ty.clone()
+            }),
+            Instruction::Call { return_type, .. } | Instruction::TryCall { return_type, .. } => {
+                Some(return_type.clone())
+            }
+            Instruction::Load { ty, .. }
+            | Instruction::Const { ty, .. }
+            | Instruction::Phi { ty, .. } => Some(ty.clone()),
+            Instruction::SizeOf { .. }
+            | Instruction::Alloc { .. }
+            | Instruction::DataAddr { .. } => Some(Type::Usize),
+            Instruction::Convert { to_ty, .. } | Instruction::ConvertChecked { to_ty, .. } => {
+                Some(to_ty.clone())
+            }
+            Instruction::PtrAdd { .. }
+            | Instruction::CallVoid { .. }
+            | Instruction::Store { .. }
+            | Instruction::Free { .. }
+            | Instruction::AssertType { .. } => None,
+        }
+    }
+
+    /// Whether executing this instruction can be observed outside the
+    /// function: calls, memory writes, host allocation and conversions
+    /// that may trap
+    pub fn has_side_effects(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Call { .. }
+                | Instruction::TryCall { .. }
+                | Instruction::CallVoid { .. }
+                | Instruction::Store { .. }
+                | Instruction::Alloc { .. }
+                | Instruction::Free { .. }
+                | Instruction::ConvertChecked { .. }
+        )
+    }
+
+    /// Get the values read by this instruction
+    pub fn operands(&self) -> Vec<ValueId> {
+        match self {
+            Instruction::BinaryOp { lhs, rhs, .. } => vec![*lhs, *rhs],
+            Instruction::UnaryOp { operand, .. } => vec![*operand],
+            Instruction::Phi { incomings, .. } => {
+                incomings.iter().map(|(_, value)| *value).collect()
+            }
+            Instruction::Call { args, .. }
+            | Instruction::TryCall { args, .. }
+            | Instruction::CallVoid { args, .. } => args.clone(),
+            Instruction::Load { address, .. } => vec![*address],
+            Instruction::Store { address, value, .. } => vec![*address, *value],
+            Instruction::Const { .. }
+            | Instruction::SizeOf { .. }
+            | Instruction::DataAddr { .. } => vec![],
+            Instruction::PtrAdd { ptr, offset, .. } => vec![*ptr, *offset],
+            Instruction::Alloc { size, .. } => vec![*size],
+            Instruction::Free { ptr } => vec![*ptr],
+            Instruction::AssertType { value, .. } => vec![*value],
+            Instruction::Convert { src, .. } | Instruction::ConvertChecked { src, .. } => {
+                vec![*src]
+            }
+        }
+    }
+}
+
+impl Terminator {
+    /// Get the branch targets of this terminator with the arguments passed to each
+    pub fn successors(&self) -> Vec<(BlockId, &[ValueId])> {
+        match self {
+            Terminator::Ret { .. } => vec![],
+            Terminator::Br { target, args } => vec![(*target, args.as_slice())],
+            Terminator::BrIf {
+                true_target,
+                true_args,
+                false_target,
+                false_args,
+                ..
+            } => vec![
+                (*true_target, true_args.as_slice()),
+                (*false_target, false_args.as_slice()),
+            ],
+            Terminator::Switch {
+                cases,
+                default,
+                default_args,
+                ..
+            } => cases
+                .iter()
+                .map(|case| (case.target, case.args.as_slice()))
+                .chain(std::iter::once((*default, default_args.as_slice())))
+                .collect(),
+        }
+    }
+
+    /// Get the values read by this terminator, including branch arguments
+    pub fn operands(&self) -> Vec<ValueId> {
+        let mut operands = match self {
+            Terminator::Ret { value } => value.iter().copied().collect(),
+            Terminator::Br { .. } => vec![],
+            Terminator::BrIf { cond, .. } => vec![*cond],
+            Terminator::Switch { value, .. } => vec![*value],
+        };
+        for (_, args) in self.successors() {
+            operands.extend_from_slice(args);
+        }
+        operands
+    }
+}
+
 /// Parse a binary operator from a string
 impl BinaryOperator {
     pub fn from_str(s: &str, ty: Type) -> Result<Self, SemanticError> {
@@ -363,6 +841,67 @@ impl BinaryOperator {
             }),
         }
     }
+
+    /// Whether this operator compares its operands, producing an i32 0 or 1
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Eq
+                | BinaryOperator::Ne
+                | BinaryOperator::Lt
+                | BinaryOperator::Le
+                | BinaryOperator::Gt
+                | BinaryOperator::Ge
+        )
+    }
+
+    /// Whether swapping the operands leaves the result unchanged
+    pub fn is_commutative(self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Add
+                | BinaryOperator::Mul
+                | BinaryOperator::And
+                | BinaryOperator::Or
+                | BinaryOperator::Xor
+                | BinaryOperator::Eq
+                | BinaryOperator::Ne
+        )
+    }
+}
+
+/// Float math intrinsics are only defined for `f32` and `f64`
+pub(crate) fn is_float(ty: &Type) -> bool {
+    matches!(ty, Type::F32 | Type::F64)
+}
+
+/// Whether `value` is a valid constant bit pattern for `ty`. Types narrower
+/// than 64 bits accept their signed and unsigned ranges, so an i32 constant
+/// may hold -2^31 through 2^32 - 1; anything wider would lose bits. A
+/// `usize` is checked against the host's pointer width.
+pub fn constant_fits(value: i64, ty: &Type) -> bool {
+    constant_fits_pointer_width(value, ty, std::mem::size_of::<usize>())
+}
+
+/// Like `constant_fits`, but with `usize` and pointers `pointer_bytes` wide,
+/// for targets whose pointer width differs from the host's
+pub fn constant_fits_pointer_width(value: i64, ty: &Type, pointer_bytes: usize) -> bool {
+    let fits_32_bits = i32::try_from(value).is_ok() || u32::try_from(value).is_ok();
+    match ty.repr() {
+        Type::I32 | Type::F32 => fits_32_bits,
+        Type::Usize | Type::Ptr(_) if pointer_bytes == 4 => fits_32_bits,
+        Type::I64 | Type::Usize | Type::F64 | Type::Void | Type::Ptr(_) => true,
+    }
+}
+
+/// Encode a float as the constant bit pattern for `ty`, which must be `f32`
+/// or `f64`. An `f32` keeps its 32 bits in the low half.
+pub fn float_constant_bits(value: f64, ty: &Type) -> i64 {
+    debug_assert!(is_float(ty), "{} is not a float type", ty);
+    match ty {
+        Type::F32 => (value as f32).to_bits() as i64,
+        _ => value.to_bits() as i64,
+    }
 }
 
 impl UnaryOperator {
@@ -370,6 +909,11 @@ impl UnaryOperator {
         match s {
             "neg" => Ok(UnaryOperator::Neg),
             "not" => Ok(UnaryOperator::Not),
+            "sqrt" if is_float(&ty) => Ok(UnaryOperator::Sqrt),
+            "floor" if is_float(&ty) => Ok(UnaryOperator::Floor),
+            "ceil" if is_float(&ty) => Ok(UnaryOperator::Ceil),
+            "abs" if is_float(&ty) => Ok(UnaryOperator::Abs),
+            "eqz" if matches!(ty, Type::I32 | Type::I64 | Type::Usize) => Ok(UnaryOperator::Eqz),
             _ => Err(SemanticError::InvalidOperation {
                 operation: s.to_string(),
                 ty,
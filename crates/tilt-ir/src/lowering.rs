@@ -7,12 +7,29 @@
 
 use crate::*;
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use tilt_ast;
 
+/// Signature of the function currently being lowered.
+/// The function body itself lives in `lower_function`, which owns the only
+/// copy so value IDs are never allocated from a stale clone.
+struct CurrentFunction {
+    name: String,
+    return_type: Type,
+}
+
+/// Options controlling how strictly the AST is checked while lowering
+#[derive(Debug, Clone, Default)]
+pub struct LoweringOptions {
+    /// Reject bare constant arguments such as the `1` in `i32.add(x, 1)`, whose
+    /// type is inferred from context; constants must come from `T.const` instead
+    pub strict: bool,
+}
+
 /// Context for lowering AST to IR with semantic validation
 pub struct LoweringContext {
-    /// Current function being processed
-    current_function: Option<Function>,
+    /// Signature of the function being processed
+    current_function: Option<CurrentFunction>,
     /// Map from block labels to block IDs for the current function
     block_map: HashMap<String, BlockId>,
     /// Map from variable names to value IDs for the current function
@@ -23,6 +40,14 @@ pub struct LoweringContext {
     errors: Vec<SemanticError>,
     /// Next block ID to assign
     next_block_id: usize,
+    /// Options this program is lowered with
+    options: LoweringOptions,
+}
+
+impl Default for LoweringContext {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl LoweringContext {
@@ -34,6 +59,7 @@ impl LoweringContext {
             functions: HashMap::new(),
             errors: Vec::new(),
             next_block_id: 0,
+            options: LoweringOptions::default(),
         }
     }
 
@@ -51,13 +77,17 @@ impl LoweringContext {
 
     /// Register a function signature
     fn register_function(&mut self, name: String, params: Vec<Type>, return_type: Type) {
-        if self.functions.contains_key(&name) {
-            self.error(SemanticError::DuplicateDefinition {
-                name: name.clone(),
-                location: "function definition".to_string(),
-            });
-        } else {
-            self.functions.insert(name, (params, return_type));
+        match self.functions.entry(name) {
+            Entry::Occupied(entry) => {
+                let name = entry.key().clone();
+                self.error(SemanticError::DuplicateDefinition {
+                    name,
+                    location: "function definition".to_string(),
+                });
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((params, return_type));
+            }
         }
     }
 
@@ -68,19 +98,23 @@ impl LoweringContext {
 
     /// Register a variable in the current scope
     fn register_variable(&mut self, name: String, value_id: ValueId, ty: Type) {
-        if self.value_map.contains_key(&name) {
-            self.error(SemanticError::DuplicateDefinition {
-                name: name.clone(),
-                location: "variable definition".to_string(),
-            });
-        } else {
-            self.value_map.insert(name, (value_id, ty));
+        match self.value_map.entry(name) {
+            Entry::Occupied(entry) => {
+                let name = entry.key().clone();
+                self.error(SemanticError::DuplicateDefinition {
+                    name,
+                    location: "variable definition".to_string(),
+                });
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((value_id, ty));
+            }
         }
     }
 
     /// Look up a variable in the current scope
     fn lookup_variable(&self, name: &str) -> Option<(ValueId, Type)> {
-        self.value_map.get(name).copied()
+        self.value_map.get(name).cloned()
     }
 
     /// Clear function-local state
@@ -93,18 +127,54 @@ impl LoweringContext {
 
 /// Main entry point for lowering AST to IR
 pub fn lower_program(ast: &tilt_ast::Program) -> Result<Program, Vec<SemanticError>> {
+    lower_program_with_options(ast, &LoweringOptions::default())
+}
+
+/// Lower AST to IR with the given options
+pub fn lower_program_with_options(
+    ast: &tilt_ast::Program,
+    options: &LoweringOptions,
+) -> Result<Program, Vec<SemanticError>> {
+    lower(ast, &HashMap::new(), options)
+}
+
+/// Lower AST to IR, letting calls resolve to functions defined elsewhere
+/// (e.g. in another source file) by their `(params, return_type)` signature.
+/// Externs are not part of the returned program; link it with `Program::merge`.
+pub fn lower_program_with_externs(
+    ast: &tilt_ast::Program,
+    externs: &HashMap<String, (Vec<Type>, Type)>,
+    options: &LoweringOptions,
+) -> Result<Program, Vec<SemanticError>> {
+    lower(ast, externs, options)
+}
+
+fn lower(
+    ast: &tilt_ast::Program,
+    externs: &HashMap<String, (Vec<Type>, Type)>,
+    options: &LoweringOptions,
+) -> Result<Program, Vec<SemanticError>> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("lower_program", items = ast.items.len()).entered();
+
     let mut ctx = LoweringContext::new();
+    ctx.options = options.clone();
+    ctx.functions.extend(externs.clone());
 
     // First pass: collect all import and function signatures
     for item in &ast.items {
         match item {
             tilt_ast::TopLevelItem::Import(import) => {
-                let params = import.params.iter().map(|p| p.ty).collect();
-                ctx.register_function(import.name.to_string(), params, import.return_type);
+                let params = import.params.iter().map(|p| p.ty.clone()).collect();
+                ctx.register_function(
+                    import.call_name().to_string(),
+                    params,
+                    import.return_type.clone(),
+                );
             }
             tilt_ast::TopLevelItem::Function(func) => {
-                let params = func.params.iter().map(|p| p.ty).collect();
-                ctx.register_function(func.name.to_string(), params, func.return_type);
+                let params = func.params.iter().map(|p| p.ty.clone()).collect();
+                ctx.register_function(func.name.to_string(), params, func.return_type.clone());
             }
         }
     }
@@ -121,7 +191,10 @@ pub fn lower_program(ast: &tilt_ast::Program) -> Result<Program, Vec<SemanticErr
             }
             tilt_ast::TopLevelItem::Function(func) => {
                 match lower_function(&mut ctx, func) {
-                    Ok(ir_func) => ir_functions.push(ir_func),
+                    Ok(ir_func) => {
+                        debug_assert_unique_values(&ir_func);
+                        ir_functions.push(ir_func);
+                    }
                     Err(_) => {
                         // Errors are already added to ctx.errors
                     }
@@ -134,6 +207,7 @@ pub fn lower_program(ast: &tilt_ast::Program) -> Result<Program, Vec<SemanticErr
         Ok(Program {
             imports: ir_imports,
             functions: ir_functions,
+            rodata: vec![],
         })
     } else {
         Err(ctx.errors)
@@ -146,8 +220,9 @@ fn lower_import(_ctx: &mut LoweringContext, import: &tilt_ast::ImportDecl) -> Im
         module: import.module.to_string(),
         name: import.name.to_string(),
         calling_convention: import.calling_convention.map(|s| s.to_string()),
-        params: import.params.iter().map(|p| p.ty).collect(),
-        return_type: import.return_type,
+        alias: import.alias.map(|s| s.to_string()),
+        params: import.params.iter().map(|p| p.ty.clone()).collect(),
+        return_type: import.return_type.clone(),
     }
 }
 
@@ -157,16 +232,19 @@ fn lower_function(ctx: &mut LoweringContext, func: &tilt_ast::FunctionDef) -> Re
 
     let mut ir_func = Function::new(
         func.name.to_string(),
-        func.params.iter().map(|p| p.ty).collect(),
-        func.return_type,
+        func.params.iter().map(|p| p.ty.clone()).collect(),
+        func.return_type.clone(),
     );
 
-    ctx.current_function = Some(ir_func.clone());
+    ctx.current_function = Some(CurrentFunction {
+        name: ir_func.name.clone(),
+        return_type: ir_func.return_type.clone(),
+    });
 
     // Register function parameters as variables
     for param in &func.params {
         let value_id = ir_func.next_value();
-        ctx.register_variable(param.name.to_string(), value_id, param.ty);
+        ctx.register_variable(param.name.to_string(), value_id, param.ty.clone());
     }
 
     // First pass: assign block IDs to all block labels
@@ -183,16 +261,23 @@ fn lower_function(ctx: &mut LoweringContext, func: &tilt_ast::FunctionDef) -> Re
     }
 
     // Set entry block (first block)
-    if let Some(first_block) = func.blocks.first() {
-        if let Some(&entry_id) = ctx.block_map.get(first_block.label) {
-            ir_func.entry_block = entry_id;
-        }
+    if let Some(first_block) = func.blocks.first()
+        && let Some(&entry_id) = ctx.block_map.get(first_block.label)
+    {
+        ir_func.entry_block = entry_id;
     }
 
+    // Branches need their targets' phi nodes, so index the AST blocks by ID
+    let ast_blocks: HashMap<BlockId, &tilt_ast::Block> = func
+        .blocks
+        .iter()
+        .filter_map(|block| Some((*ctx.block_map.get(block.label)?, block)))
+        .collect();
+
     // Second pass: lower each block
     let mut ir_blocks = Vec::new();
     for block in &func.blocks {
-        match lower_block(ctx, &mut ir_func, block) {
+        match lower_block(ctx, &mut ir_func, block, &ast_blocks) {
             Ok(ir_block) => ir_blocks.push(ir_block),
             Err(_) => {
                 // Errors already added to ctx
@@ -200,6 +285,30 @@ fn lower_function(ctx: &mut LoweringContext, func: &tilt_ast::FunctionDef) -> Re
         }
     }
 
+    // Every phi incoming must name a block that branches to the phi's block
+    for block in &func.blocks {
+        for (_, nodes) in leading_phis(block) {
+            for (pred_label, _) in nodes {
+                let is_predecessor = ctx
+                    .block_map
+                    .get(*pred_label)
+                    .and_then(|pred_id| ir_blocks.iter().find(|b| b.id == *pred_id))
+                    .is_some_and(|pred| {
+                        pred.terminator
+                            .successors()
+                            .iter()
+                            .any(|(target, _)| Some(target) == ctx.block_map.get(block.label))
+                    });
+                if !is_predecessor {
+                    ctx.error(SemanticError::InvalidPhiReference {
+                        block: block.label.to_string(),
+                        referenced_block: pred_label.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
     ir_func.blocks = ir_blocks;
 
     if ctx.errors.is_empty() {
@@ -214,6 +323,7 @@ fn lower_block(
     ctx: &mut LoweringContext,
     func: &mut Function,
     block: &tilt_ast::Block,
+    ast_blocks: &HashMap<BlockId, &tilt_ast::Block>,
 ) -> Result<BasicBlock, ()> {
     let block_id = ctx
         .block_map
@@ -225,17 +335,26 @@ fn lower_block(
 
     // Add block parameters
     for param in &block.params {
-        let param_type = param.ty;
+        let param_type = param.ty.clone();
         let value_id = func.next_value();
-        ir_block.params.push((value_id, param_type));
+        ir_block.params.push((value_id, param_type.clone()));
 
         // Map the parameter name to the value ID with its type
         ctx.value_map
             .insert(param.name.to_string(), (value_id, param_type));
     }
 
+    // Phi nodes become further block parameters, filled in by predecessors
+    let mut phi_count = 0;
+    for (dest, _) in leading_phis(block) {
+        let value_id = func.next_value();
+        ir_block.params.push((value_id, dest.ty.clone()));
+        ctx.register_variable(dest.name.to_string(), value_id, dest.ty.clone());
+        phi_count += 1;
+    }
+
     // Lower instructions
-    for instruction in &block.instructions {
+    for instruction in &block.instructions[phi_count..] {
         match lower_instruction(ctx, func, instruction) {
             Ok(ir_instruction) => ir_block.instructions.push(ir_instruction),
             Err(_) => {
@@ -244,9 +363,13 @@ fn lower_block(
         }
     }
 
-    // Lower terminator
-    match lower_terminator(ctx, func, &block.terminator) {
-        Ok(ir_terminator) => ir_block.terminator = ir_terminator,
+    // Lower terminator, then pass this block's values to successor phis
+    match lower_terminator(ctx, func, &block.terminator, ast_blocks) {
+        Ok(mut ir_terminator) => {
+            if lower_phi_arguments(ctx, func, block.label, &mut ir_terminator, ast_blocks).is_ok() {
+                ir_block.terminator = ir_terminator;
+            }
+        }
         Err(_) => {
             // Error already added to ctx
         }
@@ -264,10 +387,12 @@ fn lower_instruction(
     match instruction {
         tilt_ast::Instruction::Assign { dest, expr } => {
             let dest_value_id = func.next_value();
-            ctx.register_variable(dest.name.to_string(), dest_value_id, dest.ty);
+            ctx.register_variable(dest.name.to_string(), dest_value_id, dest.ty.clone());
 
             match expr {
-                tilt_ast::Expression::Call { name, args } => {
+                tilt_ast::Expression::Call { name, args }
+                | tilt_ast::Expression::TryCall { name, args } => {
+                    let recover = matches!(expr, tilt_ast::Expression::TryCall { .. });
                     // Look up function
                     if let Some((param_types, return_type)) = ctx.lookup_function(name).cloned() {
                         // Check argument count
@@ -284,13 +409,26 @@ fn lower_instruction(
                         // Check return type matches destination
                         if return_type != dest.ty {
                             ctx.error(SemanticError::TypeMismatch {
-                                expected: dest.ty,
+                                expected: dest.ty.clone(),
                                 found: return_type,
                                 location: format!("assignment in block '{}'", "current"),
                             });
                             return Err(());
                         }
 
+                        // The error code -1 needs an integer to live in
+                        if recover && !matches!(return_type, Type::I32 | Type::I64 | Type::Usize) {
+                            ctx.error(SemanticError::InvalidOperation {
+                                operation: "try_call".to_string(),
+                                ty: return_type,
+                                location: format!(
+                                    "try_call to '{}'; only integer results can hold the error code",
+                                    name
+                                ),
+                            });
+                            return Err(());
+                        }
+
                         // Lower arguments
                         let mut ir_args = Vec::new();
                         for (arg, expected_type) in args.iter().zip(param_types.iter()) {
@@ -300,9 +438,9 @@ fn lower_instruction(
                                         ctx.lookup_variable(var_name)
                                     {
                                         // Type check
-                                        if actual_type != *expected_type {
+                                        if !accepts_argument(expected_type, &actual_type) {
                                             ctx.error(SemanticError::TypeMismatch {
-                                                expected: *expected_type,
+                                                expected: expected_type.clone(),
                                                 found: actual_type,
                                                 location: format!(
                                                     "argument to function '{}'",
@@ -320,24 +458,32 @@ fn lower_instruction(
                                         return Err(());
                                     }
                                 }
-                                tilt_ast::Value::Constant(const_val) => {
+                                literal => {
                                     // Create a constant instruction for this argument
+                                    let bits = literal_bits(ctx, literal, expected_type)?;
                                     let const_value_id = func.next_value();
-                                    func.constants.insert(
-                                        const_value_id,
-                                        (*const_val as i64, *expected_type),
-                                    );
+                                    func.constants
+                                        .insert(const_value_id, (bits, expected_type.clone()));
                                     ir_args.push(const_value_id);
                                 }
                             }
                         }
 
-                        Ok(Instruction::Call {
-                            dest: dest_value_id,
-                            function: name.to_string(),
-                            args: ir_args,
-                            return_type: return_type,
-                        })
+                        if recover {
+                            Ok(Instruction::TryCall {
+                                dest: dest_value_id,
+                                function: name.to_string(),
+                                args: ir_args,
+                                return_type,
+                            })
+                        } else {
+                            Ok(Instruction::Call {
+                                dest: dest_value_id,
+                                function: name.to_string(),
+                                args: ir_args,
+                                return_type,
+                            })
+                        }
                     } else {
                         ctx.error(SemanticError::FunctionNotFound {
                             name: name.to_string(),
@@ -371,7 +517,7 @@ fn lower_instruction(
                             // Check return type matches destination
                             if return_type != dest.ty {
                                 ctx.error(SemanticError::TypeMismatch {
-                                    expected: dest.ty,
+                                    expected: dest.ty.clone(),
                                     found: return_type,
                                     location: "function call return type".to_string(),
                                 });
@@ -381,9 +527,9 @@ fn lower_instruction(
                             // Lower arguments
                             let mut arg_ids = Vec::new();
                             for (i, arg) in args.iter().enumerate() {
-                                let expected_type = param_types[i];
+                                let expected_type = param_types[i].clone();
                                 let (arg_id, arg_type) =
-                                    lower_value_with_func(ctx, func, arg, expected_type)?;
+                                    lower_value_with_func(ctx, func, arg, expected_type.clone())?;
 
                                 if arg_type != expected_type {
                                     ctx.error(SemanticError::TypeMismatch {
@@ -419,16 +565,16 @@ fn lower_instruction(
                                     "usize.add with {} arguments (expected 2)",
                                     args.len()
                                 ),
-                                ty: dest.ty,
+                                ty: dest.ty.clone(),
                                 location: "usize.add operation".to_string(),
                             });
                             return Err(());
                         }
 
-                        if dest.ty != Type::Usize {
+                        if !dest.ty.is_pointer() {
                             ctx.error(SemanticError::TypeMismatch {
                                 expected: Type::Usize,
-                                found: dest.ty,
+                                found: dest.ty.clone(),
                                 location: "usize.add result".to_string(),
                             });
                             return Err(());
@@ -439,7 +585,7 @@ fn lower_instruction(
                         let (offset_id, offset_type) =
                             lower_value_with_func(ctx, func, &args[1], Type::Usize)?;
 
-                        if ptr_type != Type::Usize {
+                        if !ptr_type.is_pointer() {
                             ctx.error(SemanticError::TypeMismatch {
                                 expected: Type::Usize,
                                 found: ptr_type,
@@ -447,6 +593,15 @@ fn lower_instruction(
                             });
                             return Err(());
                         }
+                        // Offsetting a typed pointer keeps its pointee type
+                        if dest.ty != ptr_type {
+                            ctx.error(SemanticError::TypeMismatch {
+                                expected: ptr_type,
+                                found: dest.ty.clone(),
+                                location: "usize.add result".to_string(),
+                            });
+                            return Err(());
+                        }
                         if offset_type != Type::Usize {
                             ctx.error(SemanticError::TypeMismatch {
                                 expected: Type::Usize,
@@ -461,7 +616,7 @@ fn lower_instruction(
                             ptr: ptr_id,
                             offset: offset_id,
                         });
-                    } else if op.starts_with("sizeof.") {
+                    } else if let Some(type_part) = op.strip_prefix("sizeof.") {
                         // sizeof.i32, sizeof.i64, etc.
                         if !args.is_empty() {
                             ctx.error(SemanticError::InvalidOperation {
@@ -469,7 +624,7 @@ fn lower_instruction(
                                     "sizeof with {} arguments (expected 0)",
                                     args.len()
                                 ),
-                                ty: dest.ty,
+                                ty: dest.ty.clone(),
                                 location: "sizeof operation".to_string(),
                             });
                             return Err(());
@@ -478,24 +633,32 @@ fn lower_instruction(
                         if dest.ty != Type::Usize {
                             ctx.error(SemanticError::TypeMismatch {
                                 expected: Type::Usize,
-                                found: dest.ty,
+                                found: dest.ty.clone(),
                                 location: "sizeof result".to_string(),
                             });
                             return Err(());
                         }
 
-                        let type_part = &op[7..]; // Skip "sizeof."
+                        // Skip "sizeof."
                         let target_type = match type_part {
                             "i32" => Type::I32,
                             "i64" => Type::I64,
                             "f32" => Type::F32,
                             "f64" => Type::F64,
                             "ptr" => Type::Usize,
-                            "void" => Type::Void,
+                            "void" => {
+                                // Void has no values, so asking its size is a bug
+                                ctx.error(SemanticError::InvalidOperation {
+                                    operation: "sizeof.void".to_string(),
+                                    ty: Type::Void,
+                                    location: "sizeof operation; void has no size".to_string(),
+                                });
+                                return Err(());
+                            }
                             _ => {
                                 ctx.error(SemanticError::InvalidOperation {
                                     operation: format!("sizeof.{} with unknown type", type_part),
-                                    ty: dest.ty,
+                                    ty: dest.ty.clone(),
                                     location: "sizeof operation".to_string(),
                                 });
                                 return Err(());
@@ -514,16 +677,16 @@ fn lower_instruction(
                                     "alloc with {} arguments (expected 1)",
                                     args.len()
                                 ),
-                                ty: dest.ty,
+                                ty: dest.ty.clone(),
                                 location: "alloc operation".to_string(),
                             });
                             return Err(());
                         }
 
-                        if dest.ty != Type::Usize {
+                        if !dest.ty.is_pointer() {
                             ctx.error(SemanticError::TypeMismatch {
                                 expected: Type::Usize,
-                                found: dest.ty,
+                                found: dest.ty.clone(),
                                 location: "alloc result".to_string(),
                             });
                             return Err(());
@@ -562,7 +725,7 @@ fn lower_instruction(
                                 op,
                                 type_part,
                                 op_part,
-                                &args,
+                                args,
                             );
                         }
 
@@ -576,7 +739,7 @@ fn lower_instruction(
                             _ => {
                                 ctx.error(SemanticError::InvalidOperation {
                                     operation: op.to_string(),
-                                    ty: dest.ty,
+                                    ty: dest.ty.clone(),
                                     location: "operation".to_string(),
                                 });
                                 return Err(());
@@ -592,7 +755,7 @@ fn lower_instruction(
                                         type_part,
                                         args.len()
                                     ),
-                                    ty: dest.ty,
+                                    ty: dest.ty.clone(),
                                     location: "load operation".to_string(),
                                 });
                                 return Err(());
@@ -600,7 +763,7 @@ fn lower_instruction(
 
                             if ty != dest.ty {
                                 ctx.error(SemanticError::TypeMismatch {
-                                    expected: dest.ty,
+                                    expected: dest.ty.clone(),
                                     found: ty,
                                     location: "load result".to_string(),
                                 });
@@ -610,14 +773,7 @@ fn lower_instruction(
                             let (addr_id, addr_type) =
                                 lower_value_with_func(ctx, func, &args[0], Type::Usize)?;
 
-                            if addr_type != Type::Usize {
-                                ctx.error(SemanticError::TypeMismatch {
-                                    expected: Type::Usize,
-                                    found: addr_type,
-                                    location: "load address operand".to_string(),
-                                });
-                                return Err(());
-                            }
+                            check_address(ctx, &addr_type, &ty, "load address operand")?;
 
                             return Ok(Instruction::Load {
                                 dest: dest_value_id,
@@ -627,13 +783,22 @@ fn lower_instruction(
                         }
 
                         // Check that destination type matches operation type
-                        if ty != dest.ty {
+                        // (zero tests and comparisons produce an i32 whatever
+                        // the operand type)
+                        let is_comparison = BinaryOperator::from_str(op_part, ty.clone())
+                            .is_ok_and(BinaryOperator::is_comparison);
+                        let result_ty = if op_part == "eqz" || is_comparison {
+                            Type::I32
+                        } else {
+                            ty.clone()
+                        };
+                        if result_ty != dest.ty {
                             ctx.error(SemanticError::TypeMismatch {
-                                expected: dest.ty,
-                                found: ty,
+                                expected: dest.ty.clone(),
+                                found: result_ty.clone(),
                                 location: format!(
                                     "operation '{}': destination expects {:?} but operation '{}' produces {:?}",
-                                    op, dest.ty, op, ty
+                                    op, dest.ty, op, result_ty
                                 ),
                             });
                             return Err(());
@@ -641,19 +806,19 @@ fn lower_instruction(
 
                         if args.len() == 2 {
                             // Binary operation
-                            let binary_op =
-                                BinaryOperator::from_str(op_part, ty).map_err(|e| ctx.error(e))?;
+                            let binary_op = BinaryOperator::from_str(op_part, ty.clone())
+                                .map_err(|e| ctx.error(e))?;
 
                             let (lhs_id, lhs_type) =
-                                lower_value_with_func(ctx, func, &args[0], ty)?;
+                                lower_value_with_func(ctx, func, &args[0], ty.clone())?;
                             let (rhs_id, rhs_type) =
-                                lower_value_with_func(ctx, func, &args[1], ty)?;
+                                lower_value_with_func(ctx, func, &args[1], ty.clone())?;
 
                             // Type check operands
                             if lhs_type != ty {
                                 ctx.error(SemanticError::TypeMismatch {
-                                    expected: ty,
-                                    found: lhs_type,
+                                    expected: ty.clone(),
+                                    found: lhs_type.clone(),
                                     location: format!(
                                         "operation '{}': left operand expected {:?} but got {:?}",
                                         op, ty, lhs_type
@@ -663,8 +828,8 @@ fn lower_instruction(
                             }
                             if rhs_type != ty {
                                 ctx.error(SemanticError::TypeMismatch {
-                                    expected: ty,
-                                    found: rhs_type,
+                                    expected: ty.clone(),
+                                    found: rhs_type.clone(),
                                     location: format!(
                                         "operation '{}': right operand expected {:?} but got {:?}",
                                         op, ty, rhs_type
@@ -684,10 +849,12 @@ fn lower_instruction(
                             // Unary operation or constant
                             if op_part == "const" {
                                 // Handle constants like "i32.const 42"
-                                if let tilt_ast::Value::Constant(val) = &args[0] {
+                                if let literal @ (tilt_ast::Value::Constant(_)
+                                | tilt_ast::Value::Float(_)) = &args[0]
+                                {
                                     Ok(Instruction::Const {
                                         dest: dest_value_id,
-                                        value: *val as i64,
+                                        value: literal_bits(ctx, literal, &ty)?,
                                         ty,
                                     })
                                 } else {
@@ -706,7 +873,7 @@ fn lower_instruction(
                                 if dest.ty != Type::I64 && dest.ty != Type::Usize {
                                     ctx.error(SemanticError::TypeMismatch {
                                         expected: Type::I64,
-                                        found: dest.ty,
+                                        found: dest.ty.clone(),
                                         location: "i32.extend result must be i64 or usize"
                                             .to_string(),
                                     });
@@ -729,14 +896,14 @@ fn lower_instruction(
                                     dest: dest_value_id,
                                     src: operand_id,
                                     from_ty: Type::I32,
-                                    to_ty: dest.ty,
+                                    to_ty: dest.ty.clone(),
                                 })
                             } else if op_part == "extend" && type_part == "usize" {
                                 // Handle usize.extend to convert usize to i64
                                 if dest.ty != Type::I64 {
                                     ctx.error(SemanticError::TypeMismatch {
                                         expected: Type::I64,
-                                        found: dest.ty,
+                                        found: dest.ty.clone(),
                                         location: "usize.extend result must be i64".to_string(),
                                     });
                                     return Err(());
@@ -765,7 +932,7 @@ fn lower_instruction(
                                 if dest.ty != Type::I32 && dest.ty != Type::Usize {
                                     ctx.error(SemanticError::TypeMismatch {
                                         expected: Type::I32,
-                                        found: dest.ty,
+                                        found: dest.ty.clone(),
                                         location: "i64.trunc result must be i32 or usize"
                                             .to_string(),
                                     });
@@ -788,14 +955,14 @@ fn lower_instruction(
                                     dest: dest_value_id,
                                     src: operand_id,
                                     from_ty: Type::I64,
-                                    to_ty: dest.ty,
+                                    to_ty: dest.ty.clone(),
                                 })
                             } else if op_part == "to_i64" && type_part == "i32" {
                                 // Handle i32.to_i64 conversion
                                 if dest.ty != Type::I64 {
                                     ctx.error(SemanticError::TypeMismatch {
                                         expected: Type::I64,
-                                        found: dest.ty,
+                                        found: dest.ty.clone(),
                                         location: "i32.to_i64 result must be i64".to_string(),
                                     });
                                     return Err(());
@@ -824,7 +991,7 @@ fn lower_instruction(
                                 if dest.ty != Type::Usize {
                                     ctx.error(SemanticError::TypeMismatch {
                                         expected: Type::Usize,
-                                        found: dest.ty,
+                                        found: dest.ty.clone(),
                                         location: "i32.to_usize result must be usize".to_string(),
                                     });
                                     return Err(());
@@ -853,7 +1020,7 @@ fn lower_instruction(
                                 if dest.ty != Type::I32 {
                                     ctx.error(SemanticError::TypeMismatch {
                                         expected: Type::I32,
-                                        found: dest.ty,
+                                        found: dest.ty.clone(),
                                         location: "i64.to_i32 result must be i32".to_string(),
                                     });
                                     return Err(());
@@ -882,7 +1049,7 @@ fn lower_instruction(
                                 if dest.ty != Type::Usize {
                                     ctx.error(SemanticError::TypeMismatch {
                                         expected: Type::Usize,
-                                        found: dest.ty,
+                                        found: dest.ty.clone(),
                                         location: "i64.to_usize result must be usize".to_string(),
                                     });
                                     return Err(());
@@ -911,7 +1078,7 @@ fn lower_instruction(
                                 if dest.ty != Type::I64 {
                                     ctx.error(SemanticError::TypeMismatch {
                                         expected: Type::I64,
-                                        found: dest.ty,
+                                        found: dest.ty.clone(),
                                         location: "usize.to_i64 result must be i64".to_string(),
                                     });
                                     return Err(());
@@ -937,11 +1104,11 @@ fn lower_instruction(
                                 })
                             } else {
                                 // Unary operation
-                                let unary_op = UnaryOperator::from_str(op_part, ty)
+                                let unary_op = UnaryOperator::from_str(op_part, ty.clone())
                                     .map_err(|e| ctx.error(e))?;
 
                                 let (operand_id, operand_type) =
-                                    lower_value_with_func(ctx, func, &args[0], ty)?;
+                                    lower_value_with_func(ctx, func, &args[0], ty.clone())?;
 
                                 if operand_type != ty {
                                     ctx.error(SemanticError::TypeMismatch {
@@ -962,7 +1129,7 @@ fn lower_instruction(
                         } else {
                             ctx.error(SemanticError::InvalidOperation {
                                 operation: format!("{} with {} arguments", op, args.len()),
-                                ty: dest.ty,
+                                ty: dest.ty.clone(),
                                 location: "operation".to_string(),
                             });
                             Err(())
@@ -970,7 +1137,7 @@ fn lower_instruction(
                     } else {
                         ctx.error(SemanticError::InvalidOperation {
                             operation: op.to_string(),
-                            ty: dest.ty,
+                            ty: dest.ty.clone(),
                             location: "operation".to_string(),
                         });
                         Err(())
@@ -980,17 +1147,17 @@ fn lower_instruction(
                     // Direct constant assignment
                     Ok(Instruction::Const {
                         dest: dest_value_id,
-                        value: *value as i64,
-                        ty: dest.ty,
+                        value: literal_bits(ctx, &tilt_ast::Value::Constant(*value), &dest.ty)?,
+                        ty: dest.ty.clone(),
                     })
                 }
                 tilt_ast::Expression::Phi { nodes: _ } => {
-                    // Phi nodes are handled as block parameters in our IR
-                    // For now, we'll skip them and handle them separately
+                    // Leading phis were already lowered to block parameters
                     ctx.error(SemanticError::InvalidOperation {
-                        operation: "phi nodes not yet implemented".to_string(),
-                        ty: dest.ty,
-                        location: "phi expression".to_string(),
+                        operation: "phi".to_string(),
+                        ty: dest.ty.clone(),
+                        location: "phi after other instructions; phis must start the block"
+                            .to_string(),
                     });
                     Err(())
                 }
@@ -1031,9 +1198,9 @@ fn lower_instruction(
                                     if let Some((value_id, actual_type)) =
                                         ctx.lookup_variable(var_name)
                                     {
-                                        if actual_type != *expected_type {
+                                        if !accepts_argument(expected_type, &actual_type) {
                                             ctx.error(SemanticError::TypeMismatch {
-                                                expected: *expected_type,
+                                                expected: expected_type.clone(),
                                                 found: actual_type,
                                                 location: format!(
                                                     "argument to function '{}'",
@@ -1051,13 +1218,12 @@ fn lower_instruction(
                                         return Err(());
                                     }
                                 }
-                                tilt_ast::Value::Constant(const_val) => {
+                                literal => {
                                     // Create a constant instruction for this argument
+                                    let bits = literal_bits(ctx, literal, expected_type)?;
                                     let const_value_id = func.next_value();
-                                    func.constants.insert(
-                                        const_value_id,
-                                        (*const_val as i64, *expected_type),
-                                    );
+                                    func.constants
+                                        .insert(const_value_id, (bits, expected_type.clone()));
                                     ir_args.push(const_value_id);
                                 }
                             }
@@ -1110,9 +1276,9 @@ fn lower_instruction(
                                     if let Some((value_id, actual_type)) =
                                         ctx.lookup_variable(var_name)
                                     {
-                                        if actual_type != *expected_type {
+                                        if !accepts_argument(expected_type, &actual_type) {
                                             ctx.error(SemanticError::TypeMismatch {
-                                                expected: *expected_type,
+                                                expected: expected_type.clone(),
                                                 found: actual_type,
                                                 location: format!("argument to function '{}'", op),
                                             });
@@ -1127,13 +1293,12 @@ fn lower_instruction(
                                         return Err(());
                                     }
                                 }
-                                tilt_ast::Value::Constant(const_val) => {
+                                literal => {
                                     // Create a constant instruction for this argument
+                                    let bits = literal_bits(ctx, literal, expected_type)?;
                                     let const_value_id = func.next_value();
-                                    func.constants.insert(
-                                        const_value_id,
-                                        (*const_val as i64, *expected_type),
-                                    );
+                                    func.constants
+                                        .insert(const_value_id, (bits, expected_type.clone()));
                                     ir_args.push(const_value_id);
                                 }
                             }
@@ -1160,15 +1325,6 @@ fn lower_instruction(
                         let (ptr_value, ptr_type) =
                             lower_value_with_func(ctx, func, &args[0], Type::Usize)?;
 
-                        if ptr_type != Type::Usize {
-                            ctx.error(SemanticError::TypeMismatch {
-                                expected: Type::Usize,
-                                found: ptr_type,
-                                location: format!("first argument to '{}'", op),
-                            });
-                            return Err(());
-                        }
-
                         let store_type = if *op == "i32.store" {
                             Type::I32
                         } else if *op == "i64.store" {
@@ -1187,9 +1343,15 @@ fn lower_instruction(
                             });
                             return Err(());
                         };
+                        check_address(
+                            ctx,
+                            &ptr_type,
+                            &store_type,
+                            &format!("first argument to '{}'", op),
+                        )?;
 
                         let (value_id, value_type) =
-                            lower_value_with_func(ctx, func, &args[1], store_type)?;
+                            lower_value_with_func(ctx, func, &args[1], store_type.clone())?;
 
                         if value_type != store_type {
                             ctx.error(SemanticError::TypeMismatch {
@@ -1219,7 +1381,7 @@ fn lower_instruction(
                         let (ptr_value, ptr_type) =
                             lower_value_with_func(ctx, func, &args[0], Type::Usize)?;
 
-                        if ptr_type != Type::Usize {
+                        if !ptr_type.is_pointer() {
                             ctx.error(SemanticError::TypeMismatch {
                                 expected: Type::Usize,
                                 found: ptr_type,
@@ -1238,6 +1400,17 @@ fn lower_instruction(
                         Err(())
                     }
                 }
+                tilt_ast::Expression::TryCall { name, .. } => {
+                    ctx.error(SemanticError::InvalidOperation {
+                        operation: "try_call".to_string(),
+                        ty: Type::Void,
+                        location: format!(
+                            "try_call to '{}' without a destination for the error code",
+                            name
+                        ),
+                    });
+                    Err(())
+                }
                 _ => {
                     ctx.error(SemanticError::InvalidOperation {
                         operation: "unknown".to_string(),
@@ -1251,18 +1424,92 @@ fn lower_instruction(
     }
 }
 
+/// The phi nodes at the start of a block, which lower to block parameters:
+/// each node's destination and its `(predecessor label, value)` incomings
+fn leading_phis<'b, 'a>(
+    block: &'b tilt_ast::Block<'a>,
+) -> impl Iterator<
+    Item = (
+        &'b tilt_ast::TypedIdentifier<'a>,
+        &'b [(tilt_ast::Identifier<'a>, tilt_ast::Value<'a>)],
+    ),
+> {
+    block
+        .instructions
+        .iter()
+        .map_while(|instruction| match instruction {
+            tilt_ast::Instruction::Assign {
+                dest,
+                expr: tilt_ast::Expression::Phi { nodes },
+            } => Some((dest, nodes.as_slice())),
+            _ => None,
+        })
+}
+
+/// Append the incoming values `label` supplies to its successors' phi nodes
+/// to the branch arguments of its terminator
+fn lower_phi_arguments(
+    ctx: &mut LoweringContext,
+    func: &mut Function,
+    label: &str,
+    terminator: &mut Terminator,
+    ast_blocks: &HashMap<BlockId, &tilt_ast::Block>,
+) -> Result<(), ()> {
+    let edges = match terminator {
+        Terminator::Br { target, args } => vec![(*target, args)],
+        Terminator::BrIf {
+            true_target,
+            true_args,
+            false_target,
+            false_args,
+            ..
+        } => vec![(*true_target, true_args), (*false_target, false_args)],
+        Terminator::Ret { .. } | Terminator::Switch { .. } => Vec::new(),
+    };
+
+    for (target, args) in edges {
+        let Some(target_block) = ast_blocks.get(&target) else {
+            continue;
+        };
+        for (dest, nodes) in leading_phis(target_block) {
+            let Some((_, value)) = nodes.iter().find(|(pred, _)| *pred == label) else {
+                ctx.error(SemanticError::InvalidOperation {
+                    operation: format!("phi without an incoming value from '{}'", label),
+                    ty: dest.ty.clone(),
+                    location: format!("phi '{}' in block '{}'", dest.name, target_block.label),
+                });
+                return Err(());
+            };
+
+            let (value_id, value_type) = lower_value_with_func(ctx, func, value, dest.ty.clone())?;
+            if value_type != dest.ty {
+                ctx.error(SemanticError::TypeMismatch {
+                    expected: dest.ty.clone(),
+                    found: value_type,
+                    location: format!("phi '{}' incoming from '{}'", dest.name, label),
+                });
+                return Err(());
+            }
+            args.push(value_id);
+        }
+    }
+
+    Ok(())
+}
+
 /// Lower a terminator
 fn lower_terminator(
     ctx: &mut LoweringContext,
     func: &mut Function,
     terminator: &tilt_ast::Terminator,
+    ast_blocks: &HashMap<BlockId, &tilt_ast::Block>,
 ) -> Result<Terminator, ()> {
     match terminator {
         tilt_ast::Terminator::Ret(value_opt) => {
             if let Some(value) = value_opt {
                 // Get the expected return type from the current function
                 let expected_type = if let Some(current_func) = &ctx.current_function {
-                    current_func.return_type
+                    current_func.return_type.clone()
                 } else {
                     Type::I32 // Default fallback
                 };
@@ -1271,15 +1518,16 @@ fn lower_terminator(
                     lower_value_with_func(ctx, func, value, expected_type)?;
 
                 // Check that return type matches function return type
-                if let Some(current_func) = &ctx.current_function {
-                    if value_type != current_func.return_type {
-                        ctx.error(SemanticError::TypeMismatch {
-                            expected: current_func.return_type,
-                            found: value_type,
-                            location: "return value".to_string(),
-                        });
-                        return Err(());
-                    }
+                if let Some(current_func) = &ctx.current_function
+                    && value_type != current_func.return_type
+                {
+                    ctx.error(SemanticError::ReturnTypeMismatch {
+                        function: current_func.name.clone(),
+                        expected: current_func.return_type.clone(),
+                        found: value_type.clone(),
+                        suggestion: conversion_suggestion(&value_type, &current_func.return_type),
+                    });
+                    return Err(());
                 }
 
                 Ok(Terminator::Ret {
@@ -1287,15 +1535,15 @@ fn lower_terminator(
                 })
             } else {
                 // Check that function return type is void
-                if let Some(current_func) = &ctx.current_function {
-                    if current_func.return_type != Type::Void {
-                        ctx.error(SemanticError::TypeMismatch {
-                            expected: current_func.return_type,
-                            found: Type::Void,
-                            location: "void return".to_string(),
-                        });
-                        return Err(());
-                    }
+                if let Some(current_func) = &ctx.current_function
+                    && current_func.return_type != Type::Void
+                {
+                    ctx.error(SemanticError::TypeMismatch {
+                        expected: current_func.return_type.clone(),
+                        found: Type::Void,
+                        location: "void return".to_string(),
+                    });
+                    return Err(());
                 }
 
                 Ok(Terminator::Ret { value: None })
@@ -1303,12 +1551,7 @@ fn lower_terminator(
         }
         tilt_ast::Terminator::Br { label, args } => {
             if let Some(&target_id) = ctx.block_map.get(*label) {
-                // Lower block arguments
-                let mut lowered_args = Vec::new();
-                for arg in args {
-                    let (arg_id, _) = lower_value_with_func(ctx, func, arg, Type::I32)?; // TODO: infer proper type
-                    lowered_args.push(arg_id);
-                }
+                let lowered_args = lower_branch_args(ctx, func, ast_blocks, target_id, args)?;
                 Ok(Terminator::Br {
                     target: target_id,
                     args: lowered_args,
@@ -1365,19 +1608,10 @@ fn lower_terminator(
                 return Err(());
             };
 
-            // Lower true branch arguments
-            let mut lowered_true_args = Vec::new();
-            for arg in true_args {
-                let (arg_id, _) = lower_value_with_func(ctx, func, arg, Type::I32)?; // TODO: infer proper type
-                lowered_true_args.push(arg_id);
-            }
-
-            // Lower false branch arguments
-            let mut lowered_false_args = Vec::new();
-            for arg in false_args {
-                let (arg_id, _) = lower_value_with_func(ctx, func, arg, Type::I32)?; // TODO: infer proper type
-                lowered_false_args.push(arg_id);
-            }
+            let lowered_true_args =
+                lower_branch_args(ctx, func, ast_blocks, true_target, true_args)?;
+            let lowered_false_args =
+                lower_branch_args(ctx, func, ast_blocks, false_target, false_args)?;
 
             Ok(Terminator::BrIf {
                 cond: cond_id,
@@ -1390,6 +1624,28 @@ fn lower_terminator(
     }
 }
 
+/// Lower the arguments a branch passes to `target`. Constants take the
+/// type of the block parameter they bind to; extra arguments default to
+/// i32 and are reported by the verifier.
+fn lower_branch_args(
+    ctx: &mut LoweringContext,
+    func: &mut Function,
+    ast_blocks: &HashMap<BlockId, &tilt_ast::Block>,
+    target: BlockId,
+    args: &[tilt_ast::Value],
+) -> Result<Vec<ValueId>, ()> {
+    let params = ast_blocks
+        .get(&target)
+        .map_or(&[][..], |block| &block.params[..]);
+    args.iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            let expected = params.get(i).map_or(Type::I32, |param| param.ty.clone());
+            Ok(lower_value_with_func(ctx, func, arg, expected)?.0)
+        })
+        .collect()
+}
+
 /// Lower a value (variable reference or constant)
 #[allow(dead_code)]
 fn lower_value(ctx: &mut LoweringContext, value: &tilt_ast::Value) -> Result<(ValueId, Type), ()> {
@@ -1405,7 +1661,7 @@ fn lower_value(ctx: &mut LoweringContext, value: &tilt_ast::Value) -> Result<(Va
                 Err(())
             }
         }
-        tilt_ast::Value::Constant(_) => {
+        tilt_ast::Value::Constant(_) | tilt_ast::Value::Float(_) => {
             // Constants need to be created as instructions
             // For now, we'll return an error and handle them in the context where the type is known
             ctx.error(SemanticError::InvalidOperation {
@@ -1437,16 +1693,114 @@ fn lower_value_with_func(
             }
         }
         tilt_ast::Value::Constant(const_val) => {
+            if ctx.options.strict {
+                ctx.error(SemanticError::ImplicitConstant {
+                    value: *const_val,
+                    inferred: expected_type,
+                });
+                return Err(());
+            }
+
             // Create a constant instruction for this value
+            let bits = literal_bits(ctx, value, &expected_type)?;
             let const_value_id = func.next_value();
             func.constants
-                .insert(const_value_id, (*const_val as i64, expected_type));
+                .insert(const_value_id, (bits, expected_type.clone()));
             Ok((const_value_id, expected_type))
         }
+        tilt_ast::Value::Float(_) => {
+            let bits = literal_bits(ctx, value, &expected_type)?;
+            let const_value_id = func.next_value();
+            func.constants
+                .insert(const_value_id, (bits, expected_type.clone()));
+            Ok((const_value_id, expected_type))
+        }
+    }
+}
+
+/// Encode a literal as a constant of type `ty`, in the form kept by
+/// `Function::constants`. Integer literals may initialize floats, and must
+/// fit in an integer `ty`; float literals may only initialize `f32` or `f64`.
+fn literal_bits(
+    ctx: &mut LoweringContext,
+    literal: &tilt_ast::Value,
+    ty: &Type,
+) -> Result<i64, ()> {
+    match literal {
+        tilt_ast::Value::Constant(value) if is_float(ty) => {
+            Ok(float_constant_bits(*value as f64, ty))
+        }
+        tilt_ast::Value::Constant(value) if constant_fits(*value, ty) => Ok(*value),
+        tilt_ast::Value::Constant(value) => {
+            ctx.error(SemanticError::ConstantOutOfRange {
+                value: *value,
+                ty: ty.clone(),
+            });
+            Err(())
+        }
+        tilt_ast::Value::Float(value) if is_float(ty) => Ok(float_constant_bits(*value, ty)),
+        tilt_ast::Value::Float(value) => {
+            ctx.error(SemanticError::TypeMismatch {
+                expected: ty.clone(),
+                found: Type::F64,
+                location: format!("float literal {}", value),
+            });
+            Err(())
+        }
+        tilt_ast::Value::Variable(name) => unreachable!("'{}' is not a literal", name),
+    }
+}
+
+/// Whether a call parameter of type `param` accepts an argument of type
+/// `arg`. Typed pointers may be passed where a raw `usize` address is
+/// expected, as to the host allocator's `free`.
+fn accepts_argument(param: &Type, arg: &Type) -> bool {
+    arg == param || (*param == Type::Usize && arg.is_pointer())
+}
+
+/// Check that a value of `addr_type` can address a `ty` in memory. A raw
+/// `usize` may address anything; a typed pointer only its pointee type.
+fn check_address(
+    ctx: &mut LoweringContext,
+    addr_type: &Type,
+    ty: &Type,
+    location: &str,
+) -> Result<(), ()> {
+    let ok = match addr_type {
+        Type::Usize => true,
+        Type::Ptr(pointee) => **pointee == *ty,
+        _ => false,
+    };
+    if !ok {
+        let expected = if addr_type.is_pointer() {
+            Type::ptr(ty.clone())
+        } else {
+            Type::Usize
+        };
+        ctx.error(SemanticError::TypeMismatch {
+            expected,
+            found: addr_type.clone(),
+            location: location.to_string(),
+        });
+        return Err(());
+    }
+    Ok(())
+}
+
+/// Suggest the conversion op that turns a `from` value into a `to` value, if one exists
+fn conversion_suggestion(from: &Type, to: &Type) -> Option<String> {
+    let is_integer = |ty: &Type| matches!(ty, Type::I32 | Type::I64 | Type::Usize);
+    let is_numeric = |ty: &Type| matches!(ty, Type::I32 | Type::I64 | Type::F32 | Type::F64);
+    if from != to && ((is_integer(from) && is_integer(to)) || (is_numeric(from) && is_numeric(to)))
+    {
+        Some(format!("{}.to_{}", from, to))
+    } else {
+        None
     }
 }
 
 /// Handle conversion operations like i32.to_usize, i64.to_i32, etc.
+#[allow(clippy::too_many_arguments)]
 fn handle_conversion_operation(
     ctx: &mut LoweringContext,
     func: &mut Function,
@@ -1460,12 +1814,19 @@ fn handle_conversion_operation(
     if args.len() != 1 {
         ctx.error(SemanticError::InvalidOperation {
             operation: format!("{} with {} arguments (expected 1)", op, args.len()),
-            ty: dest.ty,
+            ty: dest.ty.clone(),
             location: "conversion operation".to_string(),
         });
         return Err(());
     }
 
+    // A `_checked` suffix asks for a trap instead of truncation, which only
+    // makes sense for conversions that can lose data
+    let (op_part, checked) = match op_part.strip_suffix("_checked") {
+        Some(base) => (base, true),
+        None => (op_part, false),
+    };
+
     // Parse the conversion: type_part.to_TARGET -> (source_type, target_type)
     let (source_type, target_type) = match (type_part, op_part) {
         ("i32", "to_i64") => (Type::I32, Type::I64),
@@ -1474,21 +1835,45 @@ fn handle_conversion_operation(
         ("i64", "to_usize") => (Type::I64, Type::Usize),
         ("usize", "to_i64") => (Type::Usize, Type::I64),
         ("usize", "to_i32") => (Type::Usize, Type::I32),
+        ("i32", "to_f32") => (Type::I32, Type::F32),
+        ("i32", "to_f64") => (Type::I32, Type::F64),
+        ("i64", "to_f32") => (Type::I64, Type::F32),
+        ("i64", "to_f64") => (Type::I64, Type::F64),
+        ("f32", "to_f64") => (Type::F32, Type::F64),
+        ("f64", "to_f32") => (Type::F64, Type::F32),
+        ("f32", "to_i32") => (Type::F32, Type::I32),
+        ("f32", "to_i64") => (Type::F32, Type::I64),
+        ("f64", "to_i32") => (Type::F64, Type::I32),
+        ("f64", "to_i64") => (Type::F64, Type::I64),
         _ => {
             ctx.error(SemanticError::InvalidOperation {
                 operation: op.to_string(),
-                ty: dest.ty,
+                ty: dest.ty.clone(),
                 location: "unsupported conversion".to_string(),
             });
             return Err(());
         }
     };
 
+    let narrowing = matches!(
+        (source_type.clone(), target_type.clone()),
+        (Type::I64, Type::I32) | (Type::Usize, Type::I32)
+    );
+    if checked && !narrowing {
+        ctx.error(SemanticError::InvalidOperation {
+            operation: op.to_string(),
+            ty: dest.ty.clone(),
+            location: "checked conversion (only i64.to_i32 and usize.to_i32 can be checked)"
+                .to_string(),
+        });
+        return Err(());
+    }
+
     // Check that destination type matches the target type
     if dest.ty != target_type {
         ctx.error(SemanticError::TypeMismatch {
-            expected: target_type,
-            found: dest.ty,
+            expected: target_type.clone(),
+            found: dest.ty.clone(),
             location: format!(
                 "conversion '{}': result type should be {:?} to match destination",
                 op, target_type
@@ -1498,13 +1883,14 @@ fn handle_conversion_operation(
     }
 
     // Lower the operand with the expected source type
-    let (operand_id, operand_type) = lower_value_with_func(ctx, func, &args[0], source_type)?;
+    let (operand_id, operand_type) =
+        lower_value_with_func(ctx, func, &args[0], source_type.clone())?;
 
     // Check that operand type matches the source type
     if operand_type != source_type {
         ctx.error(SemanticError::TypeMismatch {
-            expected: source_type,
-            found: operand_type,
+            expected: source_type.clone(),
+            found: operand_type.clone(),
             location: format!(
                 "conversion '{}': operand expected {:?} but got {:?}",
                 op, source_type, operand_type
@@ -1513,6 +1899,15 @@ fn handle_conversion_operation(
         return Err(());
     }
 
+    if checked {
+        return Ok(Instruction::ConvertChecked {
+            dest: dest_value_id,
+            src: operand_id,
+            from_ty: source_type,
+            to_ty: target_type,
+        });
+    }
+
     Ok(Instruction::Convert {
         dest: dest_value_id,
         src: operand_id,
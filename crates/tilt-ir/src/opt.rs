@@ -0,0 +1,718 @@
+// ===================================================================
+// FILE: opt.rs (tilt-ir crate)
+//
+// DESC: Optional IR optimization passes. None of them change what a
+//       program computes; backends may use their results to emit
+//       better code.
+// ===================================================================
+
+use crate::*;
+use std::collections::{HashMap, HashSet};
+
+/// Find blocks ending in `c = cmp a, b` followed by `br_if c, ...` where `c`
+/// has no other use, and record them in `Function::fused_compare_branches`
+/// so a backend can branch on the comparison itself
+pub fn fold_compare_branch(func: &mut Function) {
+    func.fused_compare_branches = fusable_compare_branches(func);
+}
+
+/// The blocks `fold_compare_branch` would record. Backends check recorded
+/// blocks against this, since a later edit can leave the record stale.
+pub fn fusable_compare_branches(func: &Function) -> HashSet<BlockId> {
+    let mut uses: HashMap<ValueId, usize> = HashMap::new();
+    for block in &func.blocks {
+        let operands = block
+            .instructions
+            .iter()
+            .flat_map(Instruction::operands)
+            .chain(block.terminator.operands());
+        for value in operands {
+            *uses.entry(value).or_default() += 1;
+        }
+    }
+
+    func.blocks
+        .iter()
+        .filter(|block| {
+            let Terminator::BrIf { cond, .. } = &block.terminator else {
+                return false;
+            };
+            let Some(Instruction::BinaryOp { dest, op, ty, .. }) = block.instructions.last() else {
+                return false;
+            };
+            dest == cond
+                && op.is_comparison()
+                && matches!(ty, Type::I32 | Type::I64 | Type::Usize)
+                && uses.get(cond) == Some(&1)
+        })
+        .map(|block| block.id)
+        .collect()
+}
+
+/// Give a function a single `ret`: every returning block branches to one new
+/// exit block instead, passing the return value as its block parameter.
+/// Returns whether the function changed; one with fewer than two `ret`s is
+/// left alone.
+pub fn normalize_single_exit(func: &mut Function) -> bool {
+    let returns = func
+        .blocks
+        .iter()
+        .filter(|block| matches!(block.terminator, Terminator::Ret { .. }))
+        .count();
+    if returns < 2 {
+        return false;
+    }
+
+    let exit_id = func.next_block_id();
+    let mut label = "exit".to_string();
+    let mut suffix = 0;
+    while func.blocks.iter().any(|b| b.label == label) {
+        suffix += 1;
+        label = format!("exit{}", suffix);
+    }
+
+    let mut exit = BasicBlock::new(exit_id, label);
+    let result = if func.return_type == Type::Void {
+        None
+    } else {
+        let param = func.next_value();
+        exit.params.push((param, func.return_type.clone()));
+        Some(param)
+    };
+    exit.terminator = Terminator::Ret { value: result };
+
+    for block in &mut func.blocks {
+        if let Terminator::Ret { value } = &block.terminator {
+            block.terminator = Terminator::Br {
+                target: exit_id,
+                args: value.iter().copied().collect(),
+            };
+        }
+    }
+    func.blocks.push(exit);
+    true
+}
+
+/// Merge blocks that do the same thing: equal parameter types, instructions
+/// and terminator, up to the values each block defines itself and branches
+/// back to itself. Branches to a duplicate go to the first copy instead and
+/// the duplicates are removed. The entry block, blocks involved in phis and
+/// blocks whose values are used elsewhere are never merged. Returns whether
+/// the function changed.
+pub fn merge_identical_blocks(func: &mut Function) -> bool {
+    let mut changed = false;
+
+    // Merging can make the blocks that branched to duplicates identical too
+    loop {
+        let candidates = mergeable_blocks(func);
+        let shapes: Vec<(BlockId, BasicBlock)> = func
+            .blocks
+            .iter()
+            .filter(|block| candidates.contains(&block.id))
+            .map(|block| (block.id, block_shape(block)))
+            .collect();
+
+        let mut redirects: HashMap<BlockId, BlockId> = HashMap::new();
+        for (i, (id, shape)) in shapes.iter().enumerate() {
+            if redirects.contains_key(id) {
+                continue;
+            }
+            for (other, other_shape) in &shapes[i + 1..] {
+                if !redirects.contains_key(other) && shape == other_shape {
+                    redirects.insert(*other, *id);
+                }
+            }
+        }
+        if redirects.is_empty() {
+            return changed;
+        }
+
+        func.blocks
+            .retain(|block| !redirects.contains_key(&block.id));
+        func.fused_compare_branches
+            .retain(|id| !redirects.contains_key(id));
+        let redirect = |target: BlockId| *redirects.get(&target).unwrap_or(&target);
+        for block in &mut func.blocks {
+            block.terminator = map_terminator(&block.terminator, |value| value, redirect);
+        }
+        changed = true;
+    }
+}
+
+/// Turn chains of equality tests on one value into a single `Switch`. A
+/// block ending in `c = eq v, K1; br_if c, case1, next`, whose `next` block
+/// only does `c2 = eq v, K2; br_if c2, case2, next2`, becomes one switch on
+/// `v` with cases `K1` and `K2` and default `next2`, and so on down the chain.
+/// A folded link must have no parameters, no other predecessor, no values
+/// used elsewhere and no part in a phi. Returns whether the function changed.
+pub fn reconstruct_switch(func: &mut Function) -> bool {
+    let mut changed = false;
+
+    // Each step folds one more link into its predecessor
+    while let Some((head, terminator, link)) = next_switch_link(func) {
+        func.blocks.retain(|block| block.id != link);
+        func.fused_compare_branches
+            .retain(|id| *id != head && *id != link);
+        if let Some(block) = func.blocks.iter_mut().find(|block| block.id == head) {
+            block.terminator = terminator;
+        }
+        changed = true;
+    }
+
+    changed
+}
+
+/// Find a block testing a value against constants whose default target is
+/// one more such test that `reconstruct_switch` can fold into it. Returns
+/// the block, its new `Switch` terminator and the link to remove.
+fn next_switch_link(func: &Function) -> Option<(BlockId, Terminator, BlockId)> {
+    let defs: HashMap<ValueId, &Instruction> = func
+        .blocks
+        .iter()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|instruction| Some((instruction.dest()?, instruction)))
+        .collect();
+
+    // `cond = eq value, K` with a constant `K`, as (value, K)
+    let equality_test = |cond: ValueId| -> Option<(ValueId, i64)> {
+        let Instruction::BinaryOp {
+            op: BinaryOperator::Eq,
+            ty,
+            lhs,
+            rhs,
+            ..
+        } = defs.get(&cond)?
+        else {
+            return None;
+        };
+        if !matches!(ty, Type::I32 | Type::I64 | Type::Usize) {
+            return None;
+        }
+        // Literal operands of lowered source live in `func.constants`
+        let constant = |value: &ValueId| match defs.get(value) {
+            Some(Instruction::Const { value, .. }) => Some(*value),
+            _ => func.constants.get(value).map(|(key, _)| *key),
+        };
+        let (value, key) = match (constant(lhs), constant(rhs)) {
+            (_, Some(key)) => (*lhs, key),
+            (Some(key), None) => (*rhs, key),
+            (None, None) => return None,
+        };
+        // Switch keys are matched against the value sign-extended to i64
+        let key = if *ty == Type::I32 {
+            key as i32 as i64
+        } else {
+            key
+        };
+        Some((value, key))
+    };
+
+    let mut predecessors: HashMap<BlockId, usize> = HashMap::new();
+    let mut used_in: HashMap<ValueId, HashSet<BlockId>> = HashMap::new();
+    let mut in_phi = HashSet::new();
+    for block in &func.blocks {
+        for (target, _) in block.terminator.successors() {
+            *predecessors.entry(target).or_default() += 1;
+        }
+        let operands = block
+            .instructions
+            .iter()
+            .flat_map(Instruction::operands)
+            .chain(block.terminator.operands());
+        for value in operands {
+            used_in.entry(value).or_default().insert(block.id);
+        }
+        for instruction in &block.instructions {
+            if let Instruction::Phi { incomings, .. } = instruction {
+                in_phi.extend(incomings.iter().map(|(pred, _)| *pred));
+            }
+        }
+    }
+    let blocks: HashMap<BlockId, &BasicBlock> =
+        func.blocks.iter().map(|block| (block.id, block)).collect();
+
+    for head in &func.blocks {
+        let (value, mut cases, link) = match &head.terminator {
+            Terminator::BrIf {
+                cond,
+                true_target,
+                true_args,
+                false_target,
+                false_args,
+            } if false_args.is_empty() => {
+                let Some((value, key)) = equality_test(*cond) else {
+                    continue;
+                };
+                let case = SwitchCase {
+                    value: key,
+                    target: *true_target,
+                    args: true_args.clone(),
+                };
+                (value, vec![case], *false_target)
+            }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+                default_args,
+            } if default_args.is_empty() => (*value, cases.clone(), *default),
+            _ => continue,
+        };
+
+        let Some(block) = blocks.get(&link) else {
+            continue;
+        };
+        let Terminator::BrIf {
+            cond,
+            true_target,
+            true_args,
+            false_target,
+            false_args,
+        } = &block.terminator
+        else {
+            continue;
+        };
+        let defined: HashSet<ValueId> = block
+            .instructions
+            .iter()
+            .filter_map(Instruction::dest)
+            .collect();
+        let foldable = link != head.id
+            && link != func.entry_block
+            && block.params.is_empty()
+            && predecessors.get(&link) == Some(&1)
+            && !in_phi.contains(&link)
+            && *true_target != link
+            && *false_target != link
+            && block
+                .instructions
+                .iter()
+                .all(|instruction| match instruction {
+                    Instruction::Const { .. } => true,
+                    Instruction::BinaryOp { op, .. } => op.is_comparison(),
+                    _ => false,
+                })
+            && defined.iter().all(|value| {
+                used_in
+                    .get(value)
+                    .is_none_or(|users| users == &HashSet::from([link]))
+            })
+            && !true_args
+                .iter()
+                .chain(false_args)
+                .any(|arg| defined.contains(arg));
+        if !foldable {
+            continue;
+        }
+        let Some((tested, key)) = equality_test(*cond) else {
+            continue;
+        };
+        if tested != value {
+            continue;
+        }
+
+        cases.push(SwitchCase {
+            value: key,
+            target: *true_target,
+            args: true_args.clone(),
+        });
+        let terminator = Terminator::Switch {
+            value,
+            cases,
+            default: *false_target,
+            default_args: false_args.clone(),
+        };
+        return Some((head.id, terminator, link));
+    }
+
+    None
+}
+
+/// Blocks `merge_identical_blocks` may merge: not the entry block, not part
+/// of a phi, and with no value of theirs used by another block
+fn mergeable_blocks(func: &Function) -> HashSet<BlockId> {
+    let mut used_by: HashMap<ValueId, HashSet<BlockId>> = HashMap::new();
+    let mut in_phis: HashSet<BlockId> = HashSet::new();
+    for block in &func.blocks {
+        let operands = block
+            .instructions
+            .iter()
+            .flat_map(Instruction::operands)
+            .chain(block.terminator.operands());
+        for value in operands {
+            used_by.entry(value).or_default().insert(block.id);
+        }
+        for instruction in &block.instructions {
+            if let Instruction::Phi { incomings, .. } = instruction {
+                in_phis.insert(block.id);
+                in_phis.extend(incomings.iter().map(|(from, _)| *from));
+            }
+        }
+    }
+
+    func.blocks
+        .iter()
+        .filter(|block| block.id != func.entry_block && !in_phis.contains(&block.id))
+        .filter(|block| {
+            let mut defined = block
+                .params
+                .iter()
+                .map(|(value, _)| *value)
+                .chain(block.instructions.iter().filter_map(Instruction::dest));
+            defined.all(|value| {
+                used_by
+                    .get(&value)
+                    .is_none_or(|users| users.iter().all(|user| *user == block.id))
+            })
+        })
+        .map(|block| block.id)
+        .collect()
+}
+
+/// A copy of `block` with the values it defines numbered from `usize::MAX`
+/// down, its own ID as `usize::MAX` and no label, so equal shapes mean
+/// interchangeable blocks
+fn block_shape(block: &BasicBlock) -> BasicBlock {
+    let locals: HashMap<ValueId, ValueId> = block
+        .params
+        .iter()
+        .map(|(value, _)| *value)
+        .chain(block.instructions.iter().filter_map(Instruction::dest))
+        .enumerate()
+        .map(|(i, value)| (value, ValueId(usize::MAX - i)))
+        .collect();
+    let value = |value: ValueId| *locals.get(&value).unwrap_or(&value);
+    let own = BlockId(usize::MAX);
+
+    let mut shape = BasicBlock::new(own, String::new());
+    shape.params = block
+        .params
+        .iter()
+        .map(|(param, ty)| (value(*param), ty.clone()))
+        .collect();
+    shape.instructions = block
+        .instructions
+        .iter()
+        .map(|instruction| map_instruction_values(instruction, value))
+        .collect();
+    shape.terminator = map_terminator(&block.terminator, value, |target| {
+        if target == block.id { own } else { target }
+    });
+    shape
+}
+
+/// Copy an instruction, passing every value it defines or reads through `f`
+fn map_instruction_values(
+    instruction: &Instruction,
+    f: impl Fn(ValueId) -> ValueId,
+) -> Instruction {
+    match instruction.clone() {
+        Instruction::BinaryOp {
+            dest,
+            op,
+            ty,
+            lhs,
+            rhs,
+        } => Instruction::BinaryOp {
+            dest: f(dest),
+            op,
+            ty,
+            lhs: f(lhs),
+            rhs: f(rhs),
+        },
+        Instruction::UnaryOp {
+            dest,
+            op,
+            ty,
+            operand,
+        } => Instruction::UnaryOp {
+            dest: f(dest),
+            op,
+            ty,
+            operand: f(operand),
+        },
+        Instruction::Call {
+            dest,
+            function,
+            args,
+            return_type,
+        } => Instruction::Call {
+            dest: f(dest),
+            function,
+            args: args.into_iter().map(&f).collect(),
+            return_type,
+        },
+        Instruction::TryCall {
+            dest,
+            function,
+            args,
+            return_type,
+        } => Instruction::TryCall {
+            dest: f(dest),
+            function,
+            args: args.into_iter().map(&f).collect(),
+            return_type,
+        },
+        Instruction::CallVoid { function, args } => Instruction::CallVoid {
+            function,
+            args: args.into_iter().map(&f).collect(),
+        },
+        Instruction::Load { dest, ty, address } => Instruction::Load {
+            dest: f(dest),
+            ty,
+            address: f(address),
+        },
+        Instruction::Store { address, value, ty } => Instruction::Store {
+            address: f(address),
+            value: f(value),
+            ty,
+        },
+        Instruction::Const { dest, value, ty } => Instruction::Const {
+            dest: f(dest),
+            value,
+            ty,
+        },
+        Instruction::PtrAdd { dest, ptr, offset } => Instruction::PtrAdd {
+            dest: f(dest),
+            ptr: f(ptr),
+            offset: f(offset),
+        },
+        Instruction::SizeOf { dest, ty } => Instruction::SizeOf { dest: f(dest), ty },
+        Instruction::Alloc { dest, size } => Instruction::Alloc {
+            dest: f(dest),
+            size: f(size),
+        },
+        Instruction::Free { ptr } => Instruction::Free { ptr: f(ptr) },
+        Instruction::AssertType { value, expected } => Instruction::AssertType {
+            value: f(value),
+            expected,
+        },
+        Instruction::DataAddr { dest, data } => Instruction::DataAddr {
+            dest: f(dest),
+            data,
+        },
+        Instruction::Convert {
+            dest,
+            src,
+            from_ty,
+            to_ty,
+        } => Instruction::Convert {
+            dest: f(dest),
+            src: f(src),
+            from_ty,
+            to_ty,
+        },
+        Instruction::ConvertChecked {
+            dest,
+            src,
+            from_ty,
+            to_ty,
+        } => Instruction::ConvertChecked {
+            dest: f(dest),
+            src: f(src),
+            from_ty,
+            to_ty,
+        },
+        Instruction::Phi {
+            dest,
+            ty,
+            incomings,
+        } => Instruction::Phi {
+            dest: f(dest),
+            ty,
+            incomings: incomings
+                .into_iter()
+                .map(|(block, value)| (block, f(value)))
+                .collect(),
+        },
+    }
+}
+
+/// Copy a terminator, passing its values through `value` and its branch
+/// targets through `target`
+fn map_terminator(
+    terminator: &Terminator,
+    value: impl Fn(ValueId) -> ValueId,
+    target: impl Fn(BlockId) -> BlockId,
+) -> Terminator {
+    let args = |args: &[ValueId]| args.iter().map(|arg| value(*arg)).collect();
+    match terminator {
+        Terminator::Ret { value: result } => Terminator::Ret {
+            value: result.map(&value),
+        },
+        Terminator::Br {
+            target: to,
+            args: a,
+        } => Terminator::Br {
+            target: target(*to),
+            args: args(a),
+        },
+        Terminator::BrIf {
+            cond,
+            true_target,
+            true_args,
+            false_target,
+            false_args,
+        } => Terminator::BrIf {
+            cond: value(*cond),
+            true_target: target(*true_target),
+            true_args: args(true_args),
+            false_target: target(*false_target),
+            false_args: args(false_args),
+        },
+        Terminator::Switch {
+            value: switched,
+            cases,
+            default,
+            default_args,
+        } => Terminator::Switch {
+            value: value(*switched),
+            cases: cases
+                .iter()
+                .map(|case| SwitchCase {
+                    value: case.value,
+                    target: target(case.target),
+                    args: args(&case.args),
+                })
+                .collect(),
+            default: target(*default),
+            default_args: args(default_args),
+        },
+    }
+}
+
+/// Put the operands of commutative operations in a canonical order: by
+/// `ValueId`, with constants last, so `add b, a` and `add a, b` read the
+/// same to value numbering. Other operations keep their operand order.
+/// Returns whether the function changed.
+pub fn canonicalize_commutative(func: &mut Function) -> bool {
+    let mut constants: HashSet<ValueId> = func.constants.keys().copied().collect();
+    constants.extend(
+        func.blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|instruction| match instruction {
+                Instruction::Const { dest, .. } => Some(*dest),
+                _ => None,
+            }),
+    );
+    let order = |value: &ValueId| (constants.contains(value), *value);
+
+    let mut changed = false;
+    for instruction in func
+        .blocks
+        .iter_mut()
+        .flat_map(|block| &mut block.instructions)
+    {
+        if let Instruction::BinaryOp { op, lhs, rhs, .. } = instruction
+            && op.is_commutative()
+            && order(lhs) > order(rhs)
+        {
+            std::mem::swap(lhs, rhs);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Remove repeated pure computations within each block: an instruction
+/// identical to an earlier one in its block, up to its result, is dropped
+/// and its uses read the earlier result instead. Commutative operands are
+/// canonicalized first so `add b, a` matches `add a, b`. Loads, phis and
+/// instructions with side effects are never merged. Returns whether the
+/// function changed.
+pub fn eliminate_common_subexpressions(func: &mut Function) -> bool {
+    let mut changed = canonicalize_commutative(func);
+    let mut replaced: HashMap<ValueId, ValueId> = HashMap::new();
+    let mut shrunk = HashSet::new();
+
+    for block in &mut func.blocks {
+        let mut available: HashMap<Instruction, ValueId> = HashMap::new();
+        let mut kept = Vec::with_capacity(block.instructions.len());
+        for instruction in std::mem::take(&mut block.instructions) {
+            let instruction =
+                map_instruction_values(&instruction, |v| *replaced.get(&v).unwrap_or(&v));
+            let reusable = !instruction.has_side_effects()
+                && !matches!(
+                    instruction,
+                    Instruction::Load { .. } | Instruction::Phi { .. }
+                );
+            if let Some(dest) = instruction.dest().filter(|_| reusable) {
+                let placeholder = ValueId(usize::MAX);
+                let key =
+                    map_instruction_values(
+                        &instruction,
+                        |v| if v == dest { placeholder } else { v },
+                    );
+                if let Some(earlier) = available.get(&key) {
+                    replaced.insert(dest, *earlier);
+                    shrunk.insert(block.id);
+                    continue;
+                }
+                available.insert(key, dest);
+            }
+            kept.push(instruction);
+        }
+        block.instructions = kept;
+    }
+
+    if !replaced.is_empty() {
+        let value = |v: ValueId| *replaced.get(&v).unwrap_or(&v);
+        for block in &mut func.blocks {
+            block.instructions = block
+                .instructions
+                .iter()
+                .map(|instruction| map_instruction_values(instruction, value))
+                .collect();
+            block.terminator = map_terminator(&block.terminator, value, |target| target);
+        }
+        // A fused block's compare must stay its last instruction
+        func.fused_compare_branches
+            .retain(|id| !shrunk.contains(id));
+        changed = true;
+    }
+
+    changed
+}
+
+/// Promote read-only data blobs that occur more than once in the program,
+/// in one function or several, to a single `Program::rodata` entry. Each
+/// function keeps the `DataId` it had for the blob, now naming the shared
+/// entry, so a backend emits the bytes once. Blobs matching an entry that
+/// is already shared join it. Returns whether the program changed.
+pub fn share_rodata(program: &mut Program) -> bool {
+    let mut shared: HashMap<Vec<u8>, usize> = program
+        .rodata
+        .iter()
+        .enumerate()
+        .map(|(index, bytes)| (bytes.clone(), index))
+        .collect();
+    let mut occurrences: HashMap<Vec<u8>, usize> = HashMap::new();
+    for function in &program.functions {
+        for (_, bytes) in &function.rodata {
+            *occurrences.entry(bytes.clone()).or_default() += 1;
+        }
+    }
+
+    let mut changed = false;
+    for function in &mut program.functions {
+        let (promoted, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut function.rodata)
+            .into_iter()
+            .partition(|(_, bytes)| occurrences[bytes] > 1 || shared.contains_key(bytes));
+        function.rodata = kept;
+
+        for (data, bytes) in promoted {
+            let index = match shared.get(&bytes) {
+                Some(&index) => index,
+                None => {
+                    program.rodata.push(bytes.clone());
+                    shared.insert(bytes, program.rodata.len() - 1);
+                    program.rodata.len() - 1
+                }
+            };
+            function.shared_rodata.push((data, index));
+            changed = true;
+        }
+    }
+    changed
+}
@@ -0,0 +1,143 @@
+// ===================================================================
+// FILE: phi.rs (tilt-ir crate)
+//
+// DESC: Rewrites explicit phi instructions into the block parameters
+//       and branch arguments that the backends understand.
+// ===================================================================
+
+use crate::*;
+
+/// Eliminate the phi instructions of every function in a program
+pub fn eliminate_program_phis(program: &mut Program) -> Result<(), VerifyError> {
+    for func in &mut program.functions {
+        eliminate_phis(func)?;
+    }
+    Ok(())
+}
+
+/// Replace each `Phi` with a new parameter on its block. The incoming value
+/// for each predecessor becomes an extra argument on that predecessor's
+/// branch, so every predecessor must have exactly one incoming entry.
+pub fn eliminate_phis(func: &mut Function) -> Result<(), VerifyError> {
+    for index in 0..func.blocks.len() {
+        let block_id = func.blocks[index].id;
+        let (phis, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut func.blocks[index].instructions)
+            .into_iter()
+            .partition(|instruction| matches!(instruction, Instruction::Phi { .. }));
+        func.blocks[index].instructions = rest;
+
+        if phis.is_empty() {
+            continue;
+        }
+
+        let invalid = |func: &Function, reason: String| VerifyError::InvalidPhi {
+            function: func.name.clone(),
+            block: func.blocks[index].label.clone(),
+            reason,
+        };
+
+        if block_id == func.entry_block {
+            return Err(invalid(
+                func,
+                "the entry block cannot have phis".to_string(),
+            ));
+        }
+
+        let predecessors: Vec<BlockId> = func
+            .blocks
+            .iter()
+            .filter(|block| {
+                block
+                    .terminator
+                    .successors()
+                    .iter()
+                    .any(|(target, _)| *target == block_id)
+            })
+            .map(|block| block.id)
+            .collect();
+
+        for phi in phis {
+            let Instruction::Phi {
+                dest,
+                ty,
+                incomings,
+            } = phi
+            else {
+                unreachable!("partitioned out non-phi instructions");
+            };
+
+            if let Some((pred, _)) = incomings
+                .iter()
+                .find(|(pred, _)| !predecessors.contains(pred))
+            {
+                return Err(invalid(
+                    func,
+                    format!("block {} is not a predecessor", pred.0),
+                ));
+            }
+
+            for pred in &predecessors {
+                let mut values = incomings.iter().filter(|(from, _)| from == pred);
+                let (Some((_, value)), None) = (values.next(), values.next()) else {
+                    return Err(invalid(
+                        func,
+                        format!("expected one incoming value from block {}", pred.0),
+                    ));
+                };
+
+                let pred_block = func
+                    .blocks
+                    .iter_mut()
+                    .find(|block| block.id == *pred)
+                    .expect("predecessor was found among the function's blocks");
+                append_branch_arg(&mut pred_block.terminator, block_id, *value);
+            }
+
+            func.blocks[index].params.push((dest, ty));
+        }
+    }
+
+    Ok(())
+}
+
+/// Pass `value` as an extra argument on every edge from `terminator` to `target`
+fn append_branch_arg(terminator: &mut Terminator, target: BlockId, value: ValueId) {
+    match terminator {
+        Terminator::Ret { .. } => {}
+        Terminator::Br {
+            target: br_target,
+            args,
+        } => {
+            if *br_target == target {
+                args.push(value);
+            }
+        }
+        Terminator::BrIf {
+            true_target,
+            true_args,
+            false_target,
+            false_args,
+            ..
+        } => {
+            if *true_target == target {
+                true_args.push(value);
+            }
+            if *false_target == target {
+                false_args.push(value);
+            }
+        }
+        Terminator::Switch {
+            cases,
+            default,
+            default_args,
+            ..
+        } => {
+            for case in cases.iter_mut().filter(|case| case.target == target) {
+                case.args.push(value);
+            }
+            if *default == target {
+                default_args.push(value);
+            }
+        }
+    }
+}
@@ -6,8 +6,12 @@
 // ===================================================================
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
-    use crate::{BlockId, SemanticError, ValueId, lowering::lower_program};
+    use crate::{
+        BlockId, LoweringOptions, SemanticError, ValueId, lower_program_with_options,
+        lowering::lower_program,
+    };
     use tilt_ast::*;
 
     fn create_test_program(items: Vec<TopLevelItem>) -> Program {
@@ -28,6 +32,7 @@ mod tests {
             module: "env",
             name: "print",
             calling_convention: None,
+            alias: None,
             params: vec![],
             return_type: Type::Void,
         };
@@ -63,12 +68,178 @@ mod tests {
         assert_eq!(result.functions[0].blocks[0].label, "entry");
     }
 
+    /// Build `import "env" "add" (a:i32, b:i32) -> i32` and a function calling it with `args`
+    fn create_typed_import_call_program(
+        params: Vec<TypedIdentifier<'static>>,
+        args: Vec<Value<'static>>,
+    ) -> Program<'static> {
+        let import = ImportDecl {
+            module: "env",
+            name: "add",
+            calling_convention: None,
+            alias: None,
+            params: vec![
+                TypedIdentifier {
+                    name: "a",
+                    ty: Type::I32,
+                },
+                TypedIdentifier {
+                    name: "b",
+                    ty: Type::I32,
+                },
+            ],
+            return_type: Type::I32,
+        };
+        let function = FunctionDef {
+            name: "main",
+            params,
+            return_type: Type::I32,
+            blocks: vec![Block {
+                label: "entry",
+                params: vec![],
+                instructions: vec![Instruction::Assign {
+                    dest: TypedIdentifier {
+                        name: "sum",
+                        ty: Type::I32,
+                    },
+                    expr: Expression::Call { name: "add", args },
+                }],
+                terminator: Terminator::Ret(Some(Value::Variable("sum"))),
+            }],
+        };
+        create_test_program(vec![
+            TopLevelItem::Import(import),
+            TopLevelItem::Function(function),
+        ])
+    }
+
+    #[test]
+    fn test_lower_typed_import_params() {
+        let ast = create_typed_import_call_program(
+            vec![TypedIdentifier {
+                name: "x",
+                ty: Type::I32,
+            }],
+            vec![Value::Variable("x"), Value::Constant(1)],
+        );
+        let result = lower_program(&ast).unwrap();
+
+        assert_eq!(result.imports[0].params, vec![Type::I32, Type::I32]);
+        assert_eq!(result.imports[0].return_type, Type::I32);
+    }
+
+    #[test]
+    fn test_lower_call_to_typed_import_checks_argument_types() {
+        let ast = create_typed_import_call_program(
+            vec![TypedIdentifier {
+                name: "x",
+                ty: Type::I64,
+            }],
+            vec![Value::Variable("x"), Value::Constant(1)],
+        );
+        let errors = lower_program(&ast).unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![SemanticError::TypeMismatch {
+                expected: Type::I32,
+                found: Type::I64,
+                location: "argument to function 'add'".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_lower_call_through_import_alias() {
+        let import = ImportDecl {
+            module: "libc",
+            name: "write",
+            calling_convention: None,
+            alias: Some("sys_write"),
+            params: vec![TypedIdentifier {
+                name: "len",
+                ty: Type::I64,
+            }],
+            return_type: Type::I64,
+        };
+        let function = FunctionDef {
+            name: "main",
+            params: vec![],
+            return_type: Type::I64,
+            blocks: vec![Block {
+                label: "entry",
+                params: vec![],
+                instructions: vec![Instruction::Assign {
+                    dest: TypedIdentifier {
+                        name: "n",
+                        ty: Type::I64,
+                    },
+                    expr: Expression::Call {
+                        name: "sys_write",
+                        args: vec![Value::Constant(3)],
+                    },
+                }],
+                terminator: Terminator::Ret(Some(Value::Variable("n"))),
+            }],
+        };
+        let ast = create_test_program(vec![
+            TopLevelItem::Import(import),
+            TopLevelItem::Function(function.clone()),
+        ]);
+        let result = lower_program(&ast).unwrap();
+
+        assert_eq!(result.imports[0].name, "write");
+        assert_eq!(result.imports[0].call_name(), "sys_write");
+        assert_eq!(result.host_symbol("sys_write"), "write");
+        let calls: Vec<&str> = result.functions[0].blocks[0]
+            .instructions
+            .iter()
+            .filter_map(|inst| match inst {
+                crate::Instruction::Call { function, .. } => Some(function.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls, vec!["sys_write"]);
+
+        // The symbol name itself is not callable once aliased
+        let mut unaliased = function;
+        unaliased.blocks[0].instructions = vec![Instruction::Assign {
+            dest: TypedIdentifier {
+                name: "n",
+                ty: Type::I64,
+            },
+            expr: Expression::Call {
+                name: "write",
+                args: vec![Value::Constant(3)],
+            },
+        }];
+        let ast = create_test_program(vec![
+            TopLevelItem::Import(ImportDecl {
+                module: "libc",
+                name: "write",
+                calling_convention: None,
+                alias: Some("sys_write"),
+                params: vec![TypedIdentifier {
+                    name: "len",
+                    ty: Type::I64,
+                }],
+                return_type: Type::I64,
+            }),
+            TopLevelItem::Function(unaliased),
+        ]);
+        assert!(matches!(
+            lower_program(&ast).unwrap_err()[0],
+            SemanticError::FunctionNotFound { .. }
+        ));
+    }
+
     #[test]
     fn test_lower_function_with_call() {
         let import = ImportDecl {
             module: "env",
             name: "getc",
             calling_convention: None,
+            alias: None,
             params: vec![],
             return_type: Type::I32,
         };
@@ -122,6 +293,7 @@ mod tests {
             module: "env",
             name: "putc",
             calling_convention: None,
+            alias: None,
             params: vec![],
             return_type: Type::Void,
         };
@@ -194,6 +366,143 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_lower_block_params_bound_by_branch_args() {
+        // fn f(n:i32) -> i32 { entry: br loop(0, n)  loop(i:i32, acc:i32): ret (acc) }
+        let function = FunctionDef {
+            name: "f",
+            params: vec![TypedIdentifier {
+                name: "n",
+                ty: Type::I32,
+            }],
+            return_type: Type::I32,
+            blocks: vec![
+                Block {
+                    label: "entry",
+                    params: vec![],
+                    instructions: vec![],
+                    terminator: Terminator::Br {
+                        label: "loop",
+                        args: vec![Value::Constant(0), Value::Variable("n")],
+                    },
+                },
+                Block {
+                    label: "loop",
+                    params: vec![
+                        TypedIdentifier {
+                            name: "i",
+                            ty: Type::I32,
+                        },
+                        TypedIdentifier {
+                            name: "acc",
+                            ty: Type::I32,
+                        },
+                    ],
+                    instructions: vec![],
+                    terminator: Terminator::Ret(Some(Value::Variable("acc"))),
+                },
+            ],
+        };
+        let ast = create_test_program(vec![TopLevelItem::Function(function)]);
+        let result = lower_program(&ast).unwrap();
+        let func = &result.functions[0];
+
+        let loop_block = &func.blocks[1];
+        let param_types: Vec<Type> = loop_block.params.iter().map(|(_, ty)| ty.clone()).collect();
+        assert_eq!(param_types, vec![Type::I32, Type::I32]);
+        let acc = loop_block.params[1].0;
+        assert_eq!(
+            loop_block.terminator,
+            crate::Terminator::Ret { value: Some(acc) }
+        );
+
+        let crate::Terminator::Br { target, args } = &func.blocks[0].terminator else {
+            panic!("entry should branch to loop");
+        };
+        assert_eq!(*target, loop_block.id);
+        assert_eq!(func.constants[&args[0]], (0, Type::I32));
+        assert_eq!(args[1], ValueId(0)); // The parameter n
+    }
+
+    #[test]
+    fn test_orphan_block_reports_unreachable_warning() {
+        let function = FunctionDef {
+            name: "test",
+            params: vec![],
+            return_type: Type::Void,
+            blocks: vec![
+                Block {
+                    label: "entry",
+                    params: vec![],
+                    instructions: vec![],
+                    terminator: Terminator::Ret(None),
+                },
+                Block {
+                    label: "orphan",
+                    params: vec![],
+                    instructions: vec![],
+                    terminator: Terminator::Ret(None),
+                },
+            ],
+        };
+        let ast = create_test_program(vec![TopLevelItem::Function(function)]);
+        let result = lower_program(&ast).unwrap();
+
+        assert!(crate::verify_program(&result).is_ok());
+        assert_eq!(
+            crate::program_warnings(&result),
+            vec![crate::VerifyWarning::UnreachableBlock {
+                function: "test".to_string(),
+                block: "orphan".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_self_branching_block_flagged_as_infinite_loop() {
+        let function = FunctionDef {
+            name: "spin",
+            params: vec![],
+            return_type: Type::Void,
+            blocks: vec![
+                Block {
+                    label: "entry",
+                    params: vec![],
+                    instructions: vec![],
+                    terminator: Terminator::Br {
+                        label: "loop",
+                        args: vec![],
+                    },
+                },
+                Block {
+                    label: "loop",
+                    params: vec![],
+                    instructions: vec![],
+                    terminator: Terminator::Br {
+                        label: "loop",
+                        args: vec![],
+                    },
+                },
+            ],
+        };
+        let ast = create_test_program(vec![TopLevelItem::Function(function)]);
+        let result = lower_program(&ast).unwrap();
+
+        let func = &result.functions[0];
+        let loop_block = func.blocks.iter().find(|b| b.label == "loop").unwrap();
+        assert_eq!(
+            crate::cfg::detect_trivial_infinite_loops(func),
+            vec![loop_block.id]
+        );
+        assert_eq!(
+            crate::program_warnings(&result),
+            vec![crate::VerifyWarning::TrivialInfiniteLoop {
+                function: "spin".to_string(),
+                block: "loop".to_string(),
+            }]
+        );
+    }
+
     // Error case tests
     #[test]
     fn test_undefined_function_error() {
@@ -232,6 +541,7 @@ mod tests {
             module: "env",
             name: "void_func",
             calling_convention: None,
+            alias: None,
             params: vec![],
             return_type: Type::Void,
         };
@@ -353,82 +663,1485 @@ mod tests {
         assert_eq!(errors.len(), 1);
         assert!(matches!(errors[0], SemanticError::TypeMismatch { .. }));
     }
-}
-
-#[cfg(test)]
-mod integration_tests {
-    use crate::{SemanticError, lowering::lower_program};
-    use tilt_ast::*;
 
     #[test]
-    fn test_complete_program() {
-        // A more complex program with multiple functions and control flow
-        let import = ImportDecl {
-            module: "env",
-            name: "print_i32",
-            calling_convention: None,
+    fn test_return_type_mismatch_suggests_conversion() {
+        let function = FunctionDef {
+            name: "widen",
             params: vec![TypedIdentifier {
-                name: "value",
+                name: "x",
                 ty: Type::I32,
             }],
-            return_type: Type::Void,
+            return_type: Type::I64,
+            blocks: vec![Block {
+                label: "entry",
+                params: vec![],
+                instructions: vec![],
+                terminator: Terminator::Ret(Some(Value::Variable("x"))),
+            }],
         };
+        let ast = create_test_program(vec![TopLevelItem::Function(function)]);
+        let errors = lower_program(&ast).unwrap_err();
 
-        let helper_func = FunctionDef {
-            name: "add_one",
+        assert_eq!(
+            errors,
+            vec![SemanticError::ReturnTypeMismatch {
+                function: "widen".to_string(),
+                expected: Type::I64,
+                found: Type::I32,
+                suggestion: Some("i32.to_i64".to_string()),
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "Returning i32 from i64 function 'widen'; use i32.to_i64"
+        );
+    }
+
+    #[test]
+    fn test_sizeof_void_is_rejected() {
+        // fn size() -> usize { s:usize = sizeof.T(); ret (s) }
+        let size_of = |op| FunctionDef {
+            name: "size",
+            params: vec![],
+            return_type: Type::Usize,
+            blocks: vec![Block {
+                label: "entry",
+                params: vec![],
+                instructions: vec![Instruction::Assign {
+                    dest: TypedIdentifier {
+                        name: "s",
+                        ty: Type::Usize,
+                    },
+                    expr: Expression::Operation { op, args: vec![] },
+                }],
+                terminator: Terminator::Ret(Some(Value::Variable("s"))),
+            }],
+        };
+
+        let ast = create_test_program(vec![TopLevelItem::Function(size_of("sizeof.i64"))]);
+        let program = lower_program(&ast).unwrap();
+        assert!(matches!(
+            program.functions[0].blocks[0].instructions[0],
+            crate::Instruction::SizeOf { ty: Type::I64, .. }
+        ));
+
+        let ast = create_test_program(vec![TopLevelItem::Function(size_of("sizeof.void"))]);
+        assert_eq!(
+            lower_program(&ast).unwrap_err(),
+            vec![SemanticError::InvalidOperation {
+                operation: "sizeof.void".to_string(),
+                ty: Type::Void,
+                location: "sizeof operation; void has no size".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_load_through_typed_pointer_checks_pointee() {
+        // fn read(p:ptr<i32>) -> T { v:T = T.load(p); ret (v) }
+        let read_through = |ty: Type, op| FunctionDef {
+            name: "read",
             params: vec![TypedIdentifier {
-                name: "x",
-                ty: Type::I32,
+                name: "p",
+                ty: Type::ptr(Type::I32),
             }],
-            return_type: Type::I32,
+            return_type: ty.clone(),
             blocks: vec![Block {
                 label: "entry",
                 params: vec![],
-                instructions: vec![],
-                terminator: Terminator::Ret(Some(Value::Variable("undefined_var"))), // This should fail
+                instructions: vec![Instruction::Assign {
+                    dest: TypedIdentifier { name: "v", ty },
+                    expr: Expression::Operation {
+                        op,
+                        args: vec![Value::Variable("p")],
+                    },
+                }],
+                terminator: Terminator::Ret(Some(Value::Variable("v"))),
             }],
         };
 
-        let main_func = FunctionDef {
-            name: "main",
+        let ast = create_test_program(vec![TopLevelItem::Function(read_through(
+            Type::I32,
+            "i32.load",
+        ))]);
+        let program = lower_program(&ast).unwrap();
+        assert_eq!(program.functions[0].params, vec![Type::ptr(Type::I32)]);
+
+        let ast = create_test_program(vec![TopLevelItem::Function(read_through(
+            Type::I64,
+            "i64.load",
+        ))]);
+        let errors = lower_program(&ast).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![SemanticError::TypeMismatch {
+                expected: Type::ptr(Type::I64),
+                found: Type::ptr(Type::I32),
+                location: "load address operand".to_string(),
+            }]
+        );
+    }
+
+    /// fn pick(c:i32) -> i32 branching to `a` or `b`, which both jump to
+    /// `join: r:i32 = phi [a: 1], [second: 2]; ret (r)`
+    fn create_phi_select_program(second: &'static str) -> Program<'static> {
+        let jump_to_join = |label| Block {
+            label,
             params: vec![],
-            return_type: Type::Void,
+            instructions: vec![],
+            terminator: Terminator::Br {
+                label: "join",
+                args: vec![],
+            },
+        };
+
+        let function = FunctionDef {
+            name: "pick",
+            params: vec![TypedIdentifier {
+                name: "c",
+                ty: Type::I32,
+            }],
+            return_type: Type::I32,
             blocks: vec![
                 Block {
                     label: "entry",
                     params: vec![],
                     instructions: vec![],
-                    terminator: Terminator::Br {
-                        label: "exit",
-                        args: vec![],
+                    terminator: Terminator::BrIf {
+                        cond: Value::Variable("c"),
+                        true_label: "a",
+                        true_args: vec![],
+                        false_label: "b",
+                        false_args: vec![],
                     },
                 },
+                jump_to_join("a"),
+                jump_to_join("b"),
                 Block {
-                    label: "exit",
+                    label: "join",
                     params: vec![],
-                    instructions: vec![],
-                    terminator: Terminator::Ret(None),
+                    instructions: vec![Instruction::Assign {
+                        dest: TypedIdentifier {
+                            name: "r",
+                            ty: Type::I32,
+                        },
+                        expr: Expression::Phi {
+                            nodes: vec![("a", Value::Constant(1)), (second, Value::Constant(2))],
+                        },
+                    }],
+                    terminator: Terminator::Ret(Some(Value::Variable("r"))),
                 },
             ],
         };
+        create_test_program(vec![TopLevelItem::Function(function)])
+    }
 
-        let ast = Program {
-            items: vec![
-                TopLevelItem::Import(import),
-                TopLevelItem::Function(helper_func),
-                TopLevelItem::Function(main_func),
-            ],
-        };
+    #[test]
+    fn test_phi_lowers_to_block_param_fed_by_predecessors() {
+        let program = lower_program(&create_phi_select_program("b")).unwrap();
+        let func = &program.functions[0];
+        let join = &func.blocks[3];
 
-        let result = lower_program(&ast);
+        assert!(join.instructions.is_empty());
+        assert_eq!(join.params.len(), 1);
+        let (r, ty) = &join.params[0];
+        assert_eq!(*ty, Type::I32);
+        assert_eq!(join.terminator, crate::Terminator::Ret { value: Some(*r) });
 
-        // This should fail because we're using an undefined variable 'x' in return
-        assert!(result.is_err());
-        let errors = result.unwrap_err();
-        assert!(
-            errors
-                .iter()
-                .any(|e| matches!(e, SemanticError::UndefinedIdentifier { .. }))
-        );
+        for (pred, expected) in [(1, 1), (2, 2)] {
+            let crate::Terminator::Br { target, args } = &func.blocks[pred].terminator else {
+                panic!("block {} should branch to join", pred);
+            };
+            assert_eq!(*target, join.id);
+            assert_eq!(args.len(), 1);
+            assert_eq!(func.constants[&args[0]], (expected, Type::I32));
+        }
+    }
+
+    #[test]
+    fn test_phi_incoming_must_come_from_a_predecessor() {
+        let errors = lower_program(&create_phi_select_program("entry")).unwrap_err();
+        assert!(errors.contains(&SemanticError::InvalidPhiReference {
+            block: "join".to_string(),
+            referenced_block: "entry".to_string(),
+        }));
+        // `b` branches to `join` without supplying a value
+        assert!(errors.iter().any(
+            |e| matches!(e, SemanticError::InvalidOperation { location, .. }
+                    if location == "phi 'r' in block 'join'")
+        ));
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_bare_constant_argument() {
+        // fn inc(x:i32) -> i32 { r:i32 = i32.add(x, 1); ret (r) }
+        let function = FunctionDef {
+            name: "inc",
+            params: vec![TypedIdentifier {
+                name: "x",
+                ty: Type::I32,
+            }],
+            return_type: Type::I32,
+            blocks: vec![Block {
+                label: "entry",
+                params: vec![],
+                instructions: vec![Instruction::Assign {
+                    dest: TypedIdentifier {
+                        name: "r",
+                        ty: Type::I32,
+                    },
+                    expr: Expression::Operation {
+                        op: "i32.add",
+                        args: vec![Value::Variable("x"), Value::Constant(1)],
+                    },
+                }],
+                terminator: Terminator::Ret(Some(Value::Variable("r"))),
+            }],
+        };
+        let ast = create_test_program(vec![TopLevelItem::Function(function)]);
+
+        assert!(lower_program(&ast).is_ok());
+
+        let strict = LoweringOptions { strict: true };
+        let errors = lower_program_with_options(&ast, &strict).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![SemanticError::ImplicitConstant {
+                value: 1,
+                inferred: Type::I32,
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "Bare constant 1 would be inferred as i32; write i32.const(1) instead"
+        );
+    }
+
+    #[test]
+    fn test_recursive_function_value_ids_stay_consistent() {
+        // fn sum_to(n:i32) -> i32: returns n + sum_to(n - 1), or 0 when n == 0
+        let function = FunctionDef {
+            name: "sum_to",
+            params: vec![TypedIdentifier {
+                name: "n",
+                ty: Type::I32,
+            }],
+            return_type: Type::I32,
+            blocks: vec![
+                Block {
+                    label: "entry",
+                    params: vec![],
+                    instructions: vec![Instruction::Assign {
+                        dest: TypedIdentifier {
+                            name: "is_zero",
+                            ty: Type::I32,
+                        },
+                        expr: Expression::Operation {
+                            op: "i32.eq",
+                            args: vec![Value::Variable("n"), Value::Constant(0)],
+                        },
+                    }],
+                    terminator: Terminator::BrIf {
+                        cond: Value::Variable("is_zero"),
+                        true_label: "done",
+                        true_args: vec![],
+                        false_label: "recurse",
+                        false_args: vec![],
+                    },
+                },
+                Block {
+                    label: "done",
+                    params: vec![],
+                    instructions: vec![],
+                    terminator: Terminator::Ret(Some(Value::Constant(0))),
+                },
+                Block {
+                    label: "recurse",
+                    params: vec![],
+                    instructions: vec![
+                        Instruction::Assign {
+                            dest: TypedIdentifier {
+                                name: "m",
+                                ty: Type::I32,
+                            },
+                            expr: Expression::Operation {
+                                op: "i32.sub",
+                                args: vec![Value::Variable("n"), Value::Constant(1)],
+                            },
+                        },
+                        Instruction::Assign {
+                            dest: TypedIdentifier {
+                                name: "rest",
+                                ty: Type::I32,
+                            },
+                            expr: Expression::Call {
+                                name: "sum_to",
+                                args: vec![Value::Variable("m")],
+                            },
+                        },
+                        Instruction::Assign {
+                            dest: TypedIdentifier {
+                                name: "total",
+                                ty: Type::I32,
+                            },
+                            expr: Expression::Operation {
+                                op: "i32.add",
+                                args: vec![Value::Variable("n"), Value::Variable("rest")],
+                            },
+                        },
+                    ],
+                    terminator: Terminator::Ret(Some(Value::Variable("total"))),
+                },
+            ],
+        };
+        let ast = create_test_program(vec![TopLevelItem::Function(function)]);
+        let program = lower_program(&ast).unwrap();
+        let func = &program.functions[0];
+
+        // Every value (parameters, block params, results and constants) gets
+        // its own ID, all allocated from the function's counter
+        let mut defined: Vec<ValueId> = (0..func.params.len()).map(ValueId::new).collect();
+        for block in &func.blocks {
+            defined.extend(block.params.iter().map(|(id, _)| *id));
+            defined.extend(block.instructions.iter().filter_map(|i| i.dest()));
+        }
+        defined.extend(func.constants.keys().copied());
+
+        let unique: std::collections::HashSet<_> = defined.iter().collect();
+        assert_eq!(
+            unique.len(),
+            defined.len(),
+            "duplicate value IDs: {:?}",
+            defined
+        );
+        assert!(
+            defined
+                .iter()
+                .all(|id| id.index() < func.next_value_id.index()),
+            "value IDs {:?} escape the counter {:?}",
+            defined,
+            func.next_value_id
+        );
+    }
+    /// fn clash(x: i32) -> i32 { v1 = 5; v2 = x + v1; ret v2 }, with the
+    /// sum written to `sum_dest`
+    fn create_sum_with_constant(sum_dest: ValueId) -> crate::Function {
+        let mut func = crate::Function::new("clash".to_string(), vec![Type::I32], Type::I32);
+        let x = func.next_value();
+        let five = func.next_value();
+        func.next_value();
+        func.constants.insert(five, (5, Type::I32));
+
+        let mut entry = crate::BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params.push((x, Type::I32));
+        entry.instructions.push(crate::Instruction::Const {
+            dest: five,
+            value: 5,
+            ty: Type::I32,
+        });
+        entry.instructions.push(crate::Instruction::BinaryOp {
+            dest: sum_dest,
+            op: crate::BinaryOperator::Add,
+            ty: Type::I32,
+            lhs: x,
+            rhs: five,
+        });
+        entry.terminator = crate::Terminator::Ret {
+            value: Some(sum_dest),
+        };
+        func.blocks.push(entry);
+        func
+    }
+
+    #[test]
+    fn test_unique_value_ids_pass_debug_assertion() {
+        // Builder-style IR: the entry block restates the parameter and a
+        // Const instruction mirrors its constant
+        let func = create_sum_with_constant(ValueId::new(2));
+        assert_eq!(crate::find_duplicate_value(&func), None);
+        crate::debug_assert_unique_values(&func);
+    }
+
+    #[test]
+    #[cfg_attr(not(debug_assertions), ignore = "the check only runs in debug builds")]
+    #[should_panic(
+        expected = "Function 'clash' defines v1 twice: as a constant and as a result in block 'entry'"
+    )]
+    fn test_colliding_value_ids_fail_debug_assertion() {
+        let func = create_sum_with_constant(ValueId::new(1));
+        crate::debug_assert_unique_values(&func);
+    }
+
+    #[test]
+    fn test_comparison_result_type_is_i32() {
+        let cmp = crate::Instruction::BinaryOp {
+            dest: ValueId::new(2),
+            op: crate::BinaryOperator::Lt,
+            ty: Type::I64,
+            lhs: ValueId::new(0),
+            rhs: ValueId::new(1),
+        };
+        assert_eq!(cmp.result_type(), Some(Type::I32));
+
+        let add = crate::Instruction::BinaryOp {
+            dest: ValueId::new(2),
+            op: crate::BinaryOperator::Add,
+            ty: Type::I64,
+            lhs: ValueId::new(0),
+            rhs: ValueId::new(1),
+        };
+        assert_eq!(add.result_type(), Some(Type::I64));
+    }
+
+    /// fn less(a: i64, b: i64) -> param_ty, passing an i64 comparison to an
+    /// `exit` block whose parameter has type `param_ty`
+    fn create_compare_to_param_function(param_ty: Type) -> crate::Function {
+        let mut func = crate::Function::new(
+            "less".to_string(),
+            vec![Type::I64, Type::I64],
+            param_ty.clone(),
+        );
+        func.next_value_id = ValueId::new(2);
+        let lt = func.next_value();
+        let result = func.next_value();
+
+        let mut entry = crate::BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params = vec![(ValueId::new(0), Type::I64), (ValueId::new(1), Type::I64)];
+        entry.instructions.push(crate::Instruction::BinaryOp {
+            dest: lt,
+            op: crate::BinaryOperator::Lt,
+            ty: Type::I64,
+            lhs: ValueId::new(0),
+            rhs: ValueId::new(1),
+        });
+        entry.terminator = crate::Terminator::Br {
+            target: BlockId::new(1),
+            args: vec![lt],
+        };
+
+        let mut exit = crate::BasicBlock::new(BlockId::new(1), "exit".to_string());
+        exit.params.push((result, param_ty));
+        exit.terminator = crate::Terminator::Ret {
+            value: Some(result),
+        };
+
+        func.blocks = vec![entry, exit];
+        func
+    }
+
+    #[test]
+    fn test_verifier_rejects_path_that_never_returns() {
+        // fn spin(c: i32) -> i32 { entry: br_if c, done, spin; done: ret c;
+        // spin: br spin }
+        let mut func = crate::Function::new("spin".to_string(), vec![Type::I32], Type::I32);
+        let c = func.next_value();
+
+        let mut entry = crate::BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params = vec![(c, Type::I32)];
+        entry.terminator = crate::Terminator::BrIf {
+            cond: c,
+            true_target: BlockId::new(1),
+            true_args: vec![],
+            false_target: BlockId::new(2),
+            false_args: vec![],
+        };
+        let mut done = crate::BasicBlock::new(BlockId::new(1), "done".to_string());
+        done.terminator = crate::Terminator::Ret { value: Some(c) };
+        let mut spin = crate::BasicBlock::new(BlockId::new(2), "spin".to_string());
+        spin.terminator = crate::Terminator::Br {
+            target: BlockId::new(2),
+            args: vec![],
+        };
+        func.blocks = vec![entry, done, spin];
+
+        assert!(!func.returns_on_all_paths());
+        assert_eq!(
+            crate::verify_function(&func),
+            vec![crate::VerifyError::NoReturnPath {
+                function: "spin".to_string(),
+                block: "spin".to_string(),
+            }]
+        );
+
+        // A loop with a way out is fine
+        func.blocks[2].terminator = crate::Terminator::BrIf {
+            cond: c,
+            true_target: BlockId::new(2),
+            true_args: vec![],
+            false_target: BlockId::new(1),
+            false_args: vec![],
+        };
+        assert!(func.returns_on_all_paths());
+        assert_eq!(crate::verify_function(&func), vec![]);
+    }
+
+    #[test]
+    fn test_verifier_types_comparisons_as_i32() {
+        let func = create_compare_to_param_function(Type::I32);
+        assert_eq!(crate::verify_function(&func), vec![]);
+
+        let func = create_compare_to_param_function(Type::I64);
+        assert_eq!(
+            crate::verify_function(&func),
+            vec![crate::VerifyError::TypeMismatch {
+                function: "less".to_string(),
+                block: "entry".to_string(),
+                value: ValueId::new(2),
+                expected: Type::I64,
+                found: Type::I32,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_verifier_checks_switch_cases() {
+        // fn pick(x: i32) -> i32 { entry: switch x [cases] default done;
+        // done: ret x }
+        let mut func = crate::Function::new("pick".to_string(), vec![Type::I32], Type::I32);
+        let x = func.next_value();
+
+        let mut entry = crate::BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params = vec![(x, Type::I32)];
+        let case = |value| crate::SwitchCase {
+            value,
+            target: BlockId::new(1),
+            args: vec![],
+        };
+        entry.terminator = crate::Terminator::Switch {
+            value: x,
+            cases: vec![case(1), case(-1), case(1 << 40), case(1)],
+            default: BlockId::new(1),
+            default_args: vec![],
+        };
+        let mut done = crate::BasicBlock::new(BlockId::new(1), "done".to_string());
+        done.terminator = crate::Terminator::Ret { value: Some(x) };
+        func.blocks = vec![entry, done];
+
+        assert_eq!(
+            crate::verify_function(&func),
+            vec![
+                crate::VerifyError::SwitchCaseOutOfRange {
+                    function: "pick".to_string(),
+                    block: "entry".to_string(),
+                    value: 1 << 40,
+                    ty: Type::I32,
+                },
+                crate::VerifyError::DuplicateSwitchCase {
+                    function: "pick".to_string(),
+                    block: "entry".to_string(),
+                    value: 1,
+                },
+            ]
+        );
+
+        // The same keys are fine on an i64 once they're distinct
+        func.params = vec![Type::I64];
+        func.blocks[0].params = vec![(x, Type::I64)];
+        func.return_type = Type::I64;
+        if let crate::Terminator::Switch { cases, .. } = &mut func.blocks[0].terminator {
+            cases.pop();
+        }
+        assert_eq!(crate::verify_function(&func), vec![]);
+    }
+
+    fn create_assert_ptr_add_function(expected: Type) -> crate::Function {
+        // fn offset(p: ptr<i32>, n: usize) { entry: q = ptr_add(p, n);
+        // assert_type q; ret }
+        let ptr_ty = Type::Ptr(Box::new(Type::I32));
+        let mut func = crate::Function::new(
+            "offset".to_string(),
+            vec![ptr_ty.clone(), Type::Usize],
+            Type::Void,
+        );
+        let p = func.next_value();
+        let n = func.next_value();
+        let q = func.next_value();
+
+        let mut entry = crate::BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params = vec![(p, ptr_ty), (n, Type::Usize)];
+        entry.instructions.push(crate::Instruction::PtrAdd {
+            dest: q,
+            ptr: p,
+            offset: n,
+        });
+        entry
+            .instructions
+            .push(crate::Instruction::AssertType { value: q, expected });
+        entry.terminator = crate::Terminator::Ret { value: None };
+        func.blocks = vec![entry];
+        func
+    }
+
+    #[test]
+    fn test_verifier_compares_assert_type_exactly() {
+        // fn double(x: i32) -> i32 { s = x + x; assert_type s, i64; ret s }
+        let mut func = crate::Function::new("double".to_string(), vec![Type::I32], Type::I32);
+        let x = func.next_value();
+        let sum = func.next_value();
+        let mut entry = crate::BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params = vec![(x, Type::I32)];
+        entry.instructions.push(crate::Instruction::BinaryOp {
+            dest: sum,
+            op: crate::BinaryOperator::Add,
+            ty: Type::I32,
+            lhs: x,
+            rhs: x,
+        });
+        entry.instructions.push(crate::Instruction::AssertType {
+            value: sum,
+            expected: Type::I64,
+        });
+        entry.terminator = crate::Terminator::Ret { value: Some(sum) };
+        func.blocks = vec![entry];
+        assert_eq!(
+            crate::verify_function(&func),
+            vec![crate::VerifyError::AssertTypeFailed {
+                function: "double".to_string(),
+                block: "entry".to_string(),
+                value: sum,
+                expected: Type::I64,
+                found: Type::I32,
+            }]
+        );
+
+        // Pointers only match their own pointee, not usize or other pointers
+        for expected in [Type::Usize, Type::Ptr(Box::new(Type::I64))] {
+            let func = create_assert_ptr_add_function(expected.clone());
+            assert_eq!(
+                crate::verify_function(&func),
+                vec![crate::VerifyError::AssertTypeFailed {
+                    function: "offset".to_string(),
+                    block: "entry".to_string(),
+                    value: ValueId::new(2),
+                    expected,
+                    found: Type::Ptr(Box::new(Type::I32)),
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_verifier_checks_assert_type_on_ptr_add_result() {
+        let func = create_assert_ptr_add_function(Type::Ptr(Box::new(Type::I32)));
+        assert_eq!(crate::verify_function(&func), vec![]);
+
+        let func = create_assert_ptr_add_function(Type::I64);
+        assert_eq!(
+            crate::verify_function(&func),
+            vec![crate::VerifyError::AssertTypeFailed {
+                function: "offset".to_string(),
+                block: "entry".to_string(),
+                value: ValueId::new(2),
+                expected: Type::I64,
+                found: Type::Ptr(Box::new(Type::I32)),
+            }]
+        );
+
+        // Offsetting an undefined pointer leaves nothing to check against
+        let mut func = create_assert_ptr_add_function(Type::Usize);
+        let undefined = func.next_value();
+        func.blocks[0].instructions[0] = crate::Instruction::PtrAdd {
+            dest: ValueId::new(2),
+            ptr: undefined,
+            offset: ValueId::new(1),
+        };
+        assert_eq!(
+            crate::verify_function(&func),
+            vec![
+                crate::VerifyError::UndefinedValue {
+                    function: "offset".to_string(),
+                    block: "entry".to_string(),
+                    value: undefined,
+                },
+                crate::VerifyError::AssertTypeUnknown {
+                    function: "offset".to_string(),
+                    block: "entry".to_string(),
+                    value: ValueId::new(2),
+                    expected: Type::Usize,
+                },
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod phi_tests {
+    use crate::phi::eliminate_phis;
+    use crate::*;
+
+    /// entry branches to `left` or `right`, which both jump to `join`,
+    /// where a phi picks 1 or 2 depending on the path taken
+    fn create_diamond_with_phi(incomings: Vec<(BlockId, ValueId)>) -> Function {
+        let mut func = Function::new("pick".to_string(), vec![Type::I32], Type::I32);
+        let cond = func.next_value();
+        let one = func.next_value();
+        let two = func.next_value();
+        let result = func.next_value();
+        func.constants.insert(one, (1, Type::I32));
+        func.constants.insert(two, (2, Type::I32));
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params.push((cond, Type::I32));
+        entry.terminator = Terminator::BrIf {
+            cond,
+            true_target: BlockId::new(1),
+            true_args: vec![],
+            false_target: BlockId::new(2),
+            false_args: vec![],
+        };
+        let mut left = BasicBlock::new(BlockId::new(1), "left".to_string());
+        left.terminator = Terminator::Br {
+            target: BlockId::new(3),
+            args: vec![],
+        };
+        let mut right = BasicBlock::new(BlockId::new(2), "right".to_string());
+        right.terminator = Terminator::Br {
+            target: BlockId::new(3),
+            args: vec![],
+        };
+        let mut join = BasicBlock::new(BlockId::new(3), "join".to_string());
+        join.instructions.push(Instruction::Phi {
+            dest: result,
+            ty: Type::I32,
+            incomings: incomings
+                .into_iter()
+                .map(|(block, value)| (block, if value.0 == 1 { one } else { two }))
+                .collect(),
+        });
+        join.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+
+        func.blocks = vec![entry, left, right, join];
+        func
+    }
+
+    #[test]
+    fn test_phi_becomes_block_param_and_branch_args() {
+        let mut func = create_diamond_with_phi(vec![
+            (BlockId::new(1), ValueId::new(1)),
+            (BlockId::new(2), ValueId::new(2)),
+        ]);
+        eliminate_phis(&mut func).unwrap();
+
+        let join = &func.blocks[3];
+        assert!(join.instructions.is_empty());
+        assert_eq!(join.params, vec![(ValueId::new(3), Type::I32)]);
+        assert_eq!(
+            func.blocks[1].terminator,
+            Terminator::Br {
+                target: BlockId::new(3),
+                args: vec![ValueId::new(1)],
+            }
+        );
+        assert_eq!(
+            func.blocks[2].terminator,
+            Terminator::Br {
+                target: BlockId::new(3),
+                args: vec![ValueId::new(2)],
+            }
+        );
+        assert!(verify_function(&func).is_empty());
+    }
+
+    #[test]
+    fn test_phi_missing_predecessor_is_rejected() {
+        let mut func = create_diamond_with_phi(vec![(BlockId::new(1), ValueId::new(1))]);
+        let err = eliminate_phis(&mut func).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid phi in block 'join' of function 'pick': expected one incoming value from block 2"
+        );
+    }
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use crate::{SemanticError, UnaryOperator, lowering::lower_program};
+    use tilt_ast::*;
+
+    #[test]
+    fn test_complete_program() {
+        // A more complex program with multiple functions and control flow
+        let import = ImportDecl {
+            module: "env",
+            name: "print_i32",
+            calling_convention: None,
+            alias: None,
+            params: vec![TypedIdentifier {
+                name: "value",
+                ty: Type::I32,
+            }],
+            return_type: Type::Void,
+        };
+
+        let helper_func = FunctionDef {
+            name: "add_one",
+            params: vec![TypedIdentifier {
+                name: "x",
+                ty: Type::I32,
+            }],
+            return_type: Type::I32,
+            blocks: vec![Block {
+                label: "entry",
+                params: vec![],
+                instructions: vec![],
+                terminator: Terminator::Ret(Some(Value::Variable("undefined_var"))), // This should fail
+            }],
+        };
+
+        let main_func = FunctionDef {
+            name: "main",
+            params: vec![],
+            return_type: Type::Void,
+            blocks: vec![
+                Block {
+                    label: "entry",
+                    params: vec![],
+                    instructions: vec![],
+                    terminator: Terminator::Br {
+                        label: "exit",
+                        args: vec![],
+                    },
+                },
+                Block {
+                    label: "exit",
+                    params: vec![],
+                    instructions: vec![],
+                    terminator: Terminator::Ret(None),
+                },
+            ],
+        };
+
+        let ast = Program {
+            items: vec![
+                TopLevelItem::Import(import),
+                TopLevelItem::Function(helper_func),
+                TopLevelItem::Function(main_func),
+            ],
+        };
+
+        let result = lower_program(&ast);
+
+        // This should fail because we're using an undefined variable 'x' in return
+        assert!(result.is_err());
+        let errors = result.unwrap_err();
+        assert!(
+            errors
+                .iter()
+                .any(|e| matches!(e, SemanticError::UndefinedIdentifier { .. }))
+        );
+    }
+
+    #[test]
+    fn test_float_intrinsics_require_float_type() {
+        assert_eq!(
+            UnaryOperator::from_str("sqrt", Type::F64),
+            Ok(UnaryOperator::Sqrt)
+        );
+        assert_eq!(
+            UnaryOperator::from_str("floor", Type::F32),
+            Ok(UnaryOperator::Floor)
+        );
+        assert!(matches!(
+            UnaryOperator::from_str("sqrt", Type::I32),
+            Err(SemanticError::InvalidOperation { .. })
+        ));
+    }
+
+    #[test]
+    fn test_eqz_rejects_float_operands() {
+        assert_eq!(
+            UnaryOperator::from_str("eqz", Type::I64),
+            Ok(UnaryOperator::Eqz)
+        );
+        assert!(UnaryOperator::from_str("eqz", Type::F64).is_err());
+    }
+}
+
+#[cfg(test)]
+mod visit_tests {
+    use crate::visit::*;
+    use crate::*;
+
+    /// fn main() -> i32 { a = f(); g(a); b = f(); ret b }
+    fn create_calling_function() -> Function {
+        let mut func = Function::new("main".to_string(), vec![], Type::I32);
+        let a = func.next_value();
+        let b = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions = vec![
+            Instruction::Call {
+                dest: a,
+                function: "f".to_string(),
+                args: vec![],
+                return_type: Type::I32,
+            },
+            Instruction::CallVoid {
+                function: "g".to_string(),
+                args: vec![a],
+            },
+            Instruction::Call {
+                dest: b,
+                function: "f".to_string(),
+                args: vec![],
+                return_type: Type::I32,
+            },
+        ];
+        entry.terminator = Terminator::Ret { value: Some(b) };
+        func.blocks.push(entry);
+        func
+    }
+
+    #[test]
+    fn test_visitor_counts_calls() {
+        #[derive(Default)]
+        struct CallCounter {
+            calls: usize,
+            void_calls: usize,
+        }
+
+        impl InstructionVisitor for CallCounter {
+            fn visit_call(&mut self, dest: Option<ValueId>, _function: &str, _args: &[ValueId]) {
+                match dest {
+                    Some(_) => self.calls += 1,
+                    None => self.void_calls += 1,
+                }
+            }
+        }
+
+        let mut counter = CallCounter::default();
+        walk_function(&create_calling_function(), &mut counter);
+
+        assert_eq!(counter.calls, 2);
+        assert_eq!(counter.void_calls, 1);
+    }
+
+    #[test]
+    fn test_rewriter_replaces_and_deletes_instructions() {
+        // Drop the void call and redirect the remaining calls to `h`
+        struct Redirect;
+
+        impl InstructionRewriter for Redirect {
+            fn rewrite_instruction(
+                &mut self,
+                _block: BlockId,
+                instruction: &Instruction,
+            ) -> Option<Vec<Instruction>> {
+                match instruction {
+                    Instruction::CallVoid { .. } => Some(vec![]),
+                    Instruction::Call {
+                        dest,
+                        args,
+                        return_type,
+                        ..
+                    } => Some(vec![Instruction::Call {
+                        dest: *dest,
+                        function: "h".to_string(),
+                        args: args.clone(),
+                        return_type: return_type.clone(),
+                    }]),
+                    _ => None,
+                }
+            }
+        }
+
+        let mut func = create_calling_function();
+        assert!(rewrite_function(&mut func, &mut Redirect));
+
+        let callees: Vec<&str> = func.blocks[0]
+            .instructions
+            .iter()
+            .map(|instruction| match instruction {
+                Instruction::Call { function, .. } => function.as_str(),
+                other => panic!("unexpected instruction {:?}", other),
+            })
+            .collect();
+        assert_eq!(callees, vec!["h", "h"]);
+    }
+}
+
+#[cfg(test)]
+mod cfg_tests {
+    use crate::cfg::*;
+    use crate::*;
+
+    /// fn name() -> i32 { r = callee(); ret r }, or { ret 0 } without a callee
+    fn create_caller(name: &str, callee: Option<&str>) -> Function {
+        let mut func = Function::new(name.to_string(), vec![], Type::I32);
+        let result = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(match callee {
+            Some(callee) => Instruction::Call {
+                dest: result,
+                function: callee.to_string(),
+                args: vec![],
+                return_type: Type::I32,
+            },
+            None => Instruction::Const {
+                dest: result,
+                value: 0,
+                ty: Type::I32,
+            },
+        });
+        entry.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+        func.blocks.push(entry);
+        func
+    }
+
+    #[test]
+    fn test_estimate_max_call_depth_of_chain() {
+        // main -> middle -> leaf, with leaf also calling a host import
+        let program = Program {
+            imports: vec![ImportDecl {
+                module: "host".to_string(),
+                name: "get".to_string(),
+                calling_convention: None,
+                alias: None,
+                params: vec![],
+                return_type: Type::I32,
+            }],
+            functions: vec![
+                create_caller("main", Some("middle")),
+                create_caller("middle", Some("leaf")),
+                create_caller("leaf", Some("get")),
+            ],
+            rodata: vec![],
+        };
+
+        assert_eq!(
+            callees(&program, &program.functions[2]),
+            Vec::<String>::new()
+        );
+        assert_eq!(estimate_max_call_depth(&program), Some(3));
+    }
+
+    #[test]
+    fn test_estimate_max_call_depth_rejects_recursion() {
+        let program = Program {
+            imports: vec![],
+            functions: vec![
+                create_caller("main", Some("ping")),
+                create_caller("ping", Some("pong")),
+                create_caller("pong", Some("ping")),
+            ],
+            rodata: vec![],
+        };
+
+        assert_eq!(estimate_max_call_depth(&program), None);
+    }
+
+    #[test]
+    fn test_estimate_max_call_depth_of_empty_program() {
+        let program = Program {
+            imports: vec![],
+            functions: vec![],
+            rodata: vec![],
+        };
+        assert_eq!(estimate_max_call_depth(&program), Some(0));
+
+        let program = Program {
+            imports: vec![],
+            functions: vec![create_caller("main", None)],
+            rodata: vec![],
+        };
+        assert_eq!(estimate_max_call_depth(&program), Some(1));
+    }
+}
+
+#[cfg(test)]
+mod cost_tests {
+    use crate::cost::*;
+    use crate::*;
+
+    /// fn name(a: i32, b: i32) -> i32 { ret a + b }, calling `callee(a)`
+    /// first when given
+    fn create_adder(name: &str, callee: Option<&str>) -> Function {
+        let mut func = Function::new(name.to_string(), vec![Type::I32, Type::I32], Type::I32);
+        let a = func.next_value();
+        let b = func.next_value();
+        let sum = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        if let Some(callee) = callee {
+            entry.instructions.push(Instruction::CallVoid {
+                function: callee.to_string(),
+                args: vec![a],
+            });
+        }
+        entry.instructions.push(Instruction::BinaryOp {
+            dest: sum,
+            op: BinaryOperator::Add,
+            ty: Type::I32,
+            lhs: a,
+            rhs: b,
+        });
+        entry.terminator = Terminator::Ret { value: Some(sum) };
+        func.blocks.push(entry);
+        func
+    }
+
+    #[test]
+    fn test_call_costs_more_than_arithmetic() {
+        let arithmetic = create_adder("add", None);
+        let with_call = create_adder("add_logged", Some("log"));
+        assert!(estimated_cost(&with_call) > estimated_cost(&arithmetic));
+
+        let program = Program {
+            imports: vec![],
+            functions: vec![arithmetic.clone(), with_call.clone()],
+            rodata: vec![],
+        };
+        assert_eq!(
+            program.estimated_cost(),
+            estimated_cost(&arithmetic) + estimated_cost(&with_call)
+        );
+    }
+
+    #[test]
+    fn test_cost_model_weights_are_tunable() {
+        let func = create_adder("add_logged", Some("log"));
+        let free_calls = CostModel {
+            call: 0,
+            ..CostModel::default()
+        };
+        assert_eq!(
+            free_calls.function_cost(&func),
+            estimated_cost(&create_adder("add", None))
+        );
+    }
+}
+
+#[cfg(test)]
+mod opt_tests {
+    use crate::opt::{
+        canonicalize_commutative, fold_compare_branch, merge_identical_blocks,
+        normalize_single_exit, share_rodata,
+    };
+    use crate::*;
+
+    /// fn max(a: i32, b: i32) -> i32 { c = lt a, b; br_if c, take_b, take_a }
+    /// with `c` also returned from `take_a` when `reuse_cond` is set
+    fn create_max_function(reuse_cond: bool) -> Function {
+        let mut func = Function::new("max".to_string(), vec![Type::I32, Type::I32], Type::I32);
+        let a = func.next_value();
+        let b = func.next_value();
+        let b_wins = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params = vec![(a, Type::I32), (b, Type::I32)];
+        entry.instructions.push(Instruction::BinaryOp {
+            dest: b_wins,
+            op: BinaryOperator::Lt,
+            ty: Type::I32,
+            lhs: a,
+            rhs: b,
+        });
+        entry.terminator = Terminator::BrIf {
+            cond: b_wins,
+            true_target: BlockId::new(1),
+            true_args: vec![],
+            false_target: BlockId::new(2),
+            false_args: vec![],
+        };
+
+        let mut take_b = BasicBlock::new(BlockId::new(1), "take_b".to_string());
+        take_b.terminator = Terminator::Ret { value: Some(b) };
+        let mut take_a = BasicBlock::new(BlockId::new(2), "take_a".to_string());
+        take_a.terminator = Terminator::Ret {
+            value: Some(if reuse_cond { b_wins } else { a }),
+        };
+
+        func.blocks = vec![entry, take_b, take_a];
+        func
+    }
+
+    #[test]
+    fn test_fold_compare_branch_recognizes_max() {
+        let mut func = create_max_function(false);
+        fold_compare_branch(&mut func);
+        assert_eq!(
+            func.fused_compare_branches,
+            [BlockId::new(0)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_normalize_single_exit_leaves_one_ret() {
+        let mut func = create_max_function(false);
+        assert!(normalize_single_exit(&mut func));
+
+        let rets: Vec<_> = func
+            .blocks
+            .iter()
+            .filter(|b| matches!(b.terminator, Terminator::Ret { .. }))
+            .collect();
+        assert_eq!(rets.len(), 1);
+        let exit = rets[0];
+        assert_eq!(exit.label, "exit");
+        assert_eq!(exit.params.len(), 1);
+        assert_eq!(
+            exit.terminator,
+            Terminator::Ret {
+                value: Some(exit.params[0].0)
+            }
+        );
+        assert_eq!(
+            func.blocks[1].terminator,
+            Terminator::Br {
+                target: exit.id,
+                args: vec![ValueId::new(1)],
+            }
+        );
+        assert_eq!(verify_function(&func), vec![]);
+
+        // Already normalized
+        assert!(!normalize_single_exit(&mut func));
+    }
+
+    #[test]
+    fn test_fold_compare_branch_skips_reused_comparison() {
+        let mut func = create_max_function(true);
+        fold_compare_branch(&mut func);
+        assert!(func.fused_compare_branches.is_empty());
+    }
+
+    /// fn inc(c: i32, x: i32) -> i32 whose two branch targets both return
+    /// `x + 1`, each computing it into its own value
+    fn create_duplicate_return_function() -> Function {
+        let mut func = Function::new("inc".to_string(), vec![Type::I32, Type::I32], Type::I32);
+        let c = func.next_value();
+        let x = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params = vec![(c, Type::I32), (x, Type::I32)];
+        let one = func.next_value();
+        func.constants.insert(one, (1, Type::I32));
+        entry.terminator = Terminator::BrIf {
+            cond: c,
+            true_target: BlockId::new(1),
+            true_args: vec![],
+            false_target: BlockId::new(2),
+            false_args: vec![],
+        };
+        func.blocks.push(entry);
+
+        for (id, label) in [(1, "then"), (2, "else")] {
+            let sum = func.next_value();
+            let mut block = BasicBlock::new(BlockId::new(id), label.to_string());
+            block.instructions.push(Instruction::BinaryOp {
+                dest: sum,
+                op: BinaryOperator::Add,
+                ty: Type::I32,
+                lhs: x,
+                rhs: one,
+            });
+            block.terminator = Terminator::Ret { value: Some(sum) };
+            func.blocks.push(block);
+        }
+        func
+    }
+
+    #[test]
+    fn test_canonicalize_commutative_orders_operands() {
+        // fn f(a: i32, b: i32) -> i32 { s = add b, a; d = sub a, b; m = mul 3, s; ... }
+        let mut func = Function::new("f".to_string(), vec![Type::I32, Type::I32], Type::I32);
+        let a = func.next_value();
+        let b = func.next_value();
+        let three = func.next_value();
+        func.constants.insert(three, (3, Type::I32));
+        let [sum, diff, product, result] = [(); 4].map(|_| func.next_value());
+
+        let binary = |dest, op, lhs, rhs| Instruction::BinaryOp {
+            dest,
+            op,
+            ty: Type::I32,
+            lhs,
+            rhs,
+        };
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions = vec![
+            binary(sum, BinaryOperator::Add, b, a),
+            binary(diff, BinaryOperator::Sub, b, a),
+            binary(product, BinaryOperator::Mul, three, sum),
+            binary(result, BinaryOperator::Xor, product, diff),
+        ];
+        entry.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+        func.blocks.push(entry);
+
+        assert!(canonicalize_commutative(&mut func));
+        assert_eq!(
+            func.blocks[0].instructions,
+            vec![
+                binary(sum, BinaryOperator::Add, a, b),
+                binary(diff, BinaryOperator::Sub, b, a),
+                binary(product, BinaryOperator::Mul, sum, three),
+                binary(result, BinaryOperator::Xor, diff, product),
+            ]
+        );
+
+        // Already canonical
+        assert!(!canonicalize_commutative(&mut func));
+    }
+
+    #[test]
+    fn test_merge_identical_return_blocks() {
+        let mut func = create_duplicate_return_function();
+        assert!(merge_identical_blocks(&mut func));
+
+        let labels: Vec<&str> = func.blocks.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, vec!["entry", "then"]);
+        assert_eq!(
+            func.blocks[0].terminator,
+            Terminator::BrIf {
+                cond: ValueId::new(0),
+                true_target: BlockId::new(1),
+                true_args: vec![],
+                false_target: BlockId::new(1),
+                false_args: vec![],
+            }
+        );
+        assert_eq!(verify_function(&func), vec![]);
+
+        // Nothing left to merge
+        assert!(!merge_identical_blocks(&mut func));
+    }
+
+    #[test]
+    fn test_merge_identical_blocks_keeps_blocks_whose_values_escape() {
+        // `then` and `else` become identical self-loops, except that the
+        // sum `else` computes is also returned by `done`
+        let mut func = create_duplicate_return_function();
+        let escaping = func.blocks[2].instructions[0].dest().unwrap();
+        let mut done = BasicBlock::new(BlockId::new(3), "done".to_string());
+        done.terminator = Terminator::Ret {
+            value: Some(escaping),
+        };
+        func.blocks.push(done);
+        for (i, block) in func.blocks.iter_mut().enumerate().skip(1).take(2) {
+            block.terminator = Terminator::BrIf {
+                cond: ValueId::new(0),
+                true_target: BlockId::new(i),
+                true_args: vec![],
+                false_target: BlockId::new(3),
+                false_args: vec![],
+            };
+        }
+        assert!(!merge_identical_blocks(&mut func));
+
+        // Without the escaping use the two loops merge, self-branch included
+        func.blocks[3].terminator = Terminator::Ret {
+            value: Some(ValueId::new(1)),
+        };
+        assert!(merge_identical_blocks(&mut func));
+        assert_eq!(func.blocks.len(), 3);
+        assert_eq!(
+            func.blocks[1].terminator,
+            Terminator::BrIf {
+                cond: ValueId::new(0),
+                true_target: BlockId::new(1),
+                true_args: vec![],
+                false_target: BlockId::new(3),
+                false_args: vec![],
+            }
+        );
+    }
+
+    /// fn `name`() -> usize returning the address of a table of `table`
+    fn create_table_function(name: &str, table: &[u8]) -> Function {
+        let mut func = Function::new(name.to_string(), vec![], Type::Usize);
+        let data = func.add_rodata(table.to_vec());
+        let addr = func.next_value();
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry
+            .instructions
+            .push(Instruction::DataAddr { dest: addr, data });
+        entry.terminator = Terminator::Ret { value: Some(addr) };
+        func.blocks.push(entry);
+        func
+    }
+
+    #[test]
+    fn test_share_rodata_leaves_one_symbol_for_identical_tables() {
+        let table: Vec<u8> = (0..64).collect();
+        let other: Vec<u8> = (64..128).collect();
+        let mut program = Program {
+            imports: vec![],
+            functions: vec![
+                create_table_function("first", &table),
+                create_table_function("second", &table),
+                create_table_function("unique", &other),
+            ],
+            rodata: vec![],
+        };
+
+        assert!(share_rodata(&mut program));
+        assert_eq!(program.rodata, vec![table.clone()]);
+        for function in &program.functions[..2] {
+            assert!(function.rodata.is_empty());
+            assert_eq!(function.shared_rodata, vec![(DataId::new(0), 0)]);
+            assert_eq!(
+                program.rodata(function, DataId::new(0)),
+                Some(table.as_slice())
+            );
+        }
+        let unique = &program.functions[2];
+        assert_eq!(unique.rodata, vec![(DataId::new(0), other)]);
+        assert!(unique.shared_rodata.is_empty());
+        assert_eq!(verify_program(&program), Ok(()));
+
+        // A second run finds nothing more to share
+        assert!(!share_rodata(&mut program));
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tracing_tests {
+    use std::sync::{Arc, Mutex};
+    use tracing::{Event, Metadata, Subscriber, span};
+
+    /// Subscriber that only records the names of the spans it sees
+    struct SpanRecorder(Arc<Mutex<Vec<&'static str>>>);
+
+    impl Subscriber for SpanRecorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut names = self.0.lock().unwrap();
+            names.push(span.metadata().name());
+            span::Id::from_u64(names.len() as u64)
+        }
+
+        fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, _span: &span::Id) {}
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+
+    #[test]
+    fn test_lowering_emits_span() {
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let ast = tilt_ast::Program { items: vec![] };
+
+        tracing::subscriber::with_default(SpanRecorder(Arc::clone(&names)), || {
+            crate::lower_program(&ast).unwrap();
+        });
+
+        assert!(names.lock().unwrap().contains(&"lower_program"));
     }
 }
@@ -0,0 +1,623 @@
+// ===================================================================
+// FILE: verify.rs (tilt-ir crate)
+//
+// DESC: Structural verifier for IR. Checks that IR built outside the
+//       lowering pass (e.g. with the IR builder) is well-formed before
+//       it reaches the VM or a code generator.
+// ===================================================================
+
+use crate::*;
+use std::collections::{HashMap, HashSet};
+
+/// Errors found while verifying IR
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyError {
+    /// Function has no blocks
+    EmptyFunction { function: String },
+    /// Entry block does not exist
+    MissingEntryBlock { function: String, entry: BlockId },
+    /// Block of a non-void function ends in the placeholder `ret` that every
+    /// block starts with, so its terminator was never set
+    MissingTerminator { function: String, block: String },
+    /// Branch to a block that does not exist
+    UndefinedBlock {
+        function: String,
+        block: String,
+        target: BlockId,
+    },
+    /// Use of a value that is never defined in the function
+    UndefinedValue {
+        function: String,
+        block: String,
+        value: ValueId,
+    },
+    /// Branch passes the wrong number of arguments to its target
+    BranchArgumentMismatch {
+        function: String,
+        block: String,
+        target: String,
+        expected: usize,
+        found: usize,
+    },
+    /// `ret` with a value in a void function
+    UnexpectedReturnValue { function: String, block: String },
+    /// Phi instruction that can't be turned into a block parameter
+    InvalidPhi {
+        function: String,
+        block: String,
+        reason: String,
+    },
+    /// `DataAddr` of a read-only data entry the function doesn't have
+    UndefinedData {
+        function: String,
+        block: String,
+        data: DataId,
+    },
+    /// Block of a non-void function from which no path reaches a `ret`
+    NoReturnPath { function: String, block: String },
+    /// Branch argument or return value whose type differs from the block
+    /// parameter or return type it feeds
+    TypeMismatch {
+        function: String,
+        block: String,
+        value: ValueId,
+        expected: Type,
+        found: Type,
+    },
+    /// `assert_type` on a value of another type
+    AssertTypeFailed {
+        function: String,
+        block: String,
+        value: ValueId,
+        expected: Type,
+        found: Type,
+    },
+    /// `assert_type` on a value whose type the verifier can't work out
+    AssertTypeUnknown {
+        function: String,
+        block: String,
+        value: ValueId,
+        expected: Type,
+    },
+    /// `switch` case whose value the scrutinee's type can't hold
+    SwitchCaseOutOfRange {
+        function: String,
+        block: String,
+        value: i64,
+        ty: Type,
+    },
+    /// `switch` with more than one case for the same value
+    DuplicateSwitchCase {
+        function: String,
+        block: String,
+        value: i64,
+    },
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::EmptyFunction { function } => {
+                write!(f, "Function '{}' has no blocks", function)
+            }
+            VerifyError::MissingEntryBlock { function, entry } => {
+                write!(
+                    f,
+                    "Function '{}' has no entry block with id {}",
+                    function, entry.0
+                )
+            }
+            VerifyError::MissingTerminator { function, block } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' is missing a terminator",
+                    block, function
+                )
+            }
+            VerifyError::UndefinedBlock {
+                function,
+                block,
+                target,
+            } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' branches to undefined block {}",
+                    block, function, target.0
+                )
+            }
+            VerifyError::UndefinedValue {
+                function,
+                block,
+                value,
+            } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' uses undefined value v{}",
+                    block, function, value.0
+                )
+            }
+            VerifyError::BranchArgumentMismatch {
+                function,
+                block,
+                target,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' passes {} arguments to '{}', which expects {}",
+                    block, function, found, target, expected
+                )
+            }
+            VerifyError::UnexpectedReturnValue { function, block } => {
+                write!(
+                    f,
+                    "Block '{}' returns a value from void function '{}'",
+                    block, function
+                )
+            }
+            VerifyError::InvalidPhi {
+                function,
+                block,
+                reason,
+            } => {
+                write!(
+                    f,
+                    "Invalid phi in block '{}' of function '{}': {}",
+                    block, function, reason
+                )
+            }
+            VerifyError::UndefinedData {
+                function,
+                block,
+                data,
+            } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' uses undefined read-only data d{}",
+                    block, function, data.0
+                )
+            }
+            VerifyError::NoReturnPath { function, block } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' never reaches a return",
+                    block, function
+                )
+            }
+            VerifyError::TypeMismatch {
+                function,
+                block,
+                value,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' passes v{} of type {} where {} is expected",
+                    block, function, value.0, found, expected
+                )
+            }
+            VerifyError::AssertTypeFailed {
+                function,
+                block,
+                value,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' asserts v{} is {}, but it is {}",
+                    block, function, value.0, expected, found
+                )
+            }
+            VerifyError::AssertTypeUnknown {
+                function,
+                block,
+                value,
+                expected,
+            } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' asserts v{} is {}, but its type can't be determined",
+                    block, function, value.0, expected
+                )
+            }
+            VerifyError::SwitchCaseOutOfRange {
+                function,
+                block,
+                value,
+                ty,
+            } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' switches on a {} but has a case for {}",
+                    block, function, ty, value
+                )
+            }
+            VerifyError::DuplicateSwitchCase {
+                function,
+                block,
+                value,
+            } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' has more than one switch case for {}",
+                    block, function, value
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Problems that do not make IR invalid but are likely mistakes
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyWarning {
+    /// Block can never be reached from the entry block
+    UnreachableBlock { function: String, block: String },
+    /// Block sits on a loop of unconditional branches with no side effects,
+    /// so reaching it hangs the program
+    TrivialInfiniteLoop { function: String, block: String },
+}
+
+impl std::fmt::Display for VerifyWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyWarning::UnreachableBlock { function, block } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' is unreachable",
+                    block, function
+                )
+            }
+            VerifyWarning::TrivialInfiniteLoop { function, block } => {
+                write!(
+                    f,
+                    "Block '{}' in function '{}' loops forever without side effects",
+                    block, function
+                )
+            }
+        }
+    }
+}
+
+/// Verify every function in a program
+pub fn verify_program(program: &Program) -> Result<(), Vec<VerifyError>> {
+    let errors: Vec<VerifyError> = program.functions.iter().flat_map(verify_function).collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Verify a single function, returning every problem found
+pub fn verify_function(func: &Function) -> Vec<VerifyError> {
+    let mut errors = Vec::new();
+
+    if func.blocks.is_empty() {
+        errors.push(VerifyError::EmptyFunction {
+            function: func.name.clone(),
+        });
+        return errors;
+    }
+
+    let blocks: HashMap<BlockId, &BasicBlock> = func.blocks.iter().map(|b| (b.id, b)).collect();
+    if !blocks.contains_key(&func.entry_block) {
+        errors.push(VerifyError::MissingEntryBlock {
+            function: func.name.clone(),
+            entry: func.entry_block,
+        });
+    }
+
+    // Values are function-scoped: block params, instruction results and constants
+    let mut defined: HashSet<ValueId> = func.constants.keys().copied().collect();
+    for block in &func.blocks {
+        defined.extend(block.params.iter().map(|(value, _)| *value));
+        defined.extend(block.instructions.iter().filter_map(Instruction::dest));
+    }
+
+    // Types of the values whose type is known without inference
+    let mut types: HashMap<ValueId, Type> = func
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| (ValueId(i), ty.clone()))
+        .collect();
+    types.extend(
+        func.constants
+            .iter()
+            .map(|(value, (_, ty))| (*value, ty.clone())),
+    );
+    for block in &func.blocks {
+        types.extend(block.params.iter().cloned());
+        types.extend(
+            block
+                .instructions
+                .iter()
+                .filter_map(|inst| Some((inst.dest()?, inst.result_type()?))),
+        );
+    }
+    // A pointer offset keeps its pointer's type; chains may cross blocks in
+    // any order, so resolve them until nothing changes
+    loop {
+        let mut changed = false;
+        for inst in func.blocks.iter().flat_map(|b| &b.instructions) {
+            if let Instruction::PtrAdd { dest, ptr, .. } = inst
+                && !types.contains_key(dest)
+                && let Some(ty) = types.get(ptr).cloned()
+            {
+                types.insert(*dest, ty);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    for block in &func.blocks {
+        let check_value = |value: ValueId, errors: &mut Vec<VerifyError>| {
+            if !defined.contains(&value) {
+                errors.push(VerifyError::UndefinedValue {
+                    function: func.name.clone(),
+                    block: block.label.clone(),
+                    value,
+                });
+            }
+        };
+        // Typed pointers and usize share a representation, so they may flow
+        // into each other
+        let check_type = |value: ValueId, expected: &Type, errors: &mut Vec<VerifyError>| {
+            if let Some(found) = types.get(&value)
+                && found.repr() != expected.repr()
+            {
+                errors.push(VerifyError::TypeMismatch {
+                    function: func.name.clone(),
+                    block: block.label.clone(),
+                    value,
+                    expected: expected.clone(),
+                    found: found.clone(),
+                });
+            }
+        };
+
+        for instruction in &block.instructions {
+            for value in instruction.operands() {
+                check_value(value, &mut errors);
+            }
+            if let Instruction::DataAddr { data, .. } = instruction
+                && !func.has_rodata(*data)
+            {
+                errors.push(VerifyError::UndefinedData {
+                    function: func.name.clone(),
+                    block: block.label.clone(),
+                    data: *data,
+                });
+            }
+            if let Instruction::AssertType { value, expected } = instruction {
+                match types.get(value) {
+                    Some(found) if found != expected => {
+                        errors.push(VerifyError::AssertTypeFailed {
+                            function: func.name.clone(),
+                            block: block.label.clone(),
+                            value: *value,
+                            expected: expected.clone(),
+                            found: found.clone(),
+                        })
+                    }
+                    Some(_) => {}
+                    // Undefined values are reported on their own
+                    None if defined.contains(value) => {
+                        errors.push(VerifyError::AssertTypeUnknown {
+                            function: func.name.clone(),
+                            block: block.label.clone(),
+                            value: *value,
+                            expected: expected.clone(),
+                        })
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        match &block.terminator {
+            Terminator::Ret { value: Some(value) } => {
+                check_value(*value, &mut errors);
+                if func.return_type == Type::Void {
+                    errors.push(VerifyError::UnexpectedReturnValue {
+                        function: func.name.clone(),
+                        block: block.label.clone(),
+                    });
+                } else {
+                    check_type(*value, &func.return_type, &mut errors);
+                }
+            }
+            Terminator::Ret { value: None } => {
+                if func.return_type != Type::Void {
+                    errors.push(VerifyError::MissingTerminator {
+                        function: func.name.clone(),
+                        block: block.label.clone(),
+                    });
+                }
+            }
+            Terminator::Br { .. } => {}
+            Terminator::BrIf { cond, .. } => check_value(*cond, &mut errors),
+            Terminator::Switch { value, cases, .. } => {
+                check_value(*value, &mut errors);
+                let mut seen = HashSet::new();
+                for case in cases {
+                    if let Some(ty) = types.get(value)
+                        && !constant_fits(case.value, ty)
+                    {
+                        errors.push(VerifyError::SwitchCaseOutOfRange {
+                            function: func.name.clone(),
+                            block: block.label.clone(),
+                            value: case.value,
+                            ty: ty.clone(),
+                        });
+                    }
+                    if !seen.insert(case.value) {
+                        errors.push(VerifyError::DuplicateSwitchCase {
+                            function: func.name.clone(),
+                            block: block.label.clone(),
+                            value: case.value,
+                        });
+                    }
+                }
+            }
+        }
+
+        for (target, args) in block.terminator.successors() {
+            for value in args {
+                check_value(*value, &mut errors);
+            }
+
+            match blocks.get(&target) {
+                Some(target_block) if target_block.params.len() != args.len() => {
+                    errors.push(VerifyError::BranchArgumentMismatch {
+                        function: func.name.clone(),
+                        block: block.label.clone(),
+                        target: target_block.label.clone(),
+                        expected: target_block.params.len(),
+                        found: args.len(),
+                    });
+                }
+                Some(target_block) => {
+                    for (value, (_, ty)) in args.iter().zip(&target_block.params) {
+                        check_type(*value, ty, &mut errors);
+                    }
+                }
+                None => errors.push(VerifyError::UndefinedBlock {
+                    function: func.name.clone(),
+                    block: block.label.clone(),
+                    target,
+                }),
+            }
+        }
+    }
+
+    // A non-void function must produce its value on every path
+    if func.return_type != Type::Void {
+        for id in crate::cfg::blocks_without_return_path(func) {
+            errors.push(VerifyError::NoReturnPath {
+                function: func.name.clone(),
+                block: blocks[&id].label.clone(),
+            });
+        }
+    }
+
+    errors
+}
+
+/// Find a value defined more than once in a function, counting function
+/// parameters (values 0 to n - 1), constants, block parameters and
+/// instruction results. The entry block may restate the function parameters
+/// as its own, in order, and a `Const` instruction may restate the constant
+/// of the same ID, as the IR builder does. Returns the value with where its
+/// first two definitions are.
+pub fn find_duplicate_value(func: &Function) -> Option<(ValueId, String, String)> {
+    let function_params =
+        (0..func.params.len()).map(|i| (ValueId::new(i), format!("function parameter {}", i)));
+    let mut constants: Vec<ValueId> = func.constants.keys().copied().collect();
+    constants.sort();
+    let block_definitions = func.blocks.iter().flat_map(|block| {
+        let is_entry = block.id == func.entry_block;
+        let params = block
+            .params
+            .iter()
+            .enumerate()
+            .filter(move |(i, (value, _))| {
+                !(is_entry && *i < func.params.len() && *value == ValueId::new(*i))
+            })
+            .map(move |(_, (value, _))| {
+                (*value, format!("a parameter of block '{}'", block.label))
+            });
+        let results = block
+            .instructions
+            .iter()
+            .filter(|instruction| match instruction {
+                Instruction::Const { dest, value, ty } => {
+                    func.constants.get(dest) != Some(&(*value, ty.clone()))
+                }
+                _ => true,
+            })
+            .filter_map(Instruction::dest)
+            .map(move |value| (value, format!("a result in block '{}'", block.label)));
+        params.chain(results)
+    });
+
+    let mut first_sites: HashMap<ValueId, String> = HashMap::new();
+    for (value, site) in function_params
+        .chain(
+            constants
+                .into_iter()
+                .map(|value| (value, "a constant".to_string())),
+        )
+        .chain(block_definitions)
+    {
+        if let Some(first) = first_sites.get(&value) {
+            return Some((value, first.clone(), site));
+        }
+        first_sites.insert(value, site);
+    }
+    None
+}
+
+/// Panic in debug builds if a function defines any value twice. Lowering
+/// and the IR builder call this on what they produce, so an ID allocation
+/// bug fails loudly where it happens instead of as a miscompile later.
+pub fn debug_assert_unique_values(func: &Function) {
+    if cfg!(debug_assertions)
+        && let Some((value, first, second)) = find_duplicate_value(func)
+    {
+        panic!(
+            "Function '{}' defines v{} twice: as {} and as {}",
+            func.name, value.0, first, second
+        );
+    }
+}
+
+/// Collect warnings for every function in a program
+pub fn program_warnings(program: &Program) -> Vec<VerifyWarning> {
+    program
+        .functions
+        .iter()
+        .flat_map(|func| {
+            let mut warnings = func.verify_reachability();
+            warnings.extend(func.verify_termination());
+            warnings
+        })
+        .collect()
+}
+
+impl Function {
+    /// Report blocks that cannot be reached from the entry block
+    pub fn verify_reachability(&self) -> Vec<VerifyWarning> {
+        let reachable = cfg::reachable_blocks(self);
+        self.blocks
+            .iter()
+            .filter(|block| !reachable.contains(&block.id))
+            .map(|block| VerifyWarning::UnreachableBlock {
+                function: self.name.clone(),
+                block: block.label.clone(),
+            })
+            .collect()
+    }
+
+    /// Report blocks stuck in a side-effect-free infinite loop
+    pub fn verify_termination(&self) -> Vec<VerifyWarning> {
+        let looping = cfg::detect_trivial_infinite_loops(self);
+        self.blocks
+            .iter()
+            .filter(|block| looping.contains(&block.id))
+            .map(|block| VerifyWarning::TrivialInfiniteLoop {
+                function: self.name.clone(),
+                block: block.label.clone(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,208 @@
+// ===================================================================
+// FILE: visit.rs (tilt-ir crate)
+//
+// DESC: Visitor and rewriter traits for writing IR passes without
+//       re-matching every instruction variant by hand.
+// ===================================================================
+
+use crate::*;
+
+/// Read-only walk over a function's IR. Every method has a no-op default,
+/// so a pass only overrides the variants it cares about.
+pub trait InstructionVisitor {
+    /// Called before the instructions of each block
+    fn visit_block(&mut self, _block: &BasicBlock) {}
+
+    /// Called for every instruction; the default dispatches to the
+    /// per-variant methods below
+    fn visit_instruction(&mut self, instruction: &Instruction) {
+        walk_instruction(self, instruction);
+    }
+
+    /// Called for each block's terminator, after its instructions
+    fn visit_terminator(&mut self, _terminator: &Terminator) {}
+
+    fn visit_binary_op(
+        &mut self,
+        _dest: ValueId,
+        _op: BinaryOperator,
+        _ty: &Type,
+        _lhs: ValueId,
+        _rhs: ValueId,
+    ) {
+    }
+
+    fn visit_unary_op(
+        &mut self,
+        _dest: ValueId,
+        _op: UnaryOperator,
+        _ty: &Type,
+        _operand: ValueId,
+    ) {
+    }
+
+    /// Called for `Call`, `TryCall` and `CallVoid`; `dest` is `None` for the
+    /// last
+    fn visit_call(&mut self, _dest: Option<ValueId>, _function: &str, _args: &[ValueId]) {}
+
+    fn visit_load(&mut self, _dest: ValueId, _ty: &Type, _address: ValueId) {}
+
+    fn visit_store(&mut self, _address: ValueId, _value: ValueId, _ty: &Type) {}
+
+    fn visit_const(&mut self, _dest: ValueId, _value: i64, _ty: &Type) {}
+
+    fn visit_ptr_add(&mut self, _dest: ValueId, _ptr: ValueId, _offset: ValueId) {}
+
+    fn visit_size_of(&mut self, _dest: ValueId, _ty: &Type) {}
+
+    fn visit_data_addr(&mut self, _dest: ValueId, _data: DataId) {}
+
+    fn visit_alloc(&mut self, _dest: ValueId, _size: ValueId) {}
+
+    fn visit_free(&mut self, _ptr: ValueId) {}
+
+    /// Called for both `Convert` and `ConvertChecked`
+    fn visit_convert(
+        &mut self,
+        _dest: ValueId,
+        _src: ValueId,
+        _from_ty: &Type,
+        _to_ty: &Type,
+        _checked: bool,
+    ) {
+    }
+
+    fn visit_assert_type(&mut self, _value: ValueId, _expected: &Type) {}
+
+    fn visit_phi(&mut self, _dest: ValueId, _ty: &Type, _incomings: &[(BlockId, ValueId)]) {}
+}
+
+/// Dispatch one instruction to the matching per-variant visitor method
+pub fn walk_instruction<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    instruction: &Instruction,
+) {
+    match instruction {
+        Instruction::BinaryOp {
+            dest,
+            op,
+            ty,
+            lhs,
+            rhs,
+        } => visitor.visit_binary_op(*dest, *op, ty, *lhs, *rhs),
+        Instruction::UnaryOp {
+            dest,
+            op,
+            ty,
+            operand,
+        } => visitor.visit_unary_op(*dest, *op, ty, *operand),
+        Instruction::Call {
+            dest,
+            function,
+            args,
+            ..
+        }
+        | Instruction::TryCall {
+            dest,
+            function,
+            args,
+            ..
+        } => visitor.visit_call(Some(*dest), function, args),
+        Instruction::CallVoid { function, args } => visitor.visit_call(None, function, args),
+        Instruction::Load { dest, ty, address } => visitor.visit_load(*dest, ty, *address),
+        Instruction::Store { address, value, ty } => visitor.visit_store(*address, *value, ty),
+        Instruction::Const { dest, value, ty } => visitor.visit_const(*dest, *value, ty),
+        Instruction::PtrAdd { dest, ptr, offset } => visitor.visit_ptr_add(*dest, *ptr, *offset),
+        Instruction::SizeOf { dest, ty } => visitor.visit_size_of(*dest, ty),
+        Instruction::DataAddr { dest, data } => visitor.visit_data_addr(*dest, *data),
+        Instruction::Alloc { dest, size } => visitor.visit_alloc(*dest, *size),
+        Instruction::Free { ptr } => visitor.visit_free(*ptr),
+        Instruction::Convert {
+            dest,
+            src,
+            from_ty,
+            to_ty,
+        } => visitor.visit_convert(*dest, *src, from_ty, to_ty, false),
+        Instruction::ConvertChecked {
+            dest,
+            src,
+            from_ty,
+            to_ty,
+        } => visitor.visit_convert(*dest, *src, from_ty, to_ty, true),
+        Instruction::AssertType { value, expected } => visitor.visit_assert_type(*value, expected),
+        Instruction::Phi {
+            dest,
+            ty,
+            incomings,
+        } => visitor.visit_phi(*dest, ty, incomings),
+    }
+}
+
+/// Visit every block, instruction and terminator of a function in order
+pub fn walk_function<V: InstructionVisitor + ?Sized>(func: &Function, visitor: &mut V) {
+    for block in &func.blocks {
+        visitor.visit_block(block);
+        for instruction in &block.instructions {
+            visitor.visit_instruction(instruction);
+        }
+        visitor.visit_terminator(&block.terminator);
+    }
+}
+
+/// Visit every function of a program in order
+pub fn walk_program<V: InstructionVisitor + ?Sized>(program: &Program, visitor: &mut V) {
+    for func in &program.functions {
+        walk_function(func, visitor);
+    }
+}
+
+/// In-place rewrite of a function's IR. Returning `None` keeps the original;
+/// returning `Some` replaces it, so an empty vector deletes an instruction
+/// and several instructions expand it.
+pub trait InstructionRewriter {
+    fn rewrite_instruction(
+        &mut self,
+        _block: BlockId,
+        _instruction: &Instruction,
+    ) -> Option<Vec<Instruction>> {
+        None
+    }
+
+    fn rewrite_terminator(
+        &mut self,
+        _block: BlockId,
+        _terminator: &Terminator,
+    ) -> Option<Terminator> {
+        None
+    }
+}
+
+/// Apply a rewriter to every instruction and terminator of a function.
+/// Returns whether anything was replaced.
+pub fn rewrite_function<R: InstructionRewriter + ?Sized>(
+    func: &mut Function,
+    rewriter: &mut R,
+) -> bool {
+    let mut changed = false;
+
+    for block in &mut func.blocks {
+        let mut instructions = Vec::with_capacity(block.instructions.len());
+        for instruction in std::mem::take(&mut block.instructions) {
+            match rewriter.rewrite_instruction(block.id, &instruction) {
+                Some(replacement) => {
+                    changed = true;
+                    instructions.extend(replacement);
+                }
+                None => instructions.push(instruction),
+            }
+        }
+        block.instructions = instructions;
+
+        if let Some(terminator) = rewriter.rewrite_terminator(block.id, &block.terminator) {
+            changed = true;
+            block.terminator = terminator;
+        }
+    }
+
+    changed
+}
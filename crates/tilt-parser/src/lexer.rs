@@ -27,6 +27,10 @@ pub enum Token<'a> {
     Phi,
     #[token("call")]
     Call,
+    #[token("try_call")]
+    TryCall,
+    #[token("as")]
+    As,
 
     // Types
     #[token("i32")]
@@ -41,6 +45,8 @@ pub enum Token<'a> {
     TUsize,
     #[token("void")]
     TVoid,
+    #[token("ptr")]
+    TPtr,
 
     // Punctuation
     #[token("{")]
@@ -63,15 +69,91 @@ pub enum Token<'a> {
     Comma,
     #[token("->")]
     Arrow,
+    #[token("-")]
+    Minus,
+    #[token("<")]
+    LAngle,
+    #[token(">")]
+    RAngle,
 
     // Literals and Identifiers
     #[regex(r#""([^"\\]|\\.)*""#, |lex| &lex.slice()[1..lex.slice().len()-1])]
     String(&'a str),
 
-    #[regex("-?[0-9]+", |lex| lex.slice())]
+    // Digits only; a leading `-` is lexed as `Minus` and handled by the grammar
+    #[regex("[0-9]+", |lex| lex.slice())]
     Number(&'a str),
 
+    // Digits with a fractional part, such as `3.14`; also unsigned
+    #[regex(r"[0-9]+\.[0-9]+", |lex| lex.slice())]
+    Float(&'a str),
+
     // An identifier or an operation code like `i32.add`
     #[regex("[a-zA-Z_.][a-zA-Z0-9_.]*", |lex| lex.slice())]
     Identifier(&'a str),
 }
+
+/// Input the lexer could not turn into a token
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    /// Byte span of the offending input
+    pub span: std::ops::Range<usize>,
+    /// 1-based line of the start of the span
+    pub line: usize,
+    /// 1-based column (in characters) of the start of the span
+    pub column: usize,
+    /// The offending input
+    pub text: String,
+}
+
+impl LexError {
+    /// Describe the input at `span` in `source`
+    pub fn new(source: &str, span: std::ops::Range<usize>) -> Self {
+        let (line, column) = line_col(source, span.start);
+        Self {
+            text: source[span.clone()].to_string(),
+            span,
+            line,
+            column,
+        }
+    }
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.text.chars().count() == 1 {
+            "character"
+        } else {
+            "input"
+        };
+        write!(
+            f,
+            "unexpected {} '{}' at line {}, col {}",
+            kind, self.text, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Convert a byte offset into a 1-based (line, column) pair
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// Tokenize source into the `(start, token, end)` triples the parser expects
+pub fn tokenize(source: &str) -> Result<Vec<(usize, Token<'_>, usize)>, LexError> {
+    let mut lexer = Token::lexer(source);
+    let mut tokens = Vec::new();
+
+    while let Some(token) = lexer.next() {
+        let span = lexer.span();
+        let token = token.map_err(|_| LexError::new(source, span.clone()))?;
+        tokens.push((span.start, token, span.end));
+    }
+    Ok(tokens)
+}
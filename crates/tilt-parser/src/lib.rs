@@ -13,6 +13,6 @@ use lalrpop_util::lalrpop_mod;
 lalrpop_mod!(pub tilt); // synthesizes the `tilt` module
 
 // Re-export for convenience
-pub use lexer::Token;
+pub use lexer::{LexError, Token};
 pub use tilt::*;
 pub use tilt_ast::*;
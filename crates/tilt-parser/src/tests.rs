@@ -7,6 +7,7 @@
 // ===================================================================
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::lexer::Token;
     use crate::tilt;
@@ -14,12 +15,12 @@ mod tests {
     use tilt_ast::*;
 
     // Helper function to tokenize input
-    fn tokenize(input: &str) -> Vec<Token> {
+    fn tokenize(input: &str) -> Vec<Token<'_>> {
         Token::lexer(input).collect::<Result<Vec<_>, _>>().unwrap()
     }
 
     // Helper function to tokenize input and create triples
-    fn tokenize_with_positions(input: &str) -> Vec<(usize, Token, usize)> {
+    fn tokenize_with_positions(input: &str) -> Vec<(usize, Token<'_>, usize)> {
         let mut lexer = Token::lexer(input);
         let mut tokens = Vec::new();
 
@@ -32,48 +33,38 @@ mod tests {
     }
 
     // Helper function to parse with error handling
-    fn parse_program(input: &str) -> Result<Program, String> {
+    fn parse_program(input: &str) -> Result<Program<'_>, String> {
         let tokens = tokenize_with_positions(input);
         let parser = tilt::ProgramParser::new();
-        parser
-            .parse(tokens.into_iter())
-            .map_err(|e| format!("{:?}", e))
+        parser.parse(tokens).map_err(|e| format!("{:?}", e))
     }
 
     // Helper function to parse expressions
-    fn parse_expression(input: &str) -> Result<Expression, String> {
+    fn parse_expression(input: &str) -> Result<Expression<'_>, String> {
         let tokens = tokenize_with_positions(input);
         let parser = tilt::ExpressionParser::new();
-        parser
-            .parse(tokens.into_iter())
-            .map_err(|e| format!("{:?}", e))
+        parser.parse(tokens).map_err(|e| format!("{:?}", e))
     }
 
     // Helper function to parse instructions
-    fn parse_instruction(input: &str) -> Result<Instruction, String> {
+    fn parse_instruction(input: &str) -> Result<Instruction<'_>, String> {
         let tokens = tokenize_with_positions(input);
         let parser = tilt::InstructionParser::new();
-        parser
-            .parse(tokens.into_iter())
-            .map_err(|e| format!("{:?}", e))
+        parser.parse(tokens).map_err(|e| format!("{:?}", e))
     }
 
     // Helper function to parse terminators
-    fn parse_terminator(input: &str) -> Result<Terminator, String> {
+    fn parse_terminator(input: &str) -> Result<Terminator<'_>, String> {
         let tokens = tokenize_with_positions(input);
         let parser = tilt::TerminatorParser::new();
-        parser
-            .parse(tokens.into_iter())
-            .map_err(|e| format!("{:?}", e))
+        parser.parse(tokens).map_err(|e| format!("{:?}", e))
     }
 
     // Helper function to parse blocks
-    fn parse_block(input: &str) -> Result<Block, String> {
+    fn parse_block(input: &str) -> Result<Block<'_>, String> {
         let tokens = tokenize_with_positions(input);
         let parser = tilt::BlockParser::new();
-        parser
-            .parse(tokens.into_iter())
-            .map_err(|e| format!("{:?}", e))
+        parser.parse(tokens).map_err(|e| format!("{:?}", e))
     }
 
     // ===============================
@@ -82,7 +73,7 @@ mod tests {
 
     #[test]
     fn test_lexer_keywords() {
-        let input = "fn import ret br br_if phi call";
+        let input = "fn import ret br br_if phi call try_call as";
         let tokens = tokenize(input);
         assert_eq!(
             tokens,
@@ -94,6 +85,8 @@ mod tests {
                 Token::BrIf,
                 Token::Phi,
                 Token::Call,
+                Token::TryCall,
+                Token::As,
             ]
         );
     }
@@ -157,18 +150,39 @@ mod tests {
     fn test_lexer_numbers() {
         let input = "123 0 -456 999";
         let tokens = tokenize(input);
-        if let Token::Number(num) = &tokens[0] {
-            assert_eq!(*num, "123");
-        }
-        if let Token::Number(num) = &tokens[1] {
-            assert_eq!(*num, "0");
-        }
-        if let Token::Number(num) = &tokens[2] {
-            assert_eq!(*num, "-456");
-        }
-        if let Token::Number(num) = &tokens[3] {
-            assert_eq!(*num, "999");
-        }
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Number("123"),
+                Token::Number("0"),
+                Token::Minus,
+                Token::Number("456"),
+                Token::Number("999"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_floats() {
+        assert_eq!(
+            tokenize("3.14 -0.5 7"),
+            vec![
+                Token::Float("3.14"),
+                Token::Minus,
+                Token::Float("0.5"),
+                Token::Number("7"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_minus_is_separate_from_number() {
+        assert_eq!(tokenize("-5"), vec![Token::Minus, Token::Number("5")]);
+        assert_eq!(
+            tokenize("a-b"),
+            vec![Token::Identifier("a"), Token::Minus, Token::Identifier("b")]
+        );
+        assert_eq!(tokenize("->"), vec![Token::Arrow]);
     }
 
     #[test]
@@ -190,6 +204,20 @@ mod tests {
         assert_eq!(tokens, vec![Token::Fn, Token::Identifier("my_func"),]);
     }
 
+    #[test]
+    fn test_lexer_error_position() {
+        let source = "fn main() -> i32 {\nentry:\n    x:i32 @ 1\n}";
+        let err = crate::lexer::tokenize(source).unwrap_err();
+
+        assert_eq!(err.line, 3);
+        assert_eq!(err.column, 11);
+        assert_eq!(&source[err.span.clone()], "@");
+        assert_eq!(
+            err.to_string(),
+            "unexpected character '@' at line 3, col 11"
+        );
+    }
+
     #[test]
     fn test_lexer_whitespace() {
         let input = "fn\n\t  my_func\r\n";
@@ -229,6 +257,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_pointer_types() {
+        let parse = |input| {
+            let tokens = tokenize_with_positions(input);
+            tilt::TypeParser::new().parse(tokens).unwrap()
+        };
+
+        assert_eq!(parse("ptr<i32>"), Type::ptr(Type::I32));
+        assert_eq!(parse("ptr<ptr<f64>>"), Type::ptr(Type::ptr(Type::F64)));
+        assert_eq!(Type::ptr(Type::I32).to_string(), "ptr<i32>");
+        assert_eq!(Type::ptr(Type::I32).pointee(), Some(&Type::I32));
+        assert_ne!(Type::ptr(Type::I32), Type::Usize);
+    }
+
     // ===============================
     // VALUE PARSING TESTS
     // ===============================
@@ -237,7 +279,7 @@ mod tests {
     fn test_parse_value_constant() {
         let parser = tilt::ValueParser::new();
         let tokens = vec![(0, Token::Number("42"), 2)];
-        let result = parser.parse(tokens.into_iter()).unwrap();
+        let result = parser.parse(tokens).unwrap();
         assert_eq!(result, Value::Constant(42));
     }
 
@@ -245,18 +287,44 @@ mod tests {
     fn test_parse_value_variable() {
         let parser = tilt::ValueParser::new();
         let tokens = vec![(0, Token::Identifier("my_var"), 6)];
-        let result = parser.parse(tokens.into_iter()).unwrap();
+        let result = parser.parse(tokens).unwrap();
         assert_eq!(result, Value::Variable("my_var"));
     }
 
     #[test]
     fn test_parse_value_negative_constant() {
         let parser = tilt::ValueParser::new();
-        let tokens = vec![(0, Token::Number("-123"), 4)];
-        let result = parser.parse(tokens.into_iter()).unwrap();
+        let result = parser.parse(tokenize_with_positions("-123")).unwrap();
         assert_eq!(result, Value::Constant(-123));
     }
 
+    #[test]
+    fn test_parse_value_integer_range() {
+        let parser = tilt::ValueParser::new();
+        let min = parser.parse(tokenize_with_positions("-9223372036854775808"));
+        assert_eq!(min.unwrap(), Value::Constant(i64::MIN));
+        assert!(parser
+            .parse(tokenize_with_positions("9223372036854775808"))
+            .is_err());
+        assert!(parser
+            .parse(tokenize_with_positions("-9223372036854775809"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_value_float() {
+        let parser = tilt::ValueParser::new();
+        let result = parser.parse(tokenize_with_positions("-2.5")).unwrap();
+        assert_eq!(result, Value::Float(-2.5));
+    }
+
+    #[test]
+    fn test_parse_value_unary_minus() {
+        let parser = tilt::ValueParser::new();
+        let result = parser.parse(tokenize_with_positions("-5")).unwrap();
+        assert_eq!(result, Value::Constant(-5));
+    }
+
     // ===============================
     // EXPRESSION PARSING TESTS
     // ===============================
@@ -285,6 +353,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_try_call() {
+        let result = parse_expression("try_call file_open(path)").unwrap();
+        assert_eq!(
+            result,
+            Expression::TryCall {
+                name: "file_open",
+                args: vec![Value::Variable("path")]
+            }
+        );
+        assert_eq!(
+            parse_expression("try_call tick()").unwrap(),
+            Expression::TryCall {
+                name: "tick",
+                args: vec![]
+            }
+        );
+    }
+
     #[test]
     fn test_parse_call_two_args() {
         let result = parse_expression("call my_func(x, 42)").unwrap();
@@ -297,6 +384,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_negative_constant_argument() {
+        let result = parse_expression("i32.add(x, -7)").unwrap();
+        assert_eq!(
+            result,
+            Expression::Operation {
+                op: "i32.add",
+                args: vec![Value::Variable("x"), Value::Constant(-7)]
+            }
+        );
+
+        let result = parse_expression("i32.const(-5)").unwrap();
+        assert_eq!(
+            result,
+            Expression::Operation {
+                op: "i32.const",
+                args: vec![Value::Constant(-5)]
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_phi() {
+        let result = parse_expression("phi [entry: v_init], [loop: v_next]").unwrap();
+        assert_eq!(
+            result,
+            Expression::Phi {
+                nodes: vec![
+                    ("entry", Value::Variable("v_init")),
+                    ("loop", Value::Variable("v_next")),
+                ]
+            }
+        );
+
+        let result = parse_expression("phi [start: 0]").unwrap();
+        assert_eq!(
+            result,
+            Expression::Phi {
+                nodes: vec![("start", Value::Constant(0))]
+            }
+        );
+
+        assert!(parse_expression("phi").is_err());
+        assert!(parse_expression("phi [entry v_init]").is_err());
+    }
+
     // ===============================
     // INSTRUCTION PARSING TESTS
     // ===============================
@@ -389,6 +522,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_terminator_br_with_args() {
+        let result = parse_terminator("br loop(i0, 0)").unwrap();
+        assert_eq!(
+            result,
+            Terminator::Br {
+                label: "loop",
+                args: vec![Value::Variable("i0"), Value::Constant(0)]
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_terminator_br_if_with_args() {
+        let result = parse_terminator("br_if more, loop(next, acc), exit(acc)").unwrap();
+        assert_eq!(
+            result,
+            Terminator::BrIf {
+                cond: Value::Variable("more"),
+                true_label: "loop",
+                true_args: vec![Value::Variable("next"), Value::Variable("acc")],
+                false_label: "exit",
+                false_args: vec![Value::Variable("acc")],
+            }
+        );
+    }
+
     // ===============================
     // BLOCK PARSING TESTS
     // ===============================
@@ -407,6 +567,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_block_with_params() {
+        let input = r#"
+        loop(i:i32, acc:i32):
+            next:i32 = i32.add(i, 1)
+            br loop(next, acc)
+        "#;
+        let result = parse_block(input).unwrap();
+
+        assert_eq!(result.label, "loop");
+        assert_eq!(
+            result.params,
+            vec![
+                TypedIdentifier {
+                    name: "i",
+                    ty: Type::I32
+                },
+                TypedIdentifier {
+                    name: "acc",
+                    ty: Type::I32
+                },
+            ]
+        );
+        assert_eq!(result.instructions.len(), 1);
+        assert_eq!(
+            result.terminator,
+            Terminator::Br {
+                label: "loop",
+                args: vec![Value::Variable("next"), Value::Variable("acc")]
+            }
+        );
+    }
+
     #[test]
     fn test_parse_block_with_instructions() {
         let input = r#"
@@ -444,6 +637,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_block_with_phi() {
+        let input = r#"
+        loop:
+            i:i32 = phi [entry: zero], [body: next]
+            done:i32 = i32.eq(i, n)
+            br_if done, exit, body
+        "#;
+        let result = parse_block(input).unwrap();
+
+        assert_eq!(result.instructions.len(), 2);
+        assert_eq!(
+            result.instructions[0],
+            Instruction::Assign {
+                dest: TypedIdentifier {
+                    name: "i",
+                    ty: Type::I32
+                },
+                expr: Expression::Phi {
+                    nodes: vec![
+                        ("entry", Value::Variable("zero")),
+                        ("body", Value::Variable("next")),
+                    ]
+                }
+            }
+        );
+    }
+
     // ===============================
     // IMPORT DECLARATION TESTS
     // ===============================
@@ -452,7 +673,7 @@ mod tests {
     fn test_parse_import_decl() {
         let parser = tilt::ImportDeclParser::new();
         let tokens = tokenize_with_positions(r#"import "stdlib" "print" -> void"#);
-        let result = parser.parse(tokens.into_iter()).unwrap();
+        let result = parser.parse(tokens).unwrap();
 
         assert_eq!(
             result,
@@ -460,6 +681,7 @@ mod tests {
                 module: "stdlib",
                 name: "print",
                 calling_convention: None,
+                alias: None,
                 params: vec![],
                 return_type: Type::Void
             }
@@ -470,7 +692,7 @@ mod tests {
     fn test_parse_import_decl_with_return_type() {
         let parser = tilt::ImportDeclParser::new();
         let tokens = tokenize_with_positions(r#"import "math" "sqrt" -> f64"#);
-        let result = parser.parse(tokens.into_iter()).unwrap();
+        let result = parser.parse(tokens).unwrap();
 
         assert_eq!(
             result,
@@ -478,12 +700,70 @@ mod tests {
                 module: "math",
                 name: "sqrt",
                 calling_convention: None,
+                alias: None,
                 params: vec![],
                 return_type: Type::F64
             }
         );
     }
 
+    #[test]
+    fn test_parse_import_decl_with_typed_params() {
+        let parser = tilt::ImportDeclParser::new();
+        let tokens = tokenize_with_positions(r#"import "env" "add" (a:i32, b:i32) -> i32"#);
+        let result = parser.parse(tokens).unwrap();
+
+        assert_eq!(
+            result,
+            ImportDecl {
+                module: "env",
+                name: "add",
+                calling_convention: None,
+                alias: None,
+                params: vec![
+                    TypedIdentifier {
+                        name: "a",
+                        ty: Type::I32
+                    },
+                    TypedIdentifier {
+                        name: "b",
+                        ty: Type::I32
+                    },
+                ],
+                return_type: Type::I32
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_import_decl_with_empty_params() {
+        let parser = tilt::ImportDeclParser::new();
+        let tokens = tokenize_with_positions(r#"import "env" "read_i32" () -> i32"#);
+        let result = parser.parse(tokens).unwrap();
+
+        assert_eq!(result.params, vec![]);
+        assert_eq!(result.return_type, Type::I32);
+    }
+
+    #[test]
+    fn test_parse_import_decl_with_alias() {
+        let parser = tilt::ImportDeclParser::new();
+        let tokens = tokenize_with_positions(
+            r#"import "libc" "write" (fd:i32, buf:usize, len:usize) -> i64 as sys_write"#,
+        );
+        let result = parser.parse(tokens).unwrap();
+
+        assert_eq!(result.name, "write");
+        assert_eq!(result.alias, Some("sys_write"));
+        assert_eq!(result.call_name(), "sys_write");
+        assert_eq!(result.params.len(), 3);
+        assert_eq!(result.return_type, Type::I64);
+
+        let tokens = tokenize_with_positions(r#"import "env" "tick" -> void as t"#);
+        let result = parser.parse(tokens).unwrap();
+        assert_eq!(result.alias, Some("t"));
+    }
+
     // ===============================
     // FUNCTION DEFINITION TESTS
     // ===============================
@@ -492,7 +772,7 @@ mod tests {
     fn test_parse_function_empty() {
         let parser = tilt::FunctionDefParser::new();
         let tokens = tokenize_with_positions("fn main() -> void { entry: ret }");
-        let result = parser.parse(tokens.into_iter()).unwrap();
+        let result = parser.parse(tokens).unwrap();
 
         assert_eq!(
             result,
@@ -528,7 +808,7 @@ mod tests {
 
         let parser = tilt::FunctionDefParser::new();
         let tokens = tokenize_with_positions(input);
-        let result = parser.parse(tokens.into_iter()).unwrap();
+        let result = parser.parse(tokens).unwrap();
 
         assert_eq!(result.name, "test_func");
         assert_eq!(result.return_type, Type::I32);
@@ -751,12 +1031,14 @@ mod tests {
         // This would require extending the grammar to support nested expressions
         // For now, test that our current grammar handles single-level calls
         let result = parse_instruction("result:i32 = call outer(inner_result)").unwrap();
-        if let Instruction::Assign { expr, .. } = result {
-            if let Expression::Call { name, args } = expr {
-                assert_eq!(name, "outer");
-                assert_eq!(args.len(), 1);
-                assert_eq!(args[0], Value::Variable("inner_result"));
-            }
+        if let Instruction::Assign {
+            expr: Expression::Call { name, args },
+            ..
+        } = result
+        {
+            assert_eq!(name, "outer");
+            assert_eq!(args.len(), 1);
+            assert_eq!(args[0], Value::Variable("inner_result"));
         }
     }
 
@@ -7,8 +7,9 @@
 // ===================================================================
 
 use std::collections::HashMap;
+use std::sync::Arc;
 use tilt_ast::Type;
-use tilt_host_abi::{HostABI, RuntimeValue};
+use tilt_host_abi::{HOST_ERROR_RESULT, HostABI, RuntimeValue};
 use tilt_ir::*;
 
 /// Error types that can occur during VM execution
@@ -16,6 +17,8 @@ use tilt_ir::*;
 pub enum VMError {
     /// Function not found
     FunctionNotFound(String),
+    /// The program imports this function but the host ABI does not provide it
+    UnresolvedImport(String),
     /// Block not found
     BlockNotFound(BlockId),
     /// Value not found in the current scope
@@ -34,12 +37,33 @@ pub enum VMError {
     StackOverflow,
     /// Invalid instruction
     InvalidInstruction(String),
+    /// A checked conversion got a value that does not fit the target type
+    ConversionOverflow { value: RuntimeValue, to: Type },
+    /// A load or store went through a null pointer, such as the result of
+    /// `alloc(0)` or of a failed allocation
+    NullPointerDereference(String),
+    /// Pointer arithmetic the host ABI rejected, such as leaving an
+    /// allocation under `MemoryHostABI`'s strict pointer mode
+    PointerOutOfBounds(String),
+    /// An import's declared signature differs from the host function's
+    ImportSignatureMismatch {
+        name: String,
+        declared: (Vec<Type>, Type),
+        host: (Vec<Type>, Type),
+    },
 }
 
 impl std::fmt::Display for VMError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             VMError::FunctionNotFound(name) => write!(f, "Function not found: {}", name),
+            VMError::UnresolvedImport(name) => {
+                write!(
+                    f,
+                    "Imported function '{}' is not provided by the host",
+                    name
+                )
+            }
             VMError::BlockNotFound(id) => write!(f, "Block not found: {:?}", id),
             VMError::ValueNotFound(id) => write!(f, "Value not found: {:?}", id),
             VMError::TypeMismatch {
@@ -57,6 +81,30 @@ impl std::fmt::Display for VMError {
             VMError::HostCallError(msg) => write!(f, "Host call error: {}", msg),
             VMError::StackOverflow => write!(f, "Stack overflow"),
             VMError::InvalidInstruction(msg) => write!(f, "Invalid instruction: {}", msg),
+            VMError::ConversionOverflow { value, to } => {
+                write!(
+                    f,
+                    "Conversion overflow: {:?} does not fit in {:?}",
+                    value, to
+                )
+            }
+            VMError::NullPointerDereference(context) => {
+                write!(f, "Null pointer dereference in {}", context)
+            }
+            VMError::PointerOutOfBounds(msg) => write!(f, "Pointer out of bounds: {}", msg),
+            VMError::ImportSignatureMismatch {
+                name,
+                declared,
+                host,
+            } => {
+                write!(
+                    f,
+                    "Import '{}' is declared as {} but the host provides {}",
+                    name,
+                    format_signature(&declared.0, &declared.1),
+                    format_signature(&host.0, &host.1)
+                )
+            }
         }
     }
 }
@@ -100,27 +148,197 @@ impl StackFrame {
     }
 }
 
+/// Where a call to a name goes, resolved once when the VM is created
+#[derive(Debug, Clone, PartialEq)]
+enum CallTarget {
+    /// A function defined in the program, by index, run on the VM even if
+    /// the host has a function of the same name
+    Local(usize),
+    /// An import, called on the host under its symbol
+    Host(String),
+}
+
+/// A read-only data entry's copy in host memory, keyed by owning function
+/// and data ID, or by `Program::rodata` index for shared data
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum RodataKey {
+    Local(String, DataId),
+    Shared(usize),
+}
+
+/// Resolve every function and import the program can call by name, as
+/// `Program::callee` does
+fn resolve_call_targets(program: &Program) -> HashMap<String, CallTarget> {
+    let names = program
+        .functions
+        .iter()
+        .map(|f| f.name.as_str())
+        .chain(program.imports.iter().map(ImportDecl::call_name));
+    names
+        .filter_map(|name| {
+            let target = match program.callee_index(name)? {
+                CalleeIndex::Local(index) => CallTarget::Local(index),
+                CalleeIndex::Import(index) => CallTarget::Host(program.imports[index].name.clone()),
+            };
+            Some((name.to_string(), target))
+        })
+        .collect()
+}
+
+/// Width of `usize` and pointers as programs running in the VM see it,
+/// independent of the host the VM itself runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PointerWidth {
+    Bits32,
+    #[default]
+    Bits64,
+}
+
+impl PointerWidth {
+    /// Size of a `usize` in bytes, as `sizeof.usize` reports it
+    pub fn bytes(self) -> usize {
+        match self {
+            PointerWidth::Bits32 => 4,
+            PointerWidth::Bits64 => 8,
+        }
+    }
+
+    /// Wrap a two's complement integer to a `usize` of this width. usize
+    /// arithmetic goes through this too, so it wraps at the logical width.
+    fn wrap(self, value: i64) -> usize {
+        match self {
+            PointerWidth::Bits32 => value as u32 as usize,
+            PointerWidth::Bits64 => value as u64 as usize,
+        }
+    }
+}
+
+/// Execution counts for one function, collected while profiling is on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// Number of times the function was called
+    pub calls: u64,
+    /// Instructions and terminators executed in the function itself, not
+    /// counting the functions it calls
+    pub instructions: u64,
+}
+
 /// The TILT Virtual Machine
 pub struct VM<H: HostABI> {
-    /// The program being executed
-    program: Program,
+    /// The program being executed, shared with other VMs running it
+    program: Arc<Program>,
     /// Call stack
     call_stack: Vec<StackFrame>,
     /// Host ABI implementation
     host_abi: H,
     /// Maximum call stack depth (to prevent infinite recursion)
     max_stack_depth: usize,
+    /// Host memory addresses of read-only data already placed
+    rodata_addrs: HashMap<RodataKey, usize>,
+    /// Logical `usize` width used by `SizeOf` and conversions
+    pointer_width: PointerWidth,
+    /// Per-function execution counts, or `None` when profiling is off
+    profile: Option<HashMap<String, FunctionProfile>>,
+    /// Call targets by name; a call to any other name fails
+    call_targets: HashMap<String, CallTarget>,
 }
 
 impl<H: HostABI> VM<H> {
-    /// Create a new VM with the given program and host ABI
-    pub fn new(program: Program, host_abi: H) -> Self {
+    /// Create a new VM with the given program and host ABI.
+    /// Pass an `Arc<Program>` to share one program between several VMs without copying it.
+    pub fn new(program: impl Into<Arc<Program>>, host_abi: H) -> Self {
+        let program = program.into();
+        let call_targets = resolve_call_targets(&program);
         Self {
             program,
             call_stack: Vec::new(),
             host_abi,
             max_stack_depth: 1000, // Reasonable default
+            rodata_addrs: HashMap::new(),
+            pointer_width: PointerWidth::default(),
+            profile: None,
+            call_targets,
+        }
+    }
+
+    /// Get the host ABI, e.g. to inspect its state between calls
+    pub fn host_abi(&self) -> &H {
+        &self.host_abi
+    }
+
+    /// Get mutable access to the host ABI
+    pub fn host_abi_mut(&mut self) -> &mut H {
+        &mut self.host_abi
+    }
+
+    /// Reset the host ABI for a fresh run. Read-only data placed in host
+    /// memory is forgotten as well, so it is copied in again when next used
+    /// rather than read from memory the reset released.
+    pub fn reset_host(&mut self) {
+        self.host_abi.reset();
+        self.rodata_addrs.clear();
+    }
+
+    /// Consume the VM and hand back its host ABI, including any memory the
+    /// program left behind
+    pub fn into_host_abi(self) -> H {
+        self.host_abi
+    }
+
+    /// Set the logical pointer width programs see (64-bit by default), so
+    /// `sizeof.usize` and usize conversions don't depend on the host
+    pub fn set_pointer_width(&mut self, width: PointerWidth) {
+        self.pointer_width = width;
+    }
+
+    /// Get the logical pointer width programs see
+    pub fn pointer_width(&self) -> PointerWidth {
+        self.pointer_width
+    }
+
+    /// Turn profiling on or off. Turning it on starts from empty counts.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(HashMap::new);
+    }
+
+    /// Get the counts collected since profiling was turned on, costliest
+    /// function first. Empty when profiling is off.
+    pub fn profile(&self) -> Vec<(&str, FunctionProfile)> {
+        let mut profile: Vec<(&str, FunctionProfile)> = self
+            .profile
+            .iter()
+            .flatten()
+            .map(|(name, counts)| (name.as_str(), *counts))
+            .collect();
+        profile.sort_by(|(a_name, a), (b_name, b)| {
+            b.instructions
+                .cmp(&a.instructions)
+                .then_with(|| a_name.cmp(b_name))
+        });
+        profile
+    }
+
+    /// Check every import against the signature the host ABI declares for
+    /// it, so a mismatch fails before execution instead of at the first
+    /// call. Pointer types match `usize`. Imports the host doesn't describe
+    /// or doesn't provide are skipped; calling the latter still fails with
+    /// `UnresolvedImport`.
+    pub fn validate_imports(&self) -> VMResult<()> {
+        for import in &self.program.imports {
+            let Some((params, return_type)) = self.host_abi.function_signature(&import.name) else {
+                continue;
+            };
+
+            if !import.matches_signature(&params, &return_type) {
+                return Err(VMError::ImportSignatureMismatch {
+                    name: import.name.clone(),
+                    declared: (import.params.clone(), import.return_type.clone()),
+                    host: (params, return_type),
+                });
+            }
         }
+
+        Ok(())
     }
 
     /// Set the maximum call stack depth
@@ -128,15 +346,39 @@ impl<H: HostABI> VM<H> {
         self.max_stack_depth = depth;
     }
 
+    /// Build the runtime value of an IR constant, reinterpreting the stored
+    /// bit pattern as the constant's type. A pattern with bits beyond the
+    /// type's width, at this VM's pointer width for `usize`, is rejected
+    /// rather than truncated, as the JIT does.
+    fn constant_value(&self, value: i64, ty: &Type) -> VMResult<RuntimeValue> {
+        if !constant_fits_pointer_width(value, ty, self.pointer_width.bytes()) {
+            return Err(VMError::InvalidInstruction(format!(
+                "Constant {} does not fit in {}",
+                value, ty
+            )));
+        }
+        Ok(match ty {
+            Type::I32 => RuntimeValue::I32(value as i32),
+            Type::I64 => RuntimeValue::I64(value),
+            Type::Usize | Type::Ptr(_) => RuntimeValue::Usize(self.pointer_width.wrap(value)),
+            Type::F32 => RuntimeValue::F32(f32::from_bits(value as u32)),
+            Type::F64 => RuntimeValue::F64(f64::from_bits(value as u64)),
+            Type::Void => RuntimeValue::Void,
+        })
+    }
+
     /// Execute a function by name with the given arguments
     pub fn call_function(&mut self, name: &str, args: Vec<RuntimeValue>) -> VMResult<RuntimeValue> {
-        // Find the function
-        let function = self
-            .program
-            .functions
-            .iter()
-            .find(|f| f.name == name)
-            .ok_or_else(|| VMError::FunctionNotFound(name.to_string()))?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("vm_call_function", name).entered();
+
+        // Find the function. Host functions were tried first, so an import
+        // that reaches here is one the host ABI doesn't provide.
+        let function = match self.call_targets.get(name) {
+            Some(CallTarget::Local(index)) => &self.program.functions[*index],
+            Some(CallTarget::Host(_)) => return Err(VMError::UnresolvedImport(name.to_string())),
+            None => return Err(VMError::FunctionNotFound(name.to_string())),
+        };
 
         // Check argument count
         if args.len() != function.params.len() {
@@ -156,7 +398,7 @@ impl<H: HostABI> VM<H> {
         // Get the entry block (first block)
         let entry_block = function
             .blocks
-            .get(0)
+            .first()
             .map(|b| b.id)
             .ok_or_else(|| VMError::InvalidInstruction("Function has no blocks".to_string()))?;
 
@@ -167,32 +409,31 @@ impl<H: HostABI> VM<H> {
         // Function parameters become ValueId(0), ValueId(1), etc.
         for (i, (arg_value, param_type)) in args.iter().zip(function.params.iter()).enumerate() {
             // Type check
-            if arg_value.get_type() != *param_type {
+            if arg_value.get_type() != param_type.repr() {
                 return Err(VMError::TypeMismatch {
-                    expected: *param_type,
+                    expected: param_type.clone(),
                     actual: arg_value.get_type(),
                     context: format!("function parameter {} in function '{}'", i, name),
                 });
             }
+            // A usize argument wider than the VM's pointers wraps, like
+            // usize arithmetic does
+            let arg_value = match arg_value {
+                RuntimeValue::Usize(v) => RuntimeValue::Usize(self.pointer_width.wrap(*v as i64)),
+                other => other.clone(),
+            };
             // Parameters start from ValueId(0)
             let param_id = ValueId(i);
-            frame.set_value(param_id, arg_value.clone());
+            frame.set_value(param_id, arg_value);
         }
 
         // Set up constants
         for (value_id, (const_value, const_type)) in &function.constants {
-            let runtime_value = match const_type {
-                Type::I32 => RuntimeValue::I32(*const_value as i32),
-                Type::I64 => RuntimeValue::I64(*const_value),
-                Type::Usize => RuntimeValue::Usize((*const_value as u64).try_into().unwrap()),
-                Type::Void => RuntimeValue::Void,
-                Type::F32 | Type::F64 => {
-                    return Err(VMError::InvalidInstruction(
-                        "Float types not yet supported".to_string(),
-                    ));
-                }
-            };
-            frame.set_value(*value_id, runtime_value);
+            frame.set_value(*value_id, self.constant_value(*const_value, const_type)?);
+        }
+
+        if let Some(profile) = &mut self.profile {
+            profile.entry(name.to_string()).or_default().calls += 1;
         }
 
         // Push the frame and execute
@@ -216,6 +457,13 @@ impl<H: HostABI> VM<H> {
                 )
             };
 
+            // Each pass through the loop runs one instruction or terminator
+            if let Some(profile) = &mut self.profile
+                && let Some(counts) = profile.get_mut(&function_name)
+            {
+                counts.instructions += 1;
+            }
+
             // Find the function and block
             let function = self
                 .program
@@ -242,47 +490,8 @@ impl<H: HostABI> VM<H> {
                         };
                     }
                     Terminator::Br { target, args } => {
-                        // Handle block arguments by setting up the parameters in the target block
                         let frame = self.call_stack.last_mut().unwrap();
-
-                        // If there are arguments, we need to pass them to the target block's parameters
-                        if !args.is_empty() {
-                            // Find the target block to get its parameters
-                            let target_block = function
-                                .blocks
-                                .iter()
-                                .find(|b| b.id == *target)
-                                .ok_or(VMError::BlockNotFound(*target))?;
-
-                            // Check that the number of arguments matches the number of parameters
-                            if args.len() != target_block.params.len() {
-                                return Err(VMError::InvalidInstruction(format!(
-                                    "Block parameter count mismatch: expected {}, got {}",
-                                    target_block.params.len(),
-                                    args.len()
-                                )));
-                            }
-
-                            // Get the argument values from the current frame
-                            let mut arg_values = Vec::new();
-                            for arg_id in args {
-                                arg_values.push(frame.get_value(*arg_id)?.clone());
-                            }
-
-                            // Set up the target block with the new parameter values
-                            frame.current_block = *target;
-                            frame.instruction_pointer = 0;
-
-                            // Map the block parameters to the argument values
-                            for (i, (param_id, _param_type)) in
-                                target_block.params.iter().enumerate()
-                            {
-                                frame.values.insert(*param_id, arg_values[i].clone());
-                            }
-                        } else {
-                            frame.current_block = *target;
-                            frame.instruction_pointer = 0;
-                        }
+                        enter_block(frame, function, *target, args)?;
                         continue;
                     }
                     Terminator::BrIf {
@@ -292,9 +501,10 @@ impl<H: HostABI> VM<H> {
                         false_target,
                         false_args,
                     } => {
-                        let frame = self.call_stack.last().unwrap();
+                        let frame = self.call_stack.last_mut().unwrap();
                         let cond_value = frame.get_value(*cond)?;
 
+                        // Nonzero is true, whatever the value (see `Terminator::BrIf`)
                         let is_true = match cond_value {
                             RuntimeValue::I32(val) => *val != 0,
                             RuntimeValue::I64(val) => *val != 0,
@@ -307,51 +517,37 @@ impl<H: HostABI> VM<H> {
                             }
                         };
 
-                        let (target_block_id, target_args) = if is_true {
-                            (*true_target, true_args)
+                        if is_true {
+                            enter_block(frame, function, *true_target, true_args)?;
                         } else {
-                            (*false_target, false_args)
-                        };
-
-                        // Handle block arguments similar to Br
+                            enter_block(frame, function, *false_target, false_args)?;
+                        }
+                        continue;
+                    }
+                    Terminator::Switch {
+                        value,
+                        cases,
+                        default,
+                        default_args,
+                    } => {
                         let frame = self.call_stack.last_mut().unwrap();
-
-                        if !target_args.is_empty() {
-                            // Find the target block to get its parameters
-                            let target_block = function
-                                .blocks
-                                .iter()
-                                .find(|b| b.id == target_block_id)
-                                .ok_or(VMError::BlockNotFound(target_block_id))?;
-
-                            // Check that the number of arguments matches the number of parameters
-                            if target_args.len() != target_block.params.len() {
-                                return Err(VMError::InvalidInstruction(format!(
-                                    "Block parameter count mismatch: expected {}, got {}",
-                                    target_block.params.len(),
-                                    target_args.len()
-                                )));
-                            }
-
-                            // Get the argument values from the current frame
-                            let mut arg_values = Vec::new();
-                            for arg_id in target_args {
-                                arg_values.push(frame.get_value(*arg_id)?.clone());
+                        let key = match frame.get_value(*value)? {
+                            RuntimeValue::I32(val) => *val as i64,
+                            RuntimeValue::I64(val) => *val,
+                            RuntimeValue::Usize(val) => *val as i64,
+                            other => {
+                                return Err(VMError::TypeMismatch {
+                                    expected: Type::I32,
+                                    actual: other.get_type(),
+                                    context: "switch value".to_string(),
+                                });
                             }
+                        };
 
-                            // Set up the target block with the new parameter values
-                            frame.current_block = target_block_id;
-                            frame.instruction_pointer = 0;
-
-                            // Map the block parameters to the argument values
-                            for (i, (param_id, _param_type)) in
-                                target_block.params.iter().enumerate()
-                            {
-                                frame.values.insert(*param_id, arg_values[i].clone());
-                            }
-                        } else {
-                            frame.current_block = target_block_id;
-                            frame.instruction_pointer = 0;
+                        // Always a plain scan; only the JIT cares about density
+                        match cases.iter().find(|case| case.value == key) {
+                            Some(case) => enter_block(frame, function, case.target, &case.args)?,
+                            None => enter_block(frame, function, *default, default_args)?,
                         }
                         continue;
                     }
@@ -368,6 +564,99 @@ impl<H: HostABI> VM<H> {
         }
     }
 
+    /// Address of a function's read-only data entry. Each entry is copied
+    /// into host memory the first time it is used and stays there; shared
+    /// entries are copied once for all functions.
+    fn rodata_address(&mut self, function: &str, data: DataId) -> VMResult<usize> {
+        let not_found = || {
+            VMError::InvalidInstruction(format!(
+                "Read-only data d{} not found in function '{}'",
+                data.0, function
+            ))
+        };
+        let owner = self
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == function)
+            .ok_or_else(not_found)?;
+        let key = match owner.shared_rodata(data) {
+            Some(index) => RodataKey::Shared(index),
+            None => RodataKey::Local(function.to_string(), data),
+        };
+        if let Some(&addr) = self.rodata_addrs.get(&key) {
+            return Ok(addr);
+        }
+
+        let bytes = self.program.rodata(owner, data).ok_or_else(not_found)?;
+
+        let size = RuntimeValue::Usize(bytes.len().max(1));
+        let addr = self
+            .host_abi
+            .call_host_function("alloc", &[size])
+            .map_err(VMError::HostCallError)?
+            .as_ptr();
+        if addr == 0 {
+            return Err(VMError::HostCallError(
+                "alloc returned null for read-only data".to_string(),
+            ));
+        }
+        self.host_abi
+            .write_memory_bytes(addr, bytes)
+            .map_err(|e| VMError::InvalidInstruction(format!("Memory write error: {}", e)))?;
+        // The JIT emits read-only data into read-only memory, so stores to
+        // it must fail here too
+        self.host_abi.mark_read_only(addr, bytes.len());
+
+        let addr = addr as usize;
+        self.rodata_addrs.insert(key, addr);
+        Ok(addr)
+    }
+
+    /// Check host call arguments against the host's declared signature, so a
+    /// bad call fails with a `VMError` instead of panicking inside the host
+    fn check_host_call(&self, function: &str, args: &[RuntimeValue]) -> VMResult<()> {
+        let Some((param_types, _)) = self.host_abi.function_signature(function) else {
+            return Ok(());
+        };
+
+        if args.len() != param_types.len() {
+            return Err(VMError::InvalidInstruction(format!(
+                "Host function '{}' expects {} arguments, got {}",
+                function,
+                param_types.len(),
+                args.len()
+            )));
+        }
+
+        for (i, (arg, expected)) in args.iter().zip(&param_types).enumerate() {
+            if arg.get_type() != expected.repr() {
+                return Err(VMError::TypeMismatch {
+                    expected: expected.clone(),
+                    actual: arg.get_type(),
+                    context: format!("argument {} to host function '{}'", i + 1, function),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The value a `try_call` leaves in its destination when the host
+    /// function fails: `HOST_ERROR_RESULT` in the call's result type
+    fn host_error_value(&self, ty: Type) -> VMResult<RuntimeValue> {
+        let code = HOST_ERROR_RESULT as i64;
+        match ty {
+            Type::I32 => Ok(RuntimeValue::I32(HOST_ERROR_RESULT)),
+            Type::I64 => Ok(RuntimeValue::I64(code)),
+            Type::Usize => Ok(RuntimeValue::Usize(self.pointer_width.wrap(code))),
+            other => Err(VMError::InvalidInstruction(format!(
+                "try_call result of type {} can't hold an error code",
+                other
+            ))),
+        }
+    }
+
     /// Execute a single instruction
     fn execute_instruction(&mut self, instruction: &Instruction) -> VMResult<()> {
         match instruction {
@@ -375,22 +664,39 @@ impl<H: HostABI> VM<H> {
                 dest,
                 function,
                 args,
-                return_type: _,
+                return_type,
+            }
+            | Instruction::TryCall {
+                dest,
+                function,
+                args,
+                return_type,
             } => {
+                let recover = matches!(instruction, Instruction::TryCall { .. });
                 // Collect argument values
                 let frame = self.call_stack.last().unwrap();
                 let arg_values: Result<Vec<_>, _> = args
                     .iter()
-                    .map(|arg_id| frame.get_value(*arg_id).map(|v| v.clone()))
+                    .map(|arg_id| frame.get_value(*arg_id).cloned())
                     .collect();
                 let arg_values = arg_values?;
 
-                // Try host function first
-                if self.host_abi.has_function(function) {
-                    let result = self
-                        .host_abi
-                        .call_host_function(function, &arg_values)
-                        .map_err(VMError::HostCallError)?;
+                // Program functions run on the VM and imports on the host,
+                // under their symbol if aliased
+                let symbol = match self.call_targets.get(function) {
+                    Some(CallTarget::Local(_)) => None,
+                    Some(CallTarget::Host(symbol)) => Some(symbol.as_str()),
+                    None => return Err(VMError::FunctionNotFound(function.clone())),
+                };
+                if let Some(symbol) = symbol
+                    && self.host_abi.has_function(symbol)
+                {
+                    self.check_host_call(symbol, &arg_values)?;
+                    let result = match self.host_abi.call_host_function(symbol, &arg_values) {
+                        Ok(result) => result,
+                        Err(_) if recover => self.host_error_value(return_type.clone())?,
+                        Err(e) => return Err(VMError::HostCallError(e)),
+                    };
 
                     let frame = self.call_stack.last_mut().unwrap();
                     frame.set_value(*dest, result);
@@ -407,14 +713,23 @@ impl<H: HostABI> VM<H> {
                 let frame = self.call_stack.last().unwrap();
                 let arg_values: Result<Vec<_>, _> = args
                     .iter()
-                    .map(|arg_id| frame.get_value(*arg_id).map(|v| v.clone()))
+                    .map(|arg_id| frame.get_value(*arg_id).cloned())
                     .collect();
                 let arg_values = arg_values?;
 
-                // Try host function first
-                if self.host_abi.has_function(function) {
+                // Program functions run on the VM and imports on the host,
+                // under their symbol if aliased
+                let symbol = match self.call_targets.get(function) {
+                    Some(CallTarget::Local(_)) => None,
+                    Some(CallTarget::Host(symbol)) => Some(symbol.as_str()),
+                    None => return Err(VMError::FunctionNotFound(function.clone())),
+                };
+                if let Some(symbol) = symbol
+                    && self.host_abi.has_function(symbol)
+                {
+                    self.check_host_call(symbol, &arg_values)?;
                     self.host_abi
-                        .call_host_function(function, &arg_values)
+                        .call_host_function(symbol, &arg_values)
                         .map_err(VMError::HostCallError)?;
                 } else {
                     // Recursive call to TILT function
@@ -423,20 +738,9 @@ impl<H: HostABI> VM<H> {
             }
 
             Instruction::Const { dest, value, ty } => {
-                let runtime_value = match ty {
-                    Type::I32 => RuntimeValue::I32(*value as i32),
-                    Type::I64 => RuntimeValue::I64(*value),
-                    Type::Usize => RuntimeValue::Usize((*value as u64).try_into().unwrap()),
-                    Type::Void => RuntimeValue::Void,
-                    Type::F32 | Type::F64 => {
-                        return Err(VMError::InvalidInstruction(
-                            "Float types not yet supported".to_string(),
-                        ));
-                    }
-                };
-
+                let constant = self.constant_value(*value, ty)?;
                 let frame = self.call_stack.last_mut().unwrap();
-                frame.set_value(*dest, runtime_value);
+                frame.set_value(*dest, constant);
             }
 
             Instruction::BinaryOp {
@@ -455,8 +759,10 @@ impl<H: HostABI> VM<H> {
                         (RuntimeValue::I32(a), RuntimeValue::I32(b)) => RuntimeValue::I32(a + b),
                         (RuntimeValue::I64(a), RuntimeValue::I64(b)) => RuntimeValue::I64(a + b),
                         (RuntimeValue::Usize(a), RuntimeValue::Usize(b)) => {
-                            RuntimeValue::Usize(a.wrapping_add(*b))
+                            RuntimeValue::Usize(self.pointer_width.wrap(a.wrapping_add(*b) as i64))
                         }
+                        (RuntimeValue::F32(a), RuntimeValue::F32(b)) => RuntimeValue::F32(a + b),
+                        (RuntimeValue::F64(a), RuntimeValue::F64(b)) => RuntimeValue::F64(a + b),
                         _ => {
                             return Err(VMError::TypeMismatch {
                                 expected: lhs_val.get_type(),
@@ -473,8 +779,10 @@ impl<H: HostABI> VM<H> {
                         (RuntimeValue::I32(a), RuntimeValue::I32(b)) => RuntimeValue::I32(a - b),
                         (RuntimeValue::I64(a), RuntimeValue::I64(b)) => RuntimeValue::I64(a - b),
                         (RuntimeValue::Usize(a), RuntimeValue::Usize(b)) => {
-                            RuntimeValue::Usize(a.wrapping_sub(*b))
+                            RuntimeValue::Usize(self.pointer_width.wrap(a.wrapping_sub(*b) as i64))
                         }
+                        (RuntimeValue::F32(a), RuntimeValue::F32(b)) => RuntimeValue::F32(a - b),
+                        (RuntimeValue::F64(a), RuntimeValue::F64(b)) => RuntimeValue::F64(a - b),
                         _ => {
                             return Err(VMError::TypeMismatch {
                                 expected: lhs_val.get_type(),
@@ -491,8 +799,10 @@ impl<H: HostABI> VM<H> {
                         (RuntimeValue::I32(a), RuntimeValue::I32(b)) => RuntimeValue::I32(a * b),
                         (RuntimeValue::I64(a), RuntimeValue::I64(b)) => RuntimeValue::I64(a * b),
                         (RuntimeValue::Usize(a), RuntimeValue::Usize(b)) => {
-                            RuntimeValue::Usize(a.wrapping_mul(*b))
+                            RuntimeValue::Usize(self.pointer_width.wrap(a.wrapping_mul(*b) as i64))
                         }
+                        (RuntimeValue::F32(a), RuntimeValue::F32(b)) => RuntimeValue::F32(a * b),
+                        (RuntimeValue::F64(a), RuntimeValue::F64(b)) => RuntimeValue::F64(a * b),
                         _ => {
                             return Err(VMError::TypeMismatch {
                                 expected: lhs_val.get_type(),
@@ -524,6 +834,9 @@ impl<H: HostABI> VM<H> {
                             }
                             RuntimeValue::Usize(a / b)
                         }
+                        // Float division by zero gives an infinity or NaN
+                        (RuntimeValue::F32(a), RuntimeValue::F32(b)) => RuntimeValue::F32(a / b),
+                        (RuntimeValue::F64(a), RuntimeValue::F64(b)) => RuntimeValue::F64(a / b),
                         _ => {
                             return Err(VMError::TypeMismatch {
                                 expected: lhs_val.get_type(),
@@ -536,50 +849,128 @@ impl<H: HostABI> VM<H> {
                             });
                         }
                     },
-                    BinaryOperator::Eq => match (lhs_val, rhs_val) {
+                    // Signed remainders take the sign of the dividend, like
+                    // Rust's `%`; i32::MIN % -1 wraps to 0 instead of trapping
+                    BinaryOperator::Rem => match (lhs_val, rhs_val) {
                         (RuntimeValue::I32(a), RuntimeValue::I32(b)) => {
-                            RuntimeValue::I32(if a == b { 1 } else { 0 })
+                            if *b == 0 {
+                                return Err(VMError::DivisionByZero);
+                            }
+                            RuntimeValue::I32(a.wrapping_rem(*b))
                         }
                         (RuntimeValue::I64(a), RuntimeValue::I64(b)) => {
-                            RuntimeValue::I32(if a == b { 1 } else { 0 })
+                            if *b == 0 {
+                                return Err(VMError::DivisionByZero);
+                            }
+                            RuntimeValue::I64(a.wrapping_rem(*b))
                         }
                         (RuntimeValue::Usize(a), RuntimeValue::Usize(b)) => {
-                            RuntimeValue::I32(if a == b { 1 } else { 0 })
+                            if *b == 0 {
+                                return Err(VMError::DivisionByZero);
+                            }
+                            RuntimeValue::Usize(a % b)
                         }
                         _ => {
                             return Err(VMError::TypeMismatch {
                                 expected: lhs_val.get_type(),
                                 actual: rhs_val.get_type(),
                                 context: format!(
-                                    "binary eq operation (lhs: {:?}, rhs: {:?})",
+                                    "binary rem operation (lhs: {:?}, rhs: {:?})",
                                     lhs_val.get_type(),
                                     rhs_val.get_type()
                                 ),
                             });
                         }
                     },
-                    BinaryOperator::Lt => match (lhs_val, rhs_val) {
-                        (RuntimeValue::I32(a), RuntimeValue::I32(b)) => {
-                            RuntimeValue::I32(if a < b { 1 } else { 0 })
-                        }
-                        (RuntimeValue::I64(a), RuntimeValue::I64(b)) => {
-                            RuntimeValue::I32(if a < b { 1 } else { 0 })
-                        }
-                        (RuntimeValue::Usize(a), RuntimeValue::Usize(b)) => {
-                            RuntimeValue::I32(if a < b { 1 } else { 0 })
+                    BinaryOperator::And | BinaryOperator::Or | BinaryOperator::Xor => {
+                        match (lhs_val, rhs_val) {
+                            (RuntimeValue::I32(a), RuntimeValue::I32(b)) => {
+                                RuntimeValue::I32(bitwise(*op, *a, *b))
+                            }
+                            (RuntimeValue::I64(a), RuntimeValue::I64(b)) => {
+                                RuntimeValue::I64(bitwise(*op, *a, *b))
+                            }
+                            (RuntimeValue::Usize(a), RuntimeValue::Usize(b)) => {
+                                RuntimeValue::Usize(bitwise(*op, *a, *b))
+                            }
+                            _ => {
+                                return Err(VMError::TypeMismatch {
+                                    expected: lhs_val.get_type(),
+                                    actual: rhs_val.get_type(),
+                                    context: format!(
+                                        "binary {:?} operation (lhs: {:?}, rhs: {:?})",
+                                        op,
+                                        lhs_val.get_type(),
+                                        rhs_val.get_type()
+                                    ),
+                                });
+                            }
                         }
-                        _ => {
-                            return Err(VMError::TypeMismatch {
-                                expected: lhs_val.get_type(),
-                                actual: rhs_val.get_type(),
-                                context: format!(
-                                    "binary lt operation (lhs: {:?}, rhs: {:?})",
-                                    lhs_val.get_type(),
-                                    rhs_val.get_type()
-                                ),
-                            });
+                    }
+                    // The shift amount wraps to the operand width, so shifting
+                    // an i32 by 32 shifts by 0. Right shifts are arithmetic for
+                    // the signed types and logical for usize.
+                    BinaryOperator::Shl | BinaryOperator::Shr => {
+                        let left = *op == BinaryOperator::Shl;
+                        match (lhs_val, rhs_val) {
+                            (RuntimeValue::I32(a), RuntimeValue::I32(b)) => {
+                                RuntimeValue::I32(if left {
+                                    a.wrapping_shl(*b as u32)
+                                } else {
+                                    a.wrapping_shr(*b as u32)
+                                })
+                            }
+                            (RuntimeValue::I64(a), RuntimeValue::I64(b)) => {
+                                RuntimeValue::I64(if left {
+                                    a.wrapping_shl(*b as u32)
+                                } else {
+                                    a.wrapping_shr(*b as u32)
+                                })
+                            }
+                            (RuntimeValue::Usize(a), RuntimeValue::Usize(b)) => {
+                                let amount = (b % (self.pointer_width.bytes() * 8)) as u32;
+                                RuntimeValue::Usize(if left {
+                                    self.pointer_width.wrap((a << amount) as i64)
+                                } else {
+                                    a >> amount
+                                })
+                            }
+                            _ => {
+                                return Err(VMError::TypeMismatch {
+                                    expected: lhs_val.get_type(),
+                                    actual: rhs_val.get_type(),
+                                    context: format!(
+                                        "binary {:?} operation (lhs: {:?}, rhs: {:?})",
+                                        op,
+                                        lhs_val.get_type(),
+                                        rhs_val.get_type()
+                                    ),
+                                });
+                            }
                         }
-                    },
+                    }
+                    op if op.is_comparison() => {
+                        let holds = match (lhs_val, rhs_val) {
+                            (RuntimeValue::I32(a), RuntimeValue::I32(b)) => compare(*op, a, b),
+                            (RuntimeValue::I64(a), RuntimeValue::I64(b)) => compare(*op, a, b),
+                            (RuntimeValue::Usize(a), RuntimeValue::Usize(b)) => compare(*op, a, b),
+                            (RuntimeValue::F32(a), RuntimeValue::F32(b)) => compare(*op, a, b),
+                            (RuntimeValue::F64(a), RuntimeValue::F64(b)) => compare(*op, a, b),
+                            _ => {
+                                return Err(VMError::TypeMismatch {
+                                    expected: lhs_val.get_type(),
+                                    actual: rhs_val.get_type(),
+                                    context: format!(
+                                        "binary {:?} operation (lhs: {:?}, rhs: {:?})",
+                                        op,
+                                        lhs_val.get_type(),
+                                        rhs_val.get_type()
+                                    ),
+                                });
+                            }
+                        };
+                        RuntimeValue::I32(holds as i32)
+                    }
                     // For now, return an error for unimplemented operators
                     _ => {
                         return Err(VMError::InvalidInstruction(format!(
@@ -593,10 +984,51 @@ impl<H: HostABI> VM<H> {
                 frame.set_value(*dest, result);
             }
 
-            Instruction::UnaryOp { .. } => {
-                return Err(VMError::InvalidInstruction(
-                    "Unary operations not yet implemented".to_string(),
-                ));
+            Instruction::UnaryOp {
+                dest,
+                op,
+                ty,
+                operand,
+            } => {
+                let frame = self.call_stack.last().unwrap();
+                let operand_val = frame.get_value(*operand)?;
+
+                let result = match (op, operand_val) {
+                    (UnaryOperator::Neg, RuntimeValue::I32(v)) => {
+                        RuntimeValue::I32(v.wrapping_neg())
+                    }
+                    (UnaryOperator::Neg, RuntimeValue::I64(v)) => {
+                        RuntimeValue::I64(v.wrapping_neg())
+                    }
+                    (UnaryOperator::Neg, RuntimeValue::Usize(v)) => {
+                        RuntimeValue::Usize(self.pointer_width.wrap((*v as i64).wrapping_neg()))
+                    }
+                    (UnaryOperator::Not, RuntimeValue::I32(v)) => RuntimeValue::I32(!v),
+                    (UnaryOperator::Not, RuntimeValue::I64(v)) => RuntimeValue::I64(!v),
+                    (UnaryOperator::Not, RuntimeValue::Usize(v)) => RuntimeValue::Usize(!v),
+                    (UnaryOperator::Eqz, RuntimeValue::I32(v)) => {
+                        RuntimeValue::I32((*v == 0) as i32)
+                    }
+                    (UnaryOperator::Eqz, RuntimeValue::I64(v)) => {
+                        RuntimeValue::I32((*v == 0) as i32)
+                    }
+                    (UnaryOperator::Eqz, RuntimeValue::Usize(v)) => {
+                        RuntimeValue::I32((*v == 0) as i32)
+                    }
+                    (_, RuntimeValue::F32(v)) => {
+                        RuntimeValue::F32(apply_float_op(*op, *v as f64)? as f32)
+                    }
+                    (_, RuntimeValue::F64(v)) => RuntimeValue::F64(apply_float_op(*op, *v)?),
+                    _ => {
+                        return Err(VMError::InvalidInstruction(format!(
+                            "Unary operator {:?} not supported for type {:?}",
+                            op, ty
+                        )));
+                    }
+                };
+
+                let frame = self.call_stack.last_mut().unwrap();
+                frame.set_value(*dest, result);
             }
 
             Instruction::Load { dest, ty, address } => {
@@ -613,11 +1045,16 @@ impl<H: HostABI> VM<H> {
                         });
                     }
                 };
+                if addr == 0 {
+                    return Err(VMError::NullPointerDereference(
+                        "load instruction".to_string(),
+                    ));
+                }
 
                 // Use the host ABI to read the value from memory
                 let result = self
                     .host_abi
-                    .read_memory_value(addr.try_into().unwrap(), *ty)
+                    .read_memory_value(addr.try_into().unwrap(), ty.clone())
                     .map_err(|e| {
                         VMError::InvalidInstruction(format!("Memory read error: {}", e))
                     })?;
@@ -626,15 +1063,21 @@ impl<H: HostABI> VM<H> {
                 frame.set_value(*dest, result);
             }
 
-            Instruction::Store {
-                address,
-                value,
-                ty: _,
-            } => {
+            Instruction::Store { address, value, ty } => {
                 let frame = self.call_stack.last().unwrap();
                 let addr_val = frame.get_value(*address)?;
                 let val = frame.get_value(*value)?;
 
+                // The stored width and representation come from the value, so
+                // it has to be of the declared type
+                if val.get_type().repr() != ty.repr() {
+                    return Err(VMError::TypeMismatch {
+                        expected: ty.clone(),
+                        actual: val.get_type(),
+                        context: "store instruction value".to_string(),
+                    });
+                }
+
                 let addr = match addr_val {
                     RuntimeValue::Usize(addr) => *addr,
                     _ => {
@@ -645,6 +1088,11 @@ impl<H: HostABI> VM<H> {
                         });
                     }
                 };
+                if addr == 0 {
+                    return Err(VMError::NullPointerDereference(
+                        "store instruction".to_string(),
+                    ));
+                }
 
                 // Use the host ABI to write the value to memory
                 self.host_abi
@@ -661,7 +1109,13 @@ impl<H: HostABI> VM<H> {
 
                 let result = match (ptr_val, offset_val) {
                     (RuntimeValue::Usize(ptr_addr), RuntimeValue::Usize(offset_bytes)) => {
-                        RuntimeValue::Usize(ptr_addr.wrapping_add(*offset_bytes))
+                        self.host_abi
+                            .check_ptr_add(*ptr_addr as u64, *offset_bytes as u64)
+                            .map_err(VMError::PointerOutOfBounds)?;
+                        RuntimeValue::Usize(
+                            self.pointer_width
+                                .wrap(ptr_addr.wrapping_add(*offset_bytes) as i64),
+                        )
                     }
                     _ => {
                         return Err(VMError::TypeMismatch {
@@ -686,7 +1140,7 @@ impl<H: HostABI> VM<H> {
                     Type::I64 => 8,
                     Type::F32 => 4,
                     Type::F64 => 8,
-                    Type::Usize => std::mem::size_of::<usize>(), // Platform-dependent
+                    Type::Usize | Type::Ptr(_) => self.pointer_width.bytes(),
                     Type::Void => 0,
                 };
 
@@ -694,16 +1148,22 @@ impl<H: HostABI> VM<H> {
                 frame.set_value(*dest, RuntimeValue::Usize(size));
             }
 
+            Instruction::DataAddr { dest, data } => {
+                let function = self.call_stack.last().unwrap().function_name.clone();
+                let addr = self.rodata_address(&function, *data)?;
+
+                let frame = self.call_stack.last_mut().unwrap();
+                frame.set_value(*dest, RuntimeValue::Usize(addr));
+            }
+
             Instruction::Alloc { dest, size } => {
                 let frame = self.call_stack.last().unwrap();
                 let size_val = frame.get_value(*size)?;
 
                 let result = if let RuntimeValue::Usize(_size_bytes) = size_val {
-                    let result = self
-                        .host_abi
-                        .call_host_function("alloc", &[size_val.clone()])
-                        .map_err(VMError::HostCallError)?;
-                    result
+                    self.host_abi
+                        .call_host_function("alloc", std::slice::from_ref(size_val))
+                        .map_err(VMError::HostCallError)?
                 } else {
                     return Err(VMError::TypeMismatch {
                         expected: Type::Usize,
@@ -722,7 +1182,7 @@ impl<H: HostABI> VM<H> {
 
                 if let RuntimeValue::Usize(_) = ptr_val {
                     self.host_abi
-                        .call_host_function("free", &[ptr_val.clone()])
+                        .call_host_function("free", std::slice::from_ref(ptr_val))
                         .map_err(VMError::HostCallError)?;
                 } else {
                     return Err(VMError::TypeMismatch {
@@ -743,9 +1203,9 @@ impl<H: HostABI> VM<H> {
                 let src_val = frame.get_value(*src)?;
 
                 // Verify source type matches expected type
-                if src_val.get_type() != *from_ty {
+                if src_val.get_type().repr() != from_ty.repr() {
                     return Err(VMError::TypeMismatch {
-                        expected: *from_ty,
+                        expected: from_ty.clone(),
                         actual: src_val.get_type(),
                         context: format!(
                             "convert instruction source type (converting {:?} to {:?})",
@@ -754,25 +1214,65 @@ impl<H: HostABI> VM<H> {
                     });
                 }
 
-                // Perform type conversion
-                let result = match (from_ty, to_ty, src_val) {
+                // Perform type conversion. The supported set must match the
+                // Cranelift backend: every pair of integer types, and every
+                // pair of i32, i64, f32 and f64. Usize results wrap to the
+                // VM's pointer width, and usize sources are zero-extended.
+                // Float-to-integer conversions saturate, and NaN becomes 0.
+                let width = self.pointer_width;
+                let result = match (from_ty.repr(), to_ty.repr(), src_val) {
+                    (Type::I32, Type::I32, _)
+                    | (Type::I64, Type::I64, _)
+                    | (Type::Usize, Type::Usize, _)
+                    | (Type::F32, Type::F32, _)
+                    | (Type::F64, Type::F64, _) => src_val.clone(),
+                    (Type::I32, Type::F32, RuntimeValue::I32(val)) => {
+                        RuntimeValue::F32(*val as f32)
+                    }
+                    (Type::I32, Type::F64, RuntimeValue::I32(val)) => {
+                        RuntimeValue::F64(*val as f64)
+                    }
+                    (Type::I64, Type::F32, RuntimeValue::I64(val)) => {
+                        RuntimeValue::F32(*val as f32)
+                    }
+                    (Type::I64, Type::F64, RuntimeValue::I64(val)) => {
+                        RuntimeValue::F64(*val as f64)
+                    }
+                    (Type::F32, Type::F64, RuntimeValue::F32(val)) => {
+                        RuntimeValue::F64(*val as f64)
+                    }
+                    (Type::F64, Type::F32, RuntimeValue::F64(val)) => {
+                        RuntimeValue::F32(*val as f32)
+                    }
+                    (Type::F32, Type::I32, RuntimeValue::F32(val)) => {
+                        RuntimeValue::I32(*val as i32)
+                    }
+                    (Type::F32, Type::I64, RuntimeValue::F32(val)) => {
+                        RuntimeValue::I64(*val as i64)
+                    }
+                    (Type::F64, Type::I32, RuntimeValue::F64(val)) => {
+                        RuntimeValue::I32(*val as i32)
+                    }
+                    (Type::F64, Type::I64, RuntimeValue::F64(val)) => {
+                        RuntimeValue::I64(*val as i64)
+                    }
                     (Type::I32, Type::I64, RuntimeValue::I32(val)) => {
                         RuntimeValue::I64(*val as i64)
                     }
                     (Type::I32, Type::Usize, RuntimeValue::I32(val)) => {
-                        RuntimeValue::Usize(*val as usize)
+                        RuntimeValue::Usize(width.wrap(*val as i64))
                     }
                     (Type::I64, Type::I32, RuntimeValue::I64(val)) => {
                         RuntimeValue::I32(*val as i32)
                     }
                     (Type::Usize, Type::I64, RuntimeValue::Usize(val)) => {
-                        RuntimeValue::I64(*val as i64)
+                        RuntimeValue::I64(width.wrap(*val as i64) as u64 as i64)
                     }
                     (Type::Usize, Type::I32, RuntimeValue::Usize(val)) => {
                         RuntimeValue::I32(*val as i32)
                     }
                     (Type::I64, Type::Usize, RuntimeValue::I64(val)) => {
-                        RuntimeValue::Usize(*val as usize)
+                        RuntimeValue::Usize(width.wrap(*val))
                     }
                     _ => {
                         return Err(VMError::InvalidInstruction(format!(
@@ -785,22 +1285,264 @@ impl<H: HostABI> VM<H> {
                 let frame = self.call_stack.last_mut().unwrap();
                 frame.set_value(*dest, result);
             }
-        }
 
-        Ok(())
-    }
-}
+            Instruction::ConvertChecked {
+                dest,
+                src,
+                from_ty,
+                to_ty,
+            } => {
+                let frame = self.call_stack.last().unwrap();
+                let src_val = frame.get_value(*src)?;
+
+                let converted = match (from_ty, to_ty, src_val) {
+                    (Type::I64, Type::I32, RuntimeValue::I64(val)) => i32::try_from(*val).ok(),
+                    (Type::Usize, Type::I32, RuntimeValue::Usize(val)) => i32::try_from(*val).ok(),
+                    _ => {
+                        return Err(VMError::InvalidInstruction(format!(
+                            "Unsupported checked conversion from {:?} to {:?} of {:?}",
+                            from_ty, to_ty, src_val
+                        )));
+                    }
+                };
+                let Some(result) = converted else {
+                    return Err(VMError::ConversionOverflow {
+                        value: src_val.clone(),
+                        to: to_ty.clone(),
+                    });
+                };
+
+                let frame = self.call_stack.last_mut().unwrap();
+                frame.set_value(*dest, RuntimeValue::I32(result));
+            }
+
+            // Only the verifier looks at type assertions
+            Instruction::AssertType { .. } => {}
+
+            Instruction::Phi { .. } => {
+                return Err(VMError::InvalidInstruction(
+                    "Phi instructions must be eliminated before execution (see tilt_ir::phi)"
+                        .to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Check branch arguments against the target block's parameter types
+fn check_block_args(block: &BasicBlock, args: &[RuntimeValue]) -> VMResult<()> {
+    for (i, ((_, param_type), arg)) in block.params.iter().zip(args).enumerate() {
+        if arg.get_type().repr() != param_type.repr() {
+            return Err(VMError::TypeMismatch {
+                expected: param_type.clone(),
+                actual: arg.get_type(),
+                context: format!("argument {} to block '{}'", i + 1, block.label),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Move `frame` to the start of `target`, binding the target's block
+/// parameters to the values of `args`
+fn enter_block(
+    frame: &mut StackFrame,
+    function: &Function,
+    target: BlockId,
+    args: &[ValueId],
+) -> VMResult<()> {
+    if !args.is_empty() {
+        let target_block = function
+            .blocks
+            .iter()
+            .find(|b| b.id == target)
+            .ok_or(VMError::BlockNotFound(target))?;
+
+        // Check that the number of arguments matches the number of parameters
+        if args.len() != target_block.params.len() {
+            return Err(VMError::InvalidInstruction(format!(
+                "Block parameter count mismatch: expected {}, got {}",
+                target_block.params.len(),
+                args.len()
+            )));
+        }
+
+        let mut arg_values = Vec::new();
+        for arg_id in args {
+            arg_values.push(frame.get_value(*arg_id)?.clone());
+        }
+        check_block_args(target_block, &arg_values)?;
+
+        for ((param_id, _), value) in target_block.params.iter().zip(arg_values) {
+            frame.values.insert(*param_id, value);
+        }
+    }
+
+    frame.current_block = target;
+    frame.instruction_pointer = 0;
+    Ok(())
+}
+
+/// Evaluate a comparison operator. Floats follow IEEE semantics through
+/// `PartialOrd`: any comparison with a NaN is false, except `ne`.
+fn compare<T: PartialOrd>(op: BinaryOperator, a: T, b: T) -> bool {
+    match op {
+        BinaryOperator::Eq => a == b,
+        BinaryOperator::Ne => a != b,
+        BinaryOperator::Lt => a < b,
+        BinaryOperator::Le => a <= b,
+        BinaryOperator::Gt => a > b,
+        BinaryOperator::Ge => a >= b,
+        _ => unreachable!("{:?} is not a comparison", op),
+    }
+}
+
+/// Evaluate a bitwise operator on two integers of the same width
+fn bitwise<T>(op: BinaryOperator, a: T, b: T) -> T
+where
+    T: std::ops::BitAnd<Output = T> + std::ops::BitOr<Output = T> + std::ops::BitXor<Output = T>,
+{
+    match op {
+        BinaryOperator::And => a & b,
+        BinaryOperator::Or => a | b,
+        BinaryOperator::Xor => a ^ b,
+        _ => unreachable!("{:?} is not a bitwise operator", op),
+    }
+}
+
+/// Apply a unary operator to a float operand
+fn apply_float_op(op: UnaryOperator, v: f64) -> VMResult<f64> {
+    match op {
+        UnaryOperator::Neg => Ok(-v),
+        UnaryOperator::Sqrt => Ok(v.sqrt()),
+        UnaryOperator::Floor => Ok(v.floor()),
+        UnaryOperator::Ceil => Ok(v.ceil()),
+        UnaryOperator::Abs => Ok(v.abs()),
+        UnaryOperator::Not | UnaryOperator::Eqz => Err(VMError::InvalidInstruction(format!(
+            "Unary operator {:?} not supported for float types",
+            op
+        ))),
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tilt_host_abi::ConsoleHostABI;
+    use tilt_host_abi::{
+        BufferedConsoleHostABI, ConsoleHostABI, EnvHostABI, HOST_ERR_INVALID_INPUT, HOST_ERR_NONE,
+        HOST_ERROR_RESULT, HostResult, LayeredHostABI, MemoryHostABI, NullHostABI,
+    };
+
+    /// Host ABI whose `open` fails recoverably and whose `crash` aborts
+    struct FailingHostABI {
+        last_error: i32,
+    }
+
+    impl HostABI for FailingHostABI {
+        fn call_host_function(&mut self, name: &str, _args: &[RuntimeValue]) -> HostResult {
+            match name {
+                "open" => {
+                    self.last_error = HOST_ERR_INVALID_INPUT;
+                    Ok(RuntimeValue::I32(HOST_ERROR_RESULT))
+                }
+                "last_error" => Ok(RuntimeValue::I32(self.last_error)),
+                "crash" => Err("crash failed".to_string()),
+                _ => Err(format!("Unknown host function: {}", name)),
+            }
+        }
+
+        fn available_functions(&self) -> Vec<&str> {
+            vec!["open", "last_error", "crash"]
+        }
+
+        fn last_error(&self) -> i32 {
+            self.last_error
+        }
+    }
+
+    /// Declare host function `name` as an import of module "host"
+    fn host_import(name: &str, params: Vec<Type>, return_type: Type) -> ImportDecl {
+        ImportDecl {
+            module: "host".to_string(),
+            name: name.to_string(),
+            calling_convention: None,
+            alias: None,
+            params,
+            return_type,
+        }
+    }
+
+    /// Build `main`, which calls `callee` and returns `last_error()` if the
+    /// call reported `HOST_ERROR_RESULT`, or the call result otherwise
+    fn create_error_check_program(callee: &str) -> Program {
+        let mut func = Function::new("main".to_string(), vec![], Type::I32);
+
+        let result = func.next_value();
+        let sentinel = func.next_value();
+        let failed = func.next_value();
+        let code = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Call {
+            dest: result,
+            function: callee.to_string(),
+            args: vec![],
+            return_type: Type::I32,
+        });
+        entry.instructions.push(Instruction::Const {
+            dest: sentinel,
+            value: HOST_ERROR_RESULT as i64,
+            ty: Type::I32,
+        });
+        entry.instructions.push(Instruction::BinaryOp {
+            dest: failed,
+            op: BinaryOperator::Eq,
+            ty: Type::I32,
+            lhs: result,
+            rhs: sentinel,
+        });
+        entry.terminator = Terminator::BrIf {
+            cond: failed,
+            true_target: BlockId::new(1),
+            true_args: vec![],
+            false_target: BlockId::new(2),
+            false_args: vec![],
+        };
+
+        let mut recover = BasicBlock::new(BlockId::new(1), "recover".to_string());
+        recover.instructions.push(Instruction::Call {
+            dest: code,
+            function: "last_error".to_string(),
+            args: vec![],
+            return_type: Type::I32,
+        });
+        recover.terminator = Terminator::Ret { value: Some(code) };
+
+        let mut done = BasicBlock::new(BlockId::new(2), "done".to_string());
+        done.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+
+        func.blocks = vec![entry, recover, done];
+
+        Program {
+            imports: vec![
+                host_import(callee, vec![], Type::I32),
+                host_import("last_error", vec![], Type::I32),
+            ],
+            functions: vec![func],
+            rodata: vec![],
+        }
+    }
 
     fn create_simple_add_program() -> Program {
         // Create a simple program that adds two numbers
         let mut program = Program {
             imports: vec![],
             functions: vec![],
+            rodata: vec![],
         };
 
         // Create the add function: fn add(a: i32, b: i32) -> i32 { return a + b; }
@@ -851,6 +1593,349 @@ mod tests {
         assert_eq!(result, RuntimeValue::I32(8));
     }
 
+    /// Build `op(a: ty, b: ty) -> ty`, which applies one binary operator
+    fn create_binary_op_program(op: BinaryOperator, ty: Type) -> Program {
+        let mut func = Function::new("op".to_string(), vec![ty.clone(), ty.clone()], ty.clone());
+        let result = ValueId::new(2);
+        func.next_value_id = ValueId::new(3);
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::BinaryOp {
+            dest: result,
+            op,
+            ty,
+            lhs: ValueId::new(0),
+            rhs: ValueId::new(1),
+        });
+        entry.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+        func.blocks.push(entry);
+
+        Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        }
+    }
+
+    #[test]
+    fn test_bitwise_operators_on_every_integer_width() {
+        let cases = [
+            (BinaryOperator::And, 0b1100 & 0b1010),
+            (BinaryOperator::Or, 0b1100 | 0b1010),
+            (BinaryOperator::Xor, 0b1100 ^ 0b1010),
+        ];
+        for (op, expected) in cases {
+            let run = |ty: Type, a: RuntimeValue, b: RuntimeValue| {
+                let mut vm = VM::new(create_binary_op_program(op, ty), NullHostABI::new());
+                vm.call_function("op", vec![a, b]).unwrap()
+            };
+
+            assert_eq!(
+                run(
+                    Type::I32,
+                    RuntimeValue::I32(0b1100),
+                    RuntimeValue::I32(0b1010)
+                ),
+                RuntimeValue::I32(expected)
+            );
+            assert_eq!(
+                run(
+                    Type::I64,
+                    RuntimeValue::I64(0b1100),
+                    RuntimeValue::I64(0b1010)
+                ),
+                RuntimeValue::I64(expected as i64)
+            );
+            assert_eq!(
+                run(
+                    Type::Usize,
+                    RuntimeValue::Usize(0b1100),
+                    RuntimeValue::Usize(0b1010)
+                ),
+                RuntimeValue::Usize(expected as _)
+            );
+        }
+
+        // Masking keeps the sign bit
+        let mut vm = VM::new(
+            create_binary_op_program(BinaryOperator::And, Type::I64),
+            NullHostABI::new(),
+        );
+        assert_eq!(
+            vm.call_function(
+                "op",
+                vec![RuntimeValue::I64(-1), RuntimeValue::I64(i64::MIN)]
+            ),
+            Ok(RuntimeValue::I64(i64::MIN))
+        );
+    }
+
+    #[test]
+    fn test_shift_amount_wraps_to_operand_width() {
+        let run = |op: BinaryOperator, ty: Type, a: RuntimeValue, b: RuntimeValue| {
+            let mut vm = VM::new(create_binary_op_program(op, ty), NullHostABI::new());
+            vm.call_function("op", vec![a, b]).unwrap()
+        };
+        let (shl, shr) = (BinaryOperator::Shl, BinaryOperator::Shr);
+
+        for (amount, expected_shl, expected_shr) in [(0, -3, -3), (31, i32::MIN, -1), (32, -3, -3)]
+        {
+            let (a, b) = (RuntimeValue::I32(-3), RuntimeValue::I32(amount));
+            assert_eq!(
+                run(shl, Type::I32, a.clone(), b.clone()),
+                RuntimeValue::I32(expected_shl)
+            );
+            assert_eq!(run(shr, Type::I32, a, b), RuntimeValue::I32(expected_shr));
+        }
+
+        for (amount, expected_shl, expected_shr) in [(0, -3, -3), (63, i64::MIN, -1), (64, -3, -3)]
+        {
+            let (a, b) = (RuntimeValue::I64(-3), RuntimeValue::I64(amount));
+            assert_eq!(
+                run(shl, Type::I64, a.clone(), b.clone()),
+                RuntimeValue::I64(expected_shl)
+            );
+            assert_eq!(run(shr, Type::I64, a, b), RuntimeValue::I64(expected_shr));
+        }
+
+        // usize right shifts are logical
+        let top = 1usize << 63;
+        for (amount, expected_shl, expected_shr) in [(0, top, top), (63, 0, 1), (64, top, top)] {
+            let (a, b) = (RuntimeValue::Usize(top), RuntimeValue::Usize(amount));
+            assert_eq!(
+                run(shl, Type::Usize, a.clone(), b.clone()),
+                RuntimeValue::Usize(expected_shl)
+            );
+            assert_eq!(
+                run(shr, Type::Usize, a, b),
+                RuntimeValue::Usize(expected_shr)
+            );
+        }
+
+        // With 32-bit pointers usize shifts wrap at 32 bits
+        let mut vm = VM::new(
+            create_binary_op_program(shl, Type::Usize),
+            NullHostABI::new(),
+        );
+        vm.set_pointer_width(PointerWidth::Bits32);
+        for (amount, expected) in [(31, 1 << 31), (32, 1), (33, 2)] {
+            assert_eq!(
+                vm.call_function(
+                    "op",
+                    vec![RuntimeValue::Usize(1), RuntimeValue::Usize(amount)]
+                ),
+                Ok(RuntimeValue::Usize(expected))
+            );
+        }
+    }
+
+    #[test]
+    fn test_pointer_width_sets_usize_size_and_conversions() {
+        // fn widths(x: i64) -> i64 { usize.to_i64(i64.to_usize(x)) + sizeof.usize }
+        let mut func = Function::new("widths".to_string(), vec![Type::I64], Type::I64);
+        let x = ValueId::new(0);
+        func.next_value_id = ValueId::new(1);
+        let size = func.next_value();
+        let wide = func.next_value();
+        let back = func.next_value();
+        let size64 = func.next_value();
+        let result = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::SizeOf {
+            dest: size,
+            ty: Type::Usize,
+        });
+        for (dest, src, from_ty, to_ty) in [
+            (wide, x, Type::I64, Type::Usize),
+            (back, wide, Type::Usize, Type::I64),
+            (size64, size, Type::Usize, Type::I64),
+        ] {
+            entry.instructions.push(Instruction::Convert {
+                dest,
+                src,
+                from_ty,
+                to_ty,
+            });
+        }
+        entry.instructions.push(Instruction::BinaryOp {
+            dest: result,
+            op: BinaryOperator::Add,
+            ty: Type::I64,
+            lhs: back,
+            rhs: size64,
+        });
+        entry.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+        func.blocks.push(entry);
+        let program = Arc::new(Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        });
+
+        // -1 survives the round trip through a 64-bit usize; plus 8 bytes
+        let mut vm = VM::new(Arc::clone(&program), NullHostABI::new());
+        assert_eq!(vm.pointer_width(), PointerWidth::Bits64);
+        assert_eq!(
+            vm.call_function("widths", vec![RuntimeValue::I64(-1)]),
+            Ok(RuntimeValue::I64(-1 + 8))
+        );
+
+        // A 32-bit usize wraps -1 to u32::MAX and is 4 bytes
+        let mut vm = VM::new(program, NullHostABI::new());
+        vm.set_pointer_width(PointerWidth::Bits32);
+        assert_eq!(
+            vm.call_function("widths", vec![RuntimeValue::I64(-1)]),
+            Ok(RuntimeValue::I64(u32::MAX as i64 + 4))
+        );
+    }
+
+    #[test]
+    fn test_usize_arithmetic_wraps_at_pointer_width() {
+        let max32 = u32::MAX as usize;
+        let cases = [
+            (BinaryOperator::Add, max32, 1, 0),
+            (BinaryOperator::Sub, 0, 1, max32),
+            (BinaryOperator::Mul, 1 << 16, 1 << 16, 0),
+        ];
+
+        for (op, a, b, expected) in cases {
+            let mut vm = VM::new(
+                create_binary_op_program(op, Type::Usize),
+                NullHostABI::new(),
+            );
+            vm.set_pointer_width(PointerWidth::Bits32);
+            assert_eq!(
+                vm.call_function("op", vec![RuntimeValue::Usize(a), RuntimeValue::Usize(b)]),
+                Ok(RuntimeValue::Usize(expected)),
+                "{:?}",
+                op
+            );
+        }
+    }
+
+    #[test]
+    fn test_usize_neg_wraps_at_pointer_width() {
+        // fn op(x: usize) -> usize { ret usize.neg(x) }
+        let mut func = Function::new("op".to_string(), vec![Type::Usize], Type::Usize);
+        let result = ValueId::new(1);
+        func.next_value_id = ValueId::new(2);
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::UnaryOp {
+            dest: result,
+            op: UnaryOperator::Neg,
+            ty: Type::Usize,
+            operand: ValueId::new(0),
+        });
+        entry.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+        func.blocks.push(entry);
+        let program = Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        for (width, expected) in [
+            (PointerWidth::Bits64, u64::MAX as usize),
+            (PointerWidth::Bits32, u32::MAX as usize),
+        ] {
+            let mut vm = VM::new(program.clone(), NullHostABI::new());
+            vm.set_pointer_width(width);
+            assert_eq!(
+                vm.call_function("op", vec![RuntimeValue::Usize(1)]),
+                Ok(RuntimeValue::Usize(expected))
+            );
+        }
+    }
+
+    #[test]
+    fn test_usize_constants_and_arguments_follow_pointer_width() {
+        // fn op() -> usize { ret usize.const(value) }
+        let constant_program = |value: i64| {
+            let mut func = Function::new("op".to_string(), vec![], Type::Usize);
+            let result = func.next_value();
+            let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+            entry.instructions.push(Instruction::Const {
+                dest: result,
+                value,
+                ty: Type::Usize,
+            });
+            entry.terminator = Terminator::Ret {
+                value: Some(result),
+            };
+            func.blocks.push(entry);
+            Program {
+                imports: vec![],
+                functions: vec![func],
+                rodata: vec![],
+            }
+        };
+
+        let mut vm = VM::new(constant_program(-1), NullHostABI::new());
+        vm.set_pointer_width(PointerWidth::Bits32);
+        assert_eq!(
+            vm.call_function("op", vec![]),
+            Ok(RuntimeValue::Usize(u32::MAX as usize))
+        );
+
+        let mut vm = VM::new(constant_program(1 << 32), NullHostABI::new());
+        vm.set_pointer_width(PointerWidth::Bits32);
+        assert!(matches!(
+            vm.call_function("op", vec![]),
+            Err(VMError::InvalidInstruction(msg)) if msg.contains("does not fit in usize")
+        ));
+        vm.set_pointer_width(PointerWidth::Bits64);
+        assert_eq!(
+            vm.call_function("op", vec![]),
+            Ok(RuntimeValue::Usize(1 << 32))
+        );
+
+        let mut vm = VM::new(
+            create_binary_op_program(BinaryOperator::Or, Type::Usize),
+            NullHostABI::new(),
+        );
+        vm.set_pointer_width(PointerWidth::Bits32);
+        let args = vec![RuntimeValue::Usize((1 << 32) + 5), RuntimeValue::Usize(0)];
+        assert_eq!(vm.call_function("op", args), Ok(RuntimeValue::Usize(5)));
+    }
+
+    #[test]
+    fn test_bitwise_operator_type_mismatch() {
+        let mut program = create_binary_op_program(BinaryOperator::Xor, Type::I32);
+        let func = &mut program.functions[0];
+        let wide = func.next_value();
+        func.blocks[0].instructions.insert(
+            0,
+            Instruction::Const {
+                dest: wide,
+                value: 1,
+                ty: Type::I64,
+            },
+        );
+        let Instruction::BinaryOp { rhs, .. } = &mut func.blocks[0].instructions[1] else {
+            unreachable!();
+        };
+        *rhs = wide;
+
+        let mut vm = VM::new(program, NullHostABI::new());
+        let result = vm.call_function("op", vec![RuntimeValue::I32(1), RuntimeValue::I32(2)]);
+        assert!(matches!(
+            result,
+            Err(VMError::TypeMismatch {
+                expected: Type::I32,
+                actual: Type::I64,
+                ..
+            })
+        ));
+    }
+
     #[test]
     fn test_function_not_found() {
         let program = create_simple_add_program();
@@ -878,8 +1963,9 @@ mod tests {
     #[test]
     fn test_host_function_call() {
         let mut program = Program {
-            imports: vec![],
+            imports: vec![host_import("print_i32", vec![Type::I32], Type::Void)],
             functions: vec![],
+            rodata: vec![],
         };
 
         // Create a function that calls a host function
@@ -914,4 +2000,770 @@ mod tests {
 
         assert_eq!(result, RuntimeValue::Void);
     }
+
+    #[test]
+    fn test_recoverable_host_error_continues_execution() {
+        let program = create_error_check_program("open");
+        let host_abi = FailingHostABI {
+            last_error: HOST_ERR_NONE,
+        };
+        let mut vm = VM::new(program, host_abi);
+
+        // The failed call returns the error sentinel and the program handles it
+        let result = vm.call_function("main", vec![]).unwrap();
+        assert_eq!(result, RuntimeValue::I32(HOST_ERR_INVALID_INPUT));
+    }
+
+    #[test]
+    fn test_host_error_aborts_execution() {
+        let program = create_error_check_program("crash");
+        let host_abi = FailingHostABI {
+            last_error: HOST_ERR_NONE,
+        };
+        let mut vm = VM::new(program, host_abi);
+
+        let result = vm.call_function("main", vec![]);
+        assert!(matches!(result, Err(VMError::HostCallError(_))));
+    }
+
+    #[test]
+    fn test_call_to_undeclared_host_function_fails() {
+        // `open` exists on the host, but the program doesn't import it
+        let mut program = create_error_check_program("open");
+        program.imports.clear();
+
+        let mut vm = VM::new(
+            program,
+            FailingHostABI {
+                last_error: HOST_ERR_NONE,
+            },
+        );
+        assert_eq!(
+            vm.call_function("main", vec![]),
+            Err(VMError::FunctionNotFound("open".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_try_call_turns_host_error_into_error_code() {
+        // fn main() -> i32 { r = try_call crash(); ret r + 10 }
+        let mut func = Function::new("main".to_string(), vec![], Type::I32);
+        let code = func.next_value();
+        let ten = func.next_value();
+        let result = func.next_value();
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::TryCall {
+            dest: code,
+            function: "crash".to_string(),
+            args: vec![],
+            return_type: Type::I32,
+        });
+        entry.instructions.push(Instruction::Const {
+            dest: ten,
+            value: 10,
+            ty: Type::I32,
+        });
+        entry.instructions.push(Instruction::BinaryOp {
+            dest: result,
+            op: BinaryOperator::Add,
+            ty: Type::I32,
+            lhs: code,
+            rhs: ten,
+        });
+        entry.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+        func.blocks = vec![entry];
+        let program = Program {
+            imports: vec![host_import("crash", vec![], Type::I32)],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut vm = VM::new(
+            program,
+            FailingHostABI {
+                last_error: HOST_ERR_NONE,
+            },
+        );
+        assert_eq!(
+            vm.call_function("main", vec![]).unwrap(),
+            RuntimeValue::I32(HOST_ERROR_RESULT + 10)
+        );
+    }
+
+    #[test]
+    fn test_print_ptr_host_function() {
+        let mut func = Function::new("show_ptr".to_string(), vec![Type::Usize], Type::Void);
+
+        let param = func.next_value();
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params.push((param, Type::Usize));
+        entry.instructions.push(Instruction::CallVoid {
+            function: "print_ptr".to_string(),
+            args: vec![param],
+        });
+        entry.terminator = Terminator::Ret { value: None };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![host_import("print_ptr", vec![Type::Usize], Type::Void)],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut vm = VM::new(program, BufferedConsoleHostABI::new());
+        vm.call_function("show_ptr", vec![RuntimeValue::Usize(0xdead_beef)])
+            .unwrap();
+
+        let output = vm.host_abi.output();
+        assert!(output.starts_with("0x"), "unexpected output: {}", output);
+        assert_eq!(output, "0xdeadbeef");
+    }
+
+    #[test]
+    fn test_host_call_argument_type_mismatch() {
+        let mut func = Function::new("bad_char".to_string(), vec![Type::Usize], Type::Void);
+
+        let param = func.next_value();
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params.push((param, Type::Usize));
+        entry.instructions.push(Instruction::CallVoid {
+            function: "print_char".to_string(),
+            args: vec![param],
+        });
+        entry.terminator = Terminator::Ret { value: None };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![host_import("print_char", vec![Type::I32], Type::Void)],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut vm = VM::new(program, BufferedConsoleHostABI::new());
+        let result = vm.call_function("bad_char", vec![RuntimeValue::Usize(0x1000)]);
+
+        assert_eq!(
+            result,
+            Err(VMError::TypeMismatch {
+                expected: Type::I32,
+                actual: Type::Usize,
+                context: "argument 1 to host function 'print_char'".to_string(),
+            })
+        );
+        assert_eq!(vm.host_abi.output(), "");
+    }
+
+    #[test]
+    fn test_vms_share_program() {
+        let program = Arc::new(create_simple_add_program());
+
+        let mut first = VM::new(Arc::clone(&program), ConsoleHostABI::new());
+        let mut second = VM::new(Arc::clone(&program), ConsoleHostABI::new());
+        assert_eq!(Arc::strong_count(&program), 3);
+
+        let args = vec![RuntimeValue::I32(2), RuntimeValue::I32(3)];
+        assert_eq!(
+            first.call_function("add", args.clone()).unwrap(),
+            RuntimeValue::I32(5)
+        );
+        assert_eq!(
+            second.call_function("add", args).unwrap(),
+            RuntimeValue::I32(5)
+        );
+    }
+
+    #[test]
+    fn test_call_to_unprovided_import_is_unresolved() {
+        // import "env" "foo" () -> i32; fn main() -> i32 { r = foo(); ret r }
+        let mut func = Function::new("main".to_string(), vec![], Type::I32);
+        let result = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Call {
+            dest: result,
+            function: "foo".to_string(),
+            args: vec![],
+            return_type: Type::I32,
+        });
+        entry.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![ImportDecl {
+                module: "env".to_string(),
+                name: "foo".to_string(),
+                calling_convention: None,
+                alias: None,
+                params: vec![],
+                return_type: Type::I32,
+            }],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut vm = VM::new(program, NullHostABI::new());
+        assert_eq!(
+            vm.call_function("main", vec![]),
+            Err(VMError::UnresolvedImport("foo".to_string()))
+        );
+        assert_eq!(
+            vm.call_function("bar", vec![]),
+            Err(VMError::FunctionNotFound("bar".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_validate_imports_rejects_mismatched_signature() {
+        let import = |name: &str, params: Vec<Type>, return_type: Type| ImportDecl {
+            module: "env".to_string(),
+            name: name.to_string(),
+            calling_convention: None,
+            alias: None,
+            params,
+            return_type,
+        };
+        let program = |imports| Program {
+            imports,
+            functions: vec![],
+            rodata: vec![],
+        };
+
+        // Pointers match the host's usize
+        let matching = program(vec![
+            import("print_i32", vec![Type::I32], Type::Void),
+            import("alloc", vec![Type::Usize], Type::ptr(Type::I32)),
+            import("not_a_host_function", vec![Type::I64], Type::I64),
+        ]);
+        assert_eq!(
+            VM::new(matching, MemoryHostABI::new()).validate_imports(),
+            Ok(())
+        );
+
+        let mismatched = program(vec![import(
+            "print_i32",
+            vec![Type::I32, Type::I32],
+            Type::Void,
+        )]);
+        let error = VM::new(mismatched, MemoryHostABI::new())
+            .validate_imports()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            VMError::ImportSignatureMismatch {
+                name: "print_i32".to_string(),
+                declared: (vec![Type::I32, Type::I32], Type::Void),
+                host: (vec![Type::I32], Type::Void),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Import 'print_i32' is declared as (i32, i32) -> void but the host provides (i32) -> void"
+        );
+    }
+
+    #[test]
+    fn test_zero_sized_alloc_is_null_and_store_through_it_fails() {
+        // fn store_empty() { p = alloc(0); store(p, 42) }
+        let mut func = Function::new("store_empty".to_string(), vec![], Type::Void);
+
+        let size = func.next_value();
+        let ptr = func.next_value();
+        let answer = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Const {
+            dest: size,
+            value: 0,
+            ty: Type::Usize,
+        });
+        entry
+            .instructions
+            .push(Instruction::Alloc { dest: ptr, size });
+        entry.instructions.push(Instruction::Const {
+            dest: answer,
+            value: 42,
+            ty: Type::I32,
+        });
+        entry.instructions.push(Instruction::Store {
+            address: ptr,
+            value: answer,
+            ty: Type::I32,
+        });
+        entry.terminator = Terminator::Ret { value: None };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        let result = vm.call_function("store_empty", vec![]);
+        assert!(
+            matches!(result, Err(VMError::NullPointerDereference(_))),
+            "unexpected result: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_store_of_wider_value_is_type_mismatch() {
+        // fn store_wide(x:i64) { p = alloc(8); i32.store(p, x) }
+        let mut func = Function::new("store_wide".to_string(), vec![Type::I64], Type::Void);
+
+        let x = func.next_value();
+        let size = func.next_value();
+        let ptr = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Const {
+            dest: size,
+            value: 8,
+            ty: Type::Usize,
+        });
+        entry
+            .instructions
+            .push(Instruction::Alloc { dest: ptr, size });
+        entry.instructions.push(Instruction::Store {
+            address: ptr,
+            value: x,
+            ty: Type::I32,
+        });
+        entry.terminator = Terminator::Ret { value: None };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        assert_eq!(
+            vm.call_function("store_wide", vec![RuntimeValue::I64(7)]),
+            Err(VMError::TypeMismatch {
+                expected: Type::I32,
+                actual: Type::I64,
+                context: "store instruction value".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_memory_inspected_after_execution() {
+        // fn store_answer() -> usize { p = alloc(4); store(p, 42); ret p }
+        let mut func = Function::new("store_answer".to_string(), vec![], Type::Usize);
+
+        let size = func.next_value();
+        let ptr = func.next_value();
+        let answer = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Const {
+            dest: size,
+            value: 4,
+            ty: Type::Usize,
+        });
+        entry.instructions.push(Instruction::Call {
+            dest: ptr,
+            function: "alloc".to_string(),
+            args: vec![size],
+            return_type: Type::Usize,
+        });
+        entry.instructions.push(Instruction::Const {
+            dest: answer,
+            value: 42,
+            ty: Type::I32,
+        });
+        entry.instructions.push(Instruction::Store {
+            address: ptr,
+            value: answer,
+            ty: Type::I32,
+        });
+        entry.terminator = Terminator::Ret { value: Some(ptr) };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![host_import("alloc", vec![Type::Usize], Type::Usize)],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut vm = VM::new(program, MemoryHostABI::new());
+        let RuntimeValue::Usize(addr) = vm.call_function("store_answer", vec![]).unwrap() else {
+            panic!("store_answer should return a pointer");
+        };
+
+        let host_abi = vm.into_host_abi();
+        assert_eq!(
+            host_abi.dump_region(addr as u64, 4).unwrap(),
+            42i32.to_le_bytes().to_vec()
+        );
+        assert!(host_abi.dump_region(addr as u64, 8).is_err());
+    }
+
+    #[test]
+    fn test_reset_host_abi_repeats_allocation_addresses() {
+        // fn alloc_twice() -> usize { alloc(8); ret alloc(8) }
+        let mut func = Function::new("alloc_twice".to_string(), vec![], Type::Usize);
+        let size = func.next_value();
+        let first = func.next_value();
+        let second = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Const {
+            dest: size,
+            value: 8,
+            ty: Type::Usize,
+        });
+        for dest in [first, second] {
+            entry.instructions.push(Instruction::Call {
+                dest,
+                function: "alloc".to_string(),
+                args: vec![size],
+                return_type: Type::Usize,
+            });
+        }
+        entry.terminator = Terminator::Ret {
+            value: Some(second),
+        };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![host_import("alloc", vec![Type::Usize], Type::Usize)],
+            functions: vec![func],
+            rodata: vec![],
+        };
+        let mut vm = VM::new(program, MemoryHostABI::new());
+
+        let first_run = vm.call_function("alloc_twice", vec![]).unwrap();
+        let without_reset = vm.call_function("alloc_twice", vec![]).unwrap();
+        assert_ne!(first_run, without_reset);
+
+        vm.reset_host();
+        let after_reset = vm.call_function("alloc_twice", vec![]).unwrap();
+        assert_eq!(first_run, after_reset);
+        assert!(
+            vm.host_abi()
+                .dump_region(without_reset.as_ptr(), 8)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_reset_host_places_rodata_again() {
+        // fn first() -> i32 { ret load.i32(&d0) }, with d0 = 7
+        let mut func = Function::new("first".to_string(), vec![], Type::I32);
+        let data = func.add_rodata(7i32.to_le_bytes().to_vec());
+        let addr = func.next_value();
+        let value = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry
+            .instructions
+            .push(Instruction::DataAddr { dest: addr, data });
+        entry.instructions.push(Instruction::Load {
+            dest: value,
+            ty: Type::I32,
+            address: addr,
+        });
+        entry.terminator = Terminator::Ret { value: Some(value) };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        };
+        let mut vm = VM::new(program, MemoryHostABI::new());
+
+        assert_eq!(
+            vm.call_function("first", vec![]).unwrap(),
+            RuntimeValue::I32(7)
+        );
+        vm.reset_host();
+        assert_eq!(
+            vm.call_function("first", vec![]).unwrap(),
+            RuntimeValue::I32(7)
+        );
+    }
+
+    #[test]
+    fn test_store_to_rodata_fails() {
+        // fn overwrite() { store.i32(&d0, 9); ret }, with d0 = 7
+        let mut func = Function::new("overwrite".to_string(), vec![], Type::Void);
+        let data = func.add_rodata(7i32.to_le_bytes().to_vec());
+        let addr = func.next_value();
+        let nine = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry
+            .instructions
+            .push(Instruction::DataAddr { dest: addr, data });
+        entry.instructions.push(Instruction::Const {
+            dest: nine,
+            value: 9,
+            ty: Type::I32,
+        });
+        entry.instructions.push(Instruction::Store {
+            address: addr,
+            value: nine,
+            ty: Type::I32,
+        });
+        entry.terminator = Terminator::Ret { value: None };
+        func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        };
+        let mut vm = VM::new(program, MemoryHostABI::new());
+
+        let err = vm.call_function("overwrite", vec![]).unwrap_err();
+        assert!(
+            matches!(&err, VMError::InvalidInstruction(msg) if msg.contains("read-only")),
+            "unexpected error: {:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_strict_pointers_flag_ptr_add_past_buffer_end() {
+        // fn overrun() -> i32 { p = alloc(8); alloc(8); q = p + 16; ret load(q) }
+        let mut func = Function::new("overrun".to_string(), vec![], Type::I32);
+        let size = func.next_value();
+        let ptr = func.next_value();
+        let other = func.next_value();
+        let offset = func.next_value();
+        let past_end = func.next_value();
+        let value = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Const {
+            dest: size,
+            value: 8,
+            ty: Type::Usize,
+        });
+        for dest in [ptr, other] {
+            entry.instructions.push(Instruction::Call {
+                dest,
+                function: "alloc".to_string(),
+                args: vec![size],
+                return_type: Type::Usize,
+            });
+        }
+        entry.instructions.push(Instruction::Const {
+            dest: offset,
+            value: 16,
+            ty: Type::Usize,
+        });
+        entry.instructions.push(Instruction::PtrAdd {
+            dest: past_end,
+            ptr,
+            offset,
+        });
+        entry.instructions.push(Instruction::Load {
+            dest: value,
+            ty: Type::I32,
+            address: past_end,
+        });
+        entry.terminator = Terminator::Ret { value: Some(value) };
+        func.blocks.push(entry);
+
+        let program = Arc::new(Program {
+            imports: vec![host_import("alloc", vec![Type::Usize], Type::Usize)],
+            functions: vec![func],
+            rodata: vec![],
+        });
+
+        // By default the overrun lands in the second buffer and goes unnoticed
+        let mut vm = VM::new(program.clone(), MemoryHostABI::new());
+        assert_eq!(
+            vm.call_function("overrun", vec![]).unwrap(),
+            RuntimeValue::I32(0)
+        );
+
+        let mut host_abi = MemoryHostABI::new();
+        host_abi.set_strict_pointers(true);
+        let mut vm = VM::new(program, host_abi);
+        let result = vm.call_function("overrun", vec![]);
+        assert!(
+            matches!(&result, Err(VMError::PointerOutOfBounds(msg)) if msg.contains("escapes its allocation")),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_phi_instruction_runs_after_elimination() {
+        // fn max(a: i32, b: i32) -> i32, joining the two arms with a phi
+        let mut func = Function::new("max".to_string(), vec![Type::I32, Type::I32], Type::I32);
+        let a = func.next_value();
+        let b = func.next_value();
+        let b_wins = func.next_value();
+        let result = func.next_value();
+
+        let (left_id, right_id, join_id) = (BlockId::new(1), BlockId::new(2), BlockId::new(3));
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.params = vec![(a, Type::I32), (b, Type::I32)];
+        entry.instructions.push(Instruction::BinaryOp {
+            dest: b_wins,
+            op: BinaryOperator::Lt,
+            ty: Type::I32,
+            lhs: a,
+            rhs: b,
+        });
+        entry.terminator = Terminator::BrIf {
+            cond: b_wins,
+            true_target: right_id,
+            true_args: vec![],
+            false_target: left_id,
+            false_args: vec![],
+        };
+
+        let mut left = BasicBlock::new(left_id, "left".to_string());
+        left.terminator = Terminator::Br {
+            target: join_id,
+            args: vec![],
+        };
+        let mut right = BasicBlock::new(right_id, "right".to_string());
+        right.terminator = Terminator::Br {
+            target: join_id,
+            args: vec![],
+        };
+
+        let mut join = BasicBlock::new(join_id, "join".to_string());
+        join.instructions.push(Instruction::Phi {
+            dest: result,
+            ty: Type::I32,
+            incomings: vec![(left_id, a), (right_id, b)],
+        });
+        join.terminator = Terminator::Ret {
+            value: Some(result),
+        };
+
+        func.blocks = vec![entry, left, right, join];
+        let mut program = Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        tilt_ir::phi::eliminate_program_phis(&mut program).unwrap();
+        assert_eq!(tilt_ir::verify_program(&program), Ok(()));
+
+        let mut vm = VM::new(program, ConsoleHostABI::new());
+        let args = vec![RuntimeValue::I32(3), RuntimeValue::I32(9)];
+        assert_eq!(vm.call_function("max", args).unwrap(), RuntimeValue::I32(9));
+        let args = vec![RuntimeValue::I32(12), RuntimeValue::I32(9)];
+        assert_eq!(
+            vm.call_function("max", args).unwrap(),
+            RuntimeValue::I32(12)
+        );
+    }
+
+    #[test]
+    fn test_block_argument_type_mismatch() {
+        // entry passes an i64 to `next`, whose parameter is declared i32
+        let mut func = Function::new("bad_branch".to_string(), vec![], Type::I32);
+        let wide = func.next_value();
+        let param = func.next_value();
+
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Const {
+            dest: wide,
+            value: 7,
+            ty: Type::I64,
+        });
+        entry.terminator = Terminator::Br {
+            target: BlockId::new(1),
+            args: vec![wide],
+        };
+
+        let mut next = BasicBlock::new(BlockId::new(1), "next".to_string());
+        next.params.push((param, Type::I32));
+        next.terminator = Terminator::Ret { value: Some(param) };
+
+        func.blocks = vec![entry, next];
+        let program = Program {
+            imports: vec![],
+            functions: vec![func],
+            rodata: vec![],
+        };
+
+        let mut vm = VM::new(program, ConsoleHostABI::new());
+        assert_eq!(
+            vm.call_function("bad_branch", vec![]),
+            Err(VMError::TypeMismatch {
+                expected: Type::I32,
+                actual: Type::I64,
+                context: "argument 1 to block 'next'".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_argv_from_env_layer() {
+        // fn arg_count() -> i32 { ret argc() }
+        let mut count_func = Function::new("arg_count".to_string(), vec![], Type::I32);
+        let count = count_func.next_value();
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Call {
+            dest: count,
+            function: "argc".to_string(),
+            args: vec![],
+            return_type: Type::I32,
+        });
+        entry.terminator = Terminator::Ret { value: Some(count) };
+        count_func.blocks.push(entry);
+
+        // fn first_arg() -> usize { ret argv(0) }
+        let mut arg_func = Function::new("first_arg".to_string(), vec![], Type::Usize);
+        let index = arg_func.next_value();
+        let ptr = arg_func.next_value();
+        arg_func.constants.insert(index, (0, Type::I32));
+        let mut entry = BasicBlock::new(BlockId::new(0), "entry".to_string());
+        entry.instructions.push(Instruction::Call {
+            dest: ptr,
+            function: "argv".to_string(),
+            args: vec![index],
+            return_type: Type::Usize,
+        });
+        entry.terminator = Terminator::Ret { value: Some(ptr) };
+        arg_func.blocks.push(entry);
+
+        let program = Program {
+            imports: vec![
+                host_import("argc", vec![], Type::I32),
+                host_import("argv", vec![Type::I32], Type::Usize),
+            ],
+            functions: vec![count_func, arg_func],
+            rodata: vec![],
+        };
+
+        let args = vec!["prog".to_string(), "--fast".to_string()];
+        let env = EnvHostABI::new(args, Default::default());
+        let mut vm = VM::new(program, LayeredHostABI::new(env, MemoryHostABI::new()));
+
+        assert_eq!(
+            vm.call_function("arg_count", vec![]).unwrap(),
+            RuntimeValue::I32(2)
+        );
+        let RuntimeValue::Usize(addr) = vm.call_function("first_arg", vec![]).unwrap() else {
+            panic!("argv should return a pointer");
+        };
+
+        let host_abi = vm.into_host_abi();
+        assert_eq!(
+            host_abi.base().dump_region(addr as u64, 5).unwrap(),
+            b"prog\0".to_vec()
+        );
+    }
 }
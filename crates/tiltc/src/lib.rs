@@ -0,0 +1,522 @@
+// ===================================================================
+// FILE: lib.rs (tiltc crate)
+//
+// DESC: Library side of the TILT compiler. Runs the pipeline (lexing,
+//       parsing, lowering and verification, then the VM or JIT) on
+//       in-memory source so tests and embedders don't have to wire the
+//       stages by hand.
+// ===================================================================
+
+use std::collections::HashMap;
+use tilt_ast::{TopLevelItem, Type};
+use tilt_codegen_cranelift::JIT;
+use tilt_host_abi::{HostABI, RuntimeValue};
+use tilt_ir::{
+    lowering::{lower_program_with_externs, lower_program_with_options, LoweringOptions},
+    verify_program, Program, SemanticError, VerifyError,
+};
+use tilt_parser::{lexer::Token, tilt::ProgramParser};
+use tilt_vm::{VMError, VM};
+
+/// A token with its start and end byte offsets, as consumed by the parser
+pub type SpannedToken<'a> = (usize, Token<'a>, usize);
+
+/// Errors produced while compiling and running TILT source
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    /// The lexer hit input it could not tokenize
+    Lex(String),
+    /// The token stream does not match the grammar
+    Parse(String),
+    /// The program parsed but failed semantic analysis
+    Semantic(Vec<SemanticError>),
+    /// Separately compiled files could not be combined into one program
+    Link(String),
+    /// The lowered program failed the IR verifier
+    Verify(Vec<VerifyError>),
+    /// The JIT could not be created or could not compile the program
+    Jit(String),
+    /// The program failed while running on the VM
+    Vm(VMError),
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Lex(msg) => write!(f, "{}", msg),
+            CompileError::Parse(msg) => write!(f, "Parsing failed: {}", msg),
+            CompileError::Semantic(errors) => {
+                writeln!(f, "Semantic analysis failed:")?;
+                for error in errors {
+                    writeln!(f, "  • {}", error)?;
+                }
+                Ok(())
+            }
+            CompileError::Link(msg) => write!(f, "Linking failed: {}", msg),
+            CompileError::Verify(errors) => {
+                writeln!(f, "Verification failed:")?;
+                for error in errors {
+                    writeln!(f, "  • {}", error)?;
+                }
+                Ok(())
+            }
+            CompileError::Jit(msg) => write!(f, "JIT compilation failed: {}", msg),
+            CompileError::Vm(error) => write!(f, "VM execution failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Tokenize source into the `(start, token, end)` triples the parser expects
+pub fn tokenize_with_positions(input: &str) -> Result<Vec<SpannedToken<'_>>, CompileError> {
+    tilt_parser::lexer::tokenize(input).map_err(|e| CompileError::Lex(e.to_string()))
+}
+
+/// Parse a token stream into an AST
+pub fn parse_tokens<'a>(
+    tokens: Vec<SpannedToken<'a>>,
+) -> Result<tilt_ast::Program<'a>, CompileError> {
+    ProgramParser::new()
+        .parse(tokens)
+        .map_err(|e| CompileError::Parse(format!("{:?}", e)))
+}
+
+/// Lex, parse and lower TILT source into IR
+pub fn compile_str(source: &str) -> Result<Program, CompileError> {
+    compile_str_with_options(source, &LoweringOptions::default())
+}
+
+/// Lex, parse and lower TILT source into IR with the given lowering options
+pub fn compile_str_with_options(
+    source: &str,
+    options: &LoweringOptions,
+) -> Result<Program, CompileError> {
+    let tokens = tokenize_with_positions(source)?;
+    let ast = parse_tokens(tokens)?;
+    lower_program_with_options(&ast, options).map_err(CompileError::Semantic)
+}
+
+/// Compile TILT source to verified IR and run `entry` on the VM
+pub fn compile_and_execute<H: HostABI>(
+    source: &str,
+    entry: &str,
+    args: Vec<RuntimeValue>,
+    host_abi: H,
+) -> Result<RuntimeValue, CompileError> {
+    let program = compile_str(source)?;
+    verify_program(&program).map_err(CompileError::Verify)?;
+    let mut vm = VM::new(program, host_abi);
+    vm.validate_imports().map_err(CompileError::Vm)?;
+    vm.call_function(entry, args).map_err(CompileError::Vm)
+}
+
+/// Compile TILT source to verified IR and then to native code, ready for
+/// `JIT::get_func_ptr`
+pub fn compile_to_jit(source: &str) -> Result<JIT, CompileError> {
+    let program = compile_str(source)?;
+    verify_program(&program).map_err(CompileError::Verify)?;
+    let mut jit = JIT::new().map_err(CompileError::Jit)?;
+    jit.compile(&program).map_err(CompileError::Jit)?;
+    Ok(jit)
+}
+
+/// Compile several `(file name, source)` pairs into one program.
+/// Each file is lowered on its own, with calls into the other files resolved
+/// by signature, and the results are linked with `Program::merge`.
+pub fn compile_files(files: &[(&str, &str)]) -> Result<Program, CompileError> {
+    compile_files_with_options(files, &LoweringOptions::default())
+}
+
+/// Compile and link several files with the given lowering options
+pub fn compile_files_with_options(
+    files: &[(&str, &str)],
+    options: &LoweringOptions,
+) -> Result<Program, CompileError> {
+    let mut asts = Vec::new();
+    for (name, source) in files {
+        let tokens = tokenize_with_positions(source).map_err(|e| match e {
+            CompileError::Lex(msg) => CompileError::Lex(format!("{}: {}", name, msg)),
+            other => other,
+        })?;
+        let ast = parse_tokens(tokens).map_err(|e| match e {
+            CompileError::Parse(msg) => CompileError::Parse(format!("{}: {}", name, msg)),
+            other => other,
+        })?;
+        asts.push((*name, ast));
+    }
+
+    // Collect every file's function signatures, rejecting duplicates up front
+    // so the error can name both files
+    let mut definitions: HashMap<&str, (&str, Vec<Type>, Type)> = HashMap::new();
+    for (file, ast) in &asts {
+        for item in &ast.items {
+            let TopLevelItem::Function(func) = item else {
+                continue;
+            };
+            if let Some((first_file, _, _)) = definitions.get(func.name) {
+                if first_file != file {
+                    return Err(CompileError::Link(format!(
+                        "function '{}' is defined in both '{}' and '{}'",
+                        func.name, first_file, file
+                    )));
+                }
+                // Duplicates within one file are reported by lowering
+                continue;
+            }
+            let params = func.params.iter().map(|p| p.ty.clone()).collect();
+            definitions.insert(func.name, (file, params, func.return_type.clone()));
+        }
+    }
+
+    let mut program = Program {
+        imports: vec![],
+        functions: vec![],
+        rodata: vec![],
+    };
+    for (file, ast) in &asts {
+        let externs = definitions
+            .iter()
+            .filter(|(_, (defined_in, _, _))| defined_in != file)
+            .map(|(name, (_, params, ret))| (name.to_string(), (params.clone(), ret.clone())))
+            .collect();
+        let lowered =
+            lower_program_with_externs(ast, &externs, options).map_err(CompileError::Semantic)?;
+        program
+            .merge(lowered)
+            .map_err(|e| CompileError::Link(format!("{}: {}", file, e)))?;
+    }
+
+    Ok(program)
+}
+
+/// Name of the function a REPL expression is wrapped in, chosen so it can't
+/// clash with a user's `main`
+pub const REPL_EXPRESSION_FUNCTION: &str = "__repl_expression";
+
+/// Everything defined over a REPL session. Each definition is kept as
+/// source, and later lines are compiled together with all of it, so a
+/// helper defined on one line can be called on the next.
+#[derive(Debug, Clone, Default)]
+pub struct ReplSession {
+    /// Source of each accepted definition line, in order
+    definitions: Vec<String>,
+    /// Names of the functions and imports defined so far
+    names: Vec<String>,
+}
+
+impl ReplSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names of everything defined so far, in definition order
+    pub fn defined_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// All definitions of the session as one program source
+    pub fn source(&self) -> String {
+        self.definitions.join("\n")
+    }
+
+    /// Add the functions and imports in `source` to the session and return
+    /// the whole program compiled with them. Nothing is added if the line
+    /// redefines an existing name or fails to compile.
+    pub fn define(
+        &mut self,
+        source: &str,
+        options: &LoweringOptions,
+    ) -> Result<(Vec<String>, Program), CompileError> {
+        let ast = parse_tokens(tokenize_with_positions(source)?)?;
+        let names: Vec<String> = ast
+            .items
+            .iter()
+            .map(|item| match item {
+                TopLevelItem::Function(func) => func.name.to_string(),
+                TopLevelItem::Import(import) => import.call_name().to_string(),
+            })
+            .collect();
+
+        let redefinitions: Vec<SemanticError> = names
+            .iter()
+            .filter(|name| self.names.contains(name))
+            .map(|name| SemanticError::DuplicateDefinition {
+                name: name.clone(),
+                location: "an earlier REPL line".to_string(),
+            })
+            .collect();
+        if !redefinitions.is_empty() {
+            return Err(CompileError::Semantic(redefinitions));
+        }
+
+        let combined = format!("{}\n{}", self.source(), source);
+        let program = compile_str_with_options(&combined, options)?;
+
+        self.definitions.push(source.to_string());
+        self.names.extend(names.iter().cloned());
+        Ok((names, program))
+    }
+
+    /// Compile an expression line against everything defined so far. The
+    /// expression becomes the i32 result of `REPL_EXPRESSION_FUNCTION`.
+    pub fn compile_expression(
+        &self,
+        expression: &str,
+        options: &LoweringOptions,
+    ) -> Result<Program, CompileError> {
+        let source = format!(
+            "{}\nfn {}() -> i32 {{\nentry:\n    result:i32 = {}\n    ret (result)\n}}\n",
+            self.source(),
+            REPL_EXPRESSION_FUNCTION,
+            expression
+        );
+        compile_str_with_options(&source, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tilt_host_abi::{NullHostABI, RuntimeValue};
+    use tilt_vm::VM;
+
+    #[test]
+    fn test_compile_str_runs_in_vm() {
+        let source = r#"
+fn add(a:i32, b:i32) -> i32 {
+entry:
+    sum:i32 = i32.add(a, b)
+    ret (sum)
+}
+
+fn main() -> i32 {
+entry:
+    result:i32 = add(40, 2)
+    ret (result)
+}
+"#;
+        let program = compile_str(source).unwrap();
+        let mut vm = VM::new(program, NullHostABI::new());
+
+        let result = vm.call_function("main", vec![]).unwrap();
+        assert_eq!(result, RuntimeValue::I32(42));
+    }
+
+    #[test]
+    fn test_compile_str_reports_lex_error_position() {
+        let result = compile_str("fn main() -> i32 {\nentry:\n    ret @\n}");
+        assert_eq!(
+            result,
+            Err(CompileError::Lex(
+                "unexpected character '@' at line 3, col 9".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_compile_files_resolves_calls_across_files() {
+        let main = "fn main() -> i32 {\nentry:\n    r:i32 = helper(20)\n    ret (r)\n}\n";
+        let helper =
+            "fn helper(x:i32) -> i32 {\nentry:\n    r:i32 = i32.add(x, 22)\n    ret (r)\n}\n";
+
+        let program = compile_files(&[("main.tilt", main), ("helper.tilt", helper)]).unwrap();
+        let mut vm = VM::new(program, NullHostABI::new());
+        assert_eq!(
+            vm.call_function("main", vec![]).unwrap(),
+            RuntimeValue::I32(42)
+        );
+    }
+
+    #[test]
+    fn test_compile_files_rejects_duplicate_functions() {
+        let source = "fn helper() -> i32 {\nentry:\n    ret (1)\n}\n";
+        let result = compile_files(&[("a.tilt", source), ("b.tilt", source)]);
+        assert_eq!(
+            result,
+            Err(CompileError::Link(
+                "function 'helper' is defined in both 'a.tilt' and 'b.tilt'".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_repl_session_calls_earlier_definitions() {
+        let options = LoweringOptions::default();
+        let mut session = ReplSession::new();
+
+        let (names, _) = session
+            .define(
+                "fn add(a:i32, b:i32) -> i32 { entry: r:i32 = i32.add(a, b) ret (r) }",
+                &options,
+            )
+            .unwrap();
+        assert_eq!(names, vec!["add".to_string()]);
+
+        let program = session.compile_expression("add(40, 2)", &options).unwrap();
+        let mut vm = VM::new(program, NullHostABI::new());
+        assert_eq!(
+            vm.call_function(REPL_EXPRESSION_FUNCTION, vec![]).unwrap(),
+            RuntimeValue::I32(42)
+        );
+    }
+
+    #[test]
+    fn test_repl_session_rejects_redefinition() {
+        let options = LoweringOptions::default();
+        let mut session = ReplSession::new();
+        let source = "fn one() -> i32 { entry: ret (1) }";
+
+        session.define(source, &options).unwrap();
+        assert_eq!(
+            session.define(source, &options).map(|(names, _)| names),
+            Err(CompileError::Semantic(vec![
+                SemanticError::DuplicateDefinition {
+                    name: "one".to_string(),
+                    location: "an earlier REPL line".to_string(),
+                }
+            ]))
+        );
+        assert_eq!(session.defined_names(), ["one".to_string()]);
+    }
+
+    #[test]
+    fn test_parameterized_loop_runs_in_vm() {
+        let source = r#"
+fn factorial(n:i32) -> i32 {
+entry:
+    br loop(n, 1)
+
+loop(i:i32, acc:i32):
+    more:i32 = i32.gt(i, 1)
+    br_if more, body(i, acc), done(acc)
+
+body(k:i32, product:i32):
+    next_acc:i32 = i32.mul(product, k)
+    next_i:i32 = i32.sub(k, 1)
+    br loop(next_i, next_acc)
+
+done(result:i32):
+    ret (result)
+}
+"#;
+        let program = compile_str(source).unwrap();
+        let mut vm = VM::new(program, NullHostABI::new());
+
+        for (n, expected) in [(0, 1), (1, 1), (5, 120), (10, 3_628_800)] {
+            let result = vm
+                .call_function("factorial", vec![RuntimeValue::I32(n)])
+                .unwrap();
+            assert_eq!(result, RuntimeValue::I32(expected), "factorial({})", n);
+        }
+    }
+
+    #[test]
+    fn test_loop_threads_i64_accumulator_through_block_params() {
+        // The literal branch arguments must take the i64 parameter types
+        let source = r#"
+fn sum_to(n:i64) -> i64 {
+entry:
+    br loop(0, 0)
+
+loop(i:i64, total:i64):
+    more:i32 = i64.lt(i, n)
+    br_if more, body(total), done(total)
+
+body(acc:i64):
+    next_total:i64 = i64.add(acc, i)
+    next_i:i64 = i64.add(i, 1)
+    br loop(next_i, next_total)
+
+done(result:i64):
+    ret (result)
+}
+"#;
+        let program = compile_str(source).unwrap();
+        let mut vm = VM::new(program, NullHostABI::new());
+
+        let result = vm
+            .call_function("sum_to", vec![RuntimeValue::I64(100_000)])
+            .unwrap();
+        assert_eq!(result, RuntimeValue::I64(100_000 * 99_999 / 2));
+    }
+
+    #[test]
+    fn test_compile_and_execute_reports_each_stage() {
+        let source = r#"
+fn main() -> i32 {
+entry:
+    r:i32 = i32.add(1, 41)
+    ret (r)
+}
+"#;
+        assert_eq!(
+            compile_and_execute(source, "main", vec![], NullHostABI::new()),
+            Ok(RuntimeValue::I32(42))
+        );
+        assert!(compile_to_jit(source)
+            .unwrap()
+            .get_func_ptr("main")
+            .is_some());
+
+        // Calling an undefined function is a semantic error
+        let undefined_call = source.replace("i32.add(1, 41)", "missing(1)");
+        let result = compile_and_execute(&undefined_call, "main", vec![], NullHostABI::new());
+        assert!(
+            matches!(&result, Err(CompileError::Semantic(errors)) if !errors.is_empty()),
+            "{:?}",
+            result
+        );
+
+        assert!(matches!(
+            compile_and_execute(source, "start", vec![], NullHostABI::new()),
+            Err(CompileError::Vm(VMError::FunctionNotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn test_bare_i64_constants_keep_their_width() {
+        let source = r#"
+fn big() -> i64 {
+entry:
+    ret (5000000000)
+}
+
+fn pass_big() -> i64 {
+entry:
+    br done(-6000000000)
+
+done(value:i64):
+    ret (value)
+}
+"#;
+        let program = compile_str(source).unwrap();
+        let mut vm = VM::new(program, NullHostABI::new());
+        assert_eq!(
+            vm.call_function("big", vec![]).unwrap(),
+            RuntimeValue::I64(5_000_000_000)
+        );
+        assert_eq!(
+            vm.call_function("pass_big", vec![]).unwrap(),
+            RuntimeValue::I64(-6_000_000_000)
+        );
+
+        // The same literal can't be an i32
+        let narrow = source.replace("fn big() -> i64", "fn big() -> i32");
+        assert_eq!(
+            compile_str(&narrow),
+            Err(CompileError::Semantic(vec![
+                SemanticError::ConstantOutOfRange {
+                    value: 5_000_000_000,
+                    ty: Type::I32,
+                }
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_compile_str_reports_parse_error() {
+        let result = compile_str("fn main( -> i32 {}");
+        assert!(matches!(result, Err(CompileError::Parse(_))));
+    }
+}
@@ -7,19 +7,29 @@
 
 use clap::{Arg, Command};
 use colored::*;
-use logos::Logos;
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
 use std::collections::HashMap;
 use std::fs;
 use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use tilt_ast::Type;
 use tilt_codegen_cranelift::JIT;
-use tilt_host_abi::{MemoryHostABI, RuntimeValue};
-use tilt_ir::{lowering::lower_program, Program};
-use tilt_parser::{lexer::Token, tilt::ProgramParser};
-use tilt_vm::VM;
+use tilt_host_abi::{
+    ConsoleHostABI, EnvHostABI, HostABI, JITMemoryHostABI, LayeredHostABI, MemoryHostABI,
+    NullHostABI, RuntimeValue,
+};
+use tilt_ir::{
+    lowering::{lower_program_with_options, LoweringOptions},
+    program_warnings, verify_program, Program, VerifyWarning,
+};
+use tilt_vm::{FunctionProfile, VM};
+use tiltc::{
+    compile_files_with_options, compile_str_with_options, parse_tokens, tokenize_with_positions,
+    CompileError, ReplSession, SpannedToken, REPL_EXPRESSION_FUNCTION,
+};
 
 #[derive(Debug, Clone)]
 struct CompilerOptions {
@@ -27,10 +37,41 @@ struct CompilerOptions {
     show_ast: bool,
     show_ir: bool,
     show_cranelift_ir: bool,
+    /// File to write every function's Cranelift IR to
+    emit_cranelift_ir: Option<PathBuf>,
     use_vm: bool,
     use_jit: bool,
     verbose: bool,
     measure_time: bool,
+    entry: String,
+    strict: bool,
+    /// Arguments the program sees through `argc`/`argv`
+    program_args: Vec<String>,
+    host_abi: HostAbiKind,
+    /// Print per-function call and instruction counts from the VM
+    profile: bool,
+}
+
+/// Host ABI the program runs against, chosen with `--host-abi`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HostAbiKind {
+    /// Console I/O, memory management and `argc`/`argv` (the default)
+    Memory,
+    /// Console I/O only
+    Console,
+    /// No host functions at all
+    Null,
+}
+
+impl HostAbiKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "memory" => Some(Self::Memory),
+            "console" => Some(Self::Console),
+            "null" => Some(Self::Null),
+            _ => None,
+        }
+    }
 }
 
 impl Default for CompilerOptions {
@@ -40,10 +81,24 @@ impl Default for CompilerOptions {
             show_ast: false,
             show_ir: false,
             show_cranelift_ir: false,
+            emit_cranelift_ir: None,
             use_vm: true,
             use_jit: false,
             verbose: false,
             measure_time: false,
+            entry: "main".to_string(),
+            strict: false,
+            program_args: Vec::new(),
+            host_abi: HostAbiKind::Memory,
+            profile: false,
+        }
+    }
+}
+
+impl CompilerOptions {
+    fn lowering_options(&self) -> LoweringOptions {
+        LoweringOptions {
+            strict: self.strict,
         }
     }
 }
@@ -55,10 +110,19 @@ fn main() {
         .about("The TILT Language Compiler and REPL")
         .arg(
             Arg::new("file")
-                .help("TILT source file to compile and execute")
+                .help("TILT source file(s) to compile, link and execute")
                 .value_name("FILE")
+                .num_args(1..)
                 .index(1),
         )
+        .arg(
+            Arg::new("args")
+                .help("Arguments passed to the program (VM only), after --")
+                .value_name("ARGS")
+                .num_args(0..)
+                .index(2)
+                .last(true),
+        )
         .arg(
             Arg::new("repl")
                 .short('r')
@@ -90,6 +154,13 @@ fn main() {
                 .help("Display Cranelift IR")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("emit-cranelift-ir")
+                .long("emit-cranelift-ir")
+                .help("Write the Cranelift IR of every function to a file")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
         .arg(
             Arg::new("show-all")
                 .long("show-all")
@@ -114,6 +185,39 @@ fn main() {
                 .help("Use both VM and JIT backends for comparison")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("entry")
+                .long("entry")
+                .help("Name of the function to execute")
+                .value_name("NAME")
+                .default_value("main"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Reject bare constants whose type is inferred from context")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("host-abi")
+                .long("host-abi")
+                .help("Host ABI the program runs against")
+                .value_name("ABI")
+                .value_parser(["memory", "console", "null"])
+                .default_value("memory"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .help("Print per-function call and instruction counts (runs the VM)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .help("Check the program for errors and warnings without executing it")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("verbose")
                 .short('v')
@@ -130,16 +234,26 @@ fn main() {
         )
         .get_matches();
 
-    let mut options = CompilerOptions::default();
-
     // Parse command line options
-    options.show_tokens = matches.get_flag("show-tokens") || matches.get_flag("show-all");
-    options.show_ast = matches.get_flag("show-ast") || matches.get_flag("show-all");
-    options.show_ir = matches.get_flag("show-ir") || matches.get_flag("show-all");
-    options.show_cranelift_ir =
-        matches.get_flag("show-cranelift-ir") || matches.get_flag("show-all");
-    options.verbose = matches.get_flag("verbose");
-    options.measure_time = matches.get_flag("time");
+    let mut options = CompilerOptions {
+        show_tokens: matches.get_flag("show-tokens") || matches.get_flag("show-all"),
+        show_ast: matches.get_flag("show-ast") || matches.get_flag("show-all"),
+        show_ir: matches.get_flag("show-ir") || matches.get_flag("show-all"),
+        show_cranelift_ir: matches.get_flag("show-cranelift-ir") || matches.get_flag("show-all"),
+        emit_cranelift_ir: matches.get_one::<PathBuf>("emit-cranelift-ir").cloned(),
+        verbose: matches.get_flag("verbose"),
+        measure_time: matches.get_flag("time"),
+        entry: matches.get_one::<String>("entry").unwrap().clone(),
+        strict: matches.get_flag("strict"),
+        program_args: matches
+            .get_many::<String>("args")
+            .map(|args| args.cloned().collect())
+            .unwrap_or_default(),
+        host_abi: HostAbiKind::from_name(matches.get_one::<String>("host-abi").unwrap())
+            .expect("clap only accepts known host ABI names"),
+        profile: matches.get_flag("profile"),
+        ..Default::default()
+    };
 
     // Determine execution backend
     if matches.get_flag("both") {
@@ -152,14 +266,26 @@ fn main() {
         options.use_vm = true;
         options.use_jit = false;
     }
+    // Only the VM can count instructions
+    if options.profile {
+        options.use_vm = true;
+    }
 
     print_banner();
 
     if matches.get_flag("repl") || matches.get_one::<String>("file").is_none() {
         start_repl(options);
     } else {
-        let filename = matches.get_one::<String>("file").unwrap();
-        compile_and_run_file(filename, options);
+        let filenames: Vec<&String> = matches.get_many::<String>("file").unwrap().collect();
+        // Like C, argv[0] is the program's own name
+        options.program_args.insert(0, filenames[0].clone());
+        if matches.get_flag("check") {
+            check_files(&filenames, &options);
+        } else if let [filename] = filenames[..] {
+            compile_and_run_file(filename, options);
+        } else {
+            compile_and_run_files(&filenames, options);
+        }
     }
 }
 
@@ -219,6 +345,7 @@ fn start_repl(mut options: CompilerOptions) {
 
     let mut rl = DefaultEditor::new().unwrap();
     let mut session_vars: HashMap<String, RuntimeValue> = HashMap::new();
+    let mut session = ReplSession::new();
     let mut line_count = 0;
 
     loop {
@@ -238,7 +365,7 @@ fn start_repl(mut options: CompilerOptions) {
                 rl.add_history_entry(line).unwrap();
 
                 // Handle REPL commands
-                if handle_repl_command(line, &mut options, &session_vars) {
+                if handle_repl_command(line, &mut options, &session_vars, &session) {
                     continue;
                 }
 
@@ -249,7 +376,7 @@ fn start_repl(mut options: CompilerOptions) {
                 }
 
                 // Try to execute as TILT code
-                execute_repl_line(line, &options, &mut session_vars);
+                execute_repl_line(line, &options, &mut session, &mut session_vars);
             }
             Err(ReadlineError::Interrupted) => {
                 println!("{}", "^C".yellow());
@@ -271,6 +398,7 @@ fn handle_repl_command(
     line: &str,
     options: &mut CompilerOptions,
     vars: &HashMap<String, RuntimeValue>,
+    session: &ReplSession,
 ) -> bool {
     match line {
         "help" => {
@@ -285,6 +413,10 @@ fn handle_repl_command(
             print_session_vars(vars);
             true
         }
+        "defs" => {
+            print_session_definitions(session);
+            true
+        }
         "clear" => {
             // Clear screen
             print!("\x1B[2J\x1B[1;1H");
@@ -311,6 +443,10 @@ fn print_help() {
         "options".green()
     );
     println!("  {}           - Show session variables", "vars".green());
+    println!(
+        "  {}           - Show functions defined this session",
+        "defs".green()
+    );
     println!("  {}          - Clear the screen", "clear".green());
     println!("  {}        - Show example TILT code", "example".green());
     println!("  {}      - Exit the REPL", "quit/exit".green());
@@ -347,6 +483,7 @@ fn print_help() {
     println!();
     println!("{}", "💡 TILT Code Examples:".blue().bold());
     println!("  Type a complete function definition or single expression");
+    println!("  Functions stay defined, so later lines can call them");
     println!("  Use 'example' to see sample code");
     println!();
 }
@@ -379,6 +516,18 @@ fn print_session_vars(vars: &HashMap<String, RuntimeValue>) {
     println!();
 }
 
+fn print_session_definitions(session: &ReplSession) {
+    if session.defined_names().is_empty() {
+        println!("{}", "📝 No functions defined yet.".yellow());
+    } else {
+        println!("{}", "📝 Session Definitions:".blue().bold());
+        for name in session.defined_names() {
+            println!("  {}", name.green());
+        }
+    }
+    println!();
+}
+
 fn format_bool(b: bool) -> colored::ColoredString {
     if b {
         "true".green()
@@ -503,22 +652,41 @@ fn show_example_code() {
 fn execute_repl_line(
     line: &str,
     options: &CompilerOptions,
+    session: &mut ReplSession,
     _vars: &mut HashMap<String, RuntimeValue>,
 ) {
     let start_time = std::time::Instant::now();
-
-    // If it's a single function, wrap it in a complete program
-    let source = if line.trim().starts_with("fn ") {
-        line.to_string()
+    print_line_front_end(line, options);
+
+    let trimmed = line.trim();
+    let result = if trimmed.starts_with("fn ") || trimmed.starts_with("import ") {
+        // Definitions join the session; defining the entry function runs it
+        match session.define(line, &options.lowering_options()) {
+            Ok((names, program)) => {
+                for name in &names {
+                    println!("{} {}", "✓ Defined".green(), name.green().bold());
+                }
+                if !names.contains(&options.entry) {
+                    println!();
+                    return;
+                }
+                execute_program(Arc::new(program), options, start_time)
+            }
+            Err(e) => Err(e.to_string()),
+        }
     } else {
-        // Try to parse as an expression and wrap in a main function
-        format!(
-            "fn main() -> i32 {{\nentry:\n    result:i32 = {}\n    ret (result)\n}}",
-            line
-        )
+        // Anything else is an expression evaluated against the session
+        let expression_options = CompilerOptions {
+            entry: REPL_EXPRESSION_FUNCTION.to_string(),
+            ..options.clone()
+        };
+        session
+            .compile_expression(line, &options.lowering_options())
+            .map_err(|e| e.to_string())
+            .and_then(|program| execute_program(Arc::new(program), &expression_options, start_time))
     };
 
-    match compile_and_execute(&source, options) {
+    match result {
         Ok(result) => {
             if let Some(value) = result {
                 println!("{} {:?}", "Result:".green().bold(), value);
@@ -538,6 +706,21 @@ fn execute_repl_line(
     println!();
 }
 
+/// Show the tokens and, for definitions, the AST of one REPL line if asked to
+fn print_line_front_end(line: &str, options: &CompilerOptions) {
+    let Ok(tokens) = tokenize_with_positions(line) else {
+        return;
+    };
+    if options.show_tokens {
+        print_tokens(&tokens);
+    }
+    if options.show_ast {
+        if let Ok(ast) = parse_tokens(tokens) {
+            print_ast(&ast);
+        }
+    }
+}
+
 fn compile_and_run_file(filename: &str, options: CompilerOptions) {
     if options.verbose {
         println!("{} {}", "📁 Loading file:".blue().bold(), filename.yellow());
@@ -584,6 +767,124 @@ fn compile_and_run_file(filename: &str, options: CompilerOptions) {
     }
 }
 
+/// Read every file, exiting with an error if any can't be read
+fn read_files(filenames: &[&String]) -> Vec<(String, String)> {
+    filenames
+        .iter()
+        .map(|filename| match fs::read_to_string(filename) {
+            Ok(content) => (filename.to_string(), content),
+            Err(e) => {
+                eprintln!(
+                    "{} Failed to read file '{}': {}",
+                    "Error:".red().bold(),
+                    filename,
+                    e
+                );
+                std::process::exit(1);
+            }
+        })
+        .collect()
+}
+
+/// Compile several files, link them into one program and run the entry
+fn compile_and_run_files(filenames: &[&String], options: CompilerOptions) {
+    if options.verbose {
+        for filename in filenames {
+            println!("{} {}", "📁 Loading file:".blue().bold(), filename.yellow());
+        }
+    }
+
+    let files = read_files(filenames);
+    let sources: Vec<(&str, &str)> = files
+        .iter()
+        .map(|(name, source)| (name.as_str(), source.as_str()))
+        .collect();
+
+    let start_time = std::time::Instant::now();
+
+    let result = compile_files_with_options(&sources, &options.lowering_options())
+        .map_err(|e| e.to_string())
+        .and_then(|program| execute_program(Arc::new(program), &options, start_time));
+
+    match result {
+        Ok(result) => {
+            if let Some(value) = result {
+                println!("{} {:?}", "Final result:".green().bold(), value);
+            } else {
+                println!("{}", "✓ Program executed successfully".green().bold());
+            }
+
+            if options.measure_time {
+                let elapsed = start_time.elapsed();
+                println!("{} {:?}", "Total execution time:".blue().bold(), elapsed);
+            }
+        }
+        Err(e) => {
+            eprintln!("{} {}", "Compilation/execution failed:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn check_files(filenames: &[&String], options: &CompilerOptions) {
+    let files = read_files(filenames);
+    let sources: Vec<(&str, &str)> = files
+        .iter()
+        .map(|(name, source)| (name.as_str(), source.as_str()))
+        .collect();
+
+    match check_sources(&sources, &options.lowering_options()) {
+        Ok(warnings) => {
+            for warning in &warnings {
+                println!("{} {}", "Warning:".yellow().bold(), warning);
+            }
+            println!(
+                "{} {} ({} warning(s))",
+                "✓".green().bold(),
+                "Check passed".green().bold(),
+                warnings.len()
+            );
+        }
+        Err(e) => {
+            eprintln!("{} {}", "Check failed:".red().bold(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parse, lower, link and verify a program without executing it
+fn check_sources(
+    sources: &[(&str, &str)],
+    options: &LoweringOptions,
+) -> Result<Vec<VerifyWarning>, String> {
+    let ir_program = match sources {
+        [(_, source)] => compile_str_with_options(source, options),
+        _ => compile_files_with_options(sources, options),
+    }
+    .map_err(|e| e.to_string())?;
+
+    verify_program(&ir_program).map_err(|errors| {
+        let mut error_msg = "Verification failed:\n".to_string();
+        for error in &errors {
+            error_msg.push_str(&format!("  • {}\n", error));
+        }
+        error_msg
+    })?;
+
+    Ok(program_warnings(&ir_program))
+}
+
+/// Make sure the program has something to run before reaching the backends
+fn check_entry(program: &Program, entry: &str) -> Result<(), String> {
+    if program.functions.is_empty() {
+        return Err("program defines no functions".to_string());
+    }
+    if !program.functions.iter().any(|f| f.name == entry) {
+        return Err(format!("no entry function '{}'; use --entry", entry));
+    }
+    Ok(())
+}
+
 fn compile_and_execute(
     source: &str,
     options: &CompilerOptions,
@@ -595,7 +896,7 @@ fn compile_and_execute(
         println!("{}", "🔍 Step 1: Lexical Analysis...".blue().bold());
     }
 
-    let tokens = tokenize_with_positions(source)?;
+    let tokens = tokenize_with_positions(source).map_err(|e| e.to_string())?;
 
     if options.show_tokens {
         print_tokens(&tokens);
@@ -606,10 +907,7 @@ fn compile_and_execute(
         println!("{}", "🔍 Step 2: Parsing...".blue().bold());
     }
 
-    let parser = ProgramParser::new();
-    let ast = parser
-        .parse(tokens)
-        .map_err(|e| format!("Parsing failed: {:?}", e))?;
+    let ast = parse_tokens(tokens).map_err(|e| e.to_string())?;
 
     if options.show_ast {
         print_ast(&ast);
@@ -620,18 +918,35 @@ fn compile_and_execute(
         println!("{}", "🔍 Step 3: IR Generation...".blue().bold());
     }
 
-    let ir_program = lower_program(&ast).map_err(|errors| {
-        let mut error_msg = "Semantic analysis failed:\n".to_string();
-        for error in &errors {
-            error_msg.push_str(&format!("  • {}\n", error));
-        }
-        error_msg
-    })?;
+    let ir_program = lower_program_with_options(&ast, &options.lowering_options())
+        .map_err(|errors| CompileError::Semantic(errors).to_string())?;
+
+    execute_program(Arc::new(ir_program), options, compilation_start)
+}
 
+/// Run a lowered program on the selected backend(s)
+fn execute_program(
+    ir_program: Arc<Program>,
+    options: &CompilerOptions,
+    compilation_start: std::time::Instant,
+) -> Result<Option<RuntimeValue>, String> {
     if options.show_ir {
         print_ir(&ir_program);
     }
 
+    check_entry(&ir_program, &options.entry)?;
+
+    if let Some(path) = &options.emit_cranelift_ir {
+        emit_cranelift_ir(&ir_program, path)?;
+        if options.verbose {
+            println!(
+                "{} {}",
+                "📄 Wrote Cranelift IR to".blue().bold(),
+                path.display().to_string().yellow()
+            );
+        }
+    }
+
     let compilation_time = compilation_start.elapsed();
     if options.measure_time {
         println!("{} {:?}", "Compilation time:".blue(), compilation_time);
@@ -646,7 +961,7 @@ fn compile_and_execute(
             println!("{}", "🔍 Step 4a: VM Execution...".blue().bold());
         }
 
-        let vm_result = execute_with_vm(&ir_program)?;
+        let vm_result = execute_with_vm(&ir_program, options)?;
 
         if options.use_jit {
             results.push(("VM", vm_result.clone()));
@@ -704,19 +1019,7 @@ fn compile_and_execute(
     Ok(None)
 }
 
-fn tokenize_with_positions(input: &str) -> Result<Vec<(usize, Token, usize)>, String> {
-    let mut lexer = Token::lexer(input);
-    let mut tokens = Vec::new();
-
-    while let Some(token) = lexer.next() {
-        let token = token.map_err(|_| "Lexing error")?;
-        let span = lexer.span();
-        tokens.push((span.start, token, span.end));
-    }
-    Ok(tokens)
-}
-
-fn print_tokens(tokens: &[(usize, Token, usize)]) {
+fn print_tokens(tokens: &[SpannedToken]) {
     println!("{}", "🔤 Token Stream:".blue().bold());
     println!(
         "{}",
@@ -760,20 +1063,102 @@ fn print_ir(ir: &Program) {
     println!();
 }
 
-fn execute_with_vm(program: &Program) -> Result<RuntimeValue, String> {
-    let host_abi = MemoryHostABI::new();
-    let mut vm = VM::new(program.clone(), host_abi);
+/// Compile the program with a throwaway JIT and write the Cranelift IR of
+/// every function to `path`, each headed by its TILT name
+fn emit_cranelift_ir(program: &Program, path: &Path) -> Result<(), String> {
+    let host_abi = Box::new(JITMemoryHostABI::new());
+    let mut jit =
+        JIT::new_with_abi(host_abi).map_err(|e| format!("Failed to create JIT: {}", e))?;
+    jit.compile(program)
+        .map_err(|e| format!("JIT compilation failed: {}", e))?;
+
+    let mut text = String::new();
+    for func in &program.functions {
+        let ir = jit
+            .cranelift_ir_for(&func.name)
+            .ok_or_else(|| format!("No Cranelift IR for function '{}'", func.name))?;
+        text.push_str(&format!("; function {}\n{}\n", func.name, ir));
+    }
+
+    fs::write(path, text).map_err(|e| {
+        format!(
+            "Failed to write Cranelift IR to '{}': {}",
+            path.display(),
+            e
+        )
+    })
+}
+
+fn execute_with_vm(
+    program: &Arc<Program>,
+    options: &CompilerOptions,
+) -> Result<RuntimeValue, String> {
+    match options.host_abi {
+        HostAbiKind::Memory => {
+            // argc/argv strings live in host memory, so only the memory ABI
+            // can carry the environment layer
+            let env = EnvHostABI::new(options.program_args.clone(), std::env::vars().collect());
+            run_on_vm(
+                program,
+                options,
+                LayeredHostABI::new(env, MemoryHostABI::new()),
+            )
+        }
+        HostAbiKind::Console => run_on_vm(program, options, ConsoleHostABI::new()),
+        HostAbiKind::Null => run_on_vm(program, options, NullHostABI::new()),
+    }
+}
 
-    // Try to find and execute main function
+fn run_on_vm<H: HostABI>(
+    program: &Arc<Program>,
+    options: &CompilerOptions,
+    host_abi: H,
+) -> Result<RuntimeValue, String> {
+    let mut vm = VM::new(Arc::clone(program), host_abi);
+    vm.set_profiling(options.profile);
+    vm.validate_imports()
+        .map_err(|e| format!("VM execution failed: {}", e))?;
+
+    // Try to find and execute the entry function
     let result = vm
-        .call_function("main", vec![])
+        .call_function(&options.entry, vec![])
         .map_err(|e| format!("VM execution failed: {:?}", e))?;
 
+    if options.profile {
+        print_profile(&vm.profile());
+    }
+
     Ok(result)
 }
 
+/// Print a VM profile as a table, costliest function first
+fn print_profile(profile: &[(&str, FunctionProfile)]) {
+    let width = profile
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(["function".len()])
+        .max()
+        .unwrap_or_default();
+
+    println!("{}", "📊 VM Profile:".blue().bold());
+    println!(
+        "  {:<width$}  {:>10}  {:>14}",
+        "function", "calls", "instructions"
+    );
+    for (name, counts) in profile {
+        println!(
+            "  {:<width$}  {:>10}  {:>14}",
+            name, counts.calls, counts.instructions
+        );
+    }
+}
+
 fn execute_with_jit(program: &Program, options: &CompilerOptions) -> Result<RuntimeValue, String> {
-    let host_abi = Box::new(tilt_host_abi::JITMemoryHostABI::new());
+    let host_abi: Box<dyn HostABI + Send + Sync> = match options.host_abi {
+        HostAbiKind::Memory => Box::new(JITMemoryHostABI::new()),
+        HostAbiKind::Console => Box::new(ConsoleHostABI::new()),
+        HostAbiKind::Null => Box::new(NullHostABI::new()),
+    };
     let mut jit =
         JIT::new_with_abi(host_abi).map_err(|e| format!("Failed to create JIT: {}", e))?;
 
@@ -785,16 +1170,16 @@ fn execute_with_jit(program: &Program, options: &CompilerOptions) -> Result<Runt
     jit.compile(program)
         .map_err(|e| format!("JIT compilation failed: {}", e))?;
 
-    // Get the main function pointer
+    // Get the entry function pointer
     let main_ptr = jit
-        .get_func_ptr("main")
+        .get_func_ptr(&options.entry)
         .ok_or("Main function not found in JIT compiled code")?;
 
-    // Find the main function to check its return type
+    // Find the entry function to check its return type
     let main_function = program
         .functions
         .iter()
-        .find(|f| f.name == "main")
+        .find(|f| f.name == options.entry)
         .ok_or("Main function not found in program")?;
 
     // Execute the function based on its return type
@@ -0,0 +1,306 @@
+// ===================================================================
+// FILE: cli.rs (tiltc integration tests)
+//
+// DESC: Runs the tiltc binary on small source files and checks the
+//       messages it reports.
+// ===================================================================
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Write `source` to a uniquely named temp file and run tiltc on it
+fn run_tiltc(name: &str, source: &str, args: &[&str]) -> Output {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("tiltc_{}_{}.tilt", name, std::process::id()));
+    std::fs::write(&path, source).expect("Failed to write test source");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tiltc"))
+        .args(args)
+        .arg(&path)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run tiltc");
+
+    let _ = std::fs::remove_file(&path);
+    output
+}
+
+/// Write each `(name, source)` pair to its own temp file and run tiltc on all of them
+fn run_tiltc_files(files: &[(&str, &str)], args: &[&str]) -> Output {
+    let paths: Vec<PathBuf> = files
+        .iter()
+        .map(|(name, source)| {
+            let path =
+                std::env::temp_dir().join(format!("tiltc_{}_{}.tilt", name, std::process::id()));
+            std::fs::write(&path, source).expect("Failed to write test source");
+            path
+        })
+        .collect();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tiltc"))
+        .args(args)
+        .args(&paths)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run tiltc");
+
+    for path in &paths {
+        let _ = std::fs::remove_file(path);
+    }
+    output
+}
+
+/// Run the tiltc REPL with `lines` piped to its input
+fn run_repl(lines: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tiltc"))
+        .arg("--repl")
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run tiltc");
+
+    let mut stdin = child.stdin.take().unwrap();
+    for line in lines {
+        writeln!(stdin, "{}", line).expect("Failed to write REPL input");
+    }
+    drop(stdin);
+
+    child.wait_with_output().expect("Failed to wait for tiltc")
+}
+
+#[test]
+fn test_empty_program_reports_no_functions() {
+    let output = run_tiltc("empty", "", &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("program defines no functions"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn test_program_without_main_reports_missing_entry() {
+    let source = r#"
+fn helper() -> i32 {
+entry:
+    result:i32 = i32.const(7)
+    ret (result)
+}
+"#;
+    let output = run_tiltc("no_main", source, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("no entry function 'main'; use --entry"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+
+    let output = run_tiltc("no_main_entry", source, &["--entry", "helper"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("Final result: I32(7)"), "{}", stdout);
+}
+
+#[test]
+fn test_multiple_files_are_linked() {
+    let main = r#"
+fn main() -> i32 {
+entry:
+    result:i32 = helper(40)
+    ret (result)
+}
+"#;
+    let helper = r#"
+fn helper(x:i32) -> i32 {
+entry:
+    result:i32 = i32.add(x, 2)
+    ret (result)
+}
+"#;
+    let output = run_tiltc_files(&[("link_main", main), ("link_helper", helper)], &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("Final result: I32(42)"), "{}", stdout);
+}
+
+#[test]
+fn test_duplicate_function_across_files_names_both() {
+    let source = r#"
+fn main() -> i32 {
+entry:
+    ret (1)
+}
+"#;
+    let output = run_tiltc_files(&[("dup_a", source), ("dup_b", source)], &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("function 'main' is defined in both"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("dup_a") && stderr.contains("dup_b"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_program_arguments_reach_argc() {
+    let source = r#"
+import "env" "argc" -> i32
+
+fn main() -> i32 {
+entry:
+    count:i32 = argc()
+    ret (count)
+}
+"#;
+    let output = run_tiltc("argc", source, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Final result: I32(1)"), "{}", stdout);
+
+    let path = std::env::temp_dir().join(format!("tiltc_argc_args_{}.tilt", std::process::id()));
+    std::fs::write(&path, source).expect("Failed to write test source");
+    let output = Command::new(env!("CARGO_BIN_EXE_tiltc"))
+        .arg(&path)
+        .args(["--", "one", "two"])
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("Failed to run tiltc");
+    let _ = std::fs::remove_file(&path);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Final result: I32(3)"), "{}", stdout);
+}
+
+#[test]
+fn test_host_abi_null_runs_pure_arithmetic() {
+    let source = r#"
+fn main() -> i32 {
+entry:
+    a:i32 = i32.const(6)
+    b:i32 = i32.const(7)
+    r:i32 = i32.mul(a, b)
+    ret (r)
+}
+"#;
+    for backend in ["--vm", "--jit"] {
+        let output = run_tiltc("host_abi_null", source, &[backend, "--host-abi", "null"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}: {}", backend, stdout);
+        assert!(
+            stdout.contains("Final result: I32(42)"),
+            "{}: {}",
+            backend,
+            stdout
+        );
+    }
+}
+
+#[test]
+fn test_profile_names_recursive_function() {
+    let source = r#"
+fn countdown(n:i32) -> i32 {
+entry:
+    done:i32 = i32.le(n, 0)
+    br_if done, stop, recurse
+
+stop:
+    ret (n)
+
+recurse:
+    m:i32 = i32.sub(n, 1)
+    r:i32 = countdown(m)
+    ret (r)
+}
+
+fn main() -> i32 {
+entry:
+    r:i32 = countdown(5)
+    ret (r)
+}
+"#;
+    let output = run_tiltc("profile", source, &["--profile"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("VM Profile"), "{}", stdout);
+
+    // countdown runs 6 times and does most of the work, so it comes first
+    let rows: Vec<Vec<&str>> = stdout
+        .lines()
+        .skip_while(|line| !line.contains("VM Profile"))
+        .skip(2)
+        .map(|line| line.split_whitespace().collect())
+        .take_while(|row: &Vec<&str>| row.len() == 3)
+        .collect();
+    assert_eq!(rows[0][..2], ["countdown", "6"], "{}", stdout);
+    assert_eq!(rows[1][..2], ["main", "1"], "{}", stdout);
+}
+
+#[test]
+fn test_emit_cranelift_ir_writes_every_function() {
+    let source = r#"
+fn double(x:i32) -> i32 {
+entry:
+    two:i32 = i32.const(2)
+    r:i32 = i32.mul(x, two)
+    ret (r)
+}
+
+fn main() -> i32 {
+entry:
+    x:i32 = i32.const(21)
+    r:i32 = call double(x)
+    ret (r)
+}
+"#;
+    let ir_path = std::env::temp_dir().join(format!("tiltc_emit_{}.clif", std::process::id()));
+    let output = run_tiltc(
+        "emit_clif",
+        source,
+        &["--emit-cranelift-ir", ir_path.to_str().unwrap()],
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Final result: I32(42)"), "{}", stdout);
+
+    let ir = std::fs::read_to_string(&ir_path).expect("Cranelift IR file should be written");
+    let _ = std::fs::remove_file(&ir_path);
+    assert!(ir.contains("; function double"), "{}", ir);
+    assert!(ir.contains("; function main"), "{}", ir);
+    assert!(ir.contains("imul"), "{}", ir);
+}
+
+#[test]
+fn test_repl_keeps_definitions_between_lines() {
+    let output = run_repl(&[
+        "fn add(a:i32, b:i32) -> i32 { entry: r:i32 = i32.add(a, b) ret (r) }",
+        "add(40, 2)",
+        "fn add(a:i32, b:i32) -> i32 { entry: ret (a) }",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("Defined add"), "{}", stdout);
+    assert!(stdout.contains("Result: I32(42)"), "{}", stdout);
+    assert!(
+        stdout.contains("Duplicate definition of 'add' at an earlier REPL line"),
+        "{}",
+        stdout
+    );
+}